@@ -1,9 +1,30 @@
 use std::{env, fs, path::PathBuf};
 
 fn main() {
+    // 按目标平台而不是构建机选择，交叉编译时两者不同
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let platform = if target_os == "windows" {
+        "windows"
+    } else {
+        "linux"
+    };
+    println!("cargo:rustc-cfg=hik_sdk_platform=\"{}\"", platform);
+
+    // Linux 版 SDK 自带 incl 目录，与仓库中的 Windows 头文件有差异，通过 HIK_SDK_INCLUDE 指定
+    println!("cargo:rerun-if-env-changed=HIK_SDK_INCLUDE");
+    println!("cargo:rerun-if-env-changed=HIK_SDK_PATH");
+    let include_dir = env::var("HIK_SDK_INCLUDE").unwrap_or_else(|_| "include".to_string());
+    let header = PathBuf::from(&include_dir).join("HCNetSDK.h");
+    if !header.exists() {
+        panic!("HCNetSDK.h not found in {:?}, check HIK_SDK_INCLUDE", include_dir);
+    }
+
+    // bindgen 把 TARGET 传给 clang，结构体对齐与 HWND 等类型按目标平台计算；
+    // 生成结果由 src/layout.rs 在编译期核对
     let bindings = bindgen::Builder::default()
         .clang_args(vec!["-x", "c++"])
-        .header("wrapper.h")
+        .clang_arg(format!("-I{}", include_dir))
+        .header(header.to_string_lossy())
         .derive_default(true)
         .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
         .generate()
@@ -17,7 +38,7 @@ fn main() {
     let sdk_path = env::var("HIK_SDK_PATH").expect("HIK_SDK_PATH must be set");
     println!("cargo:rustc-link-search={}", sdk_path);
 
-    if cfg!(target_os = "windows") {
+    if platform == "windows" {
         println!("cargo:rustc-link-lib=HCNetSDK");
         copy_sdk(&sdk_path);
     } else {
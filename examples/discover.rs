@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use hik_net_sdk::common;
+
+// 用法: cargo run --example discover -- [timeout_secs]
+fn main() -> anyhow::Result<()> {
+    let timeout = match std::env::args().nth(1) {
+        Some(secs) => Duration::from_secs(secs.parse()?),
+        None => Duration::from_secs(3),
+    };
+
+    let devices = common::discover_devices(timeout)?;
+    println!(
+        "{:<16} {:<24} {:<16} {:<6} {:<18} {:<10} Firmware",
+        "IPv4", "Model", "Serial", "Port", "MAC", "Activated"
+    );
+    for device in &devices {
+        let ip = device
+            .ipv4_address
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<16} {:<24} {:<16} {:<6} {:<18} {:<10} {}",
+            ip,
+            device.device_type,
+            device.serial_number,
+            device.port,
+            device.mac_address,
+            if device.activated { "yes" } else { "no" },
+            device.firmware_version
+        );
+    }
+    println!("Found {} device(s)", devices.len());
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use hik_net_sdk::{common, device::HikDevice, motion::HandleType};
+
+// 用法: cargo run --example enable_motion -- <ip> <username> <password> [port]
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 {
+        eprintln!("Usage: {} <ip> <username> <password> [port]", args[0]);
+        std::process::exit(1);
+    }
+    let port = match args.get(4) {
+        Some(port) => port.parse()?,
+        None => 8000,
+    };
+
+    common::init()?;
+
+    let mut device = HikDevice::new();
+    device.login(&args[1], &args[2], &args[3], port)?;
+
+    // 通道 1 全画面移动侦测，并上传到报警中心
+    let mut config = device.get_motion_config(1)?;
+    config.enabled = true;
+    config.sensitivity = Some(3);
+    config.grid.fill_all();
+    config.handle_type |= HandleType::UPLOAD_CENTER;
+    device.set_motion_config(1, &config)?;
+    println!("Motion detection enabled on channel 1");
+
+    device.logout()?;
+    common::cleanup()?;
+    Ok(())
+}
@@ -0,0 +1,26 @@
+use hik_net_sdk::{common, device::HikDevice};
+
+// 用法: cargo run --example isapi_device_info -- <ip> <username> <password> [port]
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 {
+        eprintln!("Usage: {} <ip> <username> <password> [port]", args[0]);
+        std::process::exit(1);
+    }
+    let port = match args.get(4) {
+        Some(port) => port.parse()?,
+        None => 8000,
+    };
+
+    common::init()?;
+
+    let mut device = HikDevice::new();
+    device.login(&args[1], &args[2], &args[3], port)?;
+
+    let xml = device.isapi_get_string("/ISAPI/System/deviceInfo")?;
+    println!("{}", xml);
+
+    device.logout()?;
+    common::cleanup()?;
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use chrono::{Days, Local, NaiveTime, TimeZone as _};
+use hik_net_sdk::{common, counting::ReportGranularity, device::HikDevice};
+
+// 用法: cargo run --example people_counting -- <ip> <username> <password> [port] [channel]
+// 按小时打印昨天的进入、离开和经过人数，设备没有数据的时段显示为 -
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 {
+        eprintln!(
+            "Usage: {} <ip> <username> <password> [port] [channel]",
+            args[0]
+        );
+        std::process::exit(1);
+    }
+    let port = match args.get(4) {
+        Some(port) => port.parse()?,
+        None => 8000,
+    };
+    let channel = match args.get(5) {
+        Some(channel) => channel.parse()?,
+        None => 1,
+    };
+
+    let today = Local::now().date_naive();
+    let yesterday = today
+        .checked_sub_days(Days::new(1))
+        .ok_or(anyhow::anyhow!("Date out of range"))?;
+    let local_midnight = |date: chrono::NaiveDate| {
+        Local
+            .from_local_datetime(&date.and_time(NaiveTime::MIN))
+            .earliest()
+            .ok_or(anyhow::anyhow!("Invalid local midnight on {}", date))
+    };
+    let start = local_midnight(yesterday)?;
+    let end = local_midnight(today)?;
+
+    common::init()?;
+
+    let mut device = HikDevice::new();
+    device.login(&args[1], &args[2], &args[3], port)?;
+
+    let samples = device.get_people_counting(channel, ReportGranularity::Hourly, start, end)?;
+    println!("Channel {} on {}", channel, yesterday);
+    println!(
+        "{:<6} {:>6} {:>6} {:>8}",
+        "Hour", "Enter", "Exit", "Pass-by"
+    );
+    let show = |count: Option<u32>| count.map_or("-".to_string(), |count| count.to_string());
+    for sample in &samples {
+        println!(
+            "{:<6} {:>6} {:>6} {:>8}",
+            sample.start.format("%H:%M"),
+            show(sample.enter),
+            show(sample.exit),
+            show(sample.pass_by)
+        );
+    }
+    let total: u32 = samples.iter().filter_map(|sample| sample.enter).sum();
+    let missing = samples.iter().filter(|sample| sample.is_missing()).count();
+    println!("Total entries: {}, hours without data: {}", total, missing);
+
+    device.logout()?;
+    common::cleanup()?;
+    Ok(())
+}
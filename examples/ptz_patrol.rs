@@ -0,0 +1,48 @@
+use std::{thread, time::Duration};
+
+use hik_net_sdk::{
+    common,
+    device::HikDevice,
+    ptz::{CruiseAction, CruiseStep},
+};
+
+const CRUISE_ROUTE: u8 = 1;
+
+// 用法: cargo run --example ptz_patrol -- <ip> <username> <password> <channel> <preset1> <preset2>
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 7 {
+        eprintln!(
+            "Usage: {} <ip> <username> <password> <channel> <preset1> <preset2>",
+            args[0]
+        );
+        std::process::exit(1);
+    }
+    let channel: u16 = args[4].parse()?;
+    let presets: [u16; 2] = [args[5].parse()?, args[6].parse()?];
+
+    common::init()?;
+
+    let mut device = HikDevice::new();
+    device.login(&args[1], &args[2], &args[3], 8000)?;
+
+    // 两个预置点之间往返，每个点停留 10 秒
+    let steps = presets
+        .iter()
+        .map(|&preset| CruiseStep {
+            preset,
+            speed: 20,
+            dwell_secs: 10,
+        })
+        .collect();
+    device.set_cruise_route(channel, CRUISE_ROUTE, steps)?;
+
+    println!("Running cruise route {} for 60 seconds", CRUISE_ROUTE);
+    device.ptz_cruise(channel, CruiseAction::Run, CRUISE_ROUTE, 0, 0)?;
+    thread::sleep(Duration::from_secs(60));
+    device.ptz_cruise(channel, CruiseAction::Stop, CRUISE_ROUTE, 0, 0)?;
+
+    device.logout()?;
+    common::cleanup()?;
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use hik_net_sdk::{common, device::HikDevice};
+
+// 用法: cargo run --example rename_channel -- <ip> <username> <password> [port]
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 {
+        eprintln!("Usage: {} <ip> <username> <password> [port]", args[0]);
+        std::process::exit(1);
+    }
+    let port = match args.get(4) {
+        Some(port) => port.parse()?,
+        None => 8000,
+    };
+
+    common::init()?;
+
+    let mut device = HikDevice::new();
+    device.login(&args[1], &args[2], &args[3], port)?;
+
+    let mut config = device.get_picture_config(1)?;
+    println!("Old name: {}", config.name);
+
+    // 中文名称会以 GBK 编码写入设备
+    config.name = "大门入口 01".to_string();
+    config.show_name = true;
+    device.set_picture_config(1, &config)?;
+
+    let config = device.get_picture_config(1)?;
+    println!("New name: {}", config.name);
+
+    device.logout()?;
+    common::cleanup()?;
+    Ok(())
+}
@@ -0,0 +1,80 @@
+#[cfg(feature = "rtsp-probe")]
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use hik_net_sdk::{
+    common,
+    device::HikDevice,
+    preview::StreamType,
+    rtsp::{CredentialStyle, RtspPathStyle},
+};
+
+// 用法: cargo run --example rtsp_url -- <ip> <username> <password> [port] [channel]
+// 打印 RTSP URL（不带凭据）；开启 rtsp-probe feature 时再发送 OPTIONS 请求确认设备在该端口上响应
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 {
+        eprintln!(
+            "Usage: {} <ip> <username> <password> [port] [channel]",
+            args[0]
+        );
+        std::process::exit(1);
+    }
+    let port = match args.get(4) {
+        Some(port) => port.parse()?,
+        None => 8000,
+    };
+    let channel = match args.get(5) {
+        Some(channel) => channel.parse()?,
+        None => 1,
+    };
+
+    common::init()?;
+
+    let mut device = HikDevice::new();
+    device.login(&args[1], &args[2], &args[3], port)?;
+
+    let style = device.rtsp_path_style()?;
+    let rtsp_port = device.get_rtsp_port()?;
+    println!("Path style: {:?}, RTSP port: {}", style, rtsp_port);
+
+    let streams: &[StreamType] = match style {
+        RtspPathStyle::Streaming => &[StreamType::Main, StreamType::Sub, StreamType::Third],
+        RtspPathStyle::Legacy => &[StreamType::Main, StreamType::Sub],
+    };
+    for stream in streams {
+        let url = device.rtsp_url(channel, *stream, CredentialStyle::None)?;
+        #[cfg(feature = "rtsp-probe")]
+        println!(
+            "{:?}: {} -> {}",
+            stream,
+            url,
+            options(&args[1], rtsp_port, &url)?
+        );
+        #[cfg(not(feature = "rtsp-probe"))]
+        println!("{:?}: {}", stream, url);
+    }
+
+    match device.get_multicast_info(channel, StreamType::Main) {
+        Ok(info) => println!("Multicast: {:?}", info),
+        Err(e) => println!("Multicast: {}", e),
+    }
+
+    device.logout()?;
+    common::cleanup()?;
+    Ok(())
+}
+
+// 返回响应的状态行，OPTIONS 不需要鉴权
+#[cfg(feature = "rtsp-probe")]
+fn options(host: &str, port: u16, url: &str) -> anyhow::Result<String> {
+    let mut stream = TcpStream::connect((host, port))?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    write!(stream, "OPTIONS {} RTSP/1.0\r\nCSeq: 1\r\n\r\n", url)?;
+    let mut status = String::new();
+    BufReader::new(stream).read_line(&mut status)?;
+    Ok(status.trim().to_string())
+}
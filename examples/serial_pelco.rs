@@ -0,0 +1,42 @@
+use std::{thread, time::Duration};
+
+use hik_net_sdk::{common, device::HikDevice, serial::SerialPortKind};
+
+// Pelco-D: FF 地址 命令1 命令2 数据1 数据2 校验和（前 5 个字节之和取低 8 位）
+fn pelco_d(address: u8, command: u8, pan_speed: u8, tilt_speed: u8) -> [u8; 7] {
+    let mut frame = [0xFF, address, 0x00, command, pan_speed, tilt_speed, 0];
+    frame[6] = frame[1..6].iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    frame
+}
+
+// 用法: cargo run --example serial_pelco -- <ip> <username> <password> [channel]
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 {
+        eprintln!("Usage: {} <ip> <username> <password> [channel]", args[0]);
+        std::process::exit(1);
+    }
+    let channel = match args.get(4) {
+        Some(channel) => channel.parse()?,
+        None => 1,
+    };
+
+    common::init()?;
+
+    let mut device = HikDevice::new();
+    device.login(&args[1], &args[2], &args[3], 8000)?;
+
+    let mut serial = device.open_serial(SerialPortKind::Rs485, channel)?;
+    serial.on_receive(|data| println!("Received: {:02X?}", data));
+
+    // 向右转动 1 秒后停止
+    serial.send(&pelco_d(1, 0x02, 0x20, 0x00))?;
+    thread::sleep(Duration::from_secs(1));
+    serial.send(&pelco_d(1, 0x00, 0x00, 0x00))?;
+    thread::sleep(Duration::from_millis(500));
+
+    serial.close()?;
+    device.logout()?;
+    common::cleanup()?;
+    Ok(())
+}
@@ -1,28 +1,41 @@
 use axum::{
-    body::Bytes,
+    Router,
+    body::{Body, Bytes},
     extract::{Path, Query, Request, State},
     http::{HeaderMap, HeaderValue, StatusCode},
     response::{Html, IntoResponse, Json, Response},
     routing::{get, post},
-    Router,
 };
 use chrono::{Local, NaiveDateTime, TimeZone};
 use hik_net_sdk::{
+    cancel::CancellationToken,
     common,
-    device::{Channel, HikDevice},
+    device::{DownloadOptions, HikDevice},
+    manager::{Credentials, DeviceAddress, DeviceHandle, HikDeviceManager, ManagerOptions},
+    preview::StreamType,
+    remux::Mp4Segment,
 };
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, hash_map::RandomState},
     fs,
+    hash::{BuildHasher, Hasher},
     path::PathBuf,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 use tokio::fs as tokio_fs;
+use tokio_stream::wrappers::ReceiverStream;
 
 // 嵌入 HTML 文件到程序中
 const INDEX_HTML: &str = include_str!("web_index.html");
 
+// 浏览器会话多久不用后释放设备句柄
+const WEB_SESSION_TTL: Duration = Duration::from_secs(30 * 60);
+// 设备会话没有任何句柄后保留多久，期间重新登录直接复用
+const DEVICE_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+const MAX_DEVICE_SESSIONS: usize = 32;
+
 // 统一的错误响应类型
 #[derive(Serialize)]
 struct ErrorResponse {
@@ -65,12 +78,72 @@ impl From<axum::http::header::InvalidHeaderValue> for AppError {
     }
 }
 
+// 浏览器会话持有设备句柄；同一设备和用户的多个浏览器会话共用一个登录，
+// 全部会话注销或过期后由 HikDeviceManager 注销设备
+struct WebSession {
+    device: DeviceHandle,
+    last_used: Instant,
+}
+
+// std Mutex 只在查找/插入会话时短暂持有，SDK 调用放到阻塞线程池
 #[derive(Clone)]
 struct AppState {
-    devices: Arc<Mutex<HashMap<String, HikDevice>>>,
+    manager: HikDeviceManager,
+    sessions: Arc<Mutex<HashMap<String, WebSession>>>,
     images_dir: PathBuf,
 }
 
+impl AppState {
+    fn device(&self, params: &HashMap<String, String>) -> Result<DeviceHandle, AppError> {
+        let session_id = params
+            .get("session_id")
+            .ok_or_else(|| anyhow::anyhow!("session_id is required"))?;
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| anyhow::anyhow!("Device not found. Please login first."))?;
+        session.last_used = Instant::now();
+        Ok(session.device.clone())
+    }
+
+    // 句柄在锁外释放，最后一个句柄释放时可能触发注销
+    fn expire_sessions(&self) {
+        let expired: Vec<WebSession> = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let ids: Vec<String> = sessions
+                .iter()
+                .filter(|(_, session)| session.last_used.elapsed() >= WEB_SESSION_TTL)
+                .map(|(id, _)| id.clone())
+                .collect();
+            ids.iter().filter_map(|id| sessions.remove(id)).collect()
+        };
+        drop(expired);
+    }
+}
+
+// SDK 调用都是阻塞的，句柄随闭包移入阻塞线程，调用期间登录不会被注销
+async fn blocking<T, F>(device: DeviceHandle, op: F) -> Result<T, AppError>
+where
+    F: FnOnce(&HikDevice) -> anyhow::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let result = tokio::task::spawn_blocking(move || op(&device))
+        .await
+        .map_err(|e| anyhow::anyhow!("Blocking task failed: {}", e))?;
+    Ok(result?)
+}
+
+fn new_session_id() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    format!("{:016x}", hasher.finish())
+}
+
 #[derive(Deserialize)]
 struct LoginRequest {
     host: String,
@@ -86,12 +159,26 @@ struct LoginResponse {
     session_id: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct LogoutRequest {
+    session_id: String,
+}
+
+#[derive(Serialize)]
+struct StatsResponse {
+    active_sessions: usize,
+    idle_sessions: usize,
+    total_logins: u64,
+    web_sessions: usize,
+}
+
 #[derive(Serialize)]
 struct ChannelInfo {
     channel_num: u16,
     channel_type: String,
     enabled: bool,
     ipv4_address: Option<String>,
+    name: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -135,20 +222,38 @@ async fn main() -> anyhow::Result<()> {
         fs::create_dir_all(&images_dir).expect("Failed to create images directory");
     }
 
+    // init
+    common::init()?;
+
     let app_state = AppState {
-        devices: Arc::new(Mutex::new(HashMap::new())),
+        manager: HikDeviceManager::new(ManagerOptions {
+            max_sessions: Some(MAX_DEVICE_SESSIONS),
+            idle_timeout: Some(DEVICE_IDLE_TIMEOUT),
+            ..Default::default()
+        }),
+        sessions: Arc::new(Mutex::new(HashMap::new())),
         images_dir,
     };
 
-    // init
-    common::init()?;
+    // 定期清理长时间不用的浏览器会话
+    let expire_state = app_state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            expire_state.expire_sessions();
+        }
+    });
 
     let app = Router::new()
         .route("/", get(index))
         .route("/api/login", post(login))
+        .route("/api/logout", post(logout))
+        .route("/api/stats", get(stats))
         .route("/api/channels", get(get_channels))
         .route("/api/capture", post(capture_image))
         .route("/api/download", post(download_recording))
+        .route("/api/stream/{channel}", get(stream_channel))
         .route("/images/{filename}", get(get_image))
         .route("/recordings/{filename}", get(get_recording))
         .route("/play/{filename}", get(play_recording))
@@ -169,12 +274,26 @@ async fn login(
     State(state): State<AppState>,
     Json(req): Json<LoginRequest>,
 ) -> Result<Json<LoginResponse>, AppError> {
-    let mut device = HikDevice::new();
-    device.login(&req.host, &req.username, &req.password, req.port)?;
-
-    let session_id = format!("{}_{}", req.host, req.port);
-    let mut devices = state.devices.lock().unwrap();
-    devices.insert(session_id.clone(), device);
+    let manager = state.manager.clone();
+    let device = tokio::task::spawn_blocking(move || {
+        manager.connect(
+            DeviceAddress::new(&req.host, req.port),
+            Credentials::new(&req.username, &req.password),
+        )
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Blocking task failed: {}", e))??;
+
+    let session_id = new_session_id();
+    let session = WebSession {
+        device,
+        last_used: Instant::now(),
+    };
+    state
+        .sessions
+        .lock()
+        .unwrap()
+        .insert(session_id.clone(), session);
 
     Ok(Json(LoginResponse {
         success: true,
@@ -183,36 +302,47 @@ async fn login(
     }))
 }
 
+// 只释放该浏览器会话的句柄，设备登录在没有其他句柄并空闲超时后注销
+async fn logout(
+    State(state): State<AppState>,
+    Json(req): Json<LogoutRequest>,
+) -> Json<LoginResponse> {
+    let session = state.sessions.lock().unwrap().remove(&req.session_id);
+    Json(LoginResponse {
+        success: session.is_some(),
+        message: if session.is_some() {
+            "Logged out".to_string()
+        } else {
+            "Session not found".to_string()
+        },
+        session_id: None,
+    })
+}
+
+async fn stats(State(state): State<AppState>) -> Json<StatsResponse> {
+    let stats = state.manager.stats();
+    Json(StatsResponse {
+        active_sessions: stats.active_sessions,
+        idle_sessions: stats.idle_sessions,
+        total_logins: stats.total_logins,
+        web_sessions: state.sessions.lock().unwrap().len(),
+    })
+}
+
 async fn get_channels(
     State(state): State<AppState>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<ChannelsResponse>, AppError> {
-    let session_id = params
-        .get("session_id")
-        .ok_or_else(|| anyhow::anyhow!("session_id is required"))?;
-
-    let devices = state.devices.lock().unwrap();
-    let device = devices
-        .get(session_id)
-        .ok_or_else(|| anyhow::anyhow!("Device not found. Please login first."))?;
-
-    let channels = device.get_channels()?;
+    let channels = blocking(state.device(&params)?, |device| device.get_channels()).await?;
 
     let channel_infos: Vec<ChannelInfo> = channels
         .iter()
-        .map(|ch| match ch {
-            Channel::Logic(info) => ChannelInfo {
-                channel_num: info.get_chan_num(),
-                channel_type: "Logic".to_string(),
-                enabled: info.is_enabled(),
-                ipv4_address: None,
-            },
-            Channel::IP(info) => ChannelInfo {
-                channel_num: info.get_chan_num(),
-                channel_type: "IP".to_string(),
-                enabled: info.is_enabled(),
-                ipv4_address: info.get_ipv4_address().cloned(),
-            },
+        .map(|ch| ChannelInfo {
+            channel_num: ch.chan_num(),
+            channel_type: if ch.is_ip() { "IP" } else { "Logic" }.to_string(),
+            enabled: ch.info().is_enabled(),
+            ipv4_address: ch.info().ipv4_address().map(str::to_string),
+            name: ch.info().name().map(str::to_string),
         })
         .collect();
 
@@ -228,14 +358,7 @@ async fn capture_image(
     Query(params): Query<HashMap<String, String>>,
     Json(req): Json<CaptureImageRequest>,
 ) -> Result<Json<CaptureImageResponse>, AppError> {
-    let session_id = params
-        .get("session_id")
-        .ok_or_else(|| anyhow::anyhow!("session_id is required"))?;
-
-    let devices = state.devices.lock().unwrap();
-    let device = devices
-        .get(session_id)
-        .ok_or_else(|| anyhow::anyhow!("Device not found. Please login first."))?;
+    let device = state.device(&params)?;
 
     let filename = format!(
         "channel_{}_{}.jpg",
@@ -244,14 +367,11 @@ async fn capture_image(
     );
     let filepath = state.images_dir.join(&filename);
 
-    device.capture_jpeg_picture(req.channel, filepath.to_str().unwrap())?;
-
-    // 检查文件是否存在
-    if !filepath.exists() {
-        return Err(AppError::from(anyhow::anyhow!(
-            "Image file not found after capture"
-        )));
-    }
+    let data = blocking(device, move |device| {
+        device.capture_jpeg_data(req.channel, Default::default())
+    })
+    .await?;
+    tokio_fs::write(&filepath, data).await?;
 
     Ok(Json(CaptureImageResponse {
         success: true,
@@ -265,14 +385,7 @@ async fn download_recording(
     Query(params): Query<HashMap<String, String>>,
     Json(req): Json<DownloadRequest>,
 ) -> Result<Json<DownloadResponse>, AppError> {
-    let session_id = params
-        .get("session_id")
-        .ok_or_else(|| anyhow::anyhow!("session_id is required"))?;
-
-    let devices = state.devices.lock().unwrap();
-    let device = devices
-        .get(session_id)
-        .ok_or_else(|| anyhow::anyhow!("Device not found. Please login first."))?;
+    let device = state.device(&params)?;
 
     // 解析时间字符串
     let start_time =
@@ -286,7 +399,7 @@ async fn download_recording(
         _ => {
             return Err(AppError::from(anyhow::anyhow!(
                 "Invalid start_time: ambiguous or non-existent time"
-            )))
+            )));
         }
     };
 
@@ -301,16 +414,11 @@ async fn download_recording(
         _ => {
             return Err(AppError::from(anyhow::anyhow!(
                 "Invalid end_time: ambiguous or non-existent time"
-            )))
+            )));
         }
     };
 
-    // 从 session_id 中提取设备 IP（格式：{host}_{port}）
-    let device_ip = session_id
-        .split('_')
-        .next()
-        .unwrap_or("unknown")
-        .replace('.', "_");
+    let device_ip = device.address().host.replace(['.', ':'], "_");
 
     let filename = format!(
         "recording_{}_ch{}_{}_{}.mp4",
@@ -326,35 +434,113 @@ async fn download_recording(
         fs::create_dir_all(parent)?;
     }
 
-    let mut download = device.get_file_by_time(
-        filepath.to_str().unwrap(),
-        req.channel,
-        start_time,
-        end_time,
-    )?;
+    let file = filepath.to_string_lossy().into_owned();
+    let download = blocking(device.clone(), move |device| {
+        let mut download = device.get_file_by_time_with(
+            &file,
+            req.channel,
+            start_time,
+            end_time,
+            DownloadOptions::default(),
+        )?;
+        download.start()?;
+        Ok(download)
+    })
+    .await?;
+
+    // 下载与设备句柄随后台任务存活，下载期间浏览器会话过期也不会注销设备
+    tokio::task::spawn_blocking(move || {
+        let _device = device;
+        if let Err(e) = download.wait(&CancellationToken::new()) {
+            eprintln!("Download failed: {}", e);
+        }
+    });
 
-    download.start()?;
+    Ok(Json(DownloadResponse {
+        success: true,
+        message: "Download started".to_string(),
+        download_id: Some(filename),
+    }))
+}
 
-    // 异步等待下载完成（简化版本，实际应该用后台任务）
+// 实时预览转封装为 fMP4，浏览器通过 MediaSource 播放
+async fn stream_channel(
+    State(state): State<AppState>,
+    Path(channel): Path<u16>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let device = state.device(&params)?;
+    let (streamer, mut segments) = blocking(device.clone(), move |device| {
+        device.start_stream_channel(channel, StreamType::Main, 256)
+    })
+    .await?;
+
+    // 第一个片段是收到 I 帧后生成的初始化段，其中带有 MSE 需要的 codec
+    let first = match tokio::time::timeout(Duration::from_secs(10), segments.recv()).await {
+        Ok(Some(segment)) => segment,
+        _ => {
+            return Err(AppError::from(streamer.take_error().unwrap_or_else(|| {
+                anyhow::anyhow!("No key frame received within 10 seconds")
+            })));
+        }
+    };
+    let codec = match &first {
+        Mp4Segment::Init { codec, .. } => codec.clone(),
+        Mp4Segment::Media { .. } => {
+            return Err(AppError::from(anyhow::anyhow!(
+                "Stream did not start with an init segment"
+            )));
+        }
+    };
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Vec<u8>, std::io::Error>>(16);
+    // streamer 和设备句柄随转发任务存活，浏览器断开后停止预览
     tokio::spawn(async move {
+        let _device = device;
+        if tx.send(Ok(first.into_data())).await.is_err() {
+            return;
+        }
+        let mut health_check = tokio::time::interval(Duration::from_secs(1));
         loop {
-            match download.get_progress() {
-                Ok(progress) => {
-                    if progress >= 100 {
+            tokio::select! {
+                segment = segments.recv() => {
+                    let Some(segment) = segment else { break };
+                    if tx.send(Ok(segment.into_data())).await.is_err() {
                         break;
                     }
                 }
-                Err(_) => break,
+                _ = health_check.tick() => {
+                    if !streamer.is_healthy() {
+                        let message = streamer
+                            .take_error()
+                            .map(|e| e.to_string())
+                            .unwrap_or_else(|| "Preview disconnected".to_string());
+                        let _ = tx.send(Err(std::io::Error::other(message))).await;
+                        break;
+                    }
+                }
+                _ = tx.closed() => break,
             }
-            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
         }
     });
 
-    Ok(Json(DownloadResponse {
-        success: true,
-        message: "Download started".to_string(),
-        download_id: Some(filename),
-    }))
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        axum::http::header::CONTENT_TYPE,
+        HeaderValue::from_static("video/mp4"),
+    );
+    headers.insert(
+        axum::http::header::CACHE_CONTROL,
+        HeaderValue::from_static("no-store"),
+    );
+    headers.insert("x-mp4-codec", HeaderValue::from_str(&codec)?);
+
+    Ok((
+        StatusCode::OK,
+        headers,
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response())
 }
 
 async fn get_image(Path(filename): Path<String>) -> Result<Response, AppError> {
@@ -0,0 +1,191 @@
+use std::os::raw::c_char;
+
+use crate::{
+    DEVICE_ABILITY_INFO, DEVICE_ALARM_ABILITY, DEVICE_ENCODE_ALL_ABILITY, DEVICE_JPEG_CAP_ABILITY,
+    DEVICE_NETWORK_ABILITY, DEVICE_SOFTHARDWARE_ABILITY, DEVICE_USER_ABILITY, DWORD,
+    IP_VIEW_DEV_ABILITY, NET_DVR_GetDeviceAbility, NET_DVR_NOENOUGH_BUF,
+    common::get_last_error_code, device::HikDevice, trace::sdk_call,
+};
+
+const INITIAL_OUT_BUFFER_SIZE: usize = 64 * 1024;
+const MAX_OUT_BUFFER_SIZE: usize = 16 * 1024 * 1024;
+
+// 出现其中任一节点即认为支持 Smart 事件
+const SMART_EVENT_TAGS: [&str; 6] = [
+    "LineDetection",
+    "FieldDetection",
+    "RegionEntrance",
+    "RegionExiting",
+    "traversingVirtualPlane",
+    "fieldDetection",
+];
+// 不同固件中最大 IP 通道数的节点名称不一致
+const MAX_IP_CHANNEL_TAGS: [&str; 3] = ["MaxIPChannelNum", "maxIPChanNum", "IPChanNum"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbilityType {
+    SoftHardware,
+    Network,
+    EncodeAll,
+    Alarm,
+    User,
+    JpegCapture,
+    // 通用能力集，需要传入描述查询内容的 XML
+    DeviceAbilityInfo,
+    IpView,
+    Other(u32),
+}
+
+impl From<AbilityType> for DWORD {
+    fn from(value: AbilityType) -> Self {
+        match value {
+            AbilityType::SoftHardware => DEVICE_SOFTHARDWARE_ABILITY,
+            AbilityType::Network => DEVICE_NETWORK_ABILITY,
+            AbilityType::EncodeAll => DEVICE_ENCODE_ALL_ABILITY,
+            AbilityType::Alarm => DEVICE_ALARM_ABILITY,
+            AbilityType::User => DEVICE_USER_ABILITY,
+            AbilityType::JpegCapture => DEVICE_JPEG_CAP_ABILITY,
+            AbilityType::DeviceAbilityInfo => DEVICE_ABILITY_INFO,
+            AbilityType::IpView => IP_VIEW_DEV_ABILITY,
+            AbilityType::Other(other) => other,
+        }
+    }
+}
+
+impl HikDevice {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_device_ability(
+        &self,
+        ability_type: AbilityType,
+        in_xml: Option<&str>,
+    ) -> anyhow::Result<String> {
+        let lu = self.user_id()?;
+        let mut in_buffer = in_xml
+            .map(|xml| xml.as_bytes().to_vec())
+            .unwrap_or_default();
+        let (in_ptr, in_len) = if in_buffer.is_empty() {
+            (std::ptr::null_mut(), 0)
+        } else {
+            (
+                in_buffer.as_mut_ptr() as *mut c_char,
+                in_buffer.len() as DWORD,
+            )
+        };
+
+        let mut out_size = INITIAL_OUT_BUFFER_SIZE;
+        loop {
+            let mut out_buffer = vec![0u8; out_size];
+            let res = unsafe {
+                sdk_call!(NET_DVR_GetDeviceAbility(
+                    lu,
+                    ability_type.into(),
+                    in_ptr,
+                    in_len,
+                    out_buffer.as_mut_ptr() as *mut c_char,
+                    out_buffer.len() as DWORD,
+                ))
+            };
+            if res == 1 {
+                let len = out_buffer
+                    .iter()
+                    .position(|&b| b == 0)
+                    .unwrap_or(out_buffer.len());
+                out_buffer.truncate(len);
+                return Ok(String::from_utf8_lossy(&out_buffer).into_owned());
+            }
+
+            let error_code = get_last_error_code();
+            if error_code == NET_DVR_NOENOUGH_BUF as i32 && out_size < MAX_OUT_BUFFER_SIZE {
+                out_size *= 4;
+                continue;
+            }
+            return Err(anyhow::anyhow!(
+                "Get device ability failed: error code {}",
+                error_code
+            ));
+        }
+    }
+
+    // 智能（VCA）通道能力，通过通用能力集查询
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn get_vca_ability(&self, channel: u16) -> anyhow::Result<String> {
+        self.resolve_channel(channel)?;
+        let in_xml = format!(
+            "<VcaChanAbility version=\"2.0\"><channelNO>{}</channelNO></VcaChanAbility>",
+            channel
+        );
+        self.get_device_ability(AbilityType::DeviceAbilityInfo, Some(&in_xml))
+    }
+
+    // 查询失败（包括设备不支持该能力集）时返回 false
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn supports_smart_events(&self) -> bool {
+        let in_xml = "<EventAbility version=\"2.0\"><channelNO>1</channelNO></EventAbility>";
+        match self.get_device_ability(AbilityType::DeviceAbilityInfo, Some(in_xml)) {
+            Ok(xml) => SMART_EVENT_TAGS
+                .iter()
+                .any(|tag| xml_tag_value(&xml, tag).is_some()),
+            Err(_) => false,
+        }
+    }
+
+    // 能力集中没有相关字段时使用登录返回的设备信息
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn max_ip_channels(&self) -> u16 {
+        let from_xml = self
+            .get_device_ability(AbilityType::IpView, None)
+            .ok()
+            .and_then(|xml| {
+                MAX_IP_CHANNEL_TAGS
+                    .iter()
+                    .find_map(|tag| xml_tag_value(&xml, tag)?.trim().parse().ok())
+            });
+        from_xml.unwrap_or_else(|| {
+            self.get_device_info()
+                .map(|info| {
+                    let v30 = info.v30();
+                    v30.byIPChanNum as u16 + v30.byHighDChanNum as u16 * 256
+                })
+                .unwrap_or(0)
+        })
+    }
+}
+
+// 只取第一个同名节点的文本，能力集 XML 结构简单，不需要完整的解析器
+pub(crate) fn xml_tag_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let mut search = xml;
+    loop {
+        let start = search.find('<')?;
+        let rest = &search[start + 1..];
+        let name_end = rest.find(|c: char| c == '>' || c == '/' || c.is_whitespace())?;
+        if &rest[..name_end] != tag {
+            search = rest;
+            continue;
+        }
+
+        let open_end = rest.find('>')?;
+        // <tag/> 这样的空节点
+        if rest[..open_end].ends_with('/') {
+            return Some("");
+        }
+        let content = &rest[open_end + 1..];
+        let close = format!("</{}>", tag);
+        return Some(content.find(&close).map_or(content, |end| &content[..end]));
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    collections::HashMap,
+    mem,
+    os::raw::{c_char, c_void},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+
+use crate::{
+    DWORD, LONG, NET_DVR_ALARMER, NET_DVR_CloseAlarmChan_V30, NET_DVR_SETUPALARM_PARAM,
+    NET_DVR_SetupAlarmChan_V41,
+    common::{HandleKind, ensure_message_callback, unwatch_handle, watch_handle},
+    device::{HikDevice, ip_chan_num, sdk_error},
+    error::HikError,
+    events::{AlarmEvent, ip_channel_states},
+    ffi_util::{call_user_callback, lock_unpoisoned},
+    session::SessionState,
+    trace::sdk_call,
+};
+
+type AlarmHandler = Arc<dyn Fn(AlarmEvent) + Send + Sync>;
+
+type AlarmRoutes = HashMap<LONG, Vec<(u64, AlarmHandler)>>;
+
+static NEXT_ROUTE_ID: AtomicU64 = AtomicU64::new(1);
+
+// 按登录句柄分发报警，同一句柄可以有多个 handler
+fn alarm_routes() -> &'static Mutex<AlarmRoutes> {
+    static ROUTES: OnceLock<Mutex<AlarmRoutes>> = OnceLock::new();
+    ROUTES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// NVR 每次上传完整的 IP 通道表，按登录句柄记住上次的在线状态，只分发发生变化的通道
+struct ChannelStatusTracker {
+    ip_start: u8,
+    last: HashMap<u16, bool>,
+}
+
+fn channel_trackers() -> &'static Mutex<HashMap<LONG, ChannelStatusTracker>> {
+    static TRACKERS: OnceLock<Mutex<HashMap<LONG, ChannelStatusTracker>>> = OnceLock::new();
+    TRACKERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn channel_status_changes(user_id: LONG, states: &[(u16, bool)]) -> Vec<AlarmEvent> {
+    let mut trackers = lock_unpoisoned(channel_trackers());
+    let Some(tracker) = trackers.get_mut(&user_id) else {
+        return Vec::new();
+    };
+    states
+        .iter()
+        .filter_map(|&(index, online)| {
+            let channel = ip_chan_num(tracker.ip_start, index);
+            (tracker.last.insert(channel, online) != Some(online))
+                .then_some(AlarmEvent::ChannelStatusChanged { channel, online })
+        })
+        .collect()
+}
+
+unsafe extern "C" fn message_callback(
+    command: LONG,
+    alarmer: *mut NET_DVR_ALARMER,
+    info: *mut c_char,
+    len: DWORD,
+    _user: *mut c_void,
+) {
+    if alarmer.is_null() {
+        return;
+    }
+    let alarmer = unsafe { &*alarmer };
+    if alarmer.byUserIDValid == 0 {
+        return;
+    }
+    // 先复制一份，避免 handler 内部取消订阅时死锁
+    let handlers: Vec<AlarmHandler> = match lock_unpoisoned(alarm_routes()).get(&alarmer.lUserID) {
+        Some(handlers) => handlers.iter().map(|(_, h)| h.clone()).collect(),
+        None => return,
+    };
+    if !info.is_null() {
+        let data = unsafe { std::slice::from_raw_parts(info as *const u8, len as usize) };
+        if let Some(states) = ip_channel_states(command as u32, data) {
+            for event in channel_status_changes(alarmer.lUserID, &states) {
+                dispatch(&handlers, &event);
+            }
+            return;
+        }
+    }
+    let event = match unsafe { AlarmEvent::decode(command, info, len) } {
+        Ok(event) => event,
+        Err(_e) => {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(command, error = %_e, "Decode alarm failed");
+            return;
+        }
+    };
+    dispatch(&handlers, &event);
+}
+
+// 某个 handler panic 不影响其他 handler
+fn dispatch(handlers: &[AlarmHandler], event: &AlarmEvent) {
+    for handler in handlers {
+        call_user_callback("alarm", || handler(event.clone()));
+    }
+}
+
+/// 报警布防，丢弃时撤防
+///
+/// handler 在 SDK 的回调线程中执行，不能在其中调用 SDK 接口或长时间阻塞
+pub struct AlarmSubscription {
+    handle: LONG,
+    // 登出时由会话撤防
+    session: Arc<SessionState>,
+    user_id: LONG,
+    route_id: u64,
+    // 由异常回调置为 false，SDK 重连成功后恢复
+    healthy: Arc<AtomicBool>,
+    closed: bool,
+}
+
+fn add_route(user_id: LONG, handler: AlarmHandler) -> u64 {
+    let route_id = NEXT_ROUTE_ID.fetch_add(1, Ordering::Relaxed);
+    lock_unpoisoned(alarm_routes())
+        .entry(user_id)
+        .or_default()
+        .push((route_id, handler));
+    route_id
+}
+
+// 先移除 handler 再撤防
+fn close_alarm(user_id: LONG, route_id: u64, handle: LONG) -> i32 {
+    if let Some(handlers) = lock_unpoisoned(alarm_routes()).get_mut(&user_id) {
+        handlers.retain(|(id, _)| *id != route_id);
+        if handlers.is_empty() {
+            lock_unpoisoned(channel_trackers()).remove(&user_id);
+        }
+    }
+    unsafe { sdk_call!(NET_DVR_CloseAlarmChan_V30(handle)) }
+}
+
+impl AlarmSubscription {
+    // 布防成功后登记到会话，登出时由会话撤防
+    fn register(session: Arc<SessionState>, user_id: LONG, route_id: u64, handle: LONG) -> Self {
+        session.register(HandleKind::Alarm, handle, move || {
+            close_alarm(user_id, route_id, handle);
+        });
+        Self {
+            handle,
+            session,
+            user_id,
+            route_id,
+            healthy: watch_handle(HandleKind::Alarm, user_id, handle),
+            closed: false,
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::SeqCst)
+    }
+
+    pub fn stop(&mut self) -> anyhow::Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        if !self.session.unregister(HandleKind::Alarm, self.handle) {
+            return Err(HikError::SessionClosed.into());
+        }
+        unwatch_handle(HandleKind::Alarm, self.handle);
+        if close_alarm(self.user_id, self.route_id, self.handle) != 1 {
+            return Err(sdk_error("Close alarm channel"));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for AlarmSubscription {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+impl HikDevice {
+    /// 布防并接收该设备上传的报警
+    ///
+    /// 大多数设备对同一登录句柄只允许布防一次，需要多个接收方时在 handler 中自行分发
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn subscribe_alarms<F>(&self, handler: F) -> anyhow::Result<AlarmSubscription>
+    where
+        F: Fn(AlarmEvent) + Send + Sync + 'static,
+    {
+        ensure_message_callback(Some(message_callback))?;
+        let user_id = self.user_id()?;
+        self.track_channel_status(user_id);
+        // 先登记 handler，避免布防后第一条报警丢失
+        let route_id = add_route(user_id, Arc::new(handler));
+
+        let mut param = NET_DVR_SETUPALARM_PARAM {
+            dwSize: mem::size_of::<NET_DVR_SETUPALARM_PARAM>() as DWORD,
+            // 中等优先级，智能报警使用新结构体，普通报警仍按 V30 上传
+            byLevel: 1,
+            byAlarmInfoType: 1,
+            ..Default::default()
+        };
+        let handle = unsafe { sdk_call!(NET_DVR_SetupAlarmChan_V41(user_id, &mut param)) };
+        if handle < 0 {
+            let error = sdk_error("Setup alarm channel");
+            if let Some(handlers) = lock_unpoisoned(alarm_routes()).get_mut(&user_id) {
+                handlers.retain(|(id, _)| *id != route_id);
+            }
+            return Err(error);
+        }
+        Ok(AlarmSubscription::register(
+            self.session.state(),
+            user_id,
+            route_id,
+            handle,
+        ))
+    }
+
+    // 用当前的通道状态作为比较基准，避免布防后第一次上传把所有通道都当作变化；查询失败时从空状态开始
+    fn track_channel_status(&self, user_id: LONG) {
+        let Some(info) = self.get_device_info() else {
+            return;
+        };
+        if info.ip_channel_count() == 0 {
+            return;
+        }
+        let ip_start = info.ip_start();
+        // 同一登录句柄重复布防时沿用已有状态
+        if channel_trackers().lock().unwrap().contains_key(&user_id) {
+            return;
+        }
+        let last = self
+            .get_channel_status()
+            .map(|status| status.into_iter().collect())
+            .unwrap_or_default();
+        channel_trackers()
+            .lock()
+            .unwrap()
+            .entry(user_id)
+            .or_insert(ChannelStatusTracker { ip_start, last });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ptr, sync::atomic::AtomicUsize};
+
+    use super::*;
+
+    fn route_count(user_id: LONG) -> usize {
+        alarm_routes()
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .map_or(0, Vec::len)
+    }
+
+    #[test]
+    fn panicking_handler_does_not_break_dispatch_or_unsubscribe() {
+        // 只在本测试中使用的登录句柄与布防句柄
+        let user_id = 9001;
+        let session = Arc::new(SessionState::default());
+        let received = Arc::new(AtomicUsize::new(0));
+
+        let panicking = add_route(user_id, Arc::new(|_| panic!("handler panicked")));
+        let mut panicking = AlarmSubscription::register(session.clone(), user_id, panicking, 9101);
+        let counter = received.clone();
+        let counting = add_route(
+            user_id,
+            Arc::new(move |event| {
+                assert!(matches!(
+                    event,
+                    AlarmEvent::Other {
+                        command: 0x9999,
+                        ..
+                    }
+                ));
+                counter.fetch_add(1, Ordering::SeqCst);
+            }),
+        );
+        let counting = AlarmSubscription::register(session.clone(), user_id, counting, 9102);
+
+        let mut alarmer = NET_DVR_ALARMER {
+            byUserIDValid: 1,
+            lUserID: user_id,
+            ..Default::default()
+        };
+        let mut data = [1u8, 2, 3];
+        for _ in 0..2 {
+            unsafe {
+                message_callback(
+                    0x9999,
+                    &mut alarmer,
+                    data.as_mut_ptr() as *mut c_char,
+                    data.len() as DWORD,
+                    ptr::null_mut(),
+                )
+            };
+        }
+        assert_eq!(received.load(Ordering::SeqCst), 2);
+        assert_eq!(route_count(user_id), 2);
+        assert_eq!(
+            session.handles(),
+            [(HandleKind::Alarm, 9101), (HandleKind::Alarm, 9102)]
+        );
+
+        // 测试中 NET_DVR_CloseAlarmChan_V30 返回失败，句柄仍需从会话中移除
+        let _ = panicking.stop();
+        assert_eq!(route_count(user_id), 1);
+        assert_eq!(session.handles(), [(HandleKind::Alarm, 9102)]);
+
+        drop(counting);
+        assert_eq!(route_count(user_id), 0);
+        assert!(session.handles().is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+use serde_json::Value;
+
+use crate::{
+    device::HikDevice,
+    error::HikError,
+    isapi::{IsapiMethod, IsapiResponse},
+};
+
+// 报警主机（AX 系列等）的布撤防与防区控制都走 ISAPI SecurityCP 接口
+const SECURITY_CP_CAPABILITIES: &str = "/ISAPI/SecurityCP/capabilities";
+const ZONE_STATUS_URL: &str = "/ISAPI/SecurityCP/status/zones?format=json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ArmMode {
+    // 外出布防，所有防区生效
+    #[default]
+    Away,
+    // 留守布防，内部防区不生效
+    Stay,
+}
+
+impl ArmMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            ArmMode::Away => "away",
+            ArmMode::Stay => "stay",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ZoneStatus {
+    pub zone: u32,
+    pub name: String,
+    // 所属子系统（分区），设备未返回时为 None
+    pub partition: Option<u32>,
+    pub armed: bool,
+    pub alarm: bool,
+    // 离线、故障或心跳异常
+    pub fault: bool,
+    pub bypassed: bool,
+    pub tamper: bool,
+}
+
+impl ZoneStatus {
+    fn from_json(zone: &Value) -> Option<Self> {
+        let flag = |key: &str| zone.get(key).and_then(Value::as_bool).unwrap_or(false);
+        let status = zone.get("status").and_then(Value::as_str).unwrap_or("");
+        Some(Self {
+            zone: zone.get("id")?.as_u64()? as u32,
+            name: zone
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            partition: zone
+                .get("subSystemNo")
+                .and_then(Value::as_u64)
+                .map(|no| no as u32),
+            armed: flag("armed"),
+            alarm: flag("alarm"),
+            fault: matches!(
+                status,
+                "offline" | "breakDown" | "breakdown" | "heartbeatAbnormal"
+            ),
+            bypassed: flag("bypassed"),
+            tamper: flag("tamperEvident"),
+        })
+    }
+}
+
+impl HikDevice {
+    // partition 为子系统号，从 1 开始
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), partition = partition)
+        )
+    )]
+    pub fn arm_partition(&self, partition: u32, mode: ArmMode) -> anyhow::Result<()> {
+        let url = format!(
+            "/ISAPI/SecurityCP/control/arm/{}?ways={}",
+            partition,
+            mode.as_str()
+        );
+        self.security_cp_put(&url, "Arm partition")
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), partition = partition)
+        )
+    )]
+    pub fn disarm_partition(&self, partition: u32) -> anyhow::Result<()> {
+        let url = format!("/ISAPI/SecurityCP/control/disarm/{}", partition);
+        self.security_cp_put(&url, "Disarm partition")
+    }
+
+    // bypass 为 false 时恢复旁路的防区
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host(), zone = zone))
+    )]
+    pub fn bypass_zone(&self, zone: u32, bypass: bool) -> anyhow::Result<()> {
+        let (url, action) = if bypass {
+            (
+                format!("/ISAPI/SecurityCP/control/bypass/{}", zone),
+                "Bypass zone",
+            )
+        } else {
+            (
+                format!("/ISAPI/SecurityCP/control/bypassRecover/{}", zone),
+                "Recover zone",
+            )
+        };
+        self.security_cp_put(&url, action)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_zone_status(&self) -> anyhow::Result<Vec<ZoneStatus>> {
+        let response = self.isapi_request(IsapiMethod::Get, ZONE_STATUS_URL, None)?;
+        let response = self.check_security_cp(response, "Get zone status")?;
+        let json: Value = serde_json::from_slice(&response.body)?;
+        let zones = json
+            .get("ZoneList")
+            .and_then(Value::as_array)
+            .ok_or(anyhow::anyhow!("Zone status response has no ZoneList"))?;
+        Ok(zones
+            .iter()
+            .filter_map(|item| ZoneStatus::from_json(item.get("Zone")?))
+            .collect())
+    }
+
+    // 查询 SecurityCP 能力集，失败（包括设备不支持）时返回 false
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn supports_security_control(&self) -> bool {
+        self.isapi_request(IsapiMethod::Get, SECURITY_CP_CAPABILITIES, None)
+            .is_ok_and(|response| response.success)
+    }
+
+    fn security_cp_put(&self, url: &str, action: &'static str) -> anyhow::Result<()> {
+        let response = self.isapi_request(IsapiMethod::Put, url, None)?;
+        self.check_security_cp(response, action).map(|_| ())
+    }
+
+    // 只在请求失败后查询能力集，区分不支持与其他错误
+    fn check_security_cp(
+        &self,
+        response: IsapiResponse,
+        action: &'static str,
+    ) -> anyhow::Result<IsapiResponse> {
+        if response.success {
+            return Ok(response);
+        }
+        if !self.supports_security_control() {
+            return Err(HikError::Unsupported {
+                feature: "Alarm host security control",
+            }
+            .into());
+        }
+        Err(anyhow::anyhow!(
+            "{} failed: error code {}, status: {}",
+            action,
+            response.error_code,
+            response.status_str().unwrap_or_default()
+        ))
+    }
+}
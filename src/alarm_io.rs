@@ -0,0 +1,267 @@
+use crate::{
+    DWORD, LONG, NET_DVR_ALARMINCFG_V30, NET_DVR_ALARMOUTCFG_V30, NET_DVR_ALARMOUTSTATUS_V30,
+    NET_DVR_GET_ALARMINCFG_V30, NET_DVR_GET_ALARMOUTCFG_V30, NET_DVR_GetAlarmOut_V30,
+    NET_DVR_SET_ALARMINCFG_V30, NET_DVR_SET_ALARMOUTCFG_V30, NET_DVR_SetAlarmOut,
+    device::{HikDevice, sdk_error},
+    error::HikError,
+    ffi_util::{copy_to_gbk_array, decode_device_string},
+    motion::HandleType,
+    trace::sdk_call,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AlarmInType {
+    // 常开
+    NormallyOpen,
+    // 常闭
+    NormallyClosed,
+    Other(u8),
+}
+
+impl From<u8> for AlarmInType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => AlarmInType::NormallyOpen,
+            1 => AlarmInType::NormallyClosed,
+            other => AlarmInType::Other(other),
+        }
+    }
+}
+
+impl From<AlarmInType> for u8 {
+    fn from(value: AlarmInType) -> Self {
+        match value {
+            AlarmInType::NormallyOpen => 0,
+            AlarmInType::NormallyClosed => 1,
+            AlarmInType::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AlarmOutDelay {
+    Secs5,
+    Secs10,
+    Secs30,
+    Mins1,
+    Mins2,
+    Mins5,
+    Mins10,
+    // 需要手动关闭
+    Manual,
+    Other(u32),
+}
+
+impl From<DWORD> for AlarmOutDelay {
+    fn from(value: DWORD) -> Self {
+        match value {
+            0 => AlarmOutDelay::Secs5,
+            1 => AlarmOutDelay::Secs10,
+            2 => AlarmOutDelay::Secs30,
+            3 => AlarmOutDelay::Mins1,
+            4 => AlarmOutDelay::Mins2,
+            5 => AlarmOutDelay::Mins5,
+            6 => AlarmOutDelay::Mins10,
+            7 => AlarmOutDelay::Manual,
+            other => AlarmOutDelay::Other(other),
+        }
+    }
+}
+
+impl From<AlarmOutDelay> for DWORD {
+    fn from(value: AlarmOutDelay) -> Self {
+        match value {
+            AlarmOutDelay::Secs5 => 0,
+            AlarmOutDelay::Secs10 => 1,
+            AlarmOutDelay::Secs30 => 2,
+            AlarmOutDelay::Mins1 => 3,
+            AlarmOutDelay::Mins2 => 4,
+            AlarmOutDelay::Mins5 => 5,
+            AlarmOutDelay::Mins10 => 6,
+            AlarmOutDelay::Manual => 7,
+            AlarmOutDelay::Other(other) => other,
+        }
+    }
+}
+
+pub struct AlarmInConfig {
+    pub name: String,
+    pub alarm_type: AlarmInType,
+    // 是否处理该报警输入
+    pub enabled: bool,
+    pub handle_type: HandleType,
+    // 联动录像的通道，下标为通道序号
+    pub record_channels: Vec<bool>,
+    // 保留设备返回的原始参数，set 时只修改上面的字段
+    raw: Box<NET_DVR_ALARMINCFG_V30>,
+}
+
+impl AlarmInConfig {
+    fn from_raw(raw: Box<NET_DVR_ALARMINCFG_V30>) -> Self {
+        Self {
+            name: decode_device_string(&raw.sAlarmInName),
+            alarm_type: AlarmInType::from(raw.byAlarmType),
+            enabled: raw.byAlarmInHandle == 1,
+            handle_type: HandleType::from_bits_retain(raw.struAlarmHandleType.dwHandleType),
+            record_channels: raw.byRelRecordChan.iter().map(|&v| v == 1).collect(),
+            raw,
+        }
+    }
+
+    fn to_raw(&self) -> anyhow::Result<Box<NET_DVR_ALARMINCFG_V30>> {
+        let mut raw = self.raw.clone();
+        copy_to_gbk_array(&mut raw.sAlarmInName, &self.name, "Alarm input name")?;
+        raw.byAlarmType = self.alarm_type.into();
+        raw.byAlarmInHandle = self.enabled as u8;
+        raw.struAlarmHandleType.dwHandleType = self.handle_type.bits();
+        for (dst, &value) in raw.byRelRecordChan.iter_mut().zip(&self.record_channels) {
+            *dst = value as u8;
+        }
+        Ok(raw)
+    }
+}
+
+pub struct AlarmOutConfig {
+    pub name: String,
+    pub delay: AlarmOutDelay,
+    // 保留设备返回的原始参数，set 时只修改上面的字段
+    raw: NET_DVR_ALARMOUTCFG_V30,
+}
+
+impl HikDevice {
+    // 报警输入、输出的 index 均从 0 开始
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_alarm_in_config(&self, index: u16) -> anyhow::Result<AlarmInConfig> {
+        self.check_alarm_in_index(index)?;
+        let raw: NET_DVR_ALARMINCFG_V30 = self.get_dvr_config(
+            NET_DVR_GET_ALARMINCFG_V30,
+            index as LONG,
+            "Get alarm input config",
+        )?;
+        Ok(AlarmInConfig::from_raw(Box::new(raw)))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn set_alarm_in_config(&self, index: u16, config: &AlarmInConfig) -> anyhow::Result<()> {
+        self.check_alarm_in_index(index)?;
+        let raw = config.to_raw()?;
+        self.set_dvr_config(
+            NET_DVR_SET_ALARMINCFG_V30,
+            index as LONG,
+            &*raw,
+            "Set alarm input config",
+        )
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_alarm_out_config(&self, index: u16) -> anyhow::Result<AlarmOutConfig> {
+        self.check_alarm_out_index(index)?;
+        let raw: NET_DVR_ALARMOUTCFG_V30 = self.get_dvr_config(
+            NET_DVR_GET_ALARMOUTCFG_V30,
+            index as LONG,
+            "Get alarm output config",
+        )?;
+        Ok(AlarmOutConfig {
+            name: decode_device_string(&raw.sAlarmOutName),
+            delay: AlarmOutDelay::from(raw.dwAlarmOutDelay),
+            raw,
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn set_alarm_out_config(&self, index: u16, config: &AlarmOutConfig) -> anyhow::Result<()> {
+        self.check_alarm_out_index(index)?;
+        let mut raw = config.raw;
+        copy_to_gbk_array(&mut raw.sAlarmOutName, &config.name, "Alarm output name")?;
+        raw.dwAlarmOutDelay = config.delay.into();
+        self.set_dvr_config(
+            NET_DVR_SET_ALARMOUTCFG_V30,
+            index as LONG,
+            &raw,
+            "Set alarm output config",
+        )
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn set_alarm_out(&self, index: u16, active: bool) -> anyhow::Result<()> {
+        self.check_alarm_out_index(index)?;
+        self.with_session(|lu| {
+            let res = unsafe { sdk_call!(NET_DVR_SetAlarmOut(lu, index as LONG, active as LONG)) };
+            if res != 1 {
+                return Err(sdk_error("Set alarm output"));
+            }
+            Ok(())
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_alarm_out_status(&self) -> anyhow::Result<Vec<bool>> {
+        let count = self.alarm_out_count()? as usize;
+        let mut status = NET_DVR_ALARMOUTSTATUS_V30::default();
+        self.with_session(|lu| {
+            let res = unsafe { sdk_call!(NET_DVR_GetAlarmOut_V30(lu, &mut status)) };
+            if res != 1 {
+                return Err(sdk_error("Get alarm output status"));
+            }
+            Ok(())
+        })?;
+        let count = count.min(status.Output.len());
+        Ok(status.Output[..count].iter().map(|&v| v == 1).collect())
+    }
+
+    fn alarm_in_count(&self) -> anyhow::Result<u16> {
+        self.get_device_info()
+            .map(|info| info.alarm_in_count())
+            .ok_or(anyhow::anyhow!("Device info not found"))
+    }
+
+    fn alarm_out_count(&self) -> anyhow::Result<u16> {
+        self.get_device_info()
+            .map(|info| info.alarm_out_count())
+            .ok_or(anyhow::anyhow!("Device info not found"))
+    }
+
+    fn check_alarm_in_index(&self, index: u16) -> anyhow::Result<()> {
+        let count = self.alarm_in_count()?;
+        if index >= count {
+            return Err(HikError::InvalidArgument {
+                field: "index",
+                reason: format!("alarm input {} out of range, device has {}", index, count),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    fn check_alarm_out_index(&self, index: u16) -> anyhow::Result<()> {
+        let count = self.alarm_out_count()?;
+        if index >= count {
+            return Err(HikError::InvalidArgument {
+                field: "index",
+                reason: format!("alarm output {} out of range, device has {}", index, count),
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
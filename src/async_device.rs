@@ -0,0 +1,168 @@
+// 所有 SDK 调用都是阻塞的网络请求，这里把它们放到 spawn_blocking 中执行，避免阻塞 tokio 工作线程
+//
+// 同一个 AsyncHikDevice 上的调用按顺序执行（设备状态在 tokio Mutex 中），
+// 登录、注销、重登录会修改会话，必须独占；配置读写、抓图、打开下载或预览这类调用
+// SDK 本身允许在同一登录句柄上并发，需要并发时可以用多个登录会话。
+// 已打开的下载、预览和转封装流在后台线程中运行，不占用这把锁。
+use std::sync::Arc;
+
+use chrono::{DateTime, Local};
+use tokio::sync::{Mutex, watch};
+
+use crate::device::{
+    Channel, DownloadOptions, DownloadState, DownloadStatus, HikDevice, HikDownload, JpegParams,
+    LoginOptions,
+};
+
+#[derive(Clone)]
+pub struct AsyncHikDevice {
+    device: Arc<Mutex<HikDevice>>,
+}
+
+impl Default for AsyncHikDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<HikDevice> for AsyncHikDevice {
+    fn from(device: HikDevice) -> Self {
+        Self {
+            device: Arc::new(Mutex::new(device)),
+        }
+    }
+}
+
+impl AsyncHikDevice {
+    pub fn new() -> Self {
+        Self::from(HikDevice::new())
+    }
+
+    // 在阻塞线程池中独占设备执行 op，用于没有单独封装的操作
+    pub async fn run<T, F>(&self, op: F) -> anyhow::Result<T>
+    where
+        F: FnOnce(&mut HikDevice) -> anyhow::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let mut device = self.device.clone().lock_owned().await;
+        tokio::task::spawn_blocking(move || op(&mut device))
+            .await
+            .map_err(|e| anyhow::anyhow!("Blocking task failed: {}", e))?
+    }
+
+    pub async fn login(&self, options: LoginOptions) -> anyhow::Result<()> {
+        self.run(move |device| device.login_v40(options).map(|_| ()))
+            .await
+    }
+
+    pub async fn logout(&self) -> anyhow::Result<()> {
+        self.run(|device| device.logout().map(|_| ())).await
+    }
+
+    pub async fn is_logged_in(&self) -> bool {
+        self.device.lock().await.is_logged_in()
+    }
+
+    pub async fn get_channels(&self) -> anyhow::Result<Vec<Channel>> {
+        self.run(|device| device.get_channels()).await
+    }
+
+    pub async fn get_time(&self) -> anyhow::Result<DateTime<Local>> {
+        self.run(|device| device.get_time()).await
+    }
+
+    pub async fn set_time(&self, time: DateTime<Local>) -> anyhow::Result<()> {
+        self.run(move |device| device.set_time(time)).await
+    }
+
+    pub async fn capture_jpeg_picture(&self, channel: u16, file: String) -> anyhow::Result<()> {
+        self.run(move |device| device.capture_jpeg_picture(channel, &file))
+            .await
+    }
+
+    pub async fn capture_jpeg_data(
+        &self,
+        channel: u16,
+        params: JpegParams,
+    ) -> anyhow::Result<Vec<u8>> {
+        self.run(move |device| device.capture_jpeg_data(channel, params))
+            .await
+    }
+
+    // 返回时下载已经开始
+    pub async fn get_file_by_time(
+        &self,
+        file: String,
+        channel: u16,
+        start_time: DateTime<Local>,
+        end_time: DateTime<Local>,
+        options: DownloadOptions,
+    ) -> anyhow::Result<AsyncDownload> {
+        self.run(move |device| {
+            let mut download =
+                device.get_file_by_time_with(&file, channel, start_time, end_time, options)?;
+            download.start()?;
+            let status = download.subscribe_watch()?;
+            Ok(AsyncDownload {
+                download: Some(download),
+                status,
+            })
+        })
+        .await
+    }
+}
+
+pub struct AsyncDownload {
+    // 只在 Drop 时取出，放到阻塞线程中停止
+    download: Option<HikDownload>,
+    status: watch::Receiver<DownloadStatus>,
+}
+
+impl AsyncDownload {
+    pub fn status(&self) -> DownloadStatus {
+        self.status.borrow().clone()
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<DownloadStatus> {
+        self.status.clone()
+    }
+
+    // 等待下载结束，失败时返回对应的 HikError
+    pub async fn wait(&mut self) -> anyhow::Result<()> {
+        let status = self
+            .status
+            .wait_for(|status| status.state != DownloadState::Running)
+            .await
+            .map_err(|_| anyhow::anyhow!("Download watcher closed"))?
+            .clone();
+        match status.state {
+            DownloadState::Failed(e) => Err(e.into()),
+            DownloadState::Cancelled => Err(anyhow::anyhow!("Download cancelled")),
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn stop(mut self) -> anyhow::Result<()> {
+        let Some(download) = self.download.take() else {
+            return Ok(());
+        };
+        tokio::task::spawn_blocking(move || download.stop())
+            .await
+            .map_err(|e| anyhow::anyhow!("Blocking task failed: {}", e))?
+    }
+}
+
+impl Drop for AsyncDownload {
+    fn drop(&mut self) {
+        let Some(download) = self.download.take() else {
+            return;
+        };
+        // HikDownload 的 Drop 会调用 SDK 停止下载并等待进度线程
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn_blocking(move || drop(download));
+            }
+            Err(_) => drop(download),
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, mpsc},
+    thread,
+    time::Duration,
+};
+
+use chrono::{DateTime, Local};
+
+use crate::{
+    LONG, NET_DVR_TIME,
+    cancel::{CancellationToken, wait_stop},
+    common::check_stream_limit,
+    device::{
+        ContainerFormat, DownloadOptions, DownloadState, DownloadStatus, HikDevice, HikDownload,
+        open_download,
+    },
+    error::HikError,
+    sdk::NetSdk,
+    session::SessionState,
+};
+
+// 后台线程查询各通道进度的间隔
+const BATCH_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    // 同时进行的下载数，设备通常限制在 4-8 路
+    pub max_concurrent: usize,
+    pub download: DownloadOptions,
+    // 支持 {channel}、{start}、{end} 占位符，扩展名按封装格式自动添加
+    pub filename_template: String,
+    // 取消后与 BatchDownload::cancel 效果相同
+    pub cancel: Option<CancellationToken>,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 4,
+            download: DownloadOptions::default(),
+            filename_template: "ch{channel}_{start}_{end}".to_string(),
+            cancel: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelOutcome {
+    pub channel: u16,
+    pub path: PathBuf,
+    // Finished、Failed 或 Cancelled
+    pub state: DownloadState,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BatchResult {
+    pub channels: Vec<ChannelOutcome>,
+}
+
+impl BatchResult {
+    pub fn succeeded(&self) -> impl Iterator<Item = &ChannelOutcome> {
+        self.channels
+            .iter()
+            .filter(|c| c.state == DownloadState::Finished)
+    }
+
+    // 设备上该时间段没有录像时，错误码通常为 NET_DVR_FILE_NOFIND 之类
+    pub fn failed(&self) -> impl Iterator<Item = (u16, &HikError)> {
+        self.channels.iter().filter_map(|c| match &c.state {
+            DownloadState::Failed(e) => Some((c.channel, e)),
+            _ => None,
+        })
+    }
+}
+
+struct BatchJob {
+    channel: u16,
+    sdk_channel: LONG,
+    path: PathBuf,
+}
+
+pub struct BatchDownload {
+    statuses: Arc<Mutex<Vec<(u16, DownloadStatus)>>>,
+    // 丢弃 Sender 即可让后台线程取消全部下载
+    cancel_tx: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<BatchResult>>,
+    result: Option<BatchResult>,
+}
+
+impl BatchDownload {
+    pub fn per_channel(&self) -> Vec<(u16, DownloadStatus)> {
+        self.statuses.lock().unwrap().clone()
+    }
+
+    // 失败和取消的通道按 100% 计算，全部结束时为 100
+    pub fn overall_percent(&self) -> u8 {
+        let statuses = self.statuses.lock().unwrap();
+        if statuses.is_empty() {
+            return 100;
+        }
+        let total: usize = statuses
+            .iter()
+            .map(|(_, status)| match status.state {
+                DownloadState::Queued | DownloadState::Running => status.percent as usize,
+                _ => 100,
+            })
+            .sum();
+        (total / statuses.len()) as u8
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_none_or(|thread| thread.is_finished())
+    }
+
+    // 停止所有进行中的下载并清空队列，返回时句柄都已停止
+    pub fn cancel(&mut self) {
+        self.cancel_tx.take();
+        self.join();
+    }
+
+    // 阻塞到全部通道结束
+    pub fn wait(mut self) -> BatchResult {
+        self.join();
+        self.result.take().unwrap_or_default()
+    }
+
+    fn join(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.result = Some(thread.join().unwrap_or_default());
+        }
+    }
+}
+
+impl Drop for BatchDownload {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl HikDevice {
+    /// 同一时间段批量下载多个通道，每个通道保存为 dir 下的一个文件
+    ///
+    /// 下载句柄在后台线程中使用当前登录句柄创建，期间会话失效时剩余通道会失败，不会自动重登录
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channels = ?channels)
+        )
+    )]
+    pub fn download_batch(
+        &self,
+        channels: &[u16],
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+        dir: &Path,
+        options: BatchOptions,
+    ) -> anyhow::Result<BatchDownload> {
+        if options.max_concurrent == 0 {
+            return Err(anyhow::anyhow!("max_concurrent must be at least 1"));
+        }
+        let lu = self.user_id()?;
+        let times = self.device_time_range(start, end, options.download.time_mode)?;
+        std::fs::create_dir_all(dir)?;
+
+        // 通道号错误属于调用方的问题，在开始前直接返回
+        let mut jobs = VecDeque::with_capacity(channels.len());
+        for &channel in channels {
+            let filename = options
+                .filename_template
+                .replace("{channel}", &channel.to_string())
+                .replace("{start}", &start.format("%Y%m%d%H%M%S").to_string())
+                .replace("{end}", &end.format("%Y%m%d%H%M%S").to_string());
+            jobs.push_back(BatchJob {
+                channel,
+                sdk_channel: self.resolve_channel(channel)?,
+                path: dir.join(format!(
+                    "{}.{}",
+                    filename,
+                    extension(options.download.container)
+                )),
+            });
+        }
+
+        let statuses = Arc::new(Mutex::new(
+            channels
+                .iter()
+                .map(|&channel| {
+                    (
+                        channel,
+                        DownloadStatus {
+                            percent: 0,
+                            state: DownloadState::Queued,
+                        },
+                    )
+                })
+                .collect(),
+        ));
+        let (cancel_tx, cancel_rx) = mpsc::channel::<()>();
+        let thread_statuses = statuses.clone();
+        let sdk = self.sdk.clone();
+        let max_streams = self.max_streams();
+        let session = self.session.state();
+        let thread = thread::spawn(move || {
+            run_batch(
+                &sdk,
+                lu,
+                &session,
+                jobs,
+                times,
+                options,
+                max_streams,
+                &thread_statuses,
+                &cancel_rx,
+            )
+        });
+
+        Ok(BatchDownload {
+            statuses,
+            cancel_tx: Some(cancel_tx),
+            thread: Some(thread),
+            result: None,
+        })
+    }
+}
+
+// 原始 PS 封装习惯上也保存为 .mp4，海康播放器可以直接打开
+fn extension(container: ContainerFormat) -> &'static str {
+    match container {
+        ContainerFormat::Native | ContainerFormat::Mp4 => "mp4",
+        ContainerFormat::Avi => "avi",
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_batch(
+    sdk: &Arc<dyn NetSdk>,
+    lu: LONG,
+    session: &Arc<SessionState>,
+    mut queue: VecDeque<BatchJob>,
+    times: [NET_DVR_TIME; 2],
+    options: BatchOptions,
+    max_streams: Option<usize>,
+    statuses: &Mutex<Vec<(u16, DownloadStatus)>>,
+    cancel_rx: &mpsc::Receiver<()>,
+) -> BatchResult {
+    let mut outcomes: Vec<ChannelOutcome> = Vec::with_capacity(queue.len());
+    let mut active: Vec<(BatchJob, HikDownload, u8)> = Vec::new();
+    let set_status = |channel: u16, status: DownloadStatus| {
+        let mut statuses = statuses.lock().unwrap();
+        if let Some((_, s)) = statuses.iter_mut().find(|(c, _)| *c == channel) {
+            *s = status;
+        }
+    };
+    let mut finish = |job: BatchJob, state: DownloadState, percent: u8| {
+        set_status(
+            job.channel,
+            DownloadStatus {
+                percent,
+                state: state.clone(),
+            },
+        );
+        outcomes.push(ChannelOutcome {
+            channel: job.channel,
+            path: job.path,
+            state,
+        });
+    };
+
+    loop {
+        while active.len() < options.max_concurrent {
+            let Some(job) = queue.pop_front() else {
+                break;
+            };
+            match start_job(sdk, lu, session, &job, times, options.download, max_streams) {
+                Ok(download) => {
+                    set_status(
+                        job.channel,
+                        DownloadStatus {
+                            percent: 0,
+                            state: DownloadState::Running,
+                        },
+                    );
+                    active.push((job, download, 0));
+                }
+                // 达到码流上限时等本批的下载结束再开始，没有可等待的下载时按失败处理
+                Err(HikError::TooManyStreams { .. }) if !active.is_empty() => {
+                    queue.push_front(job);
+                    break;
+                }
+                // 单个通道失败（例如该时间段没有录像）不影响其他通道
+                Err(e) => finish(job, DownloadState::Failed(e), 0),
+            }
+        }
+        if active.is_empty() && queue.is_empty() {
+            break;
+        }
+
+        let mut i = 0;
+        while i < active.len() {
+            let status = active[i].1.poll(active[i].2);
+            match status.state {
+                DownloadState::Running => {
+                    active[i].2 = status.percent;
+                    set_status(active[i].0.channel, status);
+                    i += 1;
+                }
+                state => {
+                    // drop 时停止句柄
+                    let (job, _, _) = active.swap_remove(i);
+                    finish(job, state, status.percent);
+                }
+            }
+        }
+
+        if !wait_stop(cancel_rx, options.cancel.as_ref(), BATCH_POLL_INTERVAL) {
+            continue;
+        }
+        for (job, download, percent) in active.drain(..) {
+            drop(download);
+            finish(job, DownloadState::Cancelled, percent);
+        }
+        for job in queue.drain(..) {
+            finish(job, DownloadState::Cancelled, 0);
+        }
+        break;
+    }
+
+    // 按传入的通道顺序返回
+    let order = statuses.lock().unwrap().clone();
+    outcomes.sort_by_key(|o| order.iter().position(|(c, _)| *c == o.channel));
+    BatchResult { channels: outcomes }
+}
+
+fn start_job(
+    sdk: &Arc<dyn NetSdk>,
+    lu: LONG,
+    session: &Arc<SessionState>,
+    job: &BatchJob,
+    [start, end]: [NET_DVR_TIME; 2],
+    options: DownloadOptions,
+    max_streams: Option<usize>,
+) -> Result<HikDownload, HikError> {
+    check_stream_limit(lu, max_streams)?;
+    let path = job.path.to_string_lossy();
+    let into_hik_error = |error: anyhow::Error| {
+        error.downcast::<HikError>().unwrap_or(HikError::Sdk {
+            action: "Start download",
+            code: sdk.get_last_error() as i32,
+        })
+    };
+    let mut download = open_download(
+        sdk,
+        lu,
+        session,
+        &path,
+        job.sdk_channel,
+        start,
+        end,
+        options,
+    )
+    .map_err(into_hik_error)?;
+    download.start().map_err(into_hik_error)?;
+    Ok(download)
+}
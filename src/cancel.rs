@@ -0,0 +1,78 @@
+use std::{
+    sync::{Arc, Condvar, Mutex, mpsc},
+    time::{Duration, Instant},
+};
+
+// 等待停止信号时检查取消标志的间隔
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 取消由本库驱动的等待与轮询（下载、批量下载、设备搜索、格式化、定时抓图等）
+///
+/// 克隆出的 token 共享同一个取消状态，取消后不能恢复
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        let (cancelled, condvar) = &*self.inner;
+        *cancelled.lock().unwrap() = true;
+        condvar.notify_all();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.inner.0.lock().unwrap()
+    }
+
+    // 等待 timeout，期间被取消时立即返回 true
+    pub(crate) fn wait_timeout(&self, timeout: Duration) -> bool {
+        let (cancelled, condvar) = &*self.inner;
+        let guard = cancelled.lock().unwrap();
+        let (guard, _) = condvar
+            .wait_timeout_while(guard, timeout, |cancelled| !*cancelled)
+            .unwrap();
+        *guard
+    }
+}
+
+// 克隆自同一个 token 时相等
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for CancellationToken {}
+
+// 后台线程的轮询间隔：收到停止信号、Sender 被丢弃或 token 被取消时返回 true
+pub(crate) fn wait_stop(
+    stop_rx: &mpsc::Receiver<()>,
+    cancel: Option<&CancellationToken>,
+    timeout: Duration,
+) -> bool {
+    let Some(cancel) = cancel else {
+        return !matches!(
+            stop_rx.recv_timeout(timeout),
+            Err(mpsc::RecvTimeoutError::Timeout)
+        );
+    };
+    let deadline = Instant::now() + timeout;
+    loop {
+        if cancel.is_cancelled() {
+            return true;
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return false;
+        }
+        match stop_rx.recv_timeout(remaining.min(CANCEL_CHECK_INTERVAL)) {
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            _ => return true,
+        }
+    }
+}
@@ -1,27 +1,874 @@
-use std::sync::OnceLock;
+use std::{
+    collections::HashMap,
+    fmt, mem,
+    os::raw::{c_char, c_void},
+    path::Path,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
 
-use crate::{NET_DVR_GetLastError, NET_DVR_Init};
+use chrono::{DateTime, Local, NaiveDate};
 
-static INIT_ONCE: OnceLock<Result<(), i32>> = OnceLock::new();
+use crate::{
+    ALARM_RECONNECTSUCCESS, DWORD, EXCEPTION_ALARM, EXCEPTION_ALARMRECONNECT,
+    EXCEPTION_AUDIOEXCHANGE, EXCEPTION_DISKFMT, EXCEPTION_EXCHANGE, EXCEPTION_PLAYBACK,
+    EXCEPTION_PREVIEW, EXCEPTION_RECONNECT, EXCEPTION_RELOGIN, EXCEPTION_RELOGIN_FAILED,
+    EXCEPTION_SERIAL, EXCEPTION_SERIALRECONNECT, EXCEPTION_VIDEO_DOWNLOAD, LONG, MSGCallBack,
+    NET_DVR_ACTIVATECFG, NET_DVR_Cleanup, NET_DVR_ERROR_RISK_PASSWORD, NET_DVR_GetLastError,
+    NET_DVR_GetSDKAbility, NET_DVR_GetSDKBuildVersion, NET_DVR_GetSDKState, NET_DVR_GetSDKVersion,
+    NET_DVR_Init, NET_DVR_SDKABL, NET_DVR_SDKSTATE, NET_DVR_SetConnectTime,
+    NET_DVR_SetDVRMessageCallBack_V50, NET_DVR_SetExceptionCallBack_V30, NET_DVR_SetLogToFile,
+    NET_DVR_SetReconnect, NET_DVR_SetRecvTimeOut, PREVIEW_RECONNECTSUCCESS, RESUME_EXCHANGE,
+    SERIAL_RECONNECTSUCCESS, as_c_string,
+    error::HikError,
+    ffi_util::{call_user_callback, copy_to_byte_array, lock_unpoisoned, path_to_cstring},
+    sdk::{NetSdk, RealSdk},
+    trace::sdk_call,
+};
+
+// 局域网搜索不依赖 SDK，放在 common 下便于与 init 等一起使用
+pub use crate::discovery::{
+    DiscoveredDevice, discover_devices, discover_devices_cancellable, discover_devices_with,
+};
+
+struct InitState {
+    initialized: bool,
+    // 存活的 SdkGuard 数量，最后一个释放时才 Cleanup
+    guards: usize,
+    // Cleanup 之后需要重新注册异常回调和报警回调
+    exception_callback_installed: bool,
+    message_callback_installed: bool,
+}
+
+static SDK_STATE: Mutex<InitState> = Mutex::new(InitState {
+    initialized: false,
+    guards: 0,
+    exception_callback_installed: false,
+    message_callback_installed: false,
+});
 
 pub fn init() -> anyhow::Result<()> {
-    let result = INIT_ONCE.get_or_init(|| {
-        unsafe {
-            // true is success, false is failed
-            let res = NET_DVR_Init();
-            if res != 1 {
-                return Err(res);
-            }
+    let mut state = SDK_STATE.lock().unwrap();
+    init_locked(&mut state)
+}
+
+fn init_locked(state: &mut InitState) -> anyhow::Result<()> {
+    if state.initialized {
+        return Ok(());
+    }
+    unsafe {
+        // true is success, false is failed
+        let res = sdk_call!(NET_DVR_Init());
+        if res != 1 {
+            return Err(anyhow::anyhow!(
+                "Init failed: error code {}",
+                get_last_error_code()
+            ));
+        }
+    }
+    state.initialized = true;
+    Ok(())
+}
+
+pub fn cleanup() -> anyhow::Result<()> {
+    let mut state = SDK_STATE.lock().unwrap();
+    cleanup_locked(&mut state)
+}
+
+fn cleanup_locked(state: &mut InitState) -> anyhow::Result<()> {
+    if !state.initialized {
+        return Ok(());
+    }
+    state.initialized = false;
+    state.exception_callback_installed = false;
+    state.message_callback_installed = false;
+    let res = unsafe { sdk_call!(NET_DVR_Cleanup()) };
+    if res != 1 {
+        return Err(anyhow::anyhow!(
+            "Cleanup failed: error code {}",
+            get_last_error_code()
+        ));
+    }
+    Ok(())
+}
+
+pub fn is_initialized() -> bool {
+    SDK_STATE.lock().unwrap().initialized
+}
+
+pub fn init_guarded() -> anyhow::Result<SdkGuard> {
+    let mut state = SDK_STATE.lock().unwrap();
+    init_locked(&mut state)?;
+    state.guards += 1;
+    Ok(SdkGuard { _private: () })
+}
+
+// init_guarded 返回的守卫，最后一个守卫释放时调用 NET_DVR_Cleanup
+pub struct SdkGuard {
+    _private: (),
+}
+
+impl Drop for SdkGuard {
+    fn drop(&mut self) {
+        let mut state = SDK_STATE.lock().unwrap();
+        state.guards = state.guards.saturating_sub(1);
+        if state.guards == 0 {
+            let _ = cleanup_locked(&mut state);
+        }
+    }
+}
+
+// 进程实际加载的 SDK 版本，例如 6.1.9.4 build20230410
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SdkVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub revision: u8,
+    pub build: u8,
+    // 读不到 SDK 库文件时为空
+    pub build_date: Option<NaiveDate>,
+}
+
+impl fmt::Display for SdkVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.major, self.minor, self.revision, self.build
+        )?;
+        if let Some(date) = self.build_date {
+            write!(f, " build{}", date.format("%Y%m%d"))?;
         }
         Ok(())
-    });
-    
-    match result {
-        Ok(()) => Ok(()),
-        Err(code) => Err(anyhow::anyhow!("Init failed: error code {}", code)),
     }
 }
 
+// 不需要初始化 SDK
+pub fn sdk_version() -> SdkVersion {
+    let build_date = sdk_build_date();
+    // 每个字节依次为主版本、次版本、修订号、build 号
+    let build = unsafe { sdk_call!(NET_DVR_GetSDKBuildVersion()) };
+    if build != 0 {
+        let [major, minor, revision, build] = build.to_be_bytes();
+        return SdkVersion {
+            major,
+            minor,
+            revision,
+            build,
+            build_date,
+        };
+    }
+    // 老版本只有高 16 位主版本、低 16 位次版本
+    let version = unsafe { sdk_call!(NET_DVR_GetSDKVersion()) };
+    SdkVersion {
+        major: (version >> 16) as u8,
+        minor: version as u8,
+        revision: 0,
+        build: 0,
+        build_date,
+    }
+}
+
+// 接口只返回版本号，构建日期取自库文件中的版本字符串（例如 V6.1.9.4_build20230410），只读取一次
+fn sdk_build_date() -> Option<NaiveDate> {
+    static BUILD_DATE: OnceLock<Option<NaiveDate>> = OnceLock::new();
+    *BUILD_DATE.get_or_init(|| {
+        let data = std::fs::read(loaded_sdk_library()?).ok()?;
+        find_build_date(&data)
+    })
+}
+
+// 从 /proc/self/maps 找到进程实际加载的 libhcnetsdk
+#[cfg(target_os = "linux")]
+fn loaded_sdk_library() -> Option<std::path::PathBuf> {
+    let maps = std::fs::read_to_string("/proc/self/maps").ok()?;
+    maps.lines()
+        .filter_map(|line| line.find('/').map(|start| &line[start..]))
+        .find(|path| {
+            Path::new(path).file_name().is_some_and(|name| {
+                name.to_string_lossy()
+                    .to_ascii_lowercase()
+                    .starts_with("libhcnetsdk")
+            })
+        })
+        .map(std::path::PathBuf::from)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn loaded_sdk_library() -> Option<std::path::PathBuf> {
+    None
+}
+
+fn find_build_date(data: &[u8]) -> Option<NaiveDate> {
+    const MARKER: &[u8] = b"_build";
+    data.windows(MARKER.len() + 8).find_map(|window| {
+        let digits = window.strip_prefix(MARKER)?;
+        if !digits.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        NaiveDate::parse_from_str(std::str::from_utf8(digits).ok()?, "%Y%m%d").ok()
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HandleUsage {
+    pub used: u32,
+    pub max: u32,
+}
+
+/// SDK 当前占用的各类句柄数与上限，用于排查句柄泄漏
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SdkState {
+    pub logins: HandleUsage,
+    pub previews: HandleUsage,
+    pub playbacks: HandleUsage,
+    pub alarm_channels: HandleUsage,
+    pub formats: HandleUsage,
+    pub file_searches: HandleUsage,
+    pub log_searches: HandleUsage,
+    pub serials: HandleUsage,
+    pub upgrades: HandleUsage,
+    pub voice_coms: HandleUsage,
+    pub broadcasts: HandleUsage,
+}
+
+pub fn sdk_state() -> anyhow::Result<SdkState> {
+    init()?;
+    let mut state = NET_DVR_SDKSTATE::default();
+    let mut ability = NET_DVR_SDKABL::default();
+    if unsafe { sdk_call!(NET_DVR_GetSDKState(&mut state)) } != 1 {
+        return Err(anyhow::anyhow!(
+            "Get SDK state failed: error code {}",
+            get_last_error_code()
+        ));
+    }
+    if unsafe { sdk_call!(NET_DVR_GetSDKAbility(&mut ability)) } != 1 {
+        return Err(anyhow::anyhow!(
+            "Get SDK ability failed: error code {}",
+            get_last_error_code()
+        ));
+    }
+    let usage = |used, max| HandleUsage { used, max };
+    Ok(SdkState {
+        logins: usage(state.dwTotalLoginNum, ability.dwMaxLoginNum),
+        previews: usage(state.dwTotalRealPlayNum, ability.dwMaxRealPlayNum),
+        playbacks: usage(state.dwTotalPlayBackNum, ability.dwMaxPlayBackNum),
+        alarm_channels: usage(state.dwTotalAlarmChanNum, ability.dwMaxAlarmChanNum),
+        formats: usage(state.dwTotalFormatNum, ability.dwMaxFormatNum),
+        file_searches: usage(state.dwTotalFileSearchNum, ability.dwMaxFileSearchNum),
+        log_searches: usage(state.dwTotalLogSearchNum, ability.dwMaxLogSearchNum),
+        serials: usage(state.dwTotalSerialNum, ability.dwMaxSerialNum),
+        upgrades: usage(state.dwTotalUpgradeNum, ability.dwMaxUpgradeNum),
+        voice_coms: usage(state.dwTotalVoiceComNum, ability.dwMaxVoiceComNum),
+        broadcasts: usage(state.dwTotalBroadCastNum, ability.dwMaxBroadCastNum),
+    })
+}
+
+// 以下配置在 SDK 未初始化时调用不会生效，这里统一先自动初始化
+
+pub fn set_connect_time(timeout_ms: u32, retries: u32) -> anyhow::Result<()> {
+    init()?;
+    let res = unsafe {
+        sdk_call!(NET_DVR_SetConnectTime(
+            timeout_ms as DWORD,
+            retries as DWORD
+        ))
+    };
+    if res != 1 {
+        return Err(anyhow::anyhow!(
+            "Set connect time failed: error code {}",
+            get_last_error_code()
+        ));
+    }
+    Ok(())
+}
+
+// 全局的接收超时，默认 5000 ms
+pub fn set_recv_timeout(timeout_ms: u32) -> anyhow::Result<()> {
+    init()?;
+    let res = unsafe { sdk_call!(NET_DVR_SetRecvTimeOut(timeout_ms as DWORD)) };
+    if res != 1 {
+        return Err(anyhow::anyhow!(
+            "Set receive timeout failed: error code {}",
+            get_last_error_code()
+        ));
+    }
+    Ok(())
+}
+
+pub fn set_reconnect(interval_ms: u32, enable: bool) -> anyhow::Result<()> {
+    init()?;
+    let res = unsafe { sdk_call!(NET_DVR_SetReconnect(interval_ms as DWORD, enable as i32)) };
+    if res != 1 {
+        return Err(anyhow::anyhow!(
+            "Set reconnect failed: error code {}",
+            get_last_error_code()
+        ));
+    }
+    Ok(())
+}
+
+/// 激活出厂未激活的设备，激活前设备拒绝任何登录，因此不需要登录句柄
+///
+/// 密码不满足设备的强度要求时返回 [`HikError::RiskyPassword`]
+pub fn activate_device(ip: &str, port: u16, password: &str) -> anyhow::Result<()> {
+    init()?;
+    activate_device_with(&RealSdk, ip, port, password)
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(ip = ip, port = port))
+)]
+pub(crate) fn activate_device_with(
+    sdk: &dyn NetSdk,
+    ip: &str,
+    port: u16,
+    password: &str,
+) -> anyhow::Result<()> {
+    let ip = as_c_string!(ip, "ip");
+    let mut config = activate_config(password)?;
+
+    let res = sdk.activate_device(&ip, port, &mut config);
+    if res != 1 {
+        let code = sdk.get_last_error() as i32;
+        if code == NET_DVR_ERROR_RISK_PASSWORD as i32 {
+            return Err(HikError::RiskyPassword.into());
+        }
+        return Err(HikError::Sdk {
+            action: "Activate device",
+            code,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+// 密码按原始字节写入 sPassword，不做编码转换
+fn activate_config(password: &str) -> anyhow::Result<NET_DVR_ACTIVATECFG> {
+    let mut config = NET_DVR_ACTIVATECFG {
+        dwSize: mem::size_of::<NET_DVR_ACTIVATECFG>() as DWORD,
+        ..Default::default()
+    };
+    copy_to_byte_array(&mut config.sPassword, password, "password")?;
+    Ok(config)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SdkLogLevel {
+    Off = 0,
+    Error = 1,
+    Debug = 2,
+    All = 3,
+}
+
+pub fn enable_sdk_log(level: SdkLogLevel, dir: &Path, auto_delete: bool) -> anyhow::Result<()> {
+    init()?;
+    let dir = path_to_cstring(dir, "dir")?;
+    let res = unsafe {
+        sdk_call!(NET_DVR_SetLogToFile(
+            level as DWORD,
+            dir.as_ptr() as *mut c_char,
+            auto_delete as i32,
+        ))
+    };
+    if res != 1 {
+        return Err(anyhow::anyhow!(
+            "Set log to file failed: error code {}",
+            get_last_error_code()
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionType {
+    // 用户交互时异常（会话断开）
+    Exchange,
+    AudioExchange,
+    Alarm,
+    Preview,
+    Serial,
+    // 预览时重连
+    Reconnect,
+    AlarmReconnect,
+    SerialReconnect,
+    SerialReconnectSuccess,
+    Playback,
+    DiskFormat,
+    PreviewReconnectSuccess,
+    AlarmReconnectSuccess,
+    // 用户交互恢复
+    ResumeExchange,
+    Relogin,
+    ReloginFailed,
+    VideoDownload,
+    Other(u32),
+}
+
+impl From<u32> for ExceptionType {
+    fn from(value: u32) -> Self {
+        match value {
+            EXCEPTION_EXCHANGE => ExceptionType::Exchange,
+            EXCEPTION_AUDIOEXCHANGE => ExceptionType::AudioExchange,
+            EXCEPTION_ALARM => ExceptionType::Alarm,
+            EXCEPTION_PREVIEW => ExceptionType::Preview,
+            EXCEPTION_SERIAL => ExceptionType::Serial,
+            EXCEPTION_RECONNECT => ExceptionType::Reconnect,
+            EXCEPTION_ALARMRECONNECT => ExceptionType::AlarmReconnect,
+            EXCEPTION_SERIALRECONNECT => ExceptionType::SerialReconnect,
+            SERIAL_RECONNECTSUCCESS => ExceptionType::SerialReconnectSuccess,
+            EXCEPTION_PLAYBACK => ExceptionType::Playback,
+            EXCEPTION_DISKFMT => ExceptionType::DiskFormat,
+            PREVIEW_RECONNECTSUCCESS => ExceptionType::PreviewReconnectSuccess,
+            ALARM_RECONNECTSUCCESS => ExceptionType::AlarmReconnectSuccess,
+            RESUME_EXCHANGE => ExceptionType::ResumeExchange,
+            EXCEPTION_RELOGIN => ExceptionType::Relogin,
+            EXCEPTION_RELOGIN_FAILED => ExceptionType::ReloginFailed,
+            EXCEPTION_VIDEO_DOWNLOAD => ExceptionType::VideoDownload,
+            other => ExceptionType::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExceptionEvent {
+    pub exception_type: ExceptionType,
+    pub user_id: i32,
+    // 预览、回放、报警等对应的句柄，与 exception_type 有关
+    pub handle: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExceptionHandlerId(u64);
+
+type ExceptionHandler = Arc<dyn Fn(ExceptionEvent) + Send + Sync>;
+
+static NEXT_HANDLER_ID: AtomicU64 = AtomicU64::new(1);
+
+fn exception_handlers() -> &'static Mutex<Vec<(ExceptionHandlerId, ExceptionHandler)>> {
+    static HANDLERS: OnceLock<Mutex<Vec<(ExceptionHandlerId, ExceptionHandler)>>> = OnceLock::new();
+    HANDLERS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+// SDK 只有一个全局异常回调，这里注册一次后分发给所有 handler
+pub fn set_exception_handler<F>(handler: F) -> anyhow::Result<ExceptionHandlerId>
+where
+    F: Fn(ExceptionEvent) + Send + Sync + 'static,
+{
+    ensure_exception_callback()?;
+    let id = ExceptionHandlerId(NEXT_HANDLER_ID.fetch_add(1, Ordering::Relaxed));
+    exception_handlers()
+        .lock()
+        .unwrap()
+        .push((id, Arc::new(handler)));
+    Ok(id)
+}
+
+pub fn remove_exception_handler(id: ExceptionHandlerId) -> bool {
+    let mut handlers = exception_handlers().lock().unwrap();
+    let len = handlers.len();
+    handlers.retain(|(handler_id, _)| *handler_id != id);
+    handlers.len() != len
+}
+
+pub(crate) fn ensure_exception_callback() -> anyhow::Result<()> {
+    let mut state = SDK_STATE.lock().unwrap();
+    init_locked(&mut state)?;
+    if state.exception_callback_installed {
+        return Ok(());
+    }
+    let res = unsafe {
+        sdk_call!(NET_DVR_SetExceptionCallBack_V30(
+            0,
+            std::ptr::null_mut(),
+            Some(exception_callback),
+            std::ptr::null_mut(),
+        ))
+    };
+    if res != 1 {
+        return Err(anyhow::anyhow!(
+            "Set exception callback failed: error code {}",
+            get_last_error_code()
+        ));
+    }
+    state.exception_callback_installed = true;
+    Ok(())
+}
+
+// 报警回调同样是全局的，由 alarm 模块按登录句柄分发
+pub(crate) fn ensure_message_callback(callback: MSGCallBack) -> anyhow::Result<()> {
+    let mut state = SDK_STATE.lock().unwrap();
+    init_locked(&mut state)?;
+    if state.message_callback_installed {
+        return Ok(());
+    }
+    let res = unsafe {
+        sdk_call!(NET_DVR_SetDVRMessageCallBack_V50(
+            0,
+            callback,
+            std::ptr::null_mut()
+        ))
+    };
+    if res != 1 {
+        return Err(anyhow::anyhow!(
+            "Set message callback failed: error code {}",
+            get_last_error_code()
+        ));
+    }
+    state.message_callback_installed = true;
+    Ok(())
+}
+
+unsafe extern "C" fn exception_callback(
+    exception_type: DWORD,
+    user_id: LONG,
+    handle: LONG,
+    _user: *mut c_void,
+) {
+    let event = ExceptionEvent {
+        exception_type: ExceptionType::from(exception_type),
+        user_id,
+        handle,
+    };
+
+    update_handle_health(&event);
+
+    // 先复制一份，避免 handler 内部增删 handler 时死锁
+    let handlers: Vec<ExceptionHandler> = lock_unpoisoned(exception_handlers())
+        .iter()
+        .map(|(_, handler)| handler.clone())
+        .collect();
+    for handler in handlers {
+        call_user_callback("exception", || handler(event));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HandleKind {
+    // 回放与按时间下载共用回放句柄
+    Playback,
+    Preview,
+    Alarm,
+}
+
+/// 本库打开且尚未关闭的 SDK 句柄，用于排查句柄泄漏
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HandleInfo {
+    pub kind: HandleKind,
+    pub handle: i32,
+    pub user_id: i32,
+    // 打开句柄时登录的设备地址，设备已登出时为空字符串
+    pub host: String,
+    pub created: DateTime<Local>,
+}
+
+struct WatchedHandle {
+    user_id: LONG,
+    host: String,
+    created: DateTime<Local>,
+    healthy: Arc<AtomicBool>,
+}
+
+// user id 到设备地址，登录、重登录与登出时维护
+fn session_hosts() -> &'static Mutex<HashMap<LONG, String>> {
+    static HOSTS: OnceLock<Mutex<HashMap<LONG, String>>> = OnceLock::new();
+    HOSTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub(crate) fn register_session_host(user_id: LONG, host: &str) {
+    session_hosts()
+        .lock()
+        .unwrap()
+        .insert(user_id, host.to_string());
+}
+
+pub(crate) fn unregister_session_host(user_id: LONG) {
+    session_hosts().lock().unwrap().remove(&user_id);
+}
+
+fn watched_handles() -> &'static Mutex<HashMap<(HandleKind, LONG), WatchedHandle>> {
+    static WATCHED: OnceLock<Mutex<HashMap<(HandleKind, LONG), WatchedHandle>>> = OnceLock::new();
+    WATCHED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// 登记句柄，异常回调会据此更新健康状态
+pub(crate) fn watch_handle(kind: HandleKind, user_id: LONG, handle: LONG) -> Arc<AtomicBool> {
+    // 注册失败只影响健康状态的更新，不影响句柄本身
+    let _ = ensure_exception_callback();
+    let healthy = Arc::new(AtomicBool::new(true));
+    let host = session_hosts()
+        .lock()
+        .unwrap()
+        .get(&user_id)
+        .cloned()
+        .unwrap_or_default();
+    watched_handles().lock().unwrap().insert(
+        (kind, handle),
+        WatchedHandle {
+            user_id,
+            host,
+            created: Local::now(),
+            healthy: healthy.clone(),
+        },
+    );
+    healthy
+}
+
+pub(crate) fn unwatch_handle(kind: HandleKind, handle: LONG) {
+    watched_handles().lock().unwrap().remove(&(kind, handle));
+}
+
+/// 列出所有设备上由本库打开的预览、回放/下载与布防句柄，按打开时间排序
+///
+/// 句柄在 stop 或 drop 时移除，一直存在的句柄通常说明调用方持有的对象没有释放
+pub fn active_handles() -> Vec<HandleInfo> {
+    let mut handles: Vec<HandleInfo> = watched_handles()
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(&(kind, handle), entry)| HandleInfo {
+            kind,
+            handle,
+            user_id: entry.user_id,
+            host: entry.host.clone(),
+            created: entry.created,
+        })
+        .collect();
+    handles.sort_by_key(|info| (info.created, info.handle));
+    handles
+}
+
+// 预览与回放/下载句柄计入码流上限，布防句柄不计入
+pub(crate) fn check_stream_limit(user_id: LONG, max: Option<usize>) -> Result<(), HikError> {
+    let Some(max) = max else {
+        return Ok(());
+    };
+    let streams = watched_handles()
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|((kind, _), entry)| *kind != HandleKind::Alarm && entry.user_id == user_id)
+        .count();
+    if streams >= max {
+        return Err(HikError::TooManyStreams { max });
+    }
+    Ok(())
+}
+
+fn update_handle_health(event: &ExceptionEvent) {
+    let watched = lock_unpoisoned(watched_handles());
+    let set = |kind: HandleKind, healthy: bool| {
+        if let Some(entry) = watched.get(&(kind, event.handle)) {
+            entry.healthy.store(healthy, Ordering::SeqCst);
+        }
+    };
+
+    match event.exception_type {
+        ExceptionType::Playback | ExceptionType::VideoDownload => set(HandleKind::Playback, false),
+        ExceptionType::Preview | ExceptionType::Reconnect => set(HandleKind::Preview, false),
+        ExceptionType::PreviewReconnectSuccess => set(HandleKind::Preview, true),
+        ExceptionType::Alarm | ExceptionType::AlarmReconnect => set(HandleKind::Alarm, false),
+        ExceptionType::AlarmReconnectSuccess => set(HandleKind::Alarm, true),
+        // 会话级别的异常影响该用户下的所有句柄
+        ExceptionType::Exchange | ExceptionType::ResumeExchange => {
+            let healthy = event.exception_type == ExceptionType::ResumeExchange;
+            for entry in watched.values() {
+                if entry.user_id == event.user_id {
+                    entry.healthy.store(healthy, Ordering::SeqCst);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// 只在调用失败后读取，因此每次读取都记录一条事件
 pub fn get_last_error_code() -> i32 {
-    unsafe { NET_DVR_GetLastError() as i32 }
+    let code = unsafe { NET_DVR_GetLastError() as i32 };
+    #[cfg(feature = "tracing")]
+    tracing::debug!(function = "NET_DVR_GetLastError", code, "SDK call failed");
+    code
+}
+
+#[cfg(feature = "tracing")]
+pub use log_bridge::set_sdk_log_bridge;
+
+#[cfg(feature = "tracing")]
+mod log_bridge {
+    use std::{
+        collections::HashMap,
+        fs::{self, File},
+        io::{Read, Seek, SeekFrom},
+        path::{Path, PathBuf},
+        sync::OnceLock,
+        thread,
+        time::Duration,
+    };
+
+    use super::{SdkLogLevel, enable_sdk_log};
+    use crate::ffi_util::decode_device_string;
+
+    const TAIL_INTERVAL: Duration = Duration::from_millis(500);
+
+    static BRIDGE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+    /// 开启 SDK 文件日志并把新写入的行转为 tracing 事件（target 为 `hik_net_sdk::sdk`）
+    ///
+    /// 日志写在临时目录下，返回该目录；重复调用直接返回已有目录
+    pub fn set_sdk_log_bridge() -> anyhow::Result<PathBuf> {
+        if let Some(dir) = BRIDGE_DIR.get() {
+            return Ok(dir.clone());
+        }
+        let dir = std::env::temp_dir().join(format!("hik-net-sdk-log-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        enable_sdk_log(SdkLogLevel::Debug, &dir, true)?;
+        if BRIDGE_DIR.set(dir.clone()).is_ok() {
+            let tail_dir = dir.clone();
+            thread::Builder::new()
+                .name("hik-sdk-log".to_string())
+                .spawn(move || tail_logs(&tail_dir))?;
+        }
+        Ok(dir)
+    }
+
+    // 每个文件记录已读取的位置，不完整的最后一行留到下次再输出
+    fn tail_logs(dir: &Path) {
+        let mut offsets: HashMap<PathBuf, (u64, Vec<u8>)> = HashMap::new();
+        loop {
+            if let Ok(entries) = fs::read_dir(dir) {
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    if path.is_file() {
+                        let (offset, pending) = offsets.entry(path.clone()).or_default();
+                        let _ = read_new_lines(&path, offset, pending);
+                    }
+                }
+            }
+            thread::sleep(TAIL_INTERVAL);
+        }
+    }
+
+    fn read_new_lines(path: &Path, offset: &mut u64, pending: &mut Vec<u8>) -> std::io::Result<()> {
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+        // SDK 按大小滚动日志时会截断文件
+        if len < *offset {
+            *offset = 0;
+            pending.clear();
+        }
+        file.seek(SeekFrom::Start(*offset))?;
+        let read = file.read_to_end(pending)?;
+        *offset += read as u64;
+
+        while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            let line = decode_device_string(&line);
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            if line.contains("[ERROR]") {
+                tracing::error!(target: "hik_net_sdk::sdk", "{}", line);
+            } else {
+                tracing::debug!(target: "hik_net_sdk::sdk", "{}", line);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sdk_version_display_includes_build_date() {
+        let mut version = SdkVersion {
+            major: 6,
+            minor: 1,
+            revision: 9,
+            build: 4,
+            build_date: None,
+        };
+        assert_eq!(version.to_string(), "6.1.9.4");
+        version.build_date = find_build_date(b"\0HCNetSDK V6.1.9.4_build20230410\0");
+        assert_eq!(version.to_string(), "6.1.9.4 build20230410");
+    }
+
+    #[test]
+    fn build_date_skips_invalid_candidates() {
+        assert_eq!(find_build_date(b"_build2023x410 _build20231341"), None);
+        assert_eq!(
+            find_build_date(b"_build2023 V6.1_build20221231"),
+            NaiveDate::from_ymd_opt(2022, 12, 31)
+        );
+    }
+
+    fn invalid_password(password: &str) -> String {
+        match activate_config(password)
+            .unwrap_err()
+            .downcast::<HikError>()
+        {
+            Ok(HikError::InvalidArgument { field, reason }) => {
+                assert_eq!(field, "password");
+                reason
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn activate_password_with_symbols() {
+        let password = r#"P@s$w0rd!"\'%&"#;
+        let config = activate_config(password).unwrap();
+        assert_eq!(
+            config.dwSize as usize,
+            mem::size_of::<NET_DVR_ACTIVATECFG>()
+        );
+        assert_eq!(&config.sPassword[..password.len()], password.as_bytes());
+        assert!(config.sPassword[password.len()..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn activate_password_non_ascii_is_copied_as_utf8() {
+        let password = "密码Ab1!";
+        let config = activate_config(password).unwrap();
+        assert_eq!(&config.sPassword[..password.len()], password.as_bytes());
+        assert_eq!(config.sPassword[password.len()], 0);
+    }
+
+    #[test]
+    fn activate_password_interior_nul() {
+        assert_eq!(
+            invalid_password("abc\0def"),
+            "contains an interior NUL byte"
+        );
+    }
+
+    #[test]
+    fn activate_password_max_length() {
+        // sPassword 为 16 字节，最多 15 个字节加结尾的 \0
+        let longest = "Abcdefgh1234!@#";
+        let config = activate_config(longest).unwrap();
+        assert_eq!(&config.sPassword[..15], longest.as_bytes());
+        assert_eq!(config.sPassword[15], 0);
+
+        assert_eq!(
+            invalid_password("Abcdefgh1234!@#$"),
+            "too long: 16 bytes, max 15"
+        );
+        // 多字节字符按字节计算长度
+        assert_eq!(
+            invalid_password("密码密码密码"),
+            "too long: 18 bytes, max 15"
+        );
+    }
 }
@@ -0,0 +1,377 @@
+use crate::{
+    DWORD, NET_DVR_COMPRESSION_INFO_V30, NET_DVR_COMPRESSIONCFG_V30, NET_DVR_GET_COMPRESSCFG_V30,
+    NET_DVR_SET_COMPRESSCFG_V30, device::HikDevice,
+};
+
+// dwVideoBitrate 的码率表，单位 kbps
+const BITRATE_TABLE: [(DWORD, u32); 27] = [
+    (1, 16),
+    (2, 32),
+    (3, 48),
+    (4, 64),
+    (5, 80),
+    (6, 96),
+    (7, 128),
+    (8, 160),
+    (9, 192),
+    (10, 224),
+    (11, 256),
+    (12, 320),
+    (13, 384),
+    (14, 448),
+    (15, 512),
+    (16, 640),
+    (17, 768),
+    (18, 896),
+    (19, 1024),
+    (20, 1280),
+    (21, 1536),
+    (22, 1792),
+    (23, 2048),
+    (24, 3072),
+    (25, 4096),
+    (26, 8192),
+    (31, 12288),
+];
+// 最高位为 1 时低 31 位为自定义码率
+const BITRATE_CUSTOM_FLAG: DWORD = 0x8000_0000;
+
+// dwVideoFrameRate 中整数帧率的编码，1/16 等分数帧率按 Other 处理
+const FRAME_RATE_TABLE: [(DWORD, u32); 28] = [
+    (5, 1),
+    (6, 2),
+    (25, 3),
+    (7, 4),
+    (26, 5),
+    (8, 6),
+    (27, 7),
+    (9, 8),
+    (28, 9),
+    (10, 10),
+    (11, 12),
+    (14, 15),
+    (12, 16),
+    (15, 18),
+    (13, 20),
+    (16, 22),
+    (31, 24),
+    (17, 25),
+    (18, 30),
+    (19, 35),
+    (20, 40),
+    (21, 45),
+    (32, 48),
+    (22, 50),
+    (23, 55),
+    (24, 60),
+    (29, 100),
+    (30, 120),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Resolution {
+    Dcif,
+    Cif,
+    Qcif,
+    // 4CIF
+    D1,
+    Qvga,
+    Vga,
+    Svga,
+    P720,
+    P900,
+    P1080,
+    // 2048*1536
+    Mp3,
+    // 2560*1440
+    Mp4,
+    // 2592*1944
+    Mp5,
+    // 3840*2160
+    Uhd4k,
+    Other(u8),
+}
+
+impl From<u8> for Resolution {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Resolution::Dcif,
+            1 => Resolution::Cif,
+            2 => Resolution::Qcif,
+            3 => Resolution::D1,
+            6 => Resolution::Qvga,
+            16 => Resolution::Vga,
+            18 => Resolution::Svga,
+            19 => Resolution::P720,
+            21 => Resolution::P900,
+            27 => Resolution::P1080,
+            30 => Resolution::Mp3,
+            70 => Resolution::Mp4,
+            67 => Resolution::Mp5,
+            64 => Resolution::Uhd4k,
+            other => Resolution::Other(other),
+        }
+    }
+}
+
+impl From<Resolution> for u8 {
+    fn from(value: Resolution) -> Self {
+        match value {
+            Resolution::Dcif => 0,
+            Resolution::Cif => 1,
+            Resolution::Qcif => 2,
+            Resolution::D1 => 3,
+            Resolution::Qvga => 6,
+            Resolution::Vga => 16,
+            Resolution::Svga => 18,
+            Resolution::P720 => 19,
+            Resolution::P900 => 21,
+            Resolution::P1080 => 27,
+            Resolution::Mp3 => 30,
+            Resolution::Mp4 => 70,
+            Resolution::Mp5 => 67,
+            Resolution::Uhd4k => 64,
+            Resolution::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VideoCodec {
+    // 海康私有 264
+    PrivateH264,
+    H264,
+    Mpeg4,
+    Mjpeg,
+    H265,
+    Other(u8),
+}
+
+impl From<u8> for VideoCodec {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => VideoCodec::PrivateH264,
+            1 => VideoCodec::H264,
+            2 => VideoCodec::Mpeg4,
+            7 => VideoCodec::Mjpeg,
+            10 => VideoCodec::H265,
+            other => VideoCodec::Other(other),
+        }
+    }
+}
+
+impl From<VideoCodec> for u8 {
+    fn from(value: VideoCodec) -> Self {
+        match value {
+            VideoCodec::PrivateH264 => 0,
+            VideoCodec::H264 => 1,
+            VideoCodec::Mpeg4 => 2,
+            VideoCodec::Mjpeg => 7,
+            VideoCodec::H265 => 10,
+            VideoCodec::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BitrateType {
+    // 变码率
+    Vbr,
+    // 定码率
+    Cbr,
+    Other(u8),
+}
+
+impl From<u8> for BitrateType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => BitrateType::Vbr,
+            1 => BitrateType::Cbr,
+            other => BitrateType::Other(other),
+        }
+    }
+}
+
+impl From<BitrateType> for u8 {
+    fn from(value: BitrateType) -> Self {
+        match value {
+            BitrateType::Vbr => 0,
+            BitrateType::Cbr => 1,
+            BitrateType::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VideoBitrate {
+    Kbps(u32),
+    // 无法识别的原始编码
+    Other(u32),
+}
+
+impl From<DWORD> for VideoBitrate {
+    fn from(value: DWORD) -> Self {
+        if value & BITRATE_CUSTOM_FLAG != 0 {
+            return VideoBitrate::Kbps(value & !BITRATE_CUSTOM_FLAG);
+        }
+        BITRATE_TABLE
+            .iter()
+            .find(|(code, _)| *code == value)
+            .map(|(_, kbps)| VideoBitrate::Kbps(*kbps))
+            .unwrap_or(VideoBitrate::Other(value))
+    }
+}
+
+impl From<VideoBitrate> for DWORD {
+    fn from(value: VideoBitrate) -> Self {
+        match value {
+            // 优先使用码率表中的编码，否则按自定义码率下发
+            VideoBitrate::Kbps(kbps) => BITRATE_TABLE
+                .iter()
+                .find(|(_, table_kbps)| *table_kbps == kbps)
+                .map(|(code, _)| *code)
+                .unwrap_or(BITRATE_CUSTOM_FLAG | kbps),
+            VideoBitrate::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FrameRate {
+    // 全帧率
+    Full,
+    Fps(u32),
+    Other(u32),
+}
+
+impl From<DWORD> for FrameRate {
+    fn from(value: DWORD) -> Self {
+        if value == 0 {
+            return FrameRate::Full;
+        }
+        FRAME_RATE_TABLE
+            .iter()
+            .find(|(code, _)| *code == value)
+            .map(|(_, fps)| FrameRate::Fps(*fps))
+            .unwrap_or(FrameRate::Other(value))
+    }
+}
+
+impl FrameRate {
+    fn to_code(self) -> anyhow::Result<DWORD> {
+        match self {
+            FrameRate::Full => Ok(0),
+            FrameRate::Fps(fps) => FRAME_RATE_TABLE
+                .iter()
+                .find(|(_, table_fps)| *table_fps == fps)
+                .map(|(code, _)| *code)
+                .ok_or(anyhow::anyhow!("Unsupported frame rate: {} fps", fps)),
+            FrameRate::Other(other) => Ok(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StreamParams {
+    pub resolution: Resolution,
+    pub codec: VideoCodec,
+    pub bitrate_type: BitrateType,
+    pub max_bitrate: VideoBitrate,
+    pub frame_rate: FrameRate,
+    // I 帧间隔
+    pub gop: u16,
+}
+
+impl From<&NET_DVR_COMPRESSION_INFO_V30> for StreamParams {
+    fn from(info: &NET_DVR_COMPRESSION_INFO_V30) -> Self {
+        Self {
+            resolution: Resolution::from(info.byResolution),
+            codec: VideoCodec::from(info.byVideoEncType),
+            bitrate_type: BitrateType::from(info.byBitrateType),
+            max_bitrate: VideoBitrate::from(info.dwVideoBitrate),
+            frame_rate: FrameRate::from(info.dwVideoFrameRate),
+            gop: info.wIntervalFrameI,
+        }
+    }
+}
+
+impl StreamParams {
+    fn apply(&self, info: &mut NET_DVR_COMPRESSION_INFO_V30) -> anyhow::Result<()> {
+        if self.frame_rate == FrameRate::Fps(0) {
+            return Err(anyhow::anyhow!("Frame rate must not be 0"));
+        }
+        if self.max_bitrate == VideoBitrate::Kbps(0) {
+            return Err(anyhow::anyhow!("Bitrate must not be 0"));
+        }
+
+        info.byResolution = self.resolution.into();
+        info.byVideoEncType = self.codec.into();
+        info.byBitrateType = self.bitrate_type.into();
+        info.dwVideoBitrate = self.max_bitrate.into();
+        info.dwVideoFrameRate = self.frame_rate.to_code()?;
+        info.wIntervalFrameI = self.gop;
+        Ok(())
+    }
+}
+
+pub struct CompressionConfig {
+    pub main: StreamParams,
+    // 子码流（网传）
+    pub sub: StreamParams,
+    // 保留设备返回的原始参数，set 时只修改上面的字段
+    raw: NET_DVR_COMPRESSIONCFG_V30,
+}
+
+impl HikDevice {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn get_compression_config(&self, channel: u16) -> anyhow::Result<CompressionConfig> {
+        let raw: NET_DVR_COMPRESSIONCFG_V30 = self.get_dvr_config(
+            NET_DVR_GET_COMPRESSCFG_V30,
+            self.resolve_channel(channel)?,
+            "Get compression config",
+        )?;
+        Ok(CompressionConfig {
+            main: StreamParams::from(&raw.struNormHighRecordPara),
+            sub: StreamParams::from(&raw.struNetPara),
+            raw,
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn set_compression_config(
+        &self,
+        channel: u16,
+        config: &CompressionConfig,
+    ) -> anyhow::Result<()> {
+        let channel = self.resolve_channel(channel)?;
+        let mut raw = config.raw;
+        config.main.apply(&mut raw.struNormHighRecordPara)?;
+        config.sub.apply(&mut raw.struNetPara)?;
+        self.set_dvr_config(
+            NET_DVR_SET_COMPRESSCFG_V30,
+            channel,
+            &raw,
+            "Set compression config",
+        )
+    }
+}
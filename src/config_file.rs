@@ -0,0 +1,179 @@
+use std::{fs, os::raw::c_char, path::Path};
+
+use crate::{
+    DWORD, NET_DVR_CHECK_PASSWORD_MISTAKE_ERROR, NET_DVR_ERR_WRONG_PASSWORD,
+    NET_DVR_GetConfigFile_V30, NET_DVR_NOENOUGH_BUF, NET_DVR_PASSWORD_ERROR,
+    NET_DVR_SetConfigFile_EX,
+    ability::xml_tag_value,
+    common::get_last_error_code,
+    device::{HikDevice, sdk_code, sdk_error},
+    error::HikError,
+    isapi::{IsapiMethod, IsapiResponse, percent_encode},
+    trace::sdk_call,
+};
+
+// 配置文件一般在几百 KB 到几 MB，不足时按设备返回的大小扩大
+const INITIAL_BUFFER_SIZE: usize = 1024 * 1024;
+const MAX_BUFFER_SIZE: usize = 64 * 1024 * 1024;
+
+// 带密码的导入导出走 ISAPI，URL 中的 secretkey 不能出现在错误信息里
+const CONFIG_DATA_URL: &str = "/ISAPI/System/configurationData";
+
+impl HikDevice {
+    /// 导出设备配置文件到 path
+    ///
+    /// password 为 None 时用 NET_DVR_GetConfigFile_V30 导出明文配置；
+    /// 为 Some 时通过 ISAPI 导出用该密码加密的配置，导入时需要同一个密码
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn export_config(&self, path: &Path, password: Option<&str>) -> anyhow::Result<()> {
+        let data = self.export_config_data(password)?;
+        fs::write(path, data)
+            .map_err(|e| anyhow::anyhow!("Write config file {} failed: {}", path.display(), e))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn export_config_data(&self, password: Option<&str>) -> anyhow::Result<Vec<u8>> {
+        match password {
+            None => self.get_config_file(),
+            Some(password) => {
+                let response = self.config_data_request(IsapiMethod::Get, password, None)?;
+                check_config_response(&response, "Export config")?;
+                Ok(response.body)
+            }
+        }
+    }
+
+    /// 导入 export_config 导出的配置文件，成功后设备会重启
+    ///
+    /// 登录句柄随之失效，可以调用 wait_for_reboot 等待设备恢复并重新登录；
+    /// 密码与导出时不一致返回 HikError::WrongConfigPassword
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn import_config(&mut self, path: &Path, password: Option<&str>) -> anyhow::Result<()> {
+        let data = fs::read(path)
+            .map_err(|e| anyhow::anyhow!("Read config file {} failed: {}", path.display(), e))?;
+        self.import_config_data(&data, password)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn import_config_data(
+        &mut self,
+        data: &[u8],
+        password: Option<&str>,
+    ) -> anyhow::Result<()> {
+        if data.is_empty() {
+            return Err(anyhow::anyhow!("Config data is empty"));
+        }
+        match password {
+            None => {
+                let lu = self.user_id()?;
+                let mut buffer = data.to_vec();
+                let res = unsafe {
+                    sdk_call!(NET_DVR_SetConfigFile_EX(
+                        lu,
+                        buffer.as_mut_ptr() as *mut c_char,
+                        buffer.len() as DWORD
+                    ))
+                };
+                if res != 1 {
+                    let error = sdk_error("Import config");
+                    return Err(match sdk_code(&error) {
+                        Some(code) if is_password_error(code) => {
+                            HikError::WrongConfigPassword.into()
+                        }
+                        _ => error,
+                    });
+                }
+            }
+            Some(password) => {
+                let response = self.config_data_request(IsapiMethod::Put, password, Some(data))?;
+                check_config_response(&response, "Import config")?;
+            }
+        }
+        // 导入后设备自动重启
+        self.invalidate_for_reboot();
+        Ok(())
+    }
+
+    fn get_config_file(&self) -> anyhow::Result<Vec<u8>> {
+        let mut size = INITIAL_BUFFER_SIZE;
+        self.with_session(|lu| {
+            loop {
+                let mut buffer = vec![0u8; size];
+                let mut returned: DWORD = 0;
+                let res = unsafe {
+                    sdk_call!(NET_DVR_GetConfigFile_V30(
+                        lu,
+                        buffer.as_mut_ptr() as *mut c_char,
+                        buffer.len() as DWORD,
+                        &mut returned
+                    ))
+                };
+                if res == 1 {
+                    buffer.truncate((returned as usize).min(buffer.len()));
+                    return Ok(buffer);
+                }
+                if get_last_error_code() == NET_DVR_NOENOUGH_BUF as i32 && size < MAX_BUFFER_SIZE {
+                    // 部分设备失败时在 returned 中给出需要的大小
+                    size = (returned as usize).max(size * 4).min(MAX_BUFFER_SIZE);
+                    continue;
+                }
+                return Err(sdk_error("Export config"));
+            }
+        })
+    }
+
+    fn config_data_request(
+        &self,
+        method: IsapiMethod,
+        password: &str,
+        body: Option<&[u8]>,
+    ) -> anyhow::Result<IsapiResponse> {
+        let url = format!("{}?secretkey={}", CONFIG_DATA_URL, percent_encode(password));
+        self.isapi_request(method, &url, body)
+    }
+}
+
+fn is_password_error(code: i32) -> bool {
+    [
+        NET_DVR_PASSWORD_ERROR,
+        NET_DVR_ERR_WRONG_PASSWORD,
+        NET_DVR_CHECK_PASSWORD_MISTAKE_ERROR,
+    ]
+    .contains(&(code as u32))
+}
+
+fn check_config_response(response: &IsapiResponse, action: &str) -> anyhow::Result<()> {
+    if response.success {
+        return Ok(());
+    }
+    let status = response.status_str().unwrap_or_default();
+    // 密码错误时 subStatusCode 一般带有 secretKey 或 password 字样
+    let sub_status = xml_tag_value(&status, "subStatusCode")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if is_password_error(response.error_code)
+        || sub_status.contains("secretkey")
+        || sub_status.contains("password")
+    {
+        return Err(HikError::WrongConfigPassword.into());
+    }
+    Err(anyhow::anyhow!(
+        "{} failed: ISAPI {} error code {}, status: {}",
+        action,
+        CONFIG_DATA_URL,
+        response.error_code,
+        status
+    ))
+}
@@ -0,0 +1,612 @@
+use std::{collections::BTreeMap, mem, os::raw::c_void, ptr, thread, time::Duration};
+
+use chrono::{
+    DateTime, Datelike as _, Days, Local, Months, NaiveDate, NaiveDateTime, NaiveTime,
+    TimeZone as _, Timelike as _,
+};
+
+use crate::{
+    BYTE, DWORD, LONG, NET_DVR_GET_HEATMAP_RESULT, NET_DVR_GET_PDC_RESULT,
+    NET_DVR_GetNextRemoteConfig, NET_DVR_HEATMAP_COND, NET_DVR_HEATMAP_RESULT,
+    NET_DVR_PDC_QUERY_COND, NET_DVR_PDC_RESULT, NET_DVR_StartRemoteConfig,
+    NET_DVR_StopRemoteConfig, NET_DVR_TIME_EX,
+    NET_SDK_GET_NEXT_STATUS_NET_SDK_GET_NETX_STATUS_NEED_WAIT,
+    NET_SDK_GET_NEXT_STATUS_NET_SDK_GET_NEXT_STATUS_FINISH,
+    NET_SDK_GET_NEXT_STATUS_NET_SDK_GET_NEXT_STATUS_SUCCESS, WORD,
+    ability::xml_tag_value,
+    device::{HikDevice, sdk_error},
+    isapi::IsapiMethod,
+    timezone::check_time_range,
+    trace::sdk_call,
+};
+
+// NET_DVR_PDC_QUERY_COND::byReportType：1 日报表（按小时），3 月报表（按天）
+const REPORT_TYPE_DAILY: BYTE = 1;
+const REPORT_TYPE_MONTHLY: BYTE = 3;
+// byMinTimeInterva：3 一小时，4 一天
+const INTERVAL_HOUR: BYTE = 3;
+const INTERVAL_DAY: BYTE = 4;
+// byStatisticType：3 进入和离开
+const STATISTIC_ALL: BYTE = 3;
+// 设备仍在统计时的重试间隔与次数，合计约 10 秒
+const NEXT_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+const NEXT_RETRY_LIMIT: u32 = 200;
+
+/// 客流统计的时间粒度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReportGranularity {
+    Hourly,
+    Daily,
+}
+
+/// 一个统计时段的客流数据
+///
+/// 计数为 None 表示设备没有该时段的数据（离线、断电、存储丢失等），与 0 人次区分
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CountingSample {
+    // 时段开始时间（设备本地时间），整点或零点
+    pub start: DateTime<Local>,
+    pub enter: Option<u32>,
+    pub exit: Option<u32>,
+    // 经过但未进入的人数，设备不支持时为 0
+    pub pass_by: Option<u32>,
+}
+
+impl CountingSample {
+    pub fn is_missing(&self) -> bool {
+        self.enter.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Counts {
+    enter: u32,
+    exit: u32,
+    pass_by: u32,
+}
+
+/// 一个统计时段的热度图
+///
+/// values 按行优先排列，共 rows * columns 个元素
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeatMapSample {
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub max: u32,
+    pub min: u32,
+    // 时段内的停留时间热度值
+    pub time_value: u32,
+    pub rows: u16,
+    pub columns: u16,
+    pub values: Vec<u32>,
+}
+
+impl ReportGranularity {
+    fn truncate(self, time: NaiveDateTime) -> NaiveDateTime {
+        let midnight = time.date().and_time(NaiveTime::MIN);
+        match self {
+            ReportGranularity::Hourly => midnight + chrono::Duration::hours(time.hour() as i64),
+            ReportGranularity::Daily => midnight,
+        }
+    }
+
+    fn step(self) -> chrono::Duration {
+        match self {
+            ReportGranularity::Hourly => chrono::Duration::hours(1),
+            ReportGranularity::Daily => chrono::Duration::days(1),
+        }
+    }
+
+    // 一次查询只能覆盖一张报表：日报表到次日零点，月报表到下月一日
+    fn report_end(self, time: NaiveDateTime) -> NaiveDateTime {
+        let date = match self {
+            ReportGranularity::Hourly => time.date().checked_add_days(Days::new(1)),
+            ReportGranularity::Daily => NaiveDate::from_ymd_opt(time.year(), time.month(), 1)
+                .and_then(|date| date.checked_add_months(Months::new(1))),
+        };
+        date.map_or(NaiveDateTime::MAX, |date| date.and_time(NaiveTime::MIN))
+    }
+
+    fn report_type(self) -> BYTE {
+        match self {
+            ReportGranularity::Hourly => REPORT_TYPE_DAILY,
+            ReportGranularity::Daily => REPORT_TYPE_MONTHLY,
+        }
+    }
+
+    fn interval(self) -> BYTE {
+        match self {
+            ReportGranularity::Hourly => INTERVAL_HOUR,
+            ReportGranularity::Daily => INTERVAL_DAY,
+        }
+    }
+
+    fn isapi_report_type(self) -> &'static str {
+        match self {
+            ReportGranularity::Hourly => "daily",
+            ReportGranularity::Daily => "monthly",
+        }
+    }
+}
+
+// NET_DVR_StartRemoteConfig 返回的句柄，drop 时关闭
+struct RemoteConfig(LONG);
+
+impl Drop for RemoteConfig {
+    fn drop(&mut self) {
+        unsafe { sdk_call!(NET_DVR_StopRemoteConfig(self.0)) };
+    }
+}
+
+impl HikDevice {
+    /// 查询通道在时间段内的客流统计（进入、离开、经过人数）
+    ///
+    /// 先使用 NET_DVR_GET_PDC_RESULT 远程配置，设备不支持时改用 ISAPI 的 counting/search。
+    /// start、end 按设备本地时间解释，第一个时段从 start 所在的整点或零点开始；
+    /// 设备没有返回数据的时段（例如断网期间）以 CountingSample::is_missing 的样本返回，而不是 0
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn get_people_counting(
+        &self,
+        channel: u16,
+        granularity: ReportGranularity,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> anyhow::Result<Vec<CountingSample>> {
+        let sdk_channel = self.resolve_channel(channel)?;
+        check_time_range(start, end)?;
+        let first = granularity.truncate(start.naive_local());
+        let end = end.naive_local();
+
+        let mut counts = BTreeMap::new();
+        let mut from = first;
+        while from < end {
+            let to = granularity.report_end(from).min(end);
+            let rows = match self.pdc_results(sdk_channel, granularity, from, to) {
+                Ok(rows) => rows,
+                Err(e) => self
+                    .isapi_counting(channel, granularity, from, to)
+                    .map_err(|_| e)?,
+            };
+            add_rows(&mut counts, granularity, rows);
+            from = to;
+        }
+        Ok(fill_slots(granularity, first, end, &counts))
+    }
+
+    /// 查询通道在时间段内的热度图统计结果
+    ///
+    /// 使用 NET_DVR_GET_HEATMAP_RESULT 远程配置。查询条件只包含通道，设备按自身配置的报表周期返回结果，
+    /// 这里只保留与 [start, end) 有交集的时段
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn get_heat_map(
+        &self,
+        channel: u16,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> anyhow::Result<Vec<HeatMapSample>> {
+        let sdk_channel = self.resolve_channel(channel)?;
+        check_time_range(start, end)?;
+        let mut cond = NET_DVR_HEATMAP_COND {
+            dwSize: mem::size_of::<NET_DVR_HEATMAP_COND>() as DWORD,
+            dwChannel: sdk_channel as DWORD,
+            ..Default::default()
+        };
+        let samples = self.with_session(|lu| {
+            remote_config_rows(
+                lu,
+                NET_DVR_GET_HEATMAP_RESULT,
+                &mut cond,
+                "Start heat map query",
+                "Get heat map result",
+                || NET_DVR_HEATMAP_RESULT {
+                    dwSize: mem::size_of::<NET_DVR_HEATMAP_RESULT>() as DWORD,
+                    ..Default::default()
+                },
+                heat_map_sample,
+            )
+        })?;
+        Ok(samples
+            .into_iter()
+            .filter(|sample| sample.start < end && sample.end > start)
+            .collect())
+    }
+
+    fn pdc_results(
+        &self,
+        channel: LONG,
+        granularity: ReportGranularity,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> anyhow::Result<Vec<(NaiveDateTime, Counts)>> {
+        let mut cond = NET_DVR_PDC_QUERY_COND {
+            dwSize: mem::size_of::<NET_DVR_PDC_QUERY_COND>() as DWORD,
+            dwChannel: channel as DWORD,
+            struStartTime: time_ex(from),
+            // 结束时间包含在查询范围内
+            struEndTime: time_ex(to - chrono::Duration::seconds(1)),
+            byReportType: granularity.report_type(),
+            byMinTimeInterva: granularity.interval(),
+            byStatisticType: STATISTIC_ALL,
+            ..Default::default()
+        };
+        self.with_session(|lu| {
+            remote_config_rows(
+                lu,
+                NET_DVR_GET_PDC_RESULT,
+                &mut cond,
+                "Start people counting query",
+                "Get people counting result",
+                || NET_DVR_PDC_RESULT {
+                    dwSize: mem::size_of::<NET_DVR_PDC_RESULT>() as DWORD,
+                    ..Default::default()
+                },
+                |result: &NET_DVR_PDC_RESULT| {
+                    let time = time_ex_to_naive(&result.struStartTime)?;
+                    Some((
+                        time,
+                        Counts {
+                            enter: result.dwEnterNum,
+                            exit: result.dwLeaveNum,
+                            pass_by: result.dwPeoplePassing,
+                        },
+                    ))
+                },
+            )
+        })
+    }
+
+    // ISAPI 的通道号与 RTSP 的通道序号一致
+    fn isapi_counting(
+        &self,
+        channel: u16,
+        granularity: ReportGranularity,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> anyhow::Result<Vec<(NaiveDateTime, Counts)>> {
+        let url = format!(
+            "/ISAPI/System/Video/inputs/channels/{}/counting/search",
+            self.rtsp_channel_id(channel)?
+        );
+        let body = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <CountingStatisticsDescription version=\"2.0\" xmlns=\"http://www.isapi.org/ver20/XMLSchema\">\
+             <statisticType>all</statisticType>\
+             <reportType>{}</reportType>\
+             <timeSpanList><timeSpan>\
+             <startTime>{}</startTime><endTime>{}</endTime>\
+             </timeSpan></timeSpanList>\
+             </CountingStatisticsDescription>",
+            granularity.isapi_report_type(),
+            from.format(ISAPI_TIME_FORMAT),
+            (to - chrono::Duration::seconds(1)).format(ISAPI_TIME_FORMAT)
+        );
+        let response = self.isapi_request(IsapiMethod::Post, &url, Some(body.as_bytes()))?;
+        if !response.success {
+            return Err(anyhow::anyhow!(
+                "ISAPI POST {} failed: error code {}, status: {}",
+                url,
+                response.error_code,
+                response.status_str().unwrap_or_default()
+            ));
+        }
+        Ok(parse_counting_result(&response.body_str()))
+    }
+}
+
+const ISAPI_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+fn time_ex(time: NaiveDateTime) -> NET_DVR_TIME_EX {
+    NET_DVR_TIME_EX {
+        wYear: time.year() as WORD,
+        byMonth: time.month() as BYTE,
+        byDay: time.day() as BYTE,
+        byHour: time.hour() as BYTE,
+        byMinute: time.minute() as BYTE,
+        bySecond: time.second() as BYTE,
+        byRes: 0,
+    }
+}
+
+fn time_ex_to_naive(time: &NET_DVR_TIME_EX) -> Option<NaiveDateTime> {
+    NaiveDate::from_ymd_opt(time.wYear as i32, time.byMonth as u32, time.byDay as u32)?.and_hms_opt(
+        time.byHour as u32,
+        time.byMinute as u32,
+        time.bySecond as u32,
+    )
+}
+
+// <matchElement> 列表，时间带时区后缀时取前面的设备本地时间
+fn parse_counting_result(xml: &str) -> Vec<(NaiveDateTime, Counts)> {
+    xml.split("<matchElement")
+        .skip(1)
+        .filter_map(|item| {
+            let time = xml_tag_value(item, "startTime")?.trim();
+            let time = NaiveDateTime::parse_from_str(time.get(..19)?, ISAPI_TIME_FORMAT).ok()?;
+            let count = |tag: &str| {
+                xml_tag_value(item, tag)
+                    .and_then(|value| value.trim().parse().ok())
+                    .unwrap_or(0)
+            };
+            Some((
+                time,
+                Counts {
+                    enter: count("enterCount"),
+                    exit: count("exitCount"),
+                    pass_by: count("passingCount"),
+                },
+            ))
+        })
+        .collect()
+}
+
+// 启动远程配置查询并逐条读取结果，直到设备返回结束
+fn remote_config_rows<C, R, T>(
+    lu: LONG,
+    command: DWORD,
+    cond: &mut C,
+    start_action: &'static str,
+    next_action: &'static str,
+    empty: impl Fn() -> R,
+    mut row: impl FnMut(&R) -> Option<T>,
+) -> anyhow::Result<Vec<T>> {
+    let handle = unsafe {
+        sdk_call!(NET_DVR_StartRemoteConfig(
+            lu,
+            command,
+            cond as *mut C as *mut c_void,
+            mem::size_of::<C>() as DWORD,
+            None,
+            ptr::null_mut(),
+        ))
+    };
+    if handle < 0 {
+        return Err(sdk_error(start_action));
+    }
+    let handle = RemoteConfig(handle);
+
+    let mut rows = Vec::new();
+    let mut retries = 0;
+    loop {
+        let mut result = empty();
+        let status = unsafe {
+            sdk_call!(NET_DVR_GetNextRemoteConfig(
+                handle.0,
+                &mut result as *mut R as *mut c_void,
+                mem::size_of::<R>() as DWORD,
+            ))
+        };
+        if status < 0 {
+            return Err(sdk_error(next_action));
+        }
+        match status as u32 {
+            NET_SDK_GET_NEXT_STATUS_NET_SDK_GET_NEXT_STATUS_SUCCESS => rows.extend(row(&result)),
+            NET_SDK_GET_NEXT_STATUS_NET_SDK_GET_NETX_STATUS_NEED_WAIT => {
+                retries += 1;
+                if retries > NEXT_RETRY_LIMIT {
+                    return Err(anyhow::anyhow!("{} timed out", next_action));
+                }
+                thread::sleep(NEXT_RETRY_INTERVAL);
+            }
+            NET_SDK_GET_NEXT_STATUS_NET_SDK_GET_NEXT_STATUS_FINISH => return Ok(rows),
+            _ => return Err(sdk_error(next_action)),
+        }
+    }
+}
+
+// 同一时段的多条记录（跨报表查询、ISAPI 分段）累加到一起
+fn add_rows(
+    counts: &mut BTreeMap<NaiveDateTime, Counts>,
+    granularity: ReportGranularity,
+    rows: Vec<(NaiveDateTime, Counts)>,
+) {
+    for (time, row) in rows {
+        let slot = counts.entry(granularity.truncate(time)).or_default();
+        slot.enter += row.enter;
+        slot.exit += row.exit;
+        slot.pass_by += row.pass_by;
+    }
+}
+
+// 按粒度列出 [first, end) 的每个时段，没有记录的时段计数为 None
+fn fill_slots(
+    granularity: ReportGranularity,
+    first: NaiveDateTime,
+    end: NaiveDateTime,
+    counts: &BTreeMap<NaiveDateTime, Counts>,
+) -> Vec<CountingSample> {
+    let mut samples = Vec::new();
+    let mut slot = first;
+    while slot < end {
+        // 夏令时跳过的整点不存在，也不会有数据
+        if let Some(start) = Local.from_local_datetime(&slot).earliest() {
+            let row = counts.get(&slot);
+            samples.push(CountingSample {
+                start,
+                enter: row.map(|row| row.enter),
+                exit: row.map(|row| row.exit),
+                pass_by: row.map(|row| row.pass_by),
+            });
+        }
+        slot += granularity.step();
+    }
+    samples
+}
+
+fn heat_map_sample(result: &NET_DVR_HEATMAP_RESULT) -> Option<HeatMapSample> {
+    let local = |time| {
+        Local
+            .from_local_datetime(&time_ex_to_naive(time)?)
+            .earliest()
+    };
+    let rows = result.wArrayLine;
+    let columns = result.wArrayColumn;
+    let unit = match result.byArrayUnitType {
+        0 => 1,
+        unit => unit as usize,
+    };
+    let len = rows as usize * columns as usize * unit;
+    // pBuffer 由 SDK 管理，只在下一次 GetNextRemoteConfig 之前有效
+    let data = if result.pBuffer.is_null() || len == 0 {
+        &[][..]
+    } else {
+        unsafe { std::slice::from_raw_parts(result.pBuffer, len) }
+    };
+    Some(HeatMapSample {
+        start: local(&result.struStartTime)?,
+        end: local(&result.struEndTime)?,
+        max: result.dwMaxHeatMapValue,
+        min: result.dwMinHeatMapValue,
+        time_value: result.dwTimeHeatMapValue,
+        rows,
+        columns,
+        values: heat_map_values(data, result.byArrayUnitType),
+    })
+}
+
+// 矩阵元素类型：1 BYTE，2 WORD，4 DWORD（0 按 BYTE 处理）
+fn heat_map_values(data: &[u8], unit_type: BYTE) -> Vec<u32> {
+    match unit_type {
+        0 | 1 => data.iter().map(|&value| value as u32).collect(),
+        2 => data
+            .chunks_exact(2)
+            .map(|value| u16::from_ne_bytes([value[0], value[1]]) as u32)
+            .collect(),
+        4 => data
+            .chunks_exact(4)
+            .map(|value| u32::from_ne_bytes([value[0], value[1], value[2], value[3]]))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    fn counts(enter: u32, exit: u32) -> Counts {
+        Counts {
+            enter,
+            exit,
+            pass_by: 0,
+        }
+    }
+
+    #[test]
+    fn missing_hours_are_none_not_zero() {
+        let mut slots = BTreeMap::new();
+        add_rows(
+            &mut slots,
+            ReportGranularity::Hourly,
+            vec![(at(15, 8), counts(3, 1)), (at(15, 10), counts(0, 0))],
+        );
+        let samples = fill_slots(ReportGranularity::Hourly, at(15, 8), at(15, 11), &slots);
+        let enter: Vec<_> = samples.iter().map(|sample| sample.enter).collect();
+        assert_eq!(enter, [Some(3), None, Some(0)]);
+        assert!(samples[1].is_missing());
+        assert_eq!(samples[1].exit, None);
+        assert_eq!(samples[1].pass_by, None);
+        assert!(!samples[2].is_missing());
+    }
+
+    #[test]
+    fn missing_days_are_none_and_rows_are_summed() {
+        let mut slots = BTreeMap::new();
+        add_rows(
+            &mut slots,
+            ReportGranularity::Daily,
+            vec![
+                (at(1, 9), counts(2, 2)),
+                (at(1, 17), counts(5, 4)),
+                (at(3, 0), counts(1, 0)),
+            ],
+        );
+        let samples = fill_slots(ReportGranularity::Daily, at(1, 0), at(4, 0), &slots);
+        let enter: Vec<_> = samples.iter().map(|sample| sample.enter).collect();
+        assert_eq!(enter, [Some(7), None, Some(1)]);
+        assert_eq!(samples[0].exit, Some(6));
+    }
+
+    #[test]
+    fn isapi_gaps_stay_missing() {
+        let xml = "<CountingStatisticsResult><matchList>\
+                   <matchElement><timeSpan><startTime>2024-01-15T08:00:00+08:00</startTime></timeSpan>\
+                   <enterCount>4</enterCount><exitCount>2</exitCount></matchElement>\
+                   <matchElement><timeSpan><startTime>2024-01-15T10:00:00</startTime></timeSpan>\
+                   <enterCount>1</enterCount><exitCount>1</exitCount><passingCount>6</passingCount></matchElement>\
+                   </matchList></CountingStatisticsResult>";
+        let rows = parse_counting_result(xml);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].0, at(15, 8));
+        assert_eq!(rows[1].0, at(15, 10));
+        assert_eq!(rows[1].1.pass_by, 6);
+
+        let mut slots = BTreeMap::new();
+        add_rows(&mut slots, ReportGranularity::Hourly, rows);
+        let samples = fill_slots(ReportGranularity::Hourly, at(15, 8), at(15, 11), &slots);
+        assert_eq!(samples[0].pass_by, Some(0));
+        assert!(samples[1].is_missing());
+        assert_eq!(samples[2].pass_by, Some(6));
+    }
+
+    #[test]
+    fn heat_map_values_follow_unit_type() {
+        assert_eq!(heat_map_values(&[1, 2, 3], 1), [1, 2, 3]);
+        assert_eq!(heat_map_values(&[7], 0), [7]);
+        let words: Vec<u8> = [300u16, 5].iter().flat_map(|v| v.to_ne_bytes()).collect();
+        assert_eq!(heat_map_values(&words, 2), [300, 5]);
+        let dwords: Vec<u8> = [70000u32].iter().flat_map(|v| v.to_ne_bytes()).collect();
+        assert_eq!(heat_map_values(&dwords, 4), [70000]);
+        assert!(heat_map_values(&[1, 2, 3], 3).is_empty());
+    }
+
+    #[test]
+    fn heat_map_sample_copies_matrix() {
+        let mut matrix = [1u8, 2, 3, 4, 5, 6];
+        let result = NET_DVR_HEATMAP_RESULT {
+            struStartTime: time_ex(at(15, 8)),
+            struEndTime: time_ex(at(15, 9)),
+            dwMaxHeatMapValue: 6,
+            dwMinHeatMapValue: 1,
+            wArrayLine: 2,
+            wArrayColumn: 3,
+            pBuffer: matrix.as_mut_ptr(),
+            byArrayUnitType: 1,
+            ..Default::default()
+        };
+        let sample = heat_map_sample(&result).unwrap();
+        assert_eq!(sample.start.naive_local(), at(15, 8));
+        assert_eq!(sample.end.naive_local(), at(15, 9));
+        assert_eq!((sample.rows, sample.columns), (2, 3));
+        assert_eq!(sample.values, [1, 2, 3, 4, 5, 6]);
+
+        let empty = NET_DVR_HEATMAP_RESULT {
+            pBuffer: ptr::null_mut(),
+            ..result
+        };
+        assert!(heat_map_sample(&empty).unwrap().values.is_empty());
+    }
+}
@@ -0,0 +1,292 @@
+use std::mem;
+
+use crate::{
+    DWORD, MAX_WINDOW, NET_DVR_DECODER_WORK_STATUS, NET_DVR_MATRIX_DEC_CHAN_STATUS,
+    NET_DVR_MATRIX_DYNAMIC_DEC, NET_DVR_MatrixGetDecChanStatus, NET_DVR_MatrixGetDeviceStatus,
+    NET_DVR_MatrixGetDisplayCfg, NET_DVR_MatrixSetDisplayCfg, NET_DVR_MatrixStartDynamic,
+    NET_DVR_MatrixStopDynamic, NET_DVR_VGA_DISP_CHAN_CFG,
+    device::{HikDevice, sdk_error},
+    ffi_util::{copy_to_byte_array, copy_to_c_array},
+    preview::StreamType,
+    trace::sdk_call,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DecodeProtocol {
+    #[default]
+    Tcp,
+    Udp,
+}
+
+// 解码器主动从前端设备取流时使用的参数
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RemoteStreamSource {
+    pub ip: String,
+    pub port: u16,
+    pub username: String,
+    // 老结构体只有 16 字节，密码最长 15 个字符
+    pub password: String,
+    // 前端设备上的通道号
+    pub channel: u8,
+    // 只支持主码流和子码流
+    pub stream_type: StreamType,
+    pub protocol: DecodeProtocol,
+}
+
+impl RemoteStreamSource {
+    fn to_sdk(&self) -> anyhow::Result<NET_DVR_MATRIX_DYNAMIC_DEC> {
+        let mut raw = NET_DVR_MATRIX_DYNAMIC_DEC {
+            dwSize: mem::size_of::<NET_DVR_MATRIX_DYNAMIC_DEC>() as DWORD,
+            ..Default::default()
+        };
+        let info = &mut raw.struDecChanInfo;
+        copy_to_c_array(&mut info.sDVRIP, &self.ip, "Source IP")?;
+        copy_to_byte_array(&mut info.sUserName, &self.username, "Username")?;
+        copy_to_byte_array(&mut info.sPassword, &self.password, "Password")?;
+        info.wDVRPort = self.port;
+        info.byChannel = self.channel;
+        info.byTransProtocol = match self.protocol {
+            DecodeProtocol::Tcp => 0,
+            DecodeProtocol::Udp => 1,
+        };
+        info.byTransMode = match self.stream_type {
+            StreamType::Main => 0,
+            StreamType::Sub => 1,
+            StreamType::Third => {
+                return Err(anyhow::anyhow!(
+                    "Dynamic decode only supports the main and sub stream"
+                ));
+            }
+        };
+        Ok(raw)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DecodeLinkState {
+    Idle,
+    Connecting,
+    Connected,
+    Decoding,
+    Other(u32),
+}
+
+impl From<DWORD> for DecodeLinkState {
+    fn from(value: DWORD) -> Self {
+        match value {
+            0 => DecodeLinkState::Idle,
+            1 => DecodeLinkState::Connecting,
+            2 => DecodeLinkState::Connected,
+            3 => DecodeLinkState::Decoding,
+            other => DecodeLinkState::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecodeStatus {
+    pub link: DecodeLinkState,
+    // 从前端取流的码率，单位 kbps
+    pub bitrate_kbps: u32,
+    // 来自设备工作状态，设备未返回该通道时为 None
+    pub frame_rate: Option<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DisplayLayout {
+    // 画面分割数：1、4、9、16 等
+    pub window_mode: u32,
+    // 每个窗口绑定的解码通道，0 表示不绑定
+    pub windows: Vec<u8>,
+    // 输出音频的窗口，None 表示关闭音频
+    pub audio_window: Option<u8>,
+}
+
+impl From<&NET_DVR_VGA_DISP_CHAN_CFG> for DisplayLayout {
+    fn from(cfg: &NET_DVR_VGA_DISP_CHAN_CFG) -> Self {
+        let count = (cfg.dwWindowMode as usize).min(MAX_WINDOW as usize);
+        Self {
+            window_mode: cfg.dwWindowMode,
+            windows: cfg.byJoinDecChan[..count].to_vec(),
+            audio_window: (cfg.byAudio == 1).then_some(cfg.byAudioWindowIdx),
+        }
+    }
+}
+
+impl HikDevice {
+    /// 解码通道主动连接前端设备并解码上墙
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), decode_channel = decode_channel)
+        )
+    )]
+    pub fn start_dynamic_decode(
+        &self,
+        decode_channel: u32,
+        remote: RemoteStreamSource,
+    ) -> anyhow::Result<()> {
+        let mut raw = remote.to_sdk()?;
+        self.with_session(|lu| {
+            let res =
+                unsafe { sdk_call!(NET_DVR_MatrixStartDynamic(lu, decode_channel, &mut raw)) };
+            if res != 1 {
+                return Err(sdk_error("Start dynamic decode"));
+            }
+            Ok(())
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), decode_channel = decode_channel)
+        )
+    )]
+    pub fn stop_dynamic_decode(&self, decode_channel: u32) -> anyhow::Result<()> {
+        self.with_session(|lu| {
+            let res = unsafe { sdk_call!(NET_DVR_MatrixStopDynamic(lu, decode_channel)) };
+            if res != 1 {
+                return Err(sdk_error("Stop dynamic decode"));
+            }
+            Ok(())
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), decode_channel = decode_channel)
+        )
+    )]
+    pub fn get_decode_status(&self, decode_channel: u32) -> anyhow::Result<DecodeStatus> {
+        let mut status = NET_DVR_MATRIX_DEC_CHAN_STATUS {
+            dwSize: mem::size_of::<NET_DVR_MATRIX_DEC_CHAN_STATUS>() as DWORD,
+            ..Default::default()
+        };
+        self.with_session(|lu| {
+            let res = unsafe {
+                sdk_call!(NET_DVR_MatrixGetDecChanStatus(
+                    lu,
+                    decode_channel,
+                    &mut status
+                ))
+            };
+            if res != 1 {
+                return Err(sdk_error("Get decode status"));
+            }
+            Ok(())
+        })?;
+
+        // 帧率只在整机状态中返回，部分型号不支持时忽略
+        let mut work = Box::new(NET_DVR_DECODER_WORK_STATUS {
+            dwSize: mem::size_of::<NET_DVR_DECODER_WORK_STATUS>() as DWORD,
+            ..Default::default()
+        });
+        let frame_rate = self
+            .with_session(|lu| {
+                let res = unsafe { sdk_call!(NET_DVR_MatrixGetDeviceStatus(lu, &mut *work)) };
+                if res != 1 {
+                    return Err(sdk_error("Get decoder status"));
+                }
+                Ok(())
+            })
+            .ok()
+            .and_then(|_| {
+                work.struDecChanStatus
+                    .iter()
+                    .find(|chan| chan.dwDecChan == decode_channel)
+                    .map(|chan| chan.byFpsDecV)
+            });
+
+        Ok(DecodeStatus {
+            link: DecodeLinkState::from(status.dwIsLinked),
+            bitrate_kbps: status.dwStreamCpRate,
+            frame_rate,
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), display_channel = display_channel)
+        )
+    )]
+    pub fn get_display_layout(&self, display_channel: u32) -> anyhow::Result<DisplayLayout> {
+        let cfg = self.get_display_cfg(display_channel)?;
+        Ok(DisplayLayout::from(&cfg))
+    }
+
+    // 先读取当前配置再修改，分辨率、制式等其他字段保持不变
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), display_channel = display_channel)
+        )
+    )]
+    pub fn set_display_layout(
+        &self,
+        display_channel: u32,
+        layout: &DisplayLayout,
+    ) -> anyhow::Result<()> {
+        if layout.windows.len() > layout.window_mode as usize
+            || layout.windows.len() > MAX_WINDOW as usize
+        {
+            return Err(anyhow::anyhow!(
+                "Too many windows for window mode {}: {}",
+                layout.window_mode,
+                layout.windows.len()
+            ));
+        }
+        let mut cfg = self.get_display_cfg(display_channel)?;
+        cfg.dwWindowMode = layout.window_mode;
+        cfg.byJoinDecChan = [0; MAX_WINDOW as usize];
+        cfg.byJoinDecChan[..layout.windows.len()].copy_from_slice(&layout.windows);
+        match layout.audio_window {
+            Some(window) => {
+                cfg.byAudio = 1;
+                cfg.byAudioWindowIdx = window;
+            }
+            None => cfg.byAudio = 0,
+        }
+        self.with_session(|lu| {
+            let res =
+                unsafe { sdk_call!(NET_DVR_MatrixSetDisplayCfg(lu, display_channel, &mut cfg)) };
+            if res != 1 {
+                return Err(sdk_error("Set display config"));
+            }
+            Ok(())
+        })
+    }
+
+    fn get_display_cfg(&self, display_channel: u32) -> anyhow::Result<NET_DVR_VGA_DISP_CHAN_CFG> {
+        let mut cfg = NET_DVR_VGA_DISP_CHAN_CFG {
+            dwSize: mem::size_of::<NET_DVR_VGA_DISP_CHAN_CFG>() as DWORD,
+            ..Default::default()
+        };
+        self.with_session(|lu| {
+            let res =
+                unsafe { sdk_call!(NET_DVR_MatrixGetDisplayCfg(lu, display_channel, &mut cfg)) };
+            if res != 1 {
+                return Err(sdk_error("Get display config"));
+            }
+            Ok(())
+        })?;
+        Ok(cfg)
+    }
+}
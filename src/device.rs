@@ -1,32 +1,82 @@
 use std::{
-    mem,
-    os::raw::c_char,
-    sync::atomic::{AtomicBool, Ordering},
+    collections::{HashMap, VecDeque},
+    fmt, mem,
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    os::raw::c_void,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
-use chrono::{DateTime, Datelike as _, Local, Timelike as _};
+use chrono::{DateTime, Local};
 
 use crate::{
-    DWORD, LONG, LPNET_DVR_DEVICEINFO_V30, NET_DVR_CaptureJPEGPicture, NET_DVR_DEVICEINFO_V30,
-    NET_DVR_GET_IPPARACFG_V40, NET_DVR_GetDVRConfig, NET_DVR_GetDownloadPos,
-    NET_DVR_GetFileByTime_V40, NET_DVR_IPPARACFG_V40, NET_DVR_JPEGPARA, NET_DVR_Login_V30,
-    NET_DVR_Logout_V30, NET_DVR_PLAYCOND, NET_DVR_PLAYSTART, NET_DVR_PlayBackControl_V40,
-    NET_DVR_StopGetFile, NET_DVR_TIME, as_c_string, common::get_last_error_code,
+    BYTE, DWORD, LONG, LPNET_DVR_DEVICEINFO_V30, MAX_IP_DEVICE_V40, NET_DVR_COMPLETE_RESTORE_CTRL,
+    NET_DVR_COMPLETE_RESTORE_INFO, NET_DVR_DEVICEINFO_V30, NET_DVR_DEVICEINFO_V40,
+    NET_DVR_GET_IPPARACFG_V40, NET_DVR_GET_NTPCFG, NET_DVR_GET_PICCFG_V40, NET_DVR_GET_TIMECFG,
+    NET_DVR_IPPARACFG_V40, NET_DVR_JPEGPARA, NET_DVR_Logout_V30, NET_DVR_NOSUPPORT,
+    NET_DVR_NOT_SUPPORT, NET_DVR_NTPPARA, NET_DVR_PASSWORD_ERROR, NET_DVR_PICCFG_V40,
+    NET_DVR_PLAYCOND, NET_DVR_PLAYSTART, NET_DVR_SET_NTPCFG, NET_DVR_SET_TIMECFG,
+    NET_DVR_SET_TRANS_TYPE, NET_DVR_STREAM_INFO, NET_DVR_TIME, NET_DVR_USER_LOCKED,
+    NET_DVR_USER_LOGIN_INFO, NET_DVR_VOD_PARA, as_c_string,
+    cancel::{CancellationToken, wait_stop},
+    common::{HandleKind, get_last_error_code, sdk_version, unwatch_handle, watch_handle},
+    error::HikError,
+    ffi_util::{c_chars_as_bytes, copy_to_byte_array, copy_to_c_array, decode_device_string},
+    playback::{HikPlayback, PlaybackControl, PlaybackEvent, play_back_control},
+    sdk::{NetSdk, RealSdk, sdk_error_from, struct_bytes, struct_bytes_mut},
+    sdk_struct::SdkStruct,
+    session::{Session, SessionState},
+    time::check_device_time,
+    timezone::{TimeMode, check_time_range},
+    trace::sdk_call,
 };
 
 pub struct HikDevice {
-    login_hanlder: Option<LONG>,
-    device_info: Option<HikDeviceInfo>,
+    pub(crate) session: Session,
+    // 登录时选择的取流协议
+    transport: TransportMode,
+    pub(crate) sdk: Arc<dyn NetSdk>,
+}
+
+// 错误报告中带上 SDK 版本，不输出凭据
+impl fmt::Debug for HikDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HikDevice")
+            .field("host", &self.session.host())
+            .field("user_id", &self.session.user_id())
+            .field(
+                "device_type",
+                &self.get_device_info().map(|info| info.v30().wDevType),
+            )
+            .field("sdk_version", &sdk_version().to_string())
+            .finish()
+    }
 }
 
 impl HikDevice {
     pub fn new() -> Self {
+        Self::with_sdk(Arc::new(RealSdk))
+    }
+
+    // 替换底层 SDK 调用，主要用于配合 MockSdk 测试
+    pub fn with_sdk(sdk: Arc<dyn NetSdk>) -> Self {
         Self {
-            login_hanlder: None,
-            device_info: None,
+            session: Session::new(),
+            transport: TransportMode::default(),
+            sdk,
         }
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = ip))
+    )]
     pub fn login(
         &mut self,
         ip: &str,
@@ -34,83 +84,283 @@ impl HikDevice {
         password: &str,
         port: u16,
     ) -> anyhow::Result<&mut Self> {
-        let ip = as_c_string!(ip);
-        let username = as_c_string!(username);
-        let password = as_c_string!(password);
-
-        let mut device_info = NET_DVR_DEVICEINFO_V30::default();
-
-        let res = unsafe {
-            NET_DVR_Login_V30(
-                ip.as_ptr() as *mut c_char,
-                port,
-                username.as_ptr() as *mut c_char,
-                password.as_ptr() as *mut c_char,
-                &mut device_info as LPNET_DVR_DEVICEINFO_V30,
-            )
+        self.login_v40(LoginOptions::new(ip, port, username, password))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %options.host))
+    )]
+    pub fn login_v40(&mut self, options: LoginOptions) -> anyhow::Result<&mut Self> {
+        self.session.apply_timeouts()?;
+        let Some(timeout_ms) = options.timeout_ms else {
+            let (user_id, device_info) = login_blocking(&*self.sdk, &options)?;
+            self.transport = options.use_transport;
+            self.session
+                .start(user_id, &options, HikDeviceInfo::from_v40(device_info));
+            return Ok(self);
         };
 
+        // 异步登录，结果通过回调返回，超时后由回调负责注销迟到的登录
+        let mut login_info = login_info(&options)?;
+        let mut device_info = NET_DVR_DEVICEINFO_V40::default();
+        let (key, receiver) = register_pending_login();
+        login_info.bUseAsynLogin = 1;
+        login_info.cbLoginResult = Some(login_result_callback);
+        login_info.pUser = key as *mut c_void;
+
+        let res = self.sdk.login_v40(&mut login_info, &mut device_info);
         if res < 0 {
-            let error_code = get_last_error_code();
-            return Err(anyhow::anyhow!("Login failed: error code {}", error_code));
+            pending_logins().lock().unwrap().remove(&key);
+            return Err(login_error(self.sdk.get_last_error() as i32, None));
         }
 
-        self.device_info = Some(HikDeviceInfo::new(device_info));
-        self.login_hanlder = Some(res);
+        let result = match receiver.recv_timeout(Duration::from_millis(timeout_ms as u64)) {
+            Ok(result) => result,
+            Err(_) => {
+                if pending_logins().lock().unwrap().remove(&key).is_some() {
+                    return Err(anyhow::anyhow!(
+                        "Login failed: timed out after {} ms",
+                        timeout_ms
+                    ));
+                }
+                // 回调已取走 sender，结果马上就会送达
+                receiver
+                    .recv()
+                    .map_err(|_| anyhow::anyhow!("Login failed: result channel closed"))?
+            }
+        };
+
+        let (user_id, device_info) = result.map_err(|code| login_error(code, None))?;
+        self.transport = options.use_transport;
+        self.session
+            .start(user_id, &options, HikDeviceInfo::new(device_info));
         Ok(self)
     }
 
+    // 同时清除自动重登录保存的凭据
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
     pub fn logout(&mut self) -> anyhow::Result<&mut Self> {
-        if let Some(login_hanlder) = self.login_hanlder.take() {
-            unsafe {
-                NET_DVR_Logout_V30(login_hanlder);
-            }
+        // 先停止该会话打开的句柄，注销后再停止会得到无效句柄错误
+        self.session.close_children();
+        if let Some(user_id) = self.session.end() {
+            self.sdk.logout(user_id);
+            self.session.emit_logged_out(user_id);
         }
-        self.device_info = None;
-        self.login_hanlder = None;
         Ok(self)
     }
 
-    pub fn get_channels(&self) -> anyhow::Result<Vec<Channel>> {
-        let channel_config = self.get_ip_channel_config()?;
-        let mut channels = match self.device_info.as_ref() {
-            Some(device_info) => device_info.get_channels(),
-            None => return Err(anyhow::anyhow!("Device info not found")),
+    // 登录时选择的取流协议，start_preview 按此取流
+    pub fn transport(&self) -> TransportMode {
+        self.transport
+    }
+
+    pub fn is_logged_in(&self) -> bool {
+        self.session.user_id().is_some()
+    }
+
+    pub(crate) fn user_id(&self) -> anyhow::Result<LONG> {
+        self.session.user_id().ok_or(HikError::NotLoggedIn.into())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn reboot(&mut self) -> anyhow::Result<()> {
+        let lu = self.user_id()?;
+        if self.sdk.reboot(lu) != 1 {
+            return Err(sdk_error_from(&*self.sdk, "Reboot"));
+        }
+        // 设备重启后连接已断开，句柄失效，不再调用 Logout
+        self.invalidate_for_reboot();
+        Ok(())
+    }
+
+    /// 等待 reboot、import_config 或固件升级后重启的设备恢复，并用原来的凭据重新登录
+    ///
+    /// 先等设备断开（最多 30 秒），避免在重启开始前就登录成功；
+    /// 密码错误或账号锁定时立即返回，不会反复尝试
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn wait_for_reboot(&mut self, timeout: Duration) -> anyhow::Result<()> {
+        let options = self.session.reboot_options().ok_or(anyhow::anyhow!(
+            "No reboot pending: call reboot, import_config or upgrade_firmware first"
+        ))?;
+        let addr = (options.host.as_str(), options.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or(anyhow::anyhow!("Cannot resolve device address"))?;
+        let start = Instant::now();
+        let deadline = start + timeout;
+
+        let down_deadline = (start + REBOOT_START_GRACE).min(deadline);
+        while Instant::now() < down_deadline && is_reachable(&addr) {
+            thread::sleep(REBOOT_POLL_INTERVAL);
+        }
+
+        let mut last_error = None;
+        loop {
+            if is_reachable(&addr) {
+                match self.login_v40(options.clone()) {
+                    Ok(_) => return Ok(()),
+                    Err(e) => {
+                        let code = self.sdk.get_last_error();
+                        if code == NET_DVR_PASSWORD_ERROR
+                            || code == NET_DVR_USER_LOCKED
+                            || e.downcast_ref::<HikError>().is_some()
+                        {
+                            return Err(e);
+                        }
+                        last_error = Some(e);
+                    }
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(match last_error {
+                    Some(e) => e.context(format!(
+                        "Device did not come back within {} s",
+                        timeout.as_secs()
+                    )),
+                    None => {
+                        anyhow::anyhow!("Device did not come back within {} s", timeout.as_secs())
+                    }
+                });
+            }
+            thread::sleep(REBOOT_POLL_INTERVAL);
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn shutdown(&mut self) -> anyhow::Result<()> {
+        let lu = self.user_id()?;
+        if self.sdk.shutdown(lu) != 1 {
+            return Err(sdk_error_from(&*self.sdk, "Shutdown"));
+        }
+        self.invalidate_session();
+        Ok(())
+    }
+
+    // full 为 true 时完全恢复出厂设置（包括网络和用户参数），否则保留网络和用户参数；
+    // 完全恢复后设备会重启且原来的账号不再有效，会话随之失效，不保留凭据
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn restore_defaults(&mut self, full: bool) -> anyhow::Result<()> {
+        let lu = self.user_id()?;
+        let res = if full {
+            let channel = self
+                .get_device_info()
+                .map(|info| info.info.struDeviceV30.byStartChan as DWORD)
+                .unwrap_or(1);
+            let mut restore_info = NET_DVR_COMPLETE_RESTORE_INFO::new_for_sdk();
+            restore_info.dwChannel = channel;
+            self.sdk.remote_control(
+                lu,
+                NET_DVR_COMPLETE_RESTORE_CTRL,
+                struct_bytes_mut(&mut restore_info),
+            )
+        } else {
+            self.sdk.restore_config(lu)
         };
+        if res != 1 {
+            return Err(sdk_error_from(&*self.sdk, "Restore defaults"));
+        }
+        if full {
+            self.invalidate_session();
+        }
+        Ok(())
+    }
+
+    // 通道号为设备的实际通道号（Channel::chan_num），模拟通道从 byStartChan 开始，
+    // IP 通道从 byStartDChan 开始，不在两段范围内的通道号直接报错
+    pub fn resolve_channel(&self, channel: u16) -> anyhow::Result<LONG> {
+        // 设备信息只在登录期间存在
+        let info = self.get_device_info().ok_or(HikError::NotLoggedIn)?;
+        let info = info.v30();
+        let channel = channel as u32;
+        let analog_start = info.byStartChan as u32;
+        let analog_end = analog_start + info.byChanNum as u32;
+        let ip_start = info.byStartDChan as u32;
+        let ip_end = ip_start + info.byIPChanNum as u32 + info.byHighDChanNum as u32 * 256;
+        if (analog_start..analog_end).contains(&channel) || (ip_start..ip_end).contains(&channel) {
+            return Ok(channel as LONG);
+        }
+        Err(HikError::InvalidArgument {
+            field: "channel",
+            reason: format!(
+                "{} is out of range: analog channels {}-{}, IP channels {}-{}",
+                channel,
+                analog_start,
+                analog_end.saturating_sub(1),
+                ip_start,
+                ip_end.saturating_sub(1)
+            ),
+        }
+        .into())
+    }
+
+    // 与 invalidate_session 相同，但保留凭据供 wait_for_reboot 使用
+    pub(crate) fn invalidate_for_reboot(&mut self) {
+        self.session.close_children();
+        if let Some(user_id) = self.session.end_for_reboot() {
+            self.session.emit_logged_out(user_id);
+        }
+    }
+
+    pub(crate) fn invalidate_session(&mut self) {
+        self.session.close_children();
+        if let Some(user_id) = self.session.end() {
+            self.session.emit_logged_out(user_id);
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_channels(&self) -> anyhow::Result<Vec<Channel>> {
+        let device_info = self
+            .get_device_info()
+            .ok_or(anyhow::anyhow!("Device info not found"))?;
+        let mut channels = device_info.get_channels();
+
+        // 每组最多 64 个 IP 通道，模拟通道的启用状态在第 0 组中返回
+        let ip_count = device_info.ip_channel_count() as usize;
+        let group_count = ip_count.div_ceil(IP_CHANNELS_PER_GROUP).max(1);
+        let mut groups = Vec::with_capacity(group_count);
+        for group in 0..group_count {
+            groups.push(self.get_ip_channel_config(group as LONG)?);
+        }
 
         for channel in channels.iter_mut() {
             match channel {
                 Channel::Logic(channel) => {
-                    channel.enable = channel_config.byAnalogChanEnable[channel.index as usize] == 1;
+                    channel.enable = groups[0]
+                        .byAnalogChanEnable
+                        .get(channel.index as usize)
+                        .is_some_and(|&enable| enable == 1);
                 }
                 Channel::IP(channel) => {
-                    let ip_dev_info = channel_config.struIPDevInfo[channel.index as usize];
-                    let stream_mode = channel_config.struStreamMode[channel.index as usize];
+                    let (group, offset) = ip_group_offset(channel.index);
+                    let channel_config = &groups[group];
+                    let ip_dev_info = channel_config.struIPDevInfo[offset];
+                    let stream_mode = channel_config.struStreamMode[offset];
                     channel.enable = ip_dev_info.byEnable == 1;
 
-                    // Safely convert i8 arrays to u8 slices for valid UTF-8 conversion
-                    let ip_address: String = {
-                        let raw = &ip_dev_info.struIP.sIpV4;
-                        let u8_slice = unsafe {
-                            std::slice::from_raw_parts(raw.as_ptr() as *const u8, raw.len())
-                        };
-                        String::from_utf8_lossy(u8_slice)
-                            .trim_end_matches('\0')
-                            .to_string()
-                    };
-
-                    channel.ipv4_address = Some(ip_address);
-
-                    let ipv6_address: String = {
-                        let raw = &ip_dev_info.struIP.byIPv6;
-                        let u8_slice = unsafe {
-                            std::slice::from_raw_parts(raw.as_ptr() as *const u8, raw.len())
-                        };
-                        String::from_utf8_lossy(u8_slice)
-                            .trim_end_matches('\0')
-                            .to_string()
-                    };
-                    channel.ipv6_address = Some(ipv6_address);
+                    // 两种地址按同样的方式解码
+                    let ipv4 = c_chars_as_bytes(&ip_dev_info.struIP.sIpV4);
+                    channel.ipv4_address = Some(decode_device_string(ipv4));
+                    channel.ipv6_address = Some(decode_device_string(&ip_dev_info.struIP.byIPv6));
 
                     let stream_type = stream_mode.byGetStreamType;
                     channel.get_stream_type = Some(stream_type);
@@ -118,75 +368,339 @@ impl HikDevice {
                         let stream = unsafe { stream_mode.uGetStream.struChanInfo };
                         channel.stream_channel = Some(stream.byChannel);
                     }
+                    channel.online = ip_channel_online(channel_config, offset);
                 }
             }
+
+            // 未启用或离线的通道取不到图像参数，名称保持为空
+            let info = channel.info_mut();
+            if info.enable {
+                info.name = self
+                    .get_pic_config(info.chan_num)
+                    .ok()
+                    .map(|pic_config| decode_device_string(&pic_config.sChanName))
+                    .filter(|name| !name.is_empty());
+            }
         }
 
         Ok(channels)
     }
 
-    fn get_ip_channel_config(&self) -> anyhow::Result<NET_DVR_IPPARACFG_V40> {
-        let lu = self
-            .login_hanlder
-            .ok_or(anyhow::anyhow!("Login hanlder not found"))?;
+    pub(crate) fn get_pic_config(&self, chan_num: u16) -> anyhow::Result<NET_DVR_PICCFG_V40> {
+        self.get_dvr_config(
+            NET_DVR_GET_PICCFG_V40,
+            self.resolve_channel(chan_num)?,
+            "Get picture config",
+        )
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_time(&self) -> anyhow::Result<DateTime<Local>> {
+        let time: NET_DVR_TIME = self.get_dvr_config(NET_DVR_GET_TIMECFG, 0, "Get time")?;
+        DateTime::try_from(time)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn set_time(&self, time: DateTime<Local>) -> anyhow::Result<()> {
+        check_device_time(&time)?;
+        #[cfg(feature = "tracing")]
+        tracing::trace!(%time, "Set time");
+        let time = NET_DVR_TIME::from(time);
+        self.set_dvr_config(NET_DVR_SET_TIMECFG, 0, &time, "Set time")
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_ntp_config(&self) -> anyhow::Result<NtpConfig> {
+        let ntp: NET_DVR_NTPPARA = self.get_dvr_config(NET_DVR_GET_NTPCFG, 0, "Get NTP config")?;
+        Ok(NtpConfig::from(&ntp))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn set_ntp_config(&self, config: NtpConfig) -> anyhow::Result<()> {
+        // 先读取再修改，保留时差等未暴露的字段
+        let mut ntp: NET_DVR_NTPPARA =
+            self.get_dvr_config(NET_DVR_GET_NTPCFG, 0, "Get NTP config")?;
+        copy_to_byte_array(&mut ntp.sNTPServer, &config.server, "NTP server")?;
+        ntp.wNtpPort = config.port;
+        ntp.wInterval = config.interval_hours;
+        ntp.byEnableNTP = config.enabled as BYTE;
+        self.set_dvr_config(NET_DVR_SET_NTPCFG, 0, &ntp, "Set NTP config")
+    }
+
+    // 按 command 读取配置，T 必须是该命令对应的结构体
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn get_config<T: SdkStruct>(&self, command: DWORD, channel: LONG) -> anyhow::Result<T> {
+        self.get_dvr_config(command, channel, "Get config")
+    }
+
+    // config 应来自 get_config 或 SdkStruct::new_for_sdk，保证 dwSize 已填写
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn set_config<T: SdkStruct>(
+        &self,
+        command: DWORD,
+        channel: LONG,
+        config: &T,
+    ) -> anyhow::Result<()> {
+        self.set_dvr_config(command, channel, config, "Set config")
+    }
+
+    pub(crate) fn get_dvr_config<T: SdkStruct>(
+        &self,
+        command: DWORD,
+        channel: LONG,
+        action: &'static str,
+    ) -> anyhow::Result<T> {
+        let mut config = T::new_for_sdk();
+        self.get_dvr_config_into(command, channel, &mut config, action)?;
+        Ok(config)
+    }
+
+    // 用于几百 KB 的大结构体（如 NET_DVR_USER_V50），避免在栈上分配
+    pub(crate) fn get_dvr_config_boxed<T: SdkStruct>(
+        &self,
+        command: DWORD,
+        channel: LONG,
+        action: &'static str,
+    ) -> anyhow::Result<Box<T>> {
+        // SdkStruct 保证全 0 是合法值
+        let mut config = unsafe { Box::<T>::new_zeroed().assume_init() };
+        config.init_for_sdk();
+        self.get_dvr_config_into(command, channel, &mut *config, action)?;
+        Ok(config)
+    }
 
-        let mut ip_access_cfg_v40: NET_DVR_IPPARACFG_V40 = unsafe { mem::zeroed() };
-        // iGroupNO = 0
-        let i_group_no: LONG = 0;
-        // 返回的大小
+    fn get_dvr_config_into<T: SdkStruct>(
+        &self,
+        command: DWORD,
+        channel: LONG,
+        config: &mut T,
+        action: &'static str,
+    ) -> anyhow::Result<()> {
         let mut dw_returned: DWORD = 0;
-        // 结构体大小
-        let size = mem::size_of::<NET_DVR_IPPARACFG_V40>() as DWORD;
 
-        // true is success, false is failed
-        let res = unsafe {
-            NET_DVR_GetDVRConfig(
+        self.with_session(|lu| {
+            let res = self.sdk.get_dvr_config(
                 lu,
-                NET_DVR_GET_IPPARACFG_V40,
-                i_group_no,
-                &mut ip_access_cfg_v40 as *mut _ as *mut std::ffi::c_void,
-                size,
+                command,
+                channel,
+                struct_bytes_mut(config),
                 &mut dw_returned,
-            )
-        };
+            );
+            if res != 1 {
+                return Err(sdk_error_from(&*self.sdk, action));
+            }
+            #[cfg(feature = "tracing")]
+            tracing::trace!(
+                command,
+                channel,
+                size = mem::size_of::<T>(),
+                returned = dw_returned,
+                "Got config"
+            );
+            Ok(())
+        })
+    }
 
-        if res != 1 {
-            let error_code = get_last_error_code();
-            return Err(anyhow::anyhow!(
-                "Get IP channel config failed: error code {}, dwReturned: {}",
-                error_code,
-                dw_returned
-            ));
-        }
+    pub(crate) fn set_dvr_config<T: SdkStruct>(
+        &self,
+        command: DWORD,
+        channel: LONG,
+        config: &T,
+        action: &'static str,
+    ) -> anyhow::Result<()> {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(command, channel, size = mem::size_of::<T>(), "Set config");
+
+        self.with_session(|lu| {
+            let res = self
+                .sdk
+                .set_dvr_config(lu, command, channel, struct_bytes(config));
+            if res != 1 {
+                return Err(sdk_error_from(&*self.sdk, action));
+            }
+            Ok(())
+        })
+    }
+
+    // NET_DVR_GetSTDConfig / NET_DVR_SetSTDConfig，条件为通道号
+    pub(crate) fn get_std_config<T: SdkStruct>(
+        &self,
+        command: DWORD,
+        channel: LONG,
+        action: &'static str,
+    ) -> anyhow::Result<T> {
+        let mut config = T::new_for_sdk();
+        let mut cond = (channel as DWORD).to_ne_bytes();
+        self.with_session(|lu| {
+            let res =
+                self.sdk
+                    .get_std_config(lu, command, &mut cond, struct_bytes_mut(&mut config));
+            if res != 1 {
+                return Err(sdk_error_from(&*self.sdk, action));
+            }
+            Ok(())
+        })?;
+        Ok(config)
+    }
+
+    pub(crate) fn set_std_config<T: SdkStruct>(
+        &self,
+        command: DWORD,
+        channel: LONG,
+        config: &T,
+        action: &'static str,
+    ) -> anyhow::Result<()> {
+        let mut cond = (channel as DWORD).to_ne_bytes();
+        self.with_session(|lu| {
+            let res = self
+                .sdk
+                .set_std_config(lu, command, &mut cond, struct_bytes(config));
+            if res != 1 {
+                return Err(sdk_error_from(&*self.sdk, action));
+            }
+            Ok(())
+        })
+    }
+
+    // 自动重登录后返回新登录取到的信息
+    pub fn get_device_info(&self) -> Option<Arc<HikDeviceInfo>> {
+        self.session.device_info()
+    }
 
-        Ok(ip_access_cfg_v40)
+    // channel 参数即 iGroupNO，第 n 组对应第 n*64 个起的 IP 通道
+    pub(crate) fn get_ip_channel_config(
+        &self,
+        group: LONG,
+    ) -> anyhow::Result<NET_DVR_IPPARACFG_V40> {
+        self.get_dvr_config(NET_DVR_GET_IPPARACFG_V40, group, "Get IP channel config")
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
     pub fn capture_jpeg_picture(&self, channel: u16, file: &str) -> anyhow::Result<()> {
-        let lu = self
-            .login_hanlder
-            .ok_or(anyhow::anyhow!("Login hanlder not found"))?;
-
-        let mut params = NET_DVR_JPEGPARA::default();
-        let file = as_c_string!(file);
-        let res = unsafe {
-            NET_DVR_CaptureJPEGPicture(
-                lu,
-                channel as i32,
-                &mut params as *mut _,
-                file.as_ptr() as *mut c_char,
-            )
-        };
-        if res != 1 {
-            let error_code = get_last_error_code();
-            return Err(anyhow::anyhow!(
-                "Capture JPEG picture failed: error code {}",
-                error_code
-            ));
-        }
-        Ok(())
+        let channel = self.resolve_channel(channel)?;
+        self.with_session(|lu| capture_jpeg(&*self.sdk, lu, channel, file, JpegParams::default()))
+    }
+
+    // 抓图到内存，图片超过 JPEG_BUFFER_SIZE 时 SDK 返回错误
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn capture_jpeg_data(&self, channel: u16, params: JpegParams) -> anyhow::Result<Vec<u8>> {
+        let channel = self.resolve_channel(channel)?;
+        self.with_session(|lu| capture_jpeg_to_vec(&*self.sdk, lu, channel, params))
+    }
+
+    // 后台线程按间隔抓图，出错时只上报，不会终止循环
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn capture_loop(
+        &self,
+        channel: u16,
+        options: CaptureLoopOptions,
+    ) -> anyhow::Result<HikCaptureScheduler> {
+        let lu = self.user_id()?;
+        let sdk_channel = self.resolve_channel(channel)?;
+        std::fs::create_dir_all(&options.dir)?;
+
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let (error_tx, error_rx) = mpsc::sync_channel(CAPTURE_ERROR_QUEUE_SIZE);
+        let sdk = self.sdk.clone();
+        let thread = std::thread::spawn(move || {
+            let mut files = VecDeque::new();
+            let mut seq: u64 = 0;
+            loop {
+                let name = render_capture_filename(&options.filename_template, channel, seq);
+                let path = options.dir.join(name);
+                seq += 1;
+
+                let result = path
+                    .to_str()
+                    .ok_or(anyhow::anyhow!("Invalid capture path: {}", path.display()))
+                    .and_then(|file| {
+                        capture_jpeg(&*sdk, lu, sdk_channel, file, options.jpeg_params)
+                    });
+                match result {
+                    Ok(()) => {
+                        files.push_back(path);
+                        if let Some(max_files) = options.max_files {
+                            while files.len() > max_files {
+                                if let Some(old) = files.pop_front() {
+                                    let _ = std::fs::remove_file(old);
+                                }
+                            }
+                        }
+                    }
+                    // 队列满时丢弃，避免调用方不读取时阻塞抓图
+                    Err(e) => {
+                        let _ = error_tx.try_send(e);
+                    }
+                }
+
+                if wait_stop(&stop_rx, options.cancel.as_ref(), options.interval) {
+                    break;
+                }
+            }
+        });
+
+        Ok(HikCaptureScheduler {
+            stop_tx: Some(stop_tx),
+            errors: error_rx,
+            thread: Some(thread),
+        })
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
     pub fn get_file_by_time(
         &self,
         file: &str,
@@ -194,95 +708,430 @@ impl HikDevice {
         start_time: DateTime<Local>,
         end_time: DateTime<Local>,
     ) -> anyhow::Result<HikDownload> {
-        let lu = self
-            .login_hanlder
-            .ok_or(anyhow::anyhow!("Login hanlder not found"))?;
-
-        let file = as_c_string!(file);
-        let mut play_cond = NET_DVR_PLAYCOND::default();
-        play_cond.dwChannel = channel as DWORD;
-        play_cond.struStartTime = NET_DVR_TIME {
-            dwYear: start_time.year() as DWORD,
-            dwMonth: start_time.month() as DWORD,
-            dwDay: start_time.day() as DWORD,
-            dwHour: start_time.hour() as DWORD,
-            dwMinute: start_time.minute() as DWORD,
-            dwSecond: start_time.second() as DWORD,
-        };
-        play_cond.struStopTime = NET_DVR_TIME {
-            dwYear: end_time.year() as DWORD,
-            dwMonth: end_time.month() as DWORD,
-            dwDay: end_time.day() as DWORD,
-            dwHour: end_time.hour() as DWORD,
-            dwMinute: end_time.minute() as DWORD,
-            dwSecond: end_time.second() as DWORD,
-        };
-        let handle = unsafe {
-            NET_DVR_GetFileByTime_V40(lu, file.as_ptr() as *mut c_char, &mut play_cond as *mut _)
+        self.get_file_by_time_with(
+            file,
+            channel,
+            start_time,
+            end_time,
+            DownloadOptions::default(),
+        )
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn get_file_by_time_with(
+        &self,
+        file: &str,
+        channel: u16,
+        start_time: DateTime<Local>,
+        end_time: DateTime<Local>,
+        options: DownloadOptions,
+    ) -> anyhow::Result<HikDownload> {
+        let channel = self.resolve_channel(channel)?;
+        let [start_time, end_time] =
+            self.device_time_range(start_time, end_time, options.time_mode)?;
+        self.with_session(|lu| {
+            self.check_stream_limit(lu)?;
+            open_download(
+                &self.sdk,
+                lu,
+                &self.session.state(),
+                file,
+                channel,
+                start_time,
+                end_time,
+                options,
+            )
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn playback_by_time<F>(
+        &self,
+        channel: u16,
+        start_time: DateTime<Local>,
+        end_time: DateTime<Local>,
+        callback: F,
+    ) -> anyhow::Result<HikPlayback>
+    where
+        F: FnMut(PlaybackEvent<'_>) + Send + 'static,
+    {
+        let channel = self.resolve_channel(channel)?;
+        check_time_range(start_time, end_time)?;
+        // hWnd 保持为空，数据全部通过回调送出
+        let vod_para = NET_DVR_VOD_PARA {
+            dwSize: mem::size_of::<NET_DVR_VOD_PARA>() as DWORD,
+            struIDInfo: NET_DVR_STREAM_INFO {
+                dwSize: mem::size_of::<NET_DVR_STREAM_INFO>() as DWORD,
+                dwChannel: channel as DWORD,
+                ..Default::default()
+            },
+            struBeginTime: start_time.into(),
+            struEndTime: end_time.into(),
+            ..Default::default()
         };
 
-        if handle < 0 {
-            let error_code = get_last_error_code();
-            return Err(anyhow::anyhow!(
-                "Get file by time failed: error code {}",
-                error_code
-            ));
+        let (lu, handle) = self.with_session(|lu| {
+            self.check_stream_limit(lu)?;
+            let handle = self.sdk.playback_by_time(lu, &vod_para);
+            if handle < 0 {
+                return Err(sdk_error_from(&*self.sdk, "Playback by time"));
+            }
+            Ok((lu, handle))
+        })?;
+
+        HikPlayback::new(self.sdk.clone(), lu, self.session.state(), handle, callback)
+    }
+}
+
+// 使用已解析的通道号打开下载句柄，批量下载在后台线程中也会调用
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn open_download(
+    sdk: &Arc<dyn NetSdk>,
+    lu: LONG,
+    session: &Arc<SessionState>,
+    file: &str,
+    channel: LONG,
+    start_time: NET_DVR_TIME,
+    end_time: NET_DVR_TIME,
+    options: DownloadOptions,
+) -> anyhow::Result<HikDownload> {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(
+        channel,
+        ?start_time,
+        ?end_time,
+        file,
+        ?options,
+        "Open download"
+    );
+    let file = as_c_string!(file, "file");
+    let mut play_cond = NET_DVR_PLAYCOND::default();
+    play_cond.dwChannel = channel as DWORD;
+    play_cond.struStartTime = start_time;
+    play_cond.struStopTime = end_time;
+    let handle = sdk.get_file_by_time(lu, &file, &mut play_cond);
+    if handle < 0 {
+        return Err(sdk_error_from(&**sdk, "Get file by time"));
+    }
+
+    let download = HikDownload::with_sdk(sdk.clone(), lu, session.clone(), handle);
+    // 转封装必须在 PLAYSTART 之前设置，失败时 download 被 drop 会停止句柄
+    if let Some(trans_type) = options.container.trans_type() {
+        play_back_control(
+            &**sdk,
+            handle,
+            NET_DVR_SET_TRANS_TYPE,
+            Some(trans_type),
+            "Set download container",
+        )
+        .map_err(|e| match sdk_code(&e) {
+            Some(code)
+                if code == NET_DVR_NOSUPPORT as i32 || code == NET_DVR_NOT_SUPPORT as i32 =>
+            {
+                HikError::ContainerNotSupported.into()
+            }
+            _ => e,
+        })?;
+    }
+    Ok(download)
+}
+
+fn capture_jpeg(
+    sdk: &dyn NetSdk,
+    lu: LONG,
+    channel: LONG,
+    file: &str,
+    params: JpegParams,
+) -> anyhow::Result<()> {
+    let mut params = NET_DVR_JPEGPARA {
+        wPicSize: params.size,
+        wPicQuality: params.quality,
+    };
+    let file = as_c_string!(file, "file");
+    let res = sdk.capture_jpeg_picture(lu, channel, &mut params, &file);
+    if res != 1 {
+        return Err(sdk_error_from(sdk, "Capture JPEG picture"));
+    }
+    Ok(())
+}
+
+// 直接使用登录句柄抓图，供后台线程调用
+pub(crate) fn capture_jpeg_to_vec(
+    sdk: &dyn NetSdk,
+    lu: LONG,
+    channel: LONG,
+    params: JpegParams,
+) -> anyhow::Result<Vec<u8>> {
+    let mut raw = NET_DVR_JPEGPARA {
+        wPicSize: params.size,
+        wPicQuality: params.quality,
+    };
+    let mut buffer = vec![0u8; JPEG_BUFFER_SIZE];
+    let mut returned: DWORD = 0;
+    let res = sdk.capture_jpeg_picture_new(lu, channel, &mut raw, &mut buffer, &mut returned);
+    if res != 1 {
+        return Err(sdk_error_from(sdk, "Capture JPEG picture"));
+    }
+    buffer.truncate(returned as usize);
+    Ok(buffer)
+}
+
+fn render_capture_filename(template: &str, channel: u16, seq: u64) -> String {
+    template
+        .replace("{channel}", &channel.to_string())
+        .replace(
+            "{timestamp}",
+            &Local::now().format("%Y%m%d%H%M%S").to_string(),
+        )
+        .replace("{seq}", &seq.to_string())
+}
+
+const CAPTURE_ERROR_QUEUE_SIZE: usize = 16;
+
+// 4K 分辨率的抓图一般在 2 MB 以内
+const JPEG_BUFFER_SIZE: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct JpegParams {
+    // wPicSize，0xff 为使用当前码流分辨率
+    pub size: u16,
+    // wPicQuality，0 最好，1 较好，2 一般
+    pub quality: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct CaptureLoopOptions {
+    pub interval: Duration,
+    pub dir: PathBuf,
+    // 支持 {channel}、{timestamp}、{seq} 占位符
+    pub filename_template: String,
+    // 只保留最近的 max_files 张，None 为不删除
+    pub max_files: Option<usize>,
+    pub jpeg_params: JpegParams,
+    // 取消后与 HikCaptureScheduler::stop 效果相同
+    pub cancel: Option<CancellationToken>,
+}
+
+impl CaptureLoopOptions {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            dir: dir.into(),
+            filename_template: "channel_{channel}_{timestamp}_{seq}.jpg".to_string(),
+            max_files: None,
+            jpeg_params: JpegParams::default(),
+            cancel: None,
+        }
+    }
+}
+
+/// capture_loop 启动的定时抓图，stop 或 drop 时等待抓图线程退出
+pub struct HikCaptureScheduler {
+    stop_tx: Option<mpsc::Sender<()>>,
+    errors: mpsc::Receiver<anyhow::Error>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+// capture_loop 返回的句柄，即 HikCaptureScheduler
+pub type CaptureLoopHandle = HikCaptureScheduler;
+
+impl HikCaptureScheduler {
+    // 抓图失败的错误，队列满时新的错误会被丢弃
+    pub fn errors(&self) -> &mpsc::Receiver<anyhow::Error> {
+        &self.errors
+    }
+
+    pub fn stop(&mut self) {
+        // 丢弃 Sender 即可唤醒等待中的线程
+        self.stop_tx.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for HikCaptureScheduler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContainerFormat {
+    // 设备原始的 PS 封装
+    #[default]
+    Native,
+    Mp4,
+    Avi,
+}
+
+impl ContainerFormat {
+    // NET_DVR_SET_TRANS_TYPE 的取值，与转封装库的封装类型一致
+    fn trans_type(self) -> Option<DWORD> {
+        match self {
+            ContainerFormat::Native => None,
+            ContainerFormat::Mp4 => Some(5),
+            ContainerFormat::Avi => Some(7),
+        }
+    }
+}
+
+// 下载的录像总是包含设备录制的音频，SDK 的下载条件与转封装都不能去掉音频
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadOptions {
+    pub container: ContainerFormat,
+    pub time_mode: TimeMode,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            container: ContainerFormat::Native,
+            time_mode: TimeMode::DeviceLocal,
         }
+    }
+}
+
+// 后台线程查询下载进度的间隔
+const DOWNLOAD_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadState {
+    // 批量下载中排队等待的通道
+    Queued,
+    Running,
+    Finished,
+    Failed(HikError),
+    // 批量下载被取消时尚未完成的通道
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadStatus {
+    pub percent: u8,
+    pub state: DownloadState,
+}
+
+// 进度只在变化时发送，Finished/Failed 之后通道关闭
+pub struct DownloadWatcher {
+    receiver: mpsc::Receiver<DownloadStatus>,
+}
+
+impl DownloadWatcher {
+    pub fn recv(&self) -> Option<DownloadStatus> {
+        self.receiver.recv().ok()
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<DownloadStatus> {
+        self.receiver.recv_timeout(timeout).ok()
+    }
 
-        Ok(HikDownload::new(handle))
+    pub fn try_recv(&self) -> Option<DownloadStatus> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Iterator for DownloadWatcher {
+    type Item = DownloadStatus;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
     }
 }
 
 pub struct HikDownload {
     handle: i32,
+    sdk: Arc<dyn NetSdk>,
+    // 登出时由会话停止句柄
+    session: Arc<SessionState>,
     is_start: AtomicBool,
+    is_stopped: AtomicBool,
+    // 由异常回调置为 false
+    healthy: Arc<AtomicBool>,
+    // 丢弃 Sender 即可让进度线程退出
+    watch_stop: Mutex<Option<mpsc::Sender<()>>>,
     thread: Option<std::thread::JoinHandle<()>>,
 }
 
 impl HikDownload {
+    // 不属于任何会话，登出不会停止它
     pub fn new(handle: i32) -> Self {
+        Self::with_sdk(Arc::new(RealSdk), -1, Arc::default(), handle)
+    }
+
+    pub(crate) fn with_sdk(
+        sdk: Arc<dyn NetSdk>,
+        user_id: LONG,
+        session: Arc<SessionState>,
+        handle: i32,
+    ) -> Self {
+        let stop_sdk = sdk.clone();
+        session.register(HandleKind::Playback, handle, move || {
+            stop_sdk.stop_get_file(handle);
+        });
         Self {
             handle,
+            sdk,
+            session,
             is_start: AtomicBool::new(false),
+            is_stopped: AtomicBool::new(false),
+            healthy: watch_handle(HandleKind::Playback, user_id, handle),
+            watch_stop: Mutex::new(None),
             thread: None,
         }
     }
 
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::SeqCst)
+    }
+
     pub fn start(&mut self) -> anyhow::Result<()> {
+        self.session.ensure_open()?;
         if self.is_start.load(Ordering::Relaxed) {
             return Ok(());
         }
-        let res = unsafe {
-            NET_DVR_PlayBackControl_V40(
-                self.handle as LONG,
-                NET_DVR_PLAYSTART,
-                std::ptr::null_mut(),
-                0,
-                std::ptr::null_mut(),
-                std::ptr::null_mut(),
-            )
-        };
-        if res != 1 {
-            let error_code = get_last_error_code();
-            return Err(anyhow::anyhow!(
-                "Start download failed: error code {}",
-                error_code
-            ));
+        if self.is_stopped.load(Ordering::Relaxed) {
+            return Err(anyhow::anyhow!("Download already stopped"));
         }
+        play_back_control(
+            &*self.sdk,
+            self.handle as LONG,
+            NET_DVR_PLAYSTART,
+            None,
+            "Start download",
+        )?;
         self.is_start.store(true, Ordering::Relaxed);
 
         Ok(())
     }
 
     pub fn get_progress(&self) -> anyhow::Result<i32> {
+        self.session.ensure_open()?;
         if !self.is_start.load(Ordering::Relaxed) {
             return Err(anyhow::anyhow!("Download not started"));
         }
+        if !self.is_healthy() {
+            return Err(anyhow::anyhow!("Download connection lost"));
+        }
 
-        let pos = unsafe { NET_DVR_GetDownloadPos(self.handle as LONG) };
+        let pos = self.sdk.get_download_pos(self.handle as LONG);
         if pos < 0 || pos > 100 {
             if pos == -1 {
-                let error_code = get_last_error_code();
+                let error_code = self.sdk.get_last_error();
                 return Err(anyhow::anyhow!(
                     "Get download progress failed: error code {}",
                     error_code
@@ -296,36 +1145,221 @@ impl HikDownload {
         Ok(pos)
     }
 
-    pub fn stop(&self) -> anyhow::Result<()> {
-        self.is_start.store(false, Ordering::Relaxed);
-        let res = unsafe { NET_DVR_StopGetFile(self.handle as LONG) };
-        if res != 1 {
-            let error_code = get_last_error_code();
-            return Err(anyhow::anyhow!(
-                "Stop download failed: error code {}",
-                error_code
-            ));
-        }
-        Ok(())
+    // 在后台线程轮询进度并推送，每个下载只能订阅一次
+    pub fn subscribe(&mut self) -> anyhow::Result<DownloadWatcher> {
+        let (tx, rx) = mpsc::channel();
+        self.spawn_watcher(move |status| tx.send(status).is_ok())?;
+        Ok(DownloadWatcher { receiver: rx })
     }
-}
 
-impl Drop for HikDownload {
-    fn drop(&mut self) {
-        let _ = self.stop();
-        if let Some(thread) = self.thread.take() {
-            let _ = thread.join();
-        }
+    #[cfg(feature = "tokio")]
+    pub fn subscribe_watch(
+        &mut self,
+    ) -> anyhow::Result<tokio::sync::watch::Receiver<DownloadStatus>> {
+        let (tx, rx) = tokio::sync::watch::channel(DownloadStatus {
+            percent: 0,
+            state: DownloadState::Running,
+        });
+        self.spawn_watcher(move |status| tx.send(status).is_ok())?;
+        Ok(rx)
+    }
+
+    fn spawn_watcher<F>(&mut self, mut publish: F) -> anyhow::Result<()>
+    where
+        F: FnMut(DownloadStatus) -> bool + Send + 'static,
+    {
+        self.session.ensure_open()?;
+        if !self.is_start.load(Ordering::Relaxed) {
+            return Err(anyhow::anyhow!("Download not started"));
+        }
+        if self.thread.is_some() {
+            return Err(anyhow::anyhow!("Download already subscribed"));
+        }
+
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        *self.watch_stop.lock().unwrap() = Some(stop_tx);
+        let handle = self.handle as LONG;
+        let healthy = self.healthy.clone();
+        let sdk = self.sdk.clone();
+        let session = self.session.clone();
+        self.thread = Some(std::thread::spawn(move || {
+            let mut last_percent = None;
+            loop {
+                let status =
+                    poll_download(&*sdk, &session, handle, &healthy, last_percent.unwrap_or(0));
+                let done = status.state != DownloadState::Running;
+                if (done || last_percent != Some(status.percent)) && !publish(status.clone()) {
+                    break;
+                }
+                if done {
+                    break;
+                }
+                last_percent = Some(status.percent);
+
+                match stop_rx.recv_timeout(DOWNLOAD_POLL_INTERVAL) {
+                    Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                    _ => break,
+                }
+            }
+        }));
+        Ok(())
+    }
+
+    // 阻塞到下载结束；取消时停止下载并返回 HikError::Cancelled
+    pub fn wait(&self, cancel: &CancellationToken) -> anyhow::Result<()> {
+        self.session.ensure_open()?;
+        if !self.is_start.load(Ordering::Relaxed) {
+            return Err(anyhow::anyhow!("Download not started"));
+        }
+        let mut percent = 0;
+        loop {
+            let status = self.poll(percent);
+            match status.state {
+                DownloadState::Running => percent = status.percent,
+                DownloadState::Failed(e) => return Err(e.into()),
+                _ => return Ok(()),
+            }
+            if cancel.wait_timeout(DOWNLOAD_POLL_INTERVAL) {
+                self.stop()?;
+                return Err(HikError::Cancelled.into());
+            }
+        }
+    }
+
+    pub fn stop(&self) -> anyhow::Result<()> {
+        self.is_start.store(false, Ordering::Relaxed);
+        // 先让进度线程退出，避免它继续查询已停止的句柄
+        self.watch_stop.lock().unwrap().take();
+        // 只停止一次，避免 Drop 时对已释放的句柄重复调用
+        if self.is_stopped.swap(true, Ordering::Relaxed) {
+            return Ok(());
+        }
+        if !self.session.unregister(HandleKind::Playback, self.handle) {
+            return Err(HikError::SessionClosed.into());
+        }
+        let res = self.sdk.stop_get_file(self.handle as LONG);
+        if res != 1 {
+            let error_code = self.sdk.get_last_error();
+            return Err(anyhow::anyhow!(
+                "Stop download failed: error code {}",
+                error_code
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn poll_download(
+    sdk: &dyn NetSdk,
+    session: &SessionState,
+    handle: LONG,
+    healthy: &AtomicBool,
+    last_percent: u8,
+) -> DownloadStatus {
+    if let Err(e) = session.ensure_open() {
+        return DownloadStatus {
+            percent: last_percent,
+            state: DownloadState::Failed(e),
+        };
+    }
+    let failed = |action| DownloadStatus {
+        percent: last_percent,
+        state: DownloadState::Failed(HikError::Sdk {
+            action,
+            code: sdk.get_last_error() as i32,
+        }),
+    };
+    if !healthy.load(Ordering::SeqCst) {
+        return failed("Download connection");
+    }
+
+    let pos = sdk.get_download_pos(handle);
+    match pos {
+        0..=99 => DownloadStatus {
+            percent: pos as u8,
+            state: DownloadState::Running,
+        },
+        100 => DownloadStatus {
+            percent: 100,
+            state: DownloadState::Finished,
+        },
+        // 200 为网络异常
+        200 => failed("Download"),
+        _ => failed("Get download progress"),
+    }
+}
+
+impl HikDownload {
+    pub(crate) fn poll(&self, last_percent: u8) -> DownloadStatus {
+        poll_download(
+            &*self.sdk,
+            &self.session,
+            self.handle as LONG,
+            &self.healthy,
+            last_percent,
+        )
+    }
+}
+
+impl PlaybackControl for HikDownload {
+    fn play_handle(&self) -> LONG {
+        self.handle as LONG
+    }
+
+    fn ensure_open(&self) -> anyhow::Result<()> {
+        Ok(self.session.ensure_open()?)
+    }
+
+    fn sdk(&self) -> &dyn NetSdk {
+        &*self.sdk
+    }
+
+    fn is_started(&self) -> bool {
+        self.is_start.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for HikDownload {
+    fn drop(&mut self) {
+        let _ = self.stop();
+        unwatch_handle(HandleKind::Playback, self.handle);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
     }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Channel {
     Logic(ChannelInfo),
     IP(ChannelInfo),
 }
 
+impl Channel {
+    pub fn info(&self) -> &ChannelInfo {
+        match self {
+            Channel::Logic(info) | Channel::IP(info) => info,
+        }
+    }
+
+    fn info_mut(&mut self) -> &mut ChannelInfo {
+        match self {
+            Channel::Logic(info) | Channel::IP(info) => info,
+        }
+    }
+
+    pub fn chan_num(&self) -> u16 {
+        self.info().chan_num
+    }
+
+    pub fn is_ip(&self) -> bool {
+        matches!(self, Channel::IP(_))
+    }
+}
+
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChannelInfo {
     index: u16,
     chan_num: u16,
@@ -334,6 +1368,9 @@ pub struct ChannelInfo {
     stream_channel: Option<u8>,
     ipv4_address: Option<String>,
     ipv6_address: Option<String>,
+    name: Option<String>,
+    // IP 通道是否在线，模拟通道和非直连取流的 IP 通道为 None
+    online: Option<bool>,
 }
 
 impl ChannelInfo {
@@ -346,6 +1383,14 @@ impl ChannelInfo {
         }
     }
 
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    pub fn chan_num(&self) -> u16 {
+        self.chan_num
+    }
+
     pub fn get_chan_num(&self) -> u16 {
         self.chan_num
     }
@@ -354,42 +1399,1021 @@ impl ChannelInfo {
         self.enable
     }
 
+    // IP 通道的取流方式，0 为直接从设备取流
+    pub fn stream_type(&self) -> Option<u8> {
+        self.get_stream_type
+    }
+
+    pub fn stream_channel(&self) -> Option<u8> {
+        self.stream_channel
+    }
+
+    pub fn ipv4_address(&self) -> Option<&str> {
+        self.ipv4_address.as_deref()
+    }
+
     pub fn get_ipv4_address(&self) -> Option<&String> {
         self.ipv4_address.as_ref()
     }
+
+    pub fn ipv6_address(&self) -> Option<&str> {
+        self.ipv6_address.as_deref()
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn is_online(&self) -> Option<bool> {
+        self.online
+    }
+}
+
+// NET_DVR_IPPARACFG_V40 每组包含的 IP 通道数
+pub(crate) const IP_CHANNELS_PER_GROUP: usize = MAX_IP_DEVICE_V40 as usize;
+
+// 只有直接从设备取流（byGetStreamType 为 0）时 struChanInfo 有效，byEnable 为在线状态；
+// 报警上传的 NET_DVR_IPALARMINFO 中同样是 NET_DVR_IPCHANINFO
+pub(crate) fn ip_channel_online(config: &NET_DVR_IPPARACFG_V40, offset: usize) -> Option<bool> {
+    let stream_mode = config.struStreamMode.get(offset)?;
+    if stream_mode.byGetStreamType != 0 {
+        return None;
+    }
+    let stream = unsafe { stream_mode.uGetStream.struChanInfo };
+    // 没有添加 IP 设备的通道
+    if stream.byIPID == 0 && stream.byIPIDHigh == 0 {
+        return None;
+    }
+    Some(stream.byEnable == 1)
+}
+
+// IP 通道的序号换算为实际通道号，轮询与报警上传都按此编号，与 resolve_channel 一致
+pub(crate) fn ip_chan_num(ip_start: u8, index: u16) -> u16 {
+    (ip_start as u16).saturating_add(index)
+}
+
+// IP 通道的序号换算为 NET_DVR_IPPARACFG_V40 的组号与组内偏移
+pub(crate) fn ip_group_offset(index: u16) -> (usize, usize) {
+    let index = index as usize;
+    (index / IP_CHANNELS_PER_GROUP, index % IP_CHANNELS_PER_GROUP)
 }
 
-pub struct HikDeviceInfo(NET_DVR_DEVICEINFO_V30);
+pub struct HikDeviceInfo {
+    info: NET_DVR_DEVICEINFO_V40,
+    // V30 登录（包括异步登录回调）拿不到 V40 的扩展字段
+    has_v40: bool,
+}
 
 impl HikDeviceInfo {
     pub fn new(device_info: NET_DVR_DEVICEINFO_V30) -> Self {
-        Self(device_info)
+        Self {
+            info: NET_DVR_DEVICEINFO_V40 {
+                struDeviceV30: device_info,
+                ..Default::default()
+            },
+            has_v40: false,
+        }
+    }
+
+    pub fn from_v40(device_info: NET_DVR_DEVICEINFO_V40) -> Self {
+        Self {
+            info: device_info,
+            has_v40: true,
+        }
+    }
+
+    pub fn retry_count_left(&self) -> Option<u8> {
+        self.has_v40.then_some(self.info.byRetryLoginTime)
+    }
+
+    pub fn password_level(&self) -> Option<PasswordLevel> {
+        self.has_v40
+            .then(|| PasswordLevel::from(self.info.byPasswordLevel))
+    }
+
+    pub fn support_lock(&self) -> Option<bool> {
+        self.has_v40.then_some(self.info.bySupportLock == 1)
+    }
+
+    pub fn surplus_lock_time(&self) -> Option<u32> {
+        self.has_v40.then_some(self.info.dwSurplusLockTime)
+    }
+
+    pub fn alarm_in_count(&self) -> u16 {
+        self.info.struDeviceV30.byAlarmInPortNum as u16
+    }
+
+    pub fn alarm_out_count(&self) -> u16 {
+        self.info.struDeviceV30.byAlarmOutPortNum as u16
+    }
+
+    pub(crate) fn v30(&self) -> &NET_DVR_DEVICEINFO_V30 {
+        &self.info.struDeviceV30
+    }
+
+    pub(crate) fn ip_start(&self) -> u8 {
+        self.info.struDeviceV30.byStartDChan
+    }
+
+    // IP通道（或者数字通道）支持的最大IP通道数
+    pub fn ip_channel_count(&self) -> u16 {
+        let info = &self.info.struDeviceV30;
+        info.byIPChanNum as u16 + info.byHighDChanNum as u16 * 256
     }
 
     pub fn get_channels(&self) -> Vec<Channel> {
-        // 模拟通道号个数
-        let byChanNum = self.0.byChanNum;
-        // 模拟通道号起始号
-        let byStartChan = self.0.byStartChan;
-        // IP通道（或者数字通道）支持的最大IP通道数
-        let maxIPChan = self.0.byIPChanNum as u16 + (self.0.byHighDChanNum as u16 * 256);
-        // IP通道（或者数字通道）起始通道号
-        let byStartDChan = self.0.byStartDChan as u16;
-        let mut channels = Vec::new();
-
-        let mut index = 0;
-        let end = byStartChan + byChanNum;
-        for num in byStartChan..end {
-            channels.push(Channel::Logic(ChannelInfo::new(index, num as u16)));
-            index += 1;
-        }
-
-        index = 0;
-        let end = byStartDChan + maxIPChan;
-        for num in byStartDChan..end {
-            channels.push(Channel::IP(ChannelInfo::new(index, num as u16)));
-            index += 1;
-        }
-        channels
+        let info = &self.info.struDeviceV30;
+        channel_layout(
+            info.byStartChan,
+            info.byChanNum,
+            info.byStartDChan,
+            self.ip_channel_count(),
+        )
+    }
+}
+
+// 通道号在 u16 中计算，起始号 + 个数可能超过 255；IP 通道的 index 为跨组的序号
+fn channel_layout(analog_start: u8, analog_count: u8, ip_start: u8, ip_count: u16) -> Vec<Channel> {
+    let analog = (0..analog_count as u16)
+        .map(|index| Channel::Logic(ChannelInfo::new(index, analog_start as u16 + index)));
+    let ip = (0..ip_count)
+        .map(|index| Channel::IP(ChannelInfo::new(index, ip_chan_num(ip_start, index))));
+    analog.chain(ip).collect()
+}
+
+/// 取流使用的传输协议，作为 start_preview 的默认取流方式
+///
+/// SDK 的登录本身只走 TCP；回放与下载也固定为 TCP，只有实时预览可以选择 UDP
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TransportMode {
+    #[default]
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordLevel {
+    Invalid,
+    Default,
+    Valid,
+    Risky,
+    Other(u8),
+}
+
+impl From<u8> for PasswordLevel {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => PasswordLevel::Invalid,
+            1 => PasswordLevel::Default,
+            2 => PasswordLevel::Valid,
+            3 => PasswordLevel::Risky,
+            other => PasswordLevel::Other(other),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct LoginOptions {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    pub use_transport: TransportMode,
+    // 通过透明通道登录（byUseTransport = 1），用于经由串口服务器等转发的设备
+    pub transparent_channel: bool,
+    // None 为同步登录；Some 时使用异步登录并最多等待这么久
+    pub timeout_ms: Option<u32>,
+}
+
+impl LoginOptions {
+    pub fn new(host: &str, port: u16, username: &str, password: &str) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+            username: username.to_string(),
+            password: password.to_string(),
+            use_transport: TransportMode::default(),
+            transparent_channel: false,
+            timeout_ms: None,
+        }
+    }
+}
+
+// SDK 的连接与接收超时，0 表示使用 SDK 默认值
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Timeouts {
+    pub connect_ms: u32,
+    pub recv_ms: u32,
+    // 连接失败后的重试次数
+    pub retries: u32,
+}
+
+fn login_info(options: &LoginOptions) -> anyhow::Result<NET_DVR_USER_LOGIN_INFO> {
+    let mut login_info = NET_DVR_USER_LOGIN_INFO::default();
+    copy_to_c_array(&mut login_info.sDeviceAddress, &options.host, "host")?;
+    copy_to_c_array(&mut login_info.sUserName, &options.username, "username")?;
+    copy_to_c_array(&mut login_info.sPassword, &options.password, "password")?;
+    login_info.wPort = options.port;
+    login_info.byUseTransport = options.transparent_channel as u8;
+    Ok(login_info)
+}
+
+// 同步登录，失败时 SDK 也会填充剩余重试次数与锁定时间
+pub(crate) fn login_blocking(
+    sdk: &dyn NetSdk,
+    options: &LoginOptions,
+) -> anyhow::Result<(LONG, NET_DVR_DEVICEINFO_V40)> {
+    let mut login_info = login_info(options)?;
+    let mut device_info = NET_DVR_DEVICEINFO_V40::default();
+    let res = sdk.login_v40(&mut login_info, &mut device_info);
+    if res < 0 {
+        return Err(login_error(sdk.get_last_error() as i32, Some(&device_info)));
+    }
+    // 不记录密码
+    #[cfg(feature = "tracing")]
+    tracing::trace!(
+        host = %options.host,
+        port = options.port,
+        username = %options.username,
+        user_id = res,
+        analog_channels = device_info.struDeviceV30.byChanNum,
+        ip_channels = device_info.struDeviceV30.byIPChanNum,
+        "Logged in"
+    );
+    Ok((res, device_info))
+}
+
+pub(crate) fn sdk_code(error: &anyhow::Error) -> Option<i32> {
+    error
+        .downcast_ref::<HikError>()
+        .and_then(HikError::sdk_code)
+}
+
+pub(crate) fn sdk_error(action: &'static str) -> anyhow::Error {
+    HikError::Sdk {
+        action,
+        code: get_last_error_code(),
+    }
+    .into()
+}
+
+// 等待设备开始重启的最长时间，超过后直接开始尝试登录
+const REBOOT_START_GRACE: Duration = Duration::from_secs(30);
+const REBOOT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const REACHABILITY_TIMEOUT: Duration = Duration::from_secs(1);
+
+// 只检查登录端口能否建立 TCP 连接
+fn is_reachable(addr: &SocketAddr) -> bool {
+    TcpStream::connect_timeout(addr, REACHABILITY_TIMEOUT).is_ok()
+}
+
+fn login_error(error_code: i32, device_info: Option<&NET_DVR_DEVICEINFO_V40>) -> anyhow::Error {
+    let error_code = error_code as u32;
+    match device_info {
+        Some(info) if error_code == NET_DVR_USER_LOCKED => HikError::AccountLocked {
+            remaining_secs: info.dwSurplusLockTime,
+        }
+        .into(),
+        Some(info)
+            if error_code == NET_DVR_PASSWORD_ERROR
+                && info.bySupportLock == 1
+                && info.byRetryLoginTime == 0 =>
+        {
+            HikError::AccountLocked {
+                remaining_secs: info.dwSurplusLockTime,
+            }
+            .into()
+        }
+        Some(info) if error_code == NET_DVR_PASSWORD_ERROR && info.bySupportLock == 1 => {
+            anyhow::anyhow!(
+                "Login failed: error code {}, {} retries left",
+                error_code,
+                info.byRetryLoginTime
+            )
+        }
+        None if error_code == NET_DVR_USER_LOCKED => {
+            HikError::AccountLocked { remaining_secs: 0 }.into()
+        }
+        _ => anyhow::anyhow!("Login failed: error code {}", error_code),
+    }
+}
+
+type AsyncLoginResult = Result<(LONG, NET_DVR_DEVICEINFO_V30), i32>;
+
+static NEXT_LOGIN_KEY: AtomicUsize = AtomicUsize::new(1);
+
+fn pending_logins() -> &'static Mutex<HashMap<usize, mpsc::Sender<AsyncLoginResult>>> {
+    static PENDING_LOGINS: OnceLock<Mutex<HashMap<usize, mpsc::Sender<AsyncLoginResult>>>> =
+        OnceLock::new();
+    PENDING_LOGINS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn register_pending_login() -> (usize, mpsc::Receiver<AsyncLoginResult>) {
+    let key = NEXT_LOGIN_KEY.fetch_add(1, Ordering::Relaxed);
+    let (sender, receiver) = mpsc::channel();
+    pending_logins().lock().unwrap().insert(key, sender);
+    (key, receiver)
+}
+
+unsafe extern "C" fn login_result_callback(
+    user_id: LONG,
+    result: DWORD,
+    device_info: LPNET_DVR_DEVICEINFO_V30,
+    user: *mut c_void,
+) {
+    let outcome = if result == 1 && !device_info.is_null() {
+        Ok((user_id, unsafe { *device_info }))
+    } else {
+        Err(get_last_error_code())
+    };
+
+    let sender = pending_logins().lock().unwrap().remove(&(user as usize));
+    let undelivered = match sender {
+        Some(sender) => sender.send(outcome).err().map(|e| e.0),
+        None => Some(outcome),
+    };
+
+    // 调用方已超时放弃，注销这次迟到的登录避免泄漏
+    if let Some(Ok((user_id, _))) = undelivered {
+        unsafe {
+            sdk_call!(NET_DVR_Logout_V30(user_id));
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NtpConfig {
+    pub server: String,
+    pub port: u16,
+    // 校时间隔，单位小时
+    pub interval_hours: u16,
+    pub enabled: bool,
+}
+
+impl From<&NET_DVR_NTPPARA> for NtpConfig {
+    fn from(ntp: &NET_DVR_NTPPARA) -> Self {
+        Self {
+            server: decode_device_string(&ntp.sNTPServer),
+            port: ntp.wNtpPort,
+            interval_hours: ntp.wInterval,
+            enabled: ntp.byEnableNTP == 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::{Duration as ChronoDuration, Local};
+
+    use super::*;
+    use crate::{
+        NET_DVR_CHECK_USER_STATUS, NET_DVR_GET_PREVIEW_DISPLAYCFG, NET_DVR_NETWORK_FAIL_CONNECT,
+        NET_DVR_PREVIEW_DISPLAYCFG, NET_DVR_SET_PREVIEW_DISPLAYCFG,
+        disk::FormatOptions,
+        ffi_util::write_str_to_c_array,
+        isapi::IsapiMethod,
+        preview::StreamType,
+        rtsp::CredentialStyle,
+        sdk::{MockCall, MockSdk, device_info, logged_in_device},
+        serial::SerialPortKind,
+    };
+
+    fn login_err(mock: &Arc<MockSdk>) -> anyhow::Error {
+        let mut device = HikDevice::with_sdk(mock.clone());
+        let err = device
+            .login("192.0.2.1", "admin", "secret", 8000)
+            .unwrap_err();
+        assert!(!device.is_logged_in());
+        err
+    }
+
+    #[test]
+    fn login_user_locked_reports_remaining_time() {
+        let mock = Arc::new(MockSdk::new());
+        let mut info = NET_DVR_DEVICEINFO_V40::default();
+        info.dwSurplusLockTime = 120;
+        mock.push_login_err(NET_DVR_USER_LOCKED, info);
+        assert_eq!(
+            login_err(&mock).downcast_ref::<HikError>(),
+            Some(&HikError::AccountLocked {
+                remaining_secs: 120
+            })
+        );
+    }
+
+    #[test]
+    fn login_password_error_reports_retries_left() {
+        let mock = Arc::new(MockSdk::new());
+        let mut info = NET_DVR_DEVICEINFO_V40::default();
+        info.bySupportLock = 1;
+        info.byRetryLoginTime = 3;
+        mock.push_login_err(NET_DVR_PASSWORD_ERROR, info);
+        let err = login_err(&mock);
+        assert!(err.downcast_ref::<HikError>().is_none());
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Login failed: error code {}, 3 retries left",
+                NET_DVR_PASSWORD_ERROR
+            )
+        );
+    }
+
+    #[test]
+    fn login_password_error_without_retries_is_locked() {
+        let mock = Arc::new(MockSdk::new());
+        let mut info = NET_DVR_DEVICEINFO_V40::default();
+        info.bySupportLock = 1;
+        info.byRetryLoginTime = 0;
+        info.dwSurplusLockTime = 60;
+        mock.push_login_err(NET_DVR_PASSWORD_ERROR, info);
+        assert_eq!(
+            login_err(&mock).downcast_ref::<HikError>(),
+            Some(&HikError::AccountLocked { remaining_secs: 60 })
+        );
+    }
+
+    #[test]
+    fn login_bare_error_code() {
+        let mock = Arc::new(MockSdk::new());
+        mock.push_login_err(NET_DVR_NETWORK_FAIL_CONNECT, Default::default());
+        assert_eq!(
+            login_err(&mock).to_string(),
+            format!("Login failed: error code {}", NET_DVR_NETWORK_FAIL_CONNECT)
+        );
+
+        // 设备不支持锁定时密码错误也只有错误码
+        mock.push_login_err(NET_DVR_PASSWORD_ERROR, Default::default());
+        assert_eq!(
+            login_err(&mock).to_string(),
+            format!("Login failed: error code {}", NET_DVR_PASSWORD_ERROR)
+        );
+    }
+
+    #[test]
+    fn channels_from_device_info_with_high_ip_count() {
+        let mock = Arc::new(MockSdk::new());
+        // 300 个 IP 通道：byIPChanNum = 44，byHighDChanNum = 1
+        let device = logged_in_device(&mock, device_info(1, 4, 33, 300));
+        let info = device.get_device_info().unwrap();
+        assert_eq!(info.ip_channel_count(), 300);
+
+        let channels = info.get_channels();
+        assert_eq!(channels.len(), 304);
+        let analog: Vec<u16> = channels[..4].iter().map(Channel::chan_num).collect();
+        assert_eq!(analog, [1, 2, 3, 4]);
+        assert!(channels[4].is_ip());
+        assert_eq!(channels[4].chan_num(), 33);
+        assert_eq!(channels[303].info().index(), 299);
+        assert_eq!(channels[303].chan_num(), 332);
+
+        assert_eq!(device.resolve_channel(4).unwrap(), 4);
+        assert_eq!(device.resolve_channel(332).unwrap(), 332);
+        for channel in [0, 5, 32, 333] {
+            assert!(device.resolve_channel(channel).is_err(), "{}", channel);
+        }
+    }
+
+    fn chan_nums(channels: &[Channel]) -> Vec<u16> {
+        channels.iter().map(Channel::chan_num).collect()
+    }
+
+    #[test]
+    fn channel_layout_analog_only() {
+        let channels = channel_layout(1, 16, 0, 0);
+        assert_eq!(chan_nums(&channels), (1..=16).collect::<Vec<_>>());
+        assert!(channels.iter().all(|channel| !channel.is_ip()));
+        assert_eq!(channels[15].info().index(), 15);
+    }
+
+    #[test]
+    fn channel_layout_ip_only() {
+        for ip_count in [64u16, 128] {
+            let channels = channel_layout(0, 0, 33, ip_count);
+            assert_eq!(channels.len(), ip_count as usize);
+            assert!(channels.iter().all(Channel::is_ip));
+            assert_eq!(
+                chan_nums(&channels),
+                (33..33 + ip_count).collect::<Vec<_>>()
+            );
+            let last = channels.last().unwrap().info();
+            assert_eq!(last.index(), ip_count - 1);
+        }
+    }
+
+    #[test]
+    fn ip_group_offset_splits_by_64() {
+        assert_eq!(IP_CHANNELS_PER_GROUP, 64);
+        assert_eq!(ip_group_offset(0), (0, 0));
+        assert_eq!(ip_group_offset(63), (0, 63));
+        assert_eq!(ip_group_offset(64), (1, 0));
+        assert_eq!(ip_group_offset(127), (1, 63));
+        assert_eq!(ip_group_offset(299), (4, 43));
+    }
+
+    #[test]
+    fn get_channels_reads_each_ip_group() {
+        let mock = Arc::new(MockSdk::new());
+        let device = logged_in_device(&mock, device_info(0, 0, 33, 128));
+        let group = |offset: usize, ip: &str| {
+            let mut config = NET_DVR_IPPARACFG_V40::new_for_sdk();
+            let dev = &mut config.struIPDevInfo[offset];
+            dev.byEnable = 1;
+            write_str_to_c_array(&mut dev.struIP.sIpV4, ip).unwrap();
+            dev.struIP.byIPv6[..11].copy_from_slice(b"2001:db8::1");
+            config
+        };
+        mock.set_config(NET_DVR_GET_IPPARACFG_V40, 0, &group(5, "10.0.0.5"));
+        mock.set_config(NET_DVR_GET_IPPARACFG_V40, 1, &group(2, "10.0.1.2"));
+
+        let channels = device.get_channels().unwrap();
+        assert_eq!(channels.len(), 128);
+        let enabled: Vec<(u16, Option<String>)> = channels
+            .iter()
+            .map(Channel::info)
+            .filter(|info| info.enable)
+            .map(|info| (info.chan_num, info.ipv4_address.clone()))
+            .collect();
+        let ipv6 = channels[5].info().ipv6_address.as_deref();
+        assert_eq!(ipv6, Some("2001:db8::1"));
+        // 序号 5 在第 0 组，序号 66 在第 1 组的偏移 2
+        assert_eq!(
+            enabled,
+            [
+                (38, Some("10.0.0.5".to_string())),
+                (99, Some("10.0.1.2".to_string())),
+            ]
+        );
+        let groups: Vec<LONG> = mock
+            .calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                MockCall::GetDvrConfig { command, channel }
+                    if command == NET_DVR_GET_IPPARACFG_V40 =>
+                {
+                    Some(channel)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(groups, [0, 1]);
+    }
+
+    // 返回 InvalidArgument 的字段名，其他结果直接失败
+    fn invalid_field<T>(result: anyhow::Result<T>) -> &'static str {
+        let Err(e) = result else {
+            panic!("expected InvalidArgument, got Ok");
+        };
+        match e.downcast::<HikError>() {
+            Ok(HikError::InvalidArgument { field, .. }) => field,
+            other => panic!("expected InvalidArgument, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn hostile_login_inputs() {
+        let mock = Arc::new(MockSdk::new());
+        let mut device = HikDevice::with_sdk(mock.clone());
+        let long = "x".repeat(200);
+        for (host, username, password, field) in [
+            ("192.0.2.1", "admin", "pa\0ss", "password"),
+            ("192.0.2.1", "ad\0min", "secret", "username"),
+            ("192.0.2.1\0", "admin", "secret", "host"),
+            (long.as_str(), "admin", "secret", "host"),
+            ("192.0.2.1", long.as_str(), "secret", "username"),
+            ("192.0.2.1", "admin", long.as_str(), "password"),
+        ] {
+            let result = device.login(host, username, password, 8000).map(|_| ());
+            assert_eq!(invalid_field(result), field, "{:?}", (host, username));
+        }
+        // 参数错误时不调用 SDK
+        assert!(mock.calls().is_empty());
+        assert!(!device.is_logged_in());
+    }
+
+    #[test]
+    fn hostile_device_inputs() {
+        let mock = Arc::new(MockSdk::new());
+        // 模拟通道 1-4，IP 通道 33-40，报警输入 0-3，报警输出 0-1
+        let mut info = device_info(1, 4, 33, 8);
+        info.struDeviceV30.byAlarmInPortNum = 4;
+        info.struDeviceV30.byAlarmOutPortNum = 2;
+        let device = logged_in_device(&mock, info);
+        let end = Local::now();
+        let start = end - ChronoDuration::hours(1);
+        // GBK 下每个汉字 2 字节，超过 32 字节的名称字段
+        let long_gbk = "报警".repeat(20);
+
+        for channel in [0, 5, 32, 41, u16::MAX] {
+            assert_eq!(
+                invalid_field(device.open_serial(SerialPortKind::Rs485, channel)),
+                "channel"
+            );
+            assert_eq!(
+                invalid_field(device.rtsp_url(channel, StreamType::Main, CredentialStyle::Login)),
+                "channel"
+            );
+            assert_eq!(
+                invalid_field(device.delete_recordings(
+                    channel,
+                    start.naive_local(),
+                    end.naive_local()
+                )),
+                "channel"
+            );
+            assert_eq!(
+                invalid_field(device.capture_jpeg_picture(channel, "/tmp/a.jpg")),
+                "channel"
+            );
+            assert_eq!(
+                invalid_field(device.get_file_by_time("/tmp/a.mp4", channel, start, end)),
+                "channel"
+            );
+        }
+        assert_eq!(
+            invalid_field(device.capture_jpeg_picture(1, "/tmp/a\0b.jpg")),
+            "file"
+        );
+        assert_eq!(
+            invalid_field(device.get_file_by_time("/tmp/a\0b.mp4", 33, start, end)),
+            "file"
+        );
+        for (from, to) in [(end, start), (start, start)] {
+            assert_eq!(
+                invalid_field(device.get_file_by_time("/tmp/a.mp4", 1, from, to)),
+                "end"
+            );
+        }
+
+        // sChanName 为 32 字节
+        let mut config = device.get_picture_config(1).unwrap();
+        config.name = "x".repeat(32);
+        assert_eq!(
+            invalid_field(device.set_picture_config(1, &config)),
+            "Channel name"
+        );
+        config.name = "a\0b".to_string();
+        assert_eq!(
+            invalid_field(device.set_picture_config(1, &config)),
+            "Channel name"
+        );
+
+        for index in [4, 255, u16::MAX] {
+            assert_eq!(invalid_field(device.get_alarm_in_config(index)), "index");
+        }
+        for index in [2, 255, u16::MAX] {
+            assert_eq!(invalid_field(device.get_alarm_out_config(index)), "index");
+            assert_eq!(invalid_field(device.set_alarm_out(index, true)), "index");
+        }
+        let mut alarm_in = device.get_alarm_in_config(0).unwrap();
+        assert_eq!(
+            invalid_field(device.set_alarm_in_config(4, &alarm_in)),
+            "index"
+        );
+        for name in [long_gbk.as_str(), "a\0b"] {
+            alarm_in.name = name.to_string();
+            assert_eq!(
+                invalid_field(device.set_alarm_in_config(0, &alarm_in)),
+                "Alarm input name"
+            );
+        }
+        let mut alarm_out = device.get_alarm_out_config(1).unwrap();
+        for name in [long_gbk.as_str(), "a\0b"] {
+            alarm_out.name = name.to_string();
+            assert_eq!(
+                invalid_field(device.set_alarm_out_config(1, &alarm_out)),
+                "Alarm output name"
+            );
+        }
+
+        for disk in [-1, 33, 0xfe, 0x100, i32::MAX] {
+            assert_eq!(
+                invalid_field(device.format_disk(disk, FormatOptions::default())),
+                "disk_index"
+            );
+        }
+
+        for url in [
+            "",
+            "ISAPI/System/deviceInfo",
+            "/ISAPI/System/deviceInfo\0/extra",
+            "/ISAPI/System/deviceInfo HTTP/1.1",
+            "/ISAPI/System/deviceInfo\r\nHost: evil",
+        ] {
+            assert_eq!(
+                invalid_field(device.isapi_request(IsapiMethod::Get, url, None)),
+                "url"
+            );
+            assert_eq!(invalid_field(device.isapi_get_string(url)), "url");
+        }
+
+        // 除了登录与读取图像、报警参数，没有任何调用到达 SDK
+        assert!(
+            mock.calls()
+                .iter()
+                .all(|call| matches!(call, MockCall::Login { .. } | MockCall::GetDvrConfig { .. }))
+        );
+    }
+
+    #[test]
+    fn transport_and_transparent_channel_are_separate() {
+        let mut options = LoginOptions::new("192.0.2.1", 8000, "admin", "secret");
+        assert_eq!(options.use_transport, TransportMode::Tcp);
+        assert_eq!(login_info(&options).unwrap().byUseTransport, 0);
+        options.transparent_channel = true;
+        assert_eq!(login_info(&options).unwrap().byUseTransport, 1);
+
+        let mock = Arc::new(MockSdk::new());
+        let mut device = HikDevice::with_sdk(mock.clone());
+        mock.push_login_ok(0, device_info(1, 4, 0, 0));
+        options.use_transport = TransportMode::Udp;
+        device.login_v40(options).unwrap();
+        assert_eq!(device.transport(), TransportMode::Udp);
+    }
+
+    fn started_download(mock: &Arc<MockSdk>) -> (HikDevice, HikDownload) {
+        let device = logged_in_device(mock, device_info(1, 4, 0, 0));
+        let end = Local::now();
+        let mut download = device
+            .get_file_by_time("/tmp/mock.mp4", 1, end - ChronoDuration::hours(1), end)
+            .unwrap();
+        download.start().unwrap();
+        (device, download)
+    }
+
+    #[test]
+    fn download_progress_sdk_failure() {
+        let mock = Arc::new(MockSdk::new());
+        let (_device, download) = started_download(&mock);
+        mock.fail("NET_DVR_GetDownloadPos", 12);
+
+        assert_eq!(
+            download.get_progress().unwrap_err().to_string(),
+            "Get download progress failed: error code 12"
+        );
+        assert_eq!(
+            download.poll(40),
+            DownloadStatus {
+                percent: 40,
+                state: DownloadState::Failed(HikError::Sdk {
+                    action: "Get download progress",
+                    code: 12
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn download_progress_network_error() {
+        let mock = Arc::new(MockSdk::new());
+        let (_device, download) = started_download(&mock);
+        mock.push_download_positions([200]);
+
+        assert_eq!(
+            download.get_progress().unwrap_err().to_string(),
+            "Get download network error"
+        );
+        assert!(matches!(
+            download.poll(10).state,
+            DownloadState::Failed(HikError::Sdk {
+                action: "Download",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn download_progress_out_of_range() {
+        let mock = Arc::new(MockSdk::new());
+        let (_device, download) = started_download(&mock);
+        mock.push_download_positions([101]);
+
+        assert_eq!(
+            download.get_progress().unwrap_err().to_string(),
+            "Get download progress failed"
+        );
+        assert!(matches!(
+            download.poll(99).state,
+            DownloadState::Failed(HikError::Sdk {
+                action: "Get download progress",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn download_progress_running_and_finished() {
+        let mock = Arc::new(MockSdk::new());
+        let (_device, download) = started_download(&mock);
+        mock.push_download_positions([42, 100]);
+
+        assert_eq!(download.poll(0).state, DownloadState::Running);
+        assert_eq!(
+            download.poll(42),
+            DownloadStatus {
+                percent: 100,
+                state: DownloadState::Finished,
+            }
+        );
+        assert_eq!(download.get_progress().unwrap(), 100);
+    }
+
+    fn assert_invalidated(mock: &MockSdk, device: &mut HikDevice) {
+        assert!(!device.is_logged_in());
+        // 设备已断开，不应再调用 Logout
+        assert!(
+            !mock
+                .calls()
+                .iter()
+                .any(|call| matches!(call, MockCall::Logout { .. }))
+        );
+        assert_eq!(
+            device.resolve_channel(1).unwrap_err().downcast_ref(),
+            Some(&HikError::NotLoggedIn)
+        );
+        assert_eq!(
+            device.reboot().unwrap_err().downcast_ref(),
+            Some(&HikError::NotLoggedIn)
+        );
+    }
+
+    #[test]
+    fn reboot_invalidates_session_and_keeps_credentials() {
+        let mock = Arc::new(MockSdk::new());
+        let mut device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        device.reboot().unwrap();
+        assert!(mock.calls().contains(&MockCall::Reboot { user_id: 0 }));
+        assert!(device.session.reboot_options().is_some());
+        assert_invalidated(&mock, &mut device);
+    }
+
+    #[test]
+    fn failed_reboot_keeps_session() {
+        let mock = Arc::new(MockSdk::new());
+        let mut device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        mock.fail("NET_DVR_RebootDVR", 23);
+        assert_eq!(
+            device.reboot().unwrap_err().downcast_ref(),
+            Some(&HikError::Sdk {
+                action: "Reboot",
+                code: 23
+            })
+        );
+        assert!(device.is_logged_in());
+    }
+
+    #[test]
+    fn std_config_goes_through_sdk() {
+        let mock = Arc::new(MockSdk::new());
+        let device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        let stored = NET_DVR_PREVIEW_DISPLAYCFG {
+            byCorrectMode: 1,
+            ..Default::default()
+        };
+        mock.set_config(NET_DVR_GET_PREVIEW_DISPLAYCFG, 2, &stored);
+
+        let config: NET_DVR_PREVIEW_DISPLAYCFG = device
+            .get_std_config(NET_DVR_GET_PREVIEW_DISPLAYCFG, 2, "Get")
+            .unwrap();
+        assert_eq!(config.byCorrectMode, 1);
+        device
+            .set_std_config(NET_DVR_SET_PREVIEW_DISPLAYCFG, 2, &config, "Set")
+            .unwrap();
+        let calls = mock.calls();
+        assert!(calls.contains(&MockCall::GetStdConfig {
+            command: NET_DVR_GET_PREVIEW_DISPLAYCFG,
+            channel: 2,
+        }));
+        assert!(calls.contains(&MockCall::SetStdConfig {
+            command: NET_DVR_SET_PREVIEW_DISPLAYCFG,
+            channel: 2,
+            data: struct_bytes(&config).to_vec(),
+        }));
+
+        mock.fail("NET_DVR_GetSTDConfig", 23);
+        assert_eq!(
+            device
+                .get_std_config::<NET_DVR_PREVIEW_DISPLAYCFG>(
+                    NET_DVR_GET_PREVIEW_DISPLAYCFG,
+                    2,
+                    "Get"
+                )
+                .unwrap_err()
+                .downcast_ref(),
+            Some(&HikError::Sdk {
+                action: "Get",
+                code: 23
+            })
+        );
+    }
+
+    #[test]
+    fn capture_jpeg_data_goes_through_sdk() {
+        let mock = Arc::new(MockSdk::new());
+        let device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        mock.set_jpeg(vec![0xff, 0xd8, 0xff, 0xd9]);
+        let jpeg = device.capture_jpeg_data(2, JpegParams::default()).unwrap();
+        assert_eq!(jpeg, [0xff, 0xd8, 0xff, 0xd9]);
+        assert!(
+            mock.calls()
+                .contains(&MockCall::CaptureJpegPictureNew { channel: 2 })
+        );
+
+        mock.fail("NET_DVR_CaptureJPEGPicture_NEW", 23);
+        assert_eq!(
+            device
+                .capture_jpeg_data(2, JpegParams::default())
+                .unwrap_err()
+                .downcast_ref(),
+            Some(&HikError::Sdk {
+                action: "Capture JPEG picture",
+                code: 23
+            })
+        );
+    }
+
+    #[test]
+    fn playback_by_time_reports_sdk_error() {
+        let mock = Arc::new(MockSdk::new());
+        let device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        mock.fail("NET_DVR_PlayBackByTime_V40", 23);
+        let end = Local::now();
+        let Err(err) = device.playback_by_time(3, end - ChronoDuration::hours(1), end, |_| {})
+        else {
+            panic!("playback should fail");
+        };
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&HikError::Sdk {
+                action: "Playback by time",
+                code: 23
+            })
+        );
+        assert!(
+            mock.calls()
+                .contains(&MockCall::PlayBackByTime { channel: 3 })
+        );
+    }
+
+    #[test]
+    fn check_alive_uses_session_sdk() {
+        let mock = Arc::new(MockSdk::new());
+        let device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        assert!(device.check_alive());
+        assert!(mock.calls().contains(&MockCall::RemoteControl {
+            user_id: 0,
+            command: NET_DVR_CHECK_USER_STATUS,
+            input: Vec::new(),
+        }));
+        mock.fail("NET_DVR_RemoteControl", NET_DVR_NETWORK_FAIL_CONNECT);
+        assert!(!device.check_alive());
+    }
+
+    #[test]
+    fn shutdown_invalidates_session() {
+        let mock = Arc::new(MockSdk::new());
+        let mut device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        device.shutdown().unwrap();
+        assert!(mock.calls().contains(&MockCall::Shutdown { user_id: 0 }));
+        assert!(device.session.reboot_options().is_none());
+        assert_invalidated(&mock, &mut device);
+    }
+
+    #[test]
+    fn full_restore_invalidates_session() {
+        let mock = Arc::new(MockSdk::new());
+        let mut device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        device.restore_defaults(true).unwrap();
+        let restore = mock
+            .calls()
+            .into_iter()
+            .find_map(|call| match call {
+                MockCall::RemoteControl { command, input, .. } => Some((command, input)),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(restore.0, NET_DVR_COMPLETE_RESTORE_CTRL);
+        assert_eq!(
+            restore.1.len(),
+            mem::size_of::<NET_DVR_COMPLETE_RESTORE_INFO>()
+        );
+        // 账号被清除，不保留凭据
+        assert!(device.session.reboot_options().is_none());
+        assert_invalidated(&mock, &mut device);
+    }
+
+    #[test]
+    fn partial_restore_keeps_session() {
+        let mock = Arc::new(MockSdk::new());
+        let mut device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        device.restore_defaults(false).unwrap();
+        assert!(
+            mock.calls()
+                .contains(&MockCall::RestoreConfig { user_id: 0 })
+        );
+        assert!(device.is_logged_in());
     }
 }
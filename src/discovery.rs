@@ -0,0 +1,169 @@
+use std::{
+    collections::HashSet,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, UdpSocket},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{ability::xml_tag_value, cancel::CancellationToken};
+
+// SADP 探测使用的组播地址与端口，设备的应答也发往该组播组
+const SADP_MULTICAST_ADDR: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const SADP_PORT: u16 = 37020;
+// 等待应答时检查取消的间隔
+const DISCOVERY_CANCEL_CHECK: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiscoveredDevice {
+    pub serial_number: String,
+    // 设备型号，例如 DS-2CD2T47G2-L
+    pub device_type: String,
+    pub ipv4_address: Option<Ipv4Addr>,
+    pub ipv6_address: Option<Ipv6Addr>,
+    // SDK 登录端口
+    pub port: u16,
+    pub http_port: u16,
+    pub activated: bool,
+    pub dhcp: bool,
+    pub mac_address: String,
+    pub firmware_version: String,
+}
+
+impl DiscoveredDevice {
+    // 未激活的设备需要先调用 common::activate_device 才能登录
+    pub fn is_activated(&self) -> bool {
+        self.activated
+    }
+
+    fn from_probe_match(xml: &str) -> Option<Self> {
+        // 自己发出的 Probe 也会被组播回环收到，这里只接受应答
+        xml_tag_value(xml, "ProbeMatch")?;
+
+        let text = |tag: &str| xml_tag_value(xml, tag).unwrap_or("").trim().to_string();
+        let flag = |tag: &str| text(tag).eq_ignore_ascii_case("true");
+        let port = |tag: &str| text(tag).parse().unwrap_or(0);
+
+        let serial_number = text("DeviceSN");
+        if serial_number.is_empty() {
+            return None;
+        }
+        Some(Self {
+            serial_number,
+            device_type: text("DeviceDescription"),
+            // 0.0.0.0 / :: 表示未配置
+            ipv4_address: text("IPv4Address")
+                .parse()
+                .ok()
+                .filter(|ip: &Ipv4Addr| !ip.is_unspecified()),
+            ipv6_address: text("IPv6Address")
+                .parse()
+                .ok()
+                .filter(|ip: &Ipv6Addr| !ip.is_unspecified()),
+            port: port("CommandPort"),
+            http_port: port("HttpPort"),
+            activated: flag("Activated"),
+            dhcp: flag("DHCP"),
+            mac_address: text("MAC"),
+            firmware_version: text("SoftwareVersion"),
+        })
+    }
+}
+
+/// 在局域网内组播搜索设备，收集 `timeout` 内的全部应答，同一序列号只返回一次
+///
+/// 不需要登录，也不依赖 SDK 初始化，可用于查找尚未激活或 IP 未知的设备
+pub fn discover_devices(timeout: Duration) -> anyhow::Result<Vec<DiscoveredDevice>> {
+    let mut devices = Vec::new();
+    discover_devices_with(timeout, |device| {
+        devices.push(device.clone());
+        true
+    })?;
+    Ok(devices)
+}
+
+/// 与 [`discover_devices`] 相同，但每发现一台设备就回调一次，回调返回 false 时提前结束搜索
+pub fn discover_devices_with<F>(timeout: Duration, on_found: F) -> anyhow::Result<()>
+where
+    F: FnMut(&DiscoveredDevice) -> bool,
+{
+    discover_devices_cancellable(timeout, &CancellationToken::new(), on_found)
+}
+
+/// 与 [`discover_devices_with`] 相同，cancel 被取消时提前结束搜索并返回已找到的结果
+pub fn discover_devices_cancellable<F>(
+    timeout: Duration,
+    cancel: &CancellationToken,
+    mut on_found: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(&DiscoveredDevice) -> bool,
+{
+    let socket = UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, SADP_PORT))
+        .map_err(|e| anyhow::anyhow!("Bind SADP port {} failed: {}", SADP_PORT, e))?;
+    socket
+        .join_multicast_v4(&SADP_MULTICAST_ADDR, &Ipv4Addr::UNSPECIFIED)
+        .map_err(|e| anyhow::anyhow!("Join SADP multicast group failed: {}", e))?;
+
+    let probe = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?><Probe><Uuid>{}</Uuid><Types>inquiry</Types></Probe>",
+        probe_uuid()
+    );
+    socket
+        .send_to(
+            probe.as_bytes(),
+            SocketAddrV4::new(SADP_MULTICAST_ADDR, SADP_PORT),
+        )
+        .map_err(|e| anyhow::anyhow!("Send SADP probe failed: {}", e))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut seen = HashSet::new();
+    let mut buffer = vec![0u8; 8 * 1024];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() || cancel.is_cancelled() {
+            return Ok(());
+        }
+        socket.set_read_timeout(Some(remaining.min(DISCOVERY_CANCEL_CHECK)))?;
+
+        let len = match socket.recv_from(&mut buffer) {
+            Ok((len, _)) => len,
+            Err(e)
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut =>
+            {
+                continue;
+            }
+            Err(e) => return Err(anyhow::anyhow!("Receive SADP response failed: {}", e)),
+        };
+
+        let xml = String::from_utf8_lossy(&buffer[..len]);
+        let Some(device) = DiscoveredDevice::from_probe_match(&xml) else {
+            continue;
+        };
+        // 设备可能从多个网卡应答
+        if !seen.insert(device.serial_number.clone()) {
+            continue;
+        }
+        if !on_found(&device) {
+            return Ok(());
+        }
+    }
+}
+
+// 设备只要求 Uuid 格式正确，用时间和进程号拼一个即可
+fn probe_uuid() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let value = nanos ^ ((std::process::id() as u128) << 96);
+    let hex = format!("{:032X}", value);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
@@ -0,0 +1,580 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::NaiveDateTime;
+
+use crate::{
+    BYTE, DWORD, LONG, MAX_DISKNUM_V30, NET_DVR_BUSY, NET_DVR_CloseFormatHandle,
+    NET_DVR_DEVICECFG_V40, NET_DVR_DISK_QUOTA, NET_DVR_DISK_QUOTA_CFG, NET_DVR_FormatDisk,
+    NET_DVR_GET_DEVICECFG_V40, NET_DVR_GET_DISK_QUOTA_CFG, NET_DVR_GET_RECORDCFG_V40,
+    NET_DVR_GetFormatProgress, NET_DVR_NOSUPPORT, NET_DVR_NOT_SUPPORT, NET_DVR_RECORD_V40,
+    NET_DVR_SET_DEVICECFG_V40, NET_DVR_SET_DISK_QUOTA_CFG, NET_DVR_SET_RECORDCFG_V40,
+    NET_DVR_StopDVRRecord, WORD,
+    cancel::CancellationToken,
+    device::{HikDevice, sdk_error},
+    error::HikError,
+    isapi::IsapiMethod,
+    sdk::{NetSdk, struct_bytes},
+    trace::sdk_call,
+};
+
+// wait 查询格式化进度的间隔
+const FORMAT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// format_disk 格式化全部硬盘
+const ALL_DISKS: i32 = 0xff;
+
+const ISAPI_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FormatOptions {
+    // 先停止所有通道的录像，格式化结束后恢复录像计划
+    pub force: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FormatProgress {
+    // 正在格式化的硬盘号，格式化全部硬盘时会依次变化
+    pub disk: i32,
+    // 当前硬盘的进度 0-100
+    pub percent: u8,
+    pub finished: bool,
+}
+
+pub struct HikFormat {
+    handle: LONG,
+    sdk: Arc<dyn NetSdk>,
+    user_id: LONG,
+    // force 时被关闭的录像计划，格式化结束后写回
+    saved_records: Vec<(LONG, NET_DVR_RECORD_V40)>,
+    closed: bool,
+}
+
+impl HikFormat {
+    // 格式化失败或与设备断开时返回错误
+    pub fn progress(&mut self) -> anyhow::Result<FormatProgress> {
+        let mut disk: LONG = 0;
+        let mut pos: LONG = 0;
+        let mut state: LONG = 0;
+        let res = unsafe {
+            sdk_call!(NET_DVR_GetFormatProgress(
+                self.handle,
+                &mut disk,
+                &mut pos,
+                &mut state
+            ))
+        };
+        if res != 1 {
+            return Err(sdk_error("Get format progress"));
+        }
+        // state：0 正在格式化，1 全部完成，2 格式化出错，3 网络断开
+        let progress = FormatProgress {
+            disk,
+            percent: pos.clamp(0, 100) as u8,
+            finished: state == 1,
+        };
+        match state {
+            0 | 1 => {
+                if progress.finished {
+                    self.close();
+                }
+                Ok(progress)
+            }
+            2 => {
+                self.close();
+                Err(anyhow::anyhow!("Formatting disk {} failed", disk))
+            }
+            _ => {
+                self.close();
+                Err(anyhow::anyhow!(
+                    "Connection lost while formatting disk {}",
+                    disk
+                ))
+            }
+        }
+    }
+
+    // 阻塞到格式化结束
+    pub fn wait(self) -> anyhow::Result<FormatProgress> {
+        self.wait_cancellable(&CancellationToken::new())
+    }
+
+    // 取消时只关闭进度句柄并返回 HikError::Cancelled，设备上的格式化不会停止
+    pub fn wait_cancellable(
+        mut self,
+        cancel: &CancellationToken,
+    ) -> anyhow::Result<FormatProgress> {
+        loop {
+            let progress = self.progress()?;
+            if progress.finished {
+                return Ok(progress);
+            }
+            if cancel.wait_timeout(FORMAT_POLL_INTERVAL) {
+                return Err(HikError::Cancelled.into());
+            }
+        }
+    }
+
+    // 关闭进度句柄并恢复录像计划，只执行一次
+    fn close(&mut self) {
+        if self.closed {
+            return;
+        }
+        self.closed = true;
+        unsafe {
+            sdk_call!(NET_DVR_CloseFormatHandle(self.handle));
+        }
+        for (channel, record) in self.saved_records.drain(..) {
+            let res = self.sdk.set_dvr_config(
+                self.user_id,
+                NET_DVR_SET_RECORDCFG_V40,
+                channel,
+                struct_bytes(&record),
+            );
+            #[cfg(feature = "tracing")]
+            if res != 1 {
+                tracing::warn!(
+                    channel,
+                    code = self.sdk.get_last_error(),
+                    "Restore record config failed"
+                );
+            }
+            #[cfg(not(feature = "tracing"))]
+            let _ = res;
+        }
+    }
+}
+
+impl Drop for HikFormat {
+    // 设备上的格式化不会因关闭句柄而停止，提前丢弃时录像计划可能恢复失败
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QuotaType {
+    // 按容量分配
+    Capacity,
+    // 按百分比分配
+    Ratio,
+    // 按保存天数
+    Period,
+    Other(u8),
+}
+
+impl From<BYTE> for QuotaType {
+    fn from(value: BYTE) -> Self {
+        match value {
+            1 => QuotaType::Capacity,
+            2 => QuotaType::Ratio,
+            3 => QuotaType::Period,
+            other => QuotaType::Other(other),
+        }
+    }
+}
+
+impl From<QuotaType> for BYTE {
+    fn from(value: QuotaType) -> Self {
+        match value {
+            QuotaType::Capacity => 1,
+            QuotaType::Ratio => 2,
+            QuotaType::Period => 3,
+            QuotaType::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quota {
+    pub quota_type: QuotaType,
+    // 分配的空间，单位 MB
+    pub capacity_mb: u64,
+    // 只读，设置时忽略
+    pub used_mb: u64,
+    // 0-100，Ratio 时有效
+    pub ratio: u8,
+    // 保存天数，Period 时有效
+    pub storage_days: u16,
+}
+
+impl From<&NET_DVR_DISK_QUOTA> for Quota {
+    fn from(raw: &NET_DVR_DISK_QUOTA) -> Self {
+        Self {
+            quota_type: raw.byQuotaType.into(),
+            capacity_mb: (raw.dwHCapacity as u64) << 32 | raw.dwLCapacity as u64,
+            used_mb: (raw.dwHUsedSpace as u64) << 32 | raw.dwLUsedSpace as u64,
+            ratio: raw.byQuotaRatio,
+            storage_days: raw.wStoragePeriod,
+        }
+    }
+}
+
+impl Quota {
+    fn apply_to(&self, raw: &mut NET_DVR_DISK_QUOTA) -> anyhow::Result<()> {
+        if self.ratio > 100 {
+            return Err(anyhow::anyhow!(
+                "Quota ratio out of range: {}, expected 0-100",
+                self.ratio
+            ));
+        }
+        raw.byQuotaType = self.quota_type.into();
+        raw.dwHCapacity = (self.capacity_mb >> 32) as DWORD;
+        raw.dwLCapacity = self.capacity_mb as DWORD;
+        raw.byQuotaRatio = self.ratio;
+        raw.wStoragePeriod = self.storage_days as WORD;
+        Ok(())
+    }
+}
+
+// 硬盘的存储模式，对应 NET_DVR_DEVICECFG_V40::byStorageMode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StorageMode {
+    // 盘组模式
+    Group,
+    // 配额模式，按通道分配 DiskQuota
+    Quota,
+    Other(u8),
+}
+
+impl From<BYTE> for StorageMode {
+    fn from(value: BYTE) -> Self {
+        match value {
+            0 => StorageMode::Group,
+            1 => StorageMode::Quota,
+            other => StorageMode::Other(other),
+        }
+    }
+}
+
+impl From<StorageMode> for BYTE {
+    fn from(value: StorageMode) -> Self {
+        match value {
+            StorageMode::Group => 0,
+            StorageMode::Quota => 1,
+            StorageMode::Other(other) => other,
+        }
+    }
+}
+
+// 配额模式下单个通道的录像与图片配额，盘组模式下设备会拒绝设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiskQuota {
+    pub record: Quota,
+    pub picture: Quota,
+}
+
+impl HikDevice {
+    /// 格式化硬盘，disk_index 为硬盘号，0xff 表示全部硬盘
+    ///
+    /// 通道正在录像时设备会拒绝格式化并返回 HikError::DiskBusy；
+    /// force 会先关闭录像计划并停止手动录像，格式化结束后只恢复录像计划
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host(), disk = disk_index))
+    )]
+    pub fn format_disk(
+        &self,
+        disk_index: i32,
+        options: FormatOptions,
+    ) -> anyhow::Result<HikFormat> {
+        if disk_index != ALL_DISKS && !(0..MAX_DISKNUM_V30 as i32).contains(&disk_index) {
+            return Err(HikError::InvalidArgument {
+                field: "disk_index",
+                reason: format!(
+                    "{} out of range: expected 0-{} or 0xff for all disks",
+                    disk_index,
+                    MAX_DISKNUM_V30 - 1
+                ),
+            }
+            .into());
+        }
+        let user_id = self.user_id()?;
+        let mut saved_records = Vec::new();
+        let handle = if options.force {
+            self.stop_all_recording(&mut saved_records)
+        } else {
+            Ok(())
+        }
+        .and_then(|_| {
+            self.with_session(|lu| {
+                let handle = unsafe { sdk_call!(NET_DVR_FormatDisk(lu, disk_index)) };
+                if handle < 0 {
+                    return Err(sdk_error("Format disk"));
+                }
+                Ok(handle)
+            })
+        });
+        match handle {
+            Ok(handle) => Ok(HikFormat {
+                handle,
+                sdk: self.sdk.clone(),
+                user_id,
+                saved_records,
+                closed: false,
+            }),
+            Err(e) => {
+                // 没有进度句柄，直接恢复录像计划
+                for (channel, record) in saved_records {
+                    let _ = self.set_dvr_config(
+                        NET_DVR_SET_RECORDCFG_V40,
+                        channel,
+                        &record,
+                        "Restore record config",
+                    );
+                }
+                Err(match e.downcast_ref::<HikError>() {
+                    Some(HikError::Sdk { code, .. }) if *code == NET_DVR_BUSY as i32 => {
+                        HikError::DiskBusy { code: *code }.into()
+                    }
+                    _ => e,
+                })
+            }
+        }
+    }
+
+    // 循环覆盖录像，硬盘满时删除最早的录像
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn set_record_overwrite(&self, enable: bool) -> anyhow::Result<()> {
+        let mut config: NET_DVR_DEVICECFG_V40 =
+            self.get_dvr_config(NET_DVR_GET_DEVICECFG_V40, 0, "Get device config")?;
+        config.dwRecycleRecord = enable as DWORD;
+        self.set_dvr_config(NET_DVR_SET_DEVICECFG_V40, 0, &config, "Set device config")
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_record_overwrite(&self) -> anyhow::Result<bool> {
+        let config: NET_DVR_DEVICECFG_V40 =
+            self.get_dvr_config(NET_DVR_GET_DEVICECFG_V40, 0, "Get device config")?;
+        Ok(config.dwRecycleRecord == 1)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_storage_mode(&self) -> anyhow::Result<StorageMode> {
+        let config: NET_DVR_DEVICECFG_V40 =
+            self.get_dvr_config(NET_DVR_GET_DEVICECFG_V40, 0, "Get device config")?;
+        Ok(config.byStorageMode.into())
+    }
+
+    // 切换后设备一般需要重启才生效，配额需在配额模式下用 set_disk_quota 设置
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn set_storage_mode(&self, mode: StorageMode) -> anyhow::Result<()> {
+        let mut config: NET_DVR_DEVICECFG_V40 =
+            self.get_dvr_config(NET_DVR_GET_DEVICECFG_V40, 0, "Get device config")?;
+        config.byStorageMode = mode.into();
+        self.set_dvr_config(NET_DVR_SET_DEVICECFG_V40, 0, &config, "Set device config")
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn get_disk_quota(&self, channel: u16) -> anyhow::Result<DiskQuota> {
+        let channel = self.resolve_channel(channel)?;
+        let raw: NET_DVR_DISK_QUOTA_CFG =
+            self.get_dvr_config(NET_DVR_GET_DISK_QUOTA_CFG, channel, "Get disk quota")?;
+        Ok(DiskQuota {
+            record: Quota::from(&raw.struRecordQuota),
+            picture: Quota::from(&raw.struPicQuota),
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn set_disk_quota(&self, channel: u16, quota: &DiskQuota) -> anyhow::Result<()> {
+        let channel = self.resolve_channel(channel)?;
+        let mut raw: NET_DVR_DISK_QUOTA_CFG =
+            self.get_dvr_config(NET_DVR_GET_DISK_QUOTA_CFG, channel, "Get disk quota")?;
+        quota.record.apply_to(&mut raw.struRecordQuota)?;
+        quota.picture.apply_to(&mut raw.struPicQuota)?;
+        self.set_dvr_config(NET_DVR_SET_DISK_QUOTA_CFG, channel, &raw, "Set disk quota")
+    }
+
+    /// 删除通道在 [start, end) 内的录像，时间为设备本地时间
+    ///
+    /// 通过 ISAPI 按时间段删除，已加锁的录像不会被删除；设备不支持时返回 HikError::Unsupported
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn delete_recordings(
+        &self,
+        channel: u16,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> anyhow::Result<()> {
+        if end <= start {
+            return Err(HikError::InvalidArgument {
+                field: "end",
+                reason: "must be later than start".into(),
+            }
+            .into());
+        }
+        // 录像轨道号为 RTSP 通道序号加 01（主码流）
+        let track = self.rtsp_channel_id(channel)? as u32 * 100 + 1;
+        let url = format!("/ISAPI/ContentMgmt/record/tracks/{}/deleteRecord", track);
+        let body = delete_record_body(track, start, end);
+        let response = self.isapi_request(IsapiMethod::Put, &url, Some(body.as_bytes()))?;
+        if response.success {
+            return Ok(());
+        }
+        let not_supported = response.error_code == NET_DVR_NOSUPPORT as i32
+            || response.error_code == NET_DVR_NOT_SUPPORT as i32
+            || response
+                .status_str()
+                .is_some_and(|status| status.contains("notSupport"));
+        if not_supported {
+            return Err(HikError::Unsupported {
+                feature: "Delete recordings by time",
+            }
+            .into());
+        }
+        Err(anyhow::anyhow!(
+            "ISAPI PUT {} failed: error code {}, status: {}",
+            url,
+            response.error_code,
+            response.status_str().unwrap_or_default()
+        ))
+    }
+
+    // 关闭正在录像通道的录像计划并停止手动录像，原来的录像计划保存到 saved
+    fn stop_all_recording(
+        &self,
+        saved: &mut Vec<(LONG, NET_DVR_RECORD_V40)>,
+    ) -> anyhow::Result<()> {
+        let state = self.get_work_state()?;
+        for channel in state.channels.iter().filter(|c| c.recording) {
+            let sdk_channel = channel.chan_num as LONG;
+            let mut record: NET_DVR_RECORD_V40 =
+                self.get_dvr_config(NET_DVR_GET_RECORDCFG_V40, sdk_channel, "Get record config")?;
+            if record.dwRecord == 1 {
+                saved.push((sdk_channel, record));
+                record.dwRecord = 0;
+                self.set_dvr_config(
+                    NET_DVR_SET_RECORDCFG_V40,
+                    sdk_channel,
+                    &record,
+                    "Set record config",
+                )?;
+            }
+            // 没有手动录像时设备也可能返回成功，失败不影响格式化
+            self.with_session(|lu| {
+                unsafe { sdk_call!(NET_DVR_StopDVRRecord(lu, sdk_channel)) };
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+}
+
+// ISAPI 的结束时间是闭区间，减去一秒避免删掉 end 之后的录像
+fn delete_record_body(track: u32, start: NaiveDateTime, end: NaiveDateTime) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+         <DeleteRecord version=\"2.0\" xmlns=\"http://www.isapi.org/ver20/XMLSchema\">\
+         <trackID>{}</trackID>\
+         <timeSpan><startTime>{}</startTime><endTime>{}</endTime></timeSpan>\
+         </DeleteRecord>",
+        track,
+        start.format(ISAPI_TIME_FORMAT),
+        (end - chrono::Duration::seconds(1)).format(ISAPI_TIME_FORMAT)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdk::{MockCall, MockSdk, device_info, logged_in_device};
+    use chrono::NaiveDate;
+
+    fn at(hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn delete_record_body_uses_track_and_inclusive_end() {
+        let body = delete_record_body(201, at(8), at(9));
+        assert!(body.contains("<trackID>201</trackID>"));
+        assert!(body.contains("<startTime>2024-03-01T08:00:00</startTime>"));
+        assert!(body.contains("<endTime>2024-03-01T08:59:59</endTime>"));
+    }
+
+    #[test]
+    fn delete_recordings_checks_arguments() {
+        let mock = Arc::new(MockSdk::new());
+        let device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        let err = device.delete_recordings(1, at(9), at(9)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(HikError::InvalidArgument { field: "end", .. })
+        ));
+        assert!(device.delete_recordings(9, at(8), at(9)).is_err());
+    }
+
+    #[test]
+    fn storage_mode_keeps_other_device_fields() {
+        let mock = Arc::new(MockSdk::new());
+        let device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        let stored = NET_DVR_DEVICECFG_V40 {
+            dwRecycleRecord: 1,
+            ..Default::default()
+        };
+        mock.set_config(NET_DVR_GET_DEVICECFG_V40, 0, &stored);
+        assert_eq!(device.get_storage_mode().unwrap(), StorageMode::Group);
+
+        device.set_storage_mode(StorageMode::Quota).unwrap();
+        let written = mock
+            .calls()
+            .into_iter()
+            .find_map(|call| match call {
+                MockCall::SetDvrConfig {
+                    command: NET_DVR_SET_DEVICECFG_V40,
+                    data,
+                    ..
+                } => Some(data),
+                _ => None,
+            })
+            .unwrap();
+        let expected = NET_DVR_DEVICECFG_V40 {
+            dwRecycleRecord: 1,
+            byStorageMode: 1,
+            ..Default::default()
+        };
+        assert_eq!(written, struct_bytes(&expected));
+        assert_eq!(StorageMode::from(2), StorageMode::Other(2));
+    }
+}
@@ -0,0 +1,260 @@
+use std::fmt;
+
+use crate::{
+    NET_DVR_EMAILCFG_V30, NET_DVR_GET_EMAILCFG_V30, NET_DVR_SET_EMAILCFG_V30,
+    device::HikDevice,
+    ffi_util::{copy_to_byte_array, copy_to_gbk_array, decode_device_string},
+};
+
+// NET_DVR_EMAILCFG_V30 中收件人的个数
+pub const MAX_EMAIL_RECEIVERS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EmailAddress {
+    pub name: String,
+    pub address: String,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct EmailConfig {
+    pub smtp_server: String,
+    pub smtp_port: u16,
+    pub username: String,
+    // 设备不会返回密码，None 表示保持原密码
+    pub password: Option<String>,
+    pub sender: EmailAddress,
+    // 最多 MAX_EMAIL_RECEIVERS 个
+    pub receivers: Vec<EmailAddress>,
+    pub ssl: bool,
+    pub attach_snapshot: bool,
+}
+
+// 不输出密码
+impl fmt::Debug for EmailConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmailConfig")
+            .field("smtp_server", &self.smtp_server)
+            .field("smtp_port", &self.smtp_port)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("sender", &self.sender)
+            .field("receivers", &self.receivers)
+            .field("ssl", &self.ssl)
+            .field("attach_snapshot", &self.attach_snapshot)
+            .finish()
+    }
+}
+
+impl From<&NET_DVR_EMAILCFG_V30> for EmailConfig {
+    fn from(raw: &NET_DVR_EMAILCFG_V30) -> Self {
+        let receivers = raw
+            .struReceiver
+            .iter()
+            .map(|receiver| EmailAddress {
+                name: decode_device_string(&receiver.sName),
+                address: decode_device_string(&receiver.sAddress),
+            })
+            .filter(|receiver| !receiver.address.is_empty())
+            .collect();
+
+        Self {
+            smtp_server: decode_device_string(&raw.sSmtpServer),
+            smtp_port: raw.wSmtpPort,
+            username: decode_device_string(&raw.sAccount),
+            password: None,
+            sender: EmailAddress {
+                name: decode_device_string(&raw.struSender.sName),
+                address: decode_device_string(&raw.struSender.sAddress),
+            },
+            receivers,
+            ssl: raw.byEnableSSL == 1,
+            attach_snapshot: raw.byAttachment == 1,
+        }
+    }
+}
+
+impl EmailConfig {
+    // 在设备当前配置上修改，POP3 服务器、发送间隔等未建模的字段保持不变
+    fn apply_to(&self, raw: &mut NET_DVR_EMAILCFG_V30) -> anyhow::Result<()> {
+        if self.receivers.len() > MAX_EMAIL_RECEIVERS {
+            return Err(anyhow::anyhow!(
+                "Too many email receivers: {}, max {}",
+                self.receivers.len(),
+                MAX_EMAIL_RECEIVERS
+            ));
+        }
+
+        copy_to_byte_array(&mut raw.sSmtpServer, &self.smtp_server, "SMTP server")?;
+        raw.wSmtpPort = self.smtp_port;
+        copy_to_byte_array(&mut raw.sAccount, &self.username, "Email username")?;
+        if let Some(password) = &self.password {
+            copy_to_byte_array(&mut raw.sPassword, password, "Email password")?;
+        }
+        copy_to_gbk_array(&mut raw.struSender.sName, &self.sender.name, "Sender name")?;
+        copy_to_byte_array(
+            &mut raw.struSender.sAddress,
+            &self.sender.address,
+            "Sender address",
+        )?;
+
+        let empty = EmailAddress::default();
+        for (i, slot) in raw.struReceiver.iter_mut().enumerate() {
+            let receiver = self.receivers.get(i).unwrap_or(&empty);
+            copy_to_gbk_array(&mut slot.sName, &receiver.name, "Receiver name")?;
+            copy_to_byte_array(&mut slot.sAddress, &receiver.address, "Receiver address")?;
+        }
+
+        raw.byEnableSSL = self.ssl as u8;
+        raw.byAttachment = self.attach_snapshot as u8;
+        Ok(())
+    }
+}
+
+impl HikDevice {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_email_config(&self) -> anyhow::Result<EmailConfig> {
+        let raw: NET_DVR_EMAILCFG_V30 =
+            self.get_dvr_config(NET_DVR_GET_EMAILCFG_V30, 0, "Get email config")?;
+        Ok(EmailConfig::from(&raw))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn set_email_config(&self, config: &EmailConfig) -> anyhow::Result<()> {
+        let mut raw: NET_DVR_EMAILCFG_V30 =
+            self.get_dvr_config(NET_DVR_GET_EMAILCFG_V30, 0, "Get email config")?;
+        config.apply_to(&mut raw)?;
+        self.set_dvr_config(NET_DVR_SET_EMAILCFG_V30, 0, &raw, "Set email config")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        sdk::{MockCall, MockSdk, device_info, logged_in_device, struct_bytes_mut},
+        sdk_struct::SdkStruct,
+    };
+
+    fn sample() -> EmailConfig {
+        EmailConfig {
+            smtp_server: "smtp.example.com".to_string(),
+            smtp_port: 465,
+            username: "alarm@example.com".to_string(),
+            password: Some("s3cret!".to_string()),
+            sender: EmailAddress {
+                name: "NVR".to_string(),
+                address: "alarm@example.com".to_string(),
+            },
+            receivers: vec![
+                EmailAddress {
+                    name: "Ops".to_string(),
+                    address: "ops@example.com".to_string(),
+                },
+                EmailAddress {
+                    name: "Guard".to_string(),
+                    address: "guard@example.com".to_string(),
+                },
+            ],
+            ssl: true,
+            attach_snapshot: true,
+        }
+    }
+
+    #[test]
+    fn round_trip_through_struct() {
+        let config = sample();
+        let mut raw = NET_DVR_EMAILCFG_V30::new_for_sdk();
+        config.apply_to(&mut raw).unwrap();
+        assert_eq!(decode_device_string(&raw.sPassword), "s3cret!");
+        // 设备不返回密码
+        assert_eq!(
+            EmailConfig::from(&raw),
+            EmailConfig {
+                password: None,
+                ..config
+            }
+        );
+    }
+
+    #[test]
+    fn none_password_keeps_current() {
+        let mut raw = NET_DVR_EMAILCFG_V30::new_for_sdk();
+        sample().apply_to(&mut raw).unwrap();
+        let config = EmailConfig {
+            password: None,
+            receivers: Vec::new(),
+            ..sample()
+        };
+        config.apply_to(&mut raw).unwrap();
+        assert_eq!(decode_device_string(&raw.sPassword), "s3cret!");
+        // 多出来的收件人被清空
+        assert!(EmailConfig::from(&raw).receivers.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        let mut raw = NET_DVR_EMAILCFG_V30::new_for_sdk();
+        let config = EmailConfig {
+            receivers: vec![EmailAddress::default(); MAX_EMAIL_RECEIVERS + 1],
+            ..sample()
+        };
+        assert!(config.apply_to(&mut raw).is_err());
+
+        let config = EmailConfig {
+            smtp_server: "a".repeat(48),
+            ..sample()
+        };
+        let err = config.apply_to(&mut raw).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid argument SMTP server: too long: 48 bytes, max 47"
+        );
+    }
+
+    #[test]
+    fn debug_redacts_password() {
+        let debug = format!("{:?}", sample());
+        assert!(!debug.contains("s3cret!"));
+        assert!(debug.contains("<redacted>"));
+    }
+
+    #[test]
+    fn set_email_config_writes_struct() {
+        let mock = Arc::new(MockSdk::new());
+        let device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        device.set_email_config(&sample()).unwrap();
+
+        let data = mock
+            .calls()
+            .into_iter()
+            .find_map(|call| match call {
+                MockCall::SetDvrConfig {
+                    command: NET_DVR_SET_EMAILCFG_V30,
+                    data,
+                    ..
+                } => Some(data),
+                _ => None,
+            })
+            .unwrap();
+        let mut raw = NET_DVR_EMAILCFG_V30::new_for_sdk();
+        struct_bytes_mut(&mut raw).copy_from_slice(&data);
+        assert_eq!(raw.wSmtpPort, 465);
+        assert_eq!(EmailConfig::from(&raw).receivers, sample().receivers);
+
+        // 读取时使用 GET 命令
+        mock.set_config(NET_DVR_GET_EMAILCFG_V30, 0, &raw);
+        assert_eq!(
+            device.get_email_config().unwrap().smtp_server,
+            "smtp.example.com"
+        );
+    }
+}
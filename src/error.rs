@@ -0,0 +1,101 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HikError {
+    // 账号因多次密码错误被锁定，remaining_secs 为剩余锁定时间
+    AccountLocked { remaining_secs: u32 },
+    // 操作被 CancellationToken 取消
+    Cancelled,
+    // 设备不支持下载时转封装为指定格式，可改用原始格式下载
+    ContainerNotSupported,
+    // 通道正在录像，设备拒绝格式化硬盘，code 为设备返回的错误码
+    DiskBusy { code: i32 },
+    // 录像正在写入或所在硬盘不支持加锁，code 为设备返回的错误码
+    FileNotLockable { code: i32 },
+    // 超时前没有收到可解码的视频帧
+    FrameTimeout,
+    // 调用参数不合法，在调用 SDK 之前返回，field 为参数名
+    InvalidArgument { field: &'static str, reason: String },
+    // 未登录或登录句柄已失效（例如设备重启后）
+    NotLoggedIn,
+    // 设备认为新密码强度过低而拒绝
+    RiskyPassword,
+    // SDK 调用返回失败，code 为 NET_DVR_GetLastError 的结果
+    Sdk { action: &'static str, code: i32 },
+    // 设备已登出，登出前打开的句柄已被停止
+    SessionClosed,
+    // HikDeviceManager 同时登录的会话数已达到 max_sessions
+    TooManySessions { max: usize },
+    // 该设备打开的预览与回放/下载数已达到 set_max_concurrent_streams 设置的上限
+    TooManyStreams { max: usize },
+    // 设备型号不支持该功能，feature 为功能名称
+    Unsupported { feature: &'static str },
+    // 转封装暂不支持的视频编码，stream_type 为 PS 流 PSM 中的值（H.265 为 0x24）
+    UnsupportedCodec { stream_type: u8 },
+    // 导入配置文件时密码与导出时设置的密码不一致
+    WrongConfigPassword,
+}
+
+impl HikError {
+    pub fn sdk_code(&self) -> Option<i32> {
+        match self {
+            HikError::Sdk { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HikError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HikError::AccountLocked { remaining_secs } => write!(
+                f,
+                "Login failed: account is locked, retry in {} seconds",
+                remaining_secs
+            ),
+            HikError::Cancelled => write!(f, "Operation cancelled"),
+            HikError::ContainerNotSupported => {
+                write!(f, "Container conversion not supported by device")
+            }
+            HikError::DiskBusy { code } => write!(
+                f,
+                "Disk is busy recording and cannot be formatted: error code {}",
+                code
+            ),
+            HikError::FileNotLockable { code } => {
+                write!(f, "File cannot be locked on this disk: error code {}", code)
+            }
+            HikError::FrameTimeout => write!(f, "No video frame received before timeout"),
+            HikError::InvalidArgument { field, reason } => {
+                write!(f, "Invalid argument {}: {}", field, reason)
+            }
+            HikError::NotLoggedIn => write!(f, "Not logged in"),
+            HikError::RiskyPassword => write!(f, "Password rejected by device: too weak"),
+            HikError::Sdk { action, code } => {
+                write!(f, "{} failed: error code {}", action, code)
+            }
+            HikError::SessionClosed => write!(f, "Session closed: device has been logged out"),
+            HikError::TooManySessions { max } => {
+                write!(f, "Too many concurrent logins: limit is {}", max)
+            }
+            HikError::TooManyStreams { max } => write!(
+                f,
+                "Too many concurrent streams: limit is {} per device",
+                max
+            ),
+            HikError::Unsupported { feature } => write!(f, "{} not supported by device", feature),
+            HikError::UnsupportedCodec { stream_type } => {
+                write!(
+                    f,
+                    "Unsupported video codec: stream type {:#04x}",
+                    stream_type
+                )
+            }
+            HikError::WrongConfigPassword => {
+                write!(f, "Config import failed: wrong config file password")
+            }
+        }
+    }
+}
+
+impl std::error::Error for HikError {}
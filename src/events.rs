@@ -0,0 +1,656 @@
+use std::{mem, os::raw::c_char, ptr};
+
+use chrono::{DateTime, Local, TimeZone as _};
+
+use crate::{
+    _VCA_EVENT_TYPE__VCA_ENTER_AREA, _VCA_EVENT_TYPE__VCA_EXIT_AREA,
+    _VCA_EVENT_TYPE__VCA_INTRUSION, _VCA_EVENT_TYPE__VCA_TRAVERSE_PLANE,
+    _VCA_RULE_EVENT_TYPE_EX__ENUM_VCA_EVENT_ENTER_AREA,
+    _VCA_RULE_EVENT_TYPE_EX__ENUM_VCA_EVENT_EXIT_AREA,
+    _VCA_RULE_EVENT_TYPE_EX__ENUM_VCA_EVENT_INTRUSION,
+    _VCA_RULE_EVENT_TYPE_EX__ENUM_VCA_EVENT_TRAVERSE_PLANE, BYTE, COMM_ALARM_PDC, COMM_ALARM_RULE,
+    COMM_ALARM_V30, COMM_ALARMHOST_CID_ALARM, COMM_IPCCFG, COMM_IPCCFG_V31, COMM_ITS_PLATE_RESULT,
+    DWORD, LONG, NET_DVR_ALARMINFO_V30, NET_DVR_CID_ALARM, NET_DVR_IPALARMINFO,
+    NET_DVR_IPALARMINFO_V31, NET_DVR_IPCHANINFO, NET_DVR_PDC_ALRAM_INFO, NET_DVR_TIME_V30,
+    NET_ITS_PLATE_RESULT, NET_VCA_RECT, NET_VCA_RULE_ALARM,
+    ffi_util::{c_array_to_string, decode_device_string},
+    time::packed_time_to_local,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VcaEventType {
+    LineCrossing,
+    Intrusion,
+    RegionEntrance,
+    RegionExit,
+    // 其他规则类型，值为 wEventTypeEx
+    Other(u32),
+}
+
+impl VcaEventType {
+    // 新设备填写 wEventTypeEx，旧设备只填写按位表示的 dwEventType
+    fn from_raw(event_type_ex: u16, event_type: u32) -> Self {
+        match event_type_ex as u32 {
+            _VCA_RULE_EVENT_TYPE_EX__ENUM_VCA_EVENT_TRAVERSE_PLANE => Self::LineCrossing,
+            _VCA_RULE_EVENT_TYPE_EX__ENUM_VCA_EVENT_ENTER_AREA => Self::RegionEntrance,
+            _VCA_RULE_EVENT_TYPE_EX__ENUM_VCA_EVENT_EXIT_AREA => Self::RegionExit,
+            _VCA_RULE_EVENT_TYPE_EX__ENUM_VCA_EVENT_INTRUSION => Self::Intrusion,
+            0 => match event_type {
+                _VCA_EVENT_TYPE__VCA_TRAVERSE_PLANE => Self::LineCrossing,
+                _VCA_EVENT_TYPE__VCA_ENTER_AREA => Self::RegionEntrance,
+                _VCA_EVENT_TYPE__VCA_EXIT_AREA => Self::RegionExit,
+                _VCA_EVENT_TYPE__VCA_INTRUSION => Self::Intrusion,
+                other => Self::Other(other),
+            },
+            other => Self::Other(other),
+        }
+    }
+}
+
+// 相对画面宽高归一化到 0-1
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NormalizedRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl From<&NET_VCA_RECT> for NormalizedRect {
+    fn from(rect: &NET_VCA_RECT) -> Self {
+        Self {
+            x: rect.fX,
+            y: rect.fY,
+            width: rect.fWidth,
+            height: rect.fHeight,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VcaRuleAlarm {
+    pub event_type: VcaEventType,
+    pub rule_id: u8,
+    pub rule_name: String,
+    pub channel: u16,
+    pub target_id: u32,
+    pub target_rect: NormalizedRect,
+    // 设备时间无效时为 None
+    pub time: Option<DateTime<Local>>,
+    // JPEG 图片，设备未上传或以 URL 方式上传时为 None
+    pub picture: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VehicleColor {
+    Unknown,
+    White,
+    Silver,
+    Gray,
+    Black,
+    Red,
+    DarkBlue,
+    Blue,
+    Yellow,
+    Green,
+    Brown,
+    Pink,
+    Purple,
+    DarkGray,
+    Cyan,
+    Other(u8),
+}
+
+// 对应 VCR_CLR_CLASS
+impl From<BYTE> for VehicleColor {
+    fn from(value: BYTE) -> Self {
+        match value {
+            0 => Self::Unknown,
+            1 => Self::White,
+            2 => Self::Silver,
+            3 => Self::Gray,
+            4 => Self::Black,
+            5 => Self::Red,
+            6 => Self::DarkBlue,
+            7 => Self::Blue,
+            8 => Self::Yellow,
+            9 => Self::Green,
+            10 => Self::Brown,
+            11 => Self::Pink,
+            12 => Self::Purple,
+            13 => Self::DarkGray,
+            14 => Self::Cyan,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlateResult {
+    // 设备上传的车牌号，通常带颜色前缀，例如 "蓝京A12345"
+    pub plate: String,
+    // 整牌置信度 0-100
+    pub confidence: u8,
+    // 车道号
+    pub lane: u8,
+    pub channel: u16,
+    pub vehicle_color: VehicleColor,
+    pub plate_rect: NormalizedRect,
+    pub time: Option<DateTime<Local>>,
+    // 场景图、车牌小图等，按设备上传顺序
+    pub pictures: Vec<Vec<u8>>,
+}
+
+// 报警主机上传的 CID 报告，包括防区报警、布撤防、旁路等
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SecurityControlEvent {
+    // 4 位 CID 事件码，例如 1130（防区报警）、3401（布防）、1401（撤防）
+    pub cid_code: String,
+    pub description: String,
+    // 子系统（分区）号，0 表示与分区无关
+    pub partition: u8,
+    // 防区号，0 表示与防区无关
+    pub zone: u16,
+    // 操作用户，报警类事件为空
+    pub user: String,
+    pub time: Option<DateTime<Local>>,
+}
+
+// 客流统计（COMM_ALARM_PDC）实时上传，计数为设备当前统计周期内的累计值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PeopleCountingAlarm {
+    pub channel: u16,
+    pub enter: u32,
+    pub exit: u32,
+    pub pass_by: u32,
+    // 按帧上传时为上传时间，按时间段上传时为 None
+    pub time: Option<DateTime<Local>>,
+    // 按时间段上传（整点、定时统计）时为统计的开始与结束时间
+    pub period: Option<(DateTime<Local>, DateTime<Local>)>,
+}
+
+#[derive(Debug, Clone)]
+pub enum AlarmEvent {
+    // 移动侦测，channels 为触发的通道号；该报警不带时间，按收到的时间处理
+    Motion { channels: Vec<u16> },
+    VcaRule(VcaRuleAlarm),
+    Plate(PlateResult),
+    SecurityControl(SecurityControlEvent),
+    PeopleCounting(PeopleCountingAlarm),
+    // IP 通道上线或离线，channel 为实际通道号，与 get_channel_status 一致
+    // 由 NVR 上传的 IP 通道配置（COMM_IPCCFG）与上一次的状态比较得出，只通过 subscribe_alarms 送达；
+    // 单独调用 decode 时该命令解析为 Other
+    ChannelStatusChanged { channel: u16, online: bool },
+    // 尚未解析的报警类型，保留原始字节，其中的指针在回调返回后失效
+    Other { command: u32, data: Vec<u8> },
+}
+
+impl AlarmEvent {
+    /// 解析报警回调收到的数据，图片等附带数据会被复制出来
+    ///
+    /// # Safety
+    ///
+    /// info 与 len 必须是 SDK 报警回调传入的缓冲区，且只能在回调返回前调用
+    pub unsafe fn decode(command: LONG, info: *const c_char, len: DWORD) -> anyhow::Result<Self> {
+        if info.is_null() {
+            return Err(anyhow::anyhow!("Alarm buffer is null"));
+        }
+        let data = unsafe { std::slice::from_raw_parts(info as *const u8, len as usize) };
+        let command = command as u32;
+        match command {
+            COMM_ALARM_V30 => {
+                let raw: NET_DVR_ALARMINFO_V30 = read_struct(data, "NET_DVR_ALARMINFO_V30")?;
+                match raw.dwAlarmType {
+                    MOTION_ALARM_TYPE => Ok(AlarmEvent::Motion {
+                        channels: alarm_channels(&raw.byChannel),
+                    }),
+                    _ => Ok(AlarmEvent::Other {
+                        command,
+                        data: data.to_vec(),
+                    }),
+                }
+            }
+            COMM_ALARM_RULE => {
+                let raw: NET_VCA_RULE_ALARM = read_struct(data, "NET_VCA_RULE_ALARM")?;
+                Ok(AlarmEvent::VcaRule(unsafe { decode_rule_alarm(&raw) }))
+            }
+            COMM_ITS_PLATE_RESULT => {
+                let raw: NET_ITS_PLATE_RESULT = read_struct(data, "NET_ITS_PLATE_RESULT")?;
+                Ok(AlarmEvent::Plate(unsafe { decode_plate_result(&raw) }))
+            }
+            COMM_ALARMHOST_CID_ALARM => {
+                let raw: NET_DVR_CID_ALARM = read_struct(data, "NET_DVR_CID_ALARM")?;
+                Ok(AlarmEvent::SecurityControl(decode_cid_alarm(&raw)))
+            }
+            COMM_ALARM_PDC => {
+                let raw: NET_DVR_PDC_ALRAM_INFO = read_struct(data, "NET_DVR_PDC_ALRAM_INFO")?;
+                Ok(AlarmEvent::PeopleCounting(unsafe {
+                    decode_pdc_alarm(&raw)
+                }))
+            }
+            _ => Ok(AlarmEvent::Other {
+                command,
+                data: data.to_vec(),
+            }),
+        }
+    }
+}
+
+// NET_DVR_ALARMINFO_V30::dwAlarmType，3 为移动侦测
+const MOTION_ALARM_TYPE: DWORD = 3;
+
+// byChannel[i] 为 1 表示通道 i + 1 触发，IP 通道从下标 32 开始，对应通道号 33
+fn alarm_channels(flags: &[BYTE]) -> Vec<u16> {
+    flags
+        .iter()
+        .enumerate()
+        .filter(|&(_, &flag)| flag == 1)
+        .map(|(i, _)| i as u16 + 1)
+        .collect()
+}
+
+// COMM_IPCCFG / COMM_IPCCFG_V31 中各 IP 通道的在线状态，返回 (IP 通道序号, 是否在线)，
+// 没有添加设备的通道不返回；其他命令返回 None
+pub(crate) fn ip_channel_states(command: u32, data: &[u8]) -> Option<Vec<(u16, bool)>> {
+    let channels: Vec<NET_DVR_IPCHANINFO> = match command {
+        COMM_IPCCFG => read_struct::<NET_DVR_IPALARMINFO>(data, "NET_DVR_IPALARMINFO")
+            .ok()?
+            .struIPChanInfo
+            .to_vec(),
+        COMM_IPCCFG_V31 => read_struct::<NET_DVR_IPALARMINFO_V31>(data, "NET_DVR_IPALARMINFO_V31")
+            .ok()?
+            .struIPChanInfo
+            .to_vec(),
+        _ => return None,
+    };
+    Some(
+        channels
+            .iter()
+            .enumerate()
+            .filter(|(_, chan)| chan.byIPID != 0 || chan.byIPIDHigh != 0)
+            .map(|(i, chan)| (i as u16, chan.byEnable == 1))
+            .collect(),
+    )
+}
+
+// 回调缓冲区不保证按结构体对齐
+fn read_struct<T>(data: &[u8], name: &str) -> anyhow::Result<T> {
+    if data.len() < mem::size_of::<T>() {
+        return Err(anyhow::anyhow!(
+            "{} buffer too short: {} bytes, expected {}",
+            name,
+            data.len(),
+            mem::size_of::<T>()
+        ));
+    }
+    Ok(unsafe { ptr::read_unaligned(data.as_ptr() as *const T) })
+}
+
+unsafe fn copy_buffer(data: *const BYTE, len: DWORD) -> Option<Vec<u8>> {
+    if data.is_null() || len == 0 {
+        return None;
+    }
+    Some(unsafe { std::slice::from_raw_parts(data, len as usize) }.to_vec())
+}
+
+unsafe fn decode_rule_alarm(raw: &NET_VCA_RULE_ALARM) -> VcaRuleAlarm {
+    let rule = &raw.struRuleInfo;
+    let channel = match raw.wDevInfoIvmsChannelEx {
+        0 => raw.struDevInfo.byChannel as u16,
+        channel => channel,
+    };
+    // byPicTransType 为 1 时 pImage 指向的是图片 URL
+    let picture = match raw.byPicTransType {
+        0 => unsafe { copy_buffer(raw.pImage, raw.dwPicDataLen) },
+        _ => None,
+    };
+
+    VcaRuleAlarm {
+        event_type: VcaEventType::from_raw(rule.wEventTypeEx, rule.dwEventType),
+        rule_id: rule.byRuleID,
+        rule_name: decode_device_string(&rule.byRuleName),
+        channel,
+        target_id: raw.struTargetInfo.dwID,
+        target_rect: NormalizedRect::from(&raw.struTargetInfo.struRect),
+        time: packed_time_to_local(raw.dwAbsTime),
+        picture,
+    }
+}
+
+fn decode_cid_alarm(raw: &NET_DVR_CID_ALARM) -> SecurityControlEvent {
+    let time = &raw.struTriggerTime;
+    SecurityControlEvent {
+        cid_code: decode_device_string(&raw.sCIDCode),
+        description: decode_device_string(&raw.sCIDDescribe),
+        partition: raw.bySubSysNo,
+        zone: raw.wDefenceNo,
+        user: decode_device_string(&raw.sUserName),
+        time: Local
+            .with_ymd_and_hms(
+                time.wYear as i32,
+                time.byMonth as u32,
+                time.byDay as u32,
+                time.byHour as u32,
+                time.byMinute as u32,
+                time.bySecond as u32,
+            )
+            .single(),
+    }
+}
+
+// byMode 为 0 时按帧上传，联合体中为 struStatFrame，否则为 struStatTime
+unsafe fn decode_pdc_alarm(raw: &NET_DVR_PDC_ALRAM_INFO) -> PeopleCountingAlarm {
+    let channel = match raw.wDevInfoIvmsChannelEx {
+        0 => raw.byChannel as u16,
+        channel => channel,
+    };
+    let (time, period) = if raw.byMode == 0 {
+        let frame = unsafe { raw.uStatModeParam.struStatFrame };
+        (packed_time_to_local(frame.dwAbsTime), None)
+    } else {
+        let stat = unsafe { raw.uStatModeParam.struStatTime };
+        let start = DateTime::try_from(stat.tmStart).ok();
+        let end = DateTime::try_from(stat.tmEnd).ok();
+        (None, start.zip(end))
+    };
+    PeopleCountingAlarm {
+        channel,
+        enter: raw.dwEnterNum,
+        exit: raw.dwLeaveNum,
+        pass_by: raw.dwPassingNum,
+        time,
+        period,
+    }
+}
+
+unsafe fn decode_plate_result(raw: &NET_ITS_PLATE_RESULT) -> PlateResult {
+    let plate = &raw.struPlateInfo;
+    let pic_num = (raw.dwPicNum as usize).min(raw.struPicInfo.len());
+    let pictures = raw.struPicInfo[..pic_num]
+        .iter()
+        .filter_map(|pic| unsafe { copy_buffer(pic.pBuffer, pic.dwDataLen) })
+        .collect();
+
+    PlateResult {
+        plate: c_array_to_string(&plate.sLicense),
+        confidence: plate.byEntireBelieve,
+        lane: raw.byDriveChan,
+        channel: raw.byChanIndexEx as u16 * 256 + raw.byChanIndex as u16,
+        vehicle_color: VehicleColor::from(raw.struVehicleInfo.byColor),
+        plate_rect: NormalizedRect::from(&plate.struPlateRect),
+        time: time_v30_to_local(&raw.struSnapFirstPicTime),
+        pictures,
+    }
+}
+
+fn time_v30_to_local(time: &NET_DVR_TIME_V30) -> Option<DateTime<Local>> {
+    Local
+        .with_ymd_and_hms(
+            time.wYear as i32,
+            time.byMonth as u32,
+            time.byDay as u32,
+            time.byHour as u32,
+            time.byMinute as u32,
+            time.bySecond as u32,
+        )
+        .single()
+        .map(|t| t + chrono::Duration::milliseconds(time.wMilliSec as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::slice;
+
+    use chrono::{Datelike as _, Timelike as _};
+
+    use super::*;
+    use crate::NET_ITS_PICTURE_INFO;
+
+    // 按 SDK 结构体的字段偏移写入字节，模拟报警回调收到的原始缓冲区
+    struct Fixture(Vec<u8>);
+
+    impl Fixture {
+        fn new<T>() -> Self {
+            Self(vec![0; mem::size_of::<T>()])
+        }
+
+        fn put<V: Copy>(&mut self, offset: usize, value: V) -> &mut Self {
+            let bytes = unsafe {
+                slice::from_raw_parts(&value as *const V as *const u8, mem::size_of::<V>())
+            };
+            self.put_bytes(offset, bytes)
+        }
+
+        fn put_bytes(&mut self, offset: usize, bytes: &[u8]) -> &mut Self {
+            self.0[offset..offset + bytes.len()].copy_from_slice(bytes);
+            self
+        }
+
+        // 回调缓冲区不保证对齐，从奇数地址开始解析
+        fn decode(&self, command: u32) -> anyhow::Result<AlarmEvent> {
+            let mut buffer = vec![0u8; self.0.len() + 1];
+            buffer[1..].copy_from_slice(&self.0);
+            let data = &buffer[1..];
+            unsafe {
+                AlarmEvent::decode(
+                    command as LONG,
+                    data.as_ptr() as *const c_char,
+                    data.len() as DWORD,
+                )
+            }
+        }
+    }
+
+    const JPEG: &[u8] = &[0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10, 0xff, 0xd9];
+    // 2024-05-06 07:08:09
+    const PACKED_TIME: DWORD = (24 << 26) | (5 << 22) | (6 << 17) | (7 << 12) | (8 << 6) | 9;
+
+    fn rule_alarm_fixture(picture: &[u8]) -> Fixture {
+        let rule = mem::offset_of!(NET_VCA_RULE_ALARM, struRuleInfo);
+        let rect = mem::offset_of!(NET_VCA_RULE_ALARM, struTargetInfo.struRect);
+        let mut fixture = Fixture::new::<NET_VCA_RULE_ALARM>();
+        fixture
+            .put(mem::offset_of!(NET_VCA_RULE_ALARM, dwAbsTime), PACKED_TIME)
+            .put(
+                rule + mem::offset_of!(crate::NET_VCA_RULE_INFO, byRuleID),
+                3u8,
+            )
+            .put(
+                rule + mem::offset_of!(crate::NET_VCA_RULE_INFO, wEventTypeEx),
+                _VCA_RULE_EVENT_TYPE_EX__ENUM_VCA_EVENT_TRAVERSE_PLANE as u16,
+            )
+            // GBK 编码的 "周界"
+            .put_bytes(
+                rule + mem::offset_of!(crate::NET_VCA_RULE_INFO, byRuleName),
+                &[0xd6, 0xdc, 0xbd, 0xe7],
+            )
+            .put(
+                mem::offset_of!(NET_VCA_RULE_ALARM, struTargetInfo.dwID),
+                77u32,
+            )
+            .put(rect + mem::offset_of!(NET_VCA_RECT, fX), 0.25f32)
+            .put(rect + mem::offset_of!(NET_VCA_RECT, fY), 0.5f32)
+            .put(rect + mem::offset_of!(NET_VCA_RECT, fWidth), 0.125f32)
+            .put(rect + mem::offset_of!(NET_VCA_RECT, fHeight), 0.375f32)
+            .put(
+                mem::offset_of!(NET_VCA_RULE_ALARM, struDevInfo.byChannel),
+                2u8,
+            )
+            .put(
+                mem::offset_of!(NET_VCA_RULE_ALARM, dwPicDataLen),
+                picture.len() as DWORD,
+            )
+            .put(
+                mem::offset_of!(NET_VCA_RULE_ALARM, pImage),
+                picture.as_ptr(),
+            );
+        fixture
+    }
+
+    fn decode_rule(fixture: &Fixture) -> VcaRuleAlarm {
+        match fixture.decode(COMM_ALARM_RULE).unwrap() {
+            AlarmEvent::VcaRule(alarm) => alarm,
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rule_alarm_with_picture() {
+        let picture = JPEG.to_vec();
+        let fixture = rule_alarm_fixture(&picture);
+        let alarm = decode_rule(&fixture);
+        // 图片必须已复制出来，不再依赖回调中的指针
+        drop(picture);
+
+        assert_eq!(alarm.event_type, VcaEventType::LineCrossing);
+        assert_eq!(alarm.rule_id, 3);
+        #[cfg(feature = "encoding")]
+        assert_eq!(alarm.rule_name, "周界");
+        assert_eq!(alarm.channel, 2);
+        assert_eq!(alarm.target_id, 77);
+        assert_eq!(
+            alarm.target_rect,
+            NormalizedRect {
+                x: 0.25,
+                y: 0.5,
+                width: 0.125,
+                height: 0.375,
+            }
+        );
+        let time = alarm.time.unwrap();
+        assert_eq!((time.year(), time.month(), time.day()), (2024, 5, 6));
+        assert_eq!((time.hour(), time.minute(), time.second()), (7, 8, 9));
+        assert_eq!(alarm.picture.as_deref(), Some(JPEG));
+    }
+
+    #[test]
+    fn rule_alarm_without_picture() {
+        let mut fixture = rule_alarm_fixture(JPEG);
+        // 以 URL 方式上传的图片不复制
+        fixture.put(mem::offset_of!(NET_VCA_RULE_ALARM, byPicTransType), 1u8);
+        assert_eq!(decode_rule(&fixture).picture, None);
+
+        let mut fixture = rule_alarm_fixture(&[]);
+        fixture.put(
+            mem::offset_of!(NET_VCA_RULE_ALARM, pImage),
+            ptr::null::<u8>(),
+        );
+        assert_eq!(decode_rule(&fixture).picture, None);
+    }
+
+    #[test]
+    fn rule_alarm_legacy_event_type_and_extended_channel() {
+        let rule = mem::offset_of!(NET_VCA_RULE_ALARM, struRuleInfo);
+        let mut fixture = rule_alarm_fixture(&[]);
+        fixture
+            .put(
+                rule + mem::offset_of!(crate::NET_VCA_RULE_INFO, wEventTypeEx),
+                0u16,
+            )
+            .put(
+                rule + mem::offset_of!(crate::NET_VCA_RULE_INFO, dwEventType),
+                _VCA_EVENT_TYPE__VCA_INTRUSION,
+            )
+            .put(
+                mem::offset_of!(NET_VCA_RULE_ALARM, wDevInfoIvmsChannelEx),
+                300u16,
+            );
+        let alarm = decode_rule(&fixture);
+        assert_eq!(alarm.event_type, VcaEventType::Intrusion);
+        assert_eq!(alarm.channel, 300);
+    }
+
+    #[test]
+    fn plate_result_with_pictures() {
+        let scene = JPEG.to_vec();
+        let plate_crop: Vec<u8> = vec![0xff, 0xd8, 0x01, 0xff, 0xd9];
+        let plate = mem::offset_of!(NET_ITS_PLATE_RESULT, struPlateInfo);
+        let picture = |index: usize, field: usize| {
+            mem::offset_of!(NET_ITS_PLATE_RESULT, struPicInfo)
+                + index * mem::size_of::<NET_ITS_PICTURE_INFO>()
+                + field
+        };
+        let mut fixture = Fixture::new::<NET_ITS_PLATE_RESULT>();
+        fixture
+            .put(mem::offset_of!(NET_ITS_PLATE_RESULT, byDriveChan), 2u8)
+            .put(mem::offset_of!(NET_ITS_PLATE_RESULT, byChanIndex), 1u8)
+            // GBK 编码的 "蓝京A12345"
+            .put_bytes(
+                plate + mem::offset_of!(crate::NET_DVR_PLATE_INFO, sLicense),
+                &[0xc0, 0xb6, 0xbe, 0xa9, b'A', b'1', b'2', b'3', b'4', b'5'],
+            )
+            .put(
+                plate + mem::offset_of!(crate::NET_DVR_PLATE_INFO, byEntireBelieve),
+                97u8,
+            )
+            .put(
+                mem::offset_of!(NET_ITS_PLATE_RESULT, struVehicleInfo.byColor),
+                1u8,
+            )
+            .put(mem::offset_of!(NET_ITS_PLATE_RESULT, dwPicNum), 2u32)
+            .put(
+                picture(0, mem::offset_of!(NET_ITS_PICTURE_INFO, dwDataLen)),
+                scene.len() as DWORD,
+            )
+            .put(
+                picture(0, mem::offset_of!(NET_ITS_PICTURE_INFO, pBuffer)),
+                scene.as_ptr(),
+            )
+            .put(
+                picture(1, mem::offset_of!(NET_ITS_PICTURE_INFO, dwDataLen)),
+                plate_crop.len() as DWORD,
+            )
+            .put(
+                picture(1, mem::offset_of!(NET_ITS_PICTURE_INFO, pBuffer)),
+                plate_crop.as_ptr(),
+            );
+
+        let result = match fixture.decode(COMM_ITS_PLATE_RESULT).unwrap() {
+            AlarmEvent::Plate(result) => result,
+            other => panic!("unexpected event: {:?}", other),
+        };
+        drop((scene, plate_crop));
+
+        #[cfg(feature = "encoding")]
+        assert_eq!(result.plate, "蓝京A12345");
+        assert_eq!(result.confidence, 97);
+        assert_eq!(result.lane, 2);
+        assert_eq!(result.channel, 1);
+        assert_eq!(result.vehicle_color, VehicleColor::White);
+        assert_eq!(
+            result.pictures,
+            vec![JPEG.to_vec(), vec![0xff, 0xd8, 0x01, 0xff, 0xd9]]
+        );
+        // 未设置的抓拍时间无效
+        assert_eq!(result.time, None);
+    }
+
+    #[test]
+    fn short_buffer_is_rejected() {
+        let fixture = Fixture(vec![0; mem::size_of::<NET_VCA_RULE_ALARM>() - 1]);
+        let err = fixture.decode(COMM_ALARM_RULE).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("NET_VCA_RULE_ALARM buffer too short")
+        );
+    }
+
+    #[test]
+    fn unknown_command_keeps_raw_bytes() {
+        let fixture = Fixture(vec![1, 2, 3]);
+        match fixture.decode(0x9999).unwrap() {
+            AlarmEvent::Other { command, data } => {
+                assert_eq!(command, 0x9999);
+                assert_eq!(data, [1, 2, 3]);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+}
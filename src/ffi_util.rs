@@ -0,0 +1,469 @@
+use std::{
+    ffi::CString,
+    fmt,
+    os::raw::c_char,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{BYTE, error::HikError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooLong {
+    pub len: usize,
+    // 需要为结尾的 \0 预留一个字节
+    pub max: usize,
+}
+
+impl fmt::Display for TooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "String is too long: {} bytes, max {}",
+            self.len, self.max
+        )
+    }
+}
+
+impl std::error::Error for TooLong {}
+
+// 结构体里的 [c_char; N] 按字节读取，遇到第一个 \0 截止
+pub(crate) fn c_chars_as_bytes(raw: &[c_char]) -> &[u8] {
+    let bytes = unsafe { std::slice::from_raw_parts(raw.as_ptr() as *const u8, raw.len()) };
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    &bytes[..len]
+}
+
+pub fn c_array_to_string(raw: &[c_char]) -> String {
+    decode_device_string(c_chars_as_bytes(raw))
+}
+
+// 超长时返回错误而不是截断，dst 保持不变
+pub fn write_str_to_c_array(dst: &mut [c_char], s: &str) -> Result<(), TooLong> {
+    if s.len() >= dst.len() {
+        return Err(TooLong {
+            len: s.len(),
+            max: dst.len().saturating_sub(1),
+        });
+    }
+    dst.fill(0);
+    for (d, b) in dst.iter_mut().zip(s.as_bytes()) {
+        *d = *b as c_char;
+    }
+    Ok(())
+}
+
+/// 转换为传给 SDK 的字符串，含 \0 时返回 InvalidArgument，field 为参数名
+pub fn to_cstring(s: &str, field: &'static str) -> Result<CString, HikError> {
+    CString::new(s).map_err(|e| HikError::InvalidArgument {
+        field,
+        reason: format!(
+            "contains an interior NUL byte at position {}",
+            e.nul_position()
+        ),
+    })
+}
+
+/// 文件路径转换为 C 字符串，拒绝空路径和非 UTF-8 路径（避免有损转换后写到别的文件）
+pub fn path_to_cstring(path: &Path, field: &'static str) -> Result<CString, HikError> {
+    let invalid = |reason: &str| HikError::InvalidArgument {
+        field,
+        reason: reason.to_string(),
+    };
+    if path.as_os_str().is_empty() {
+        return Err(invalid("path is empty"));
+    }
+    let path = path
+        .to_str()
+        .ok_or_else(|| invalid("path is not valid UTF-8"))?;
+    to_cstring(path, field)
+}
+
+/// 在 SDK 回调线程中执行用户回调
+///
+/// panic 不能越过 extern "C" 边界展开（会直接终止进程），这里捕获后丢弃，之后的数据照常送达
+pub(crate) fn call_user_callback(callback: &'static str, f: impl FnOnce()) {
+    if panic::catch_unwind(AssertUnwindSafe(f)).is_err() {
+        #[cfg(feature = "tracing")]
+        tracing::error!(callback, "User callback panicked");
+        #[cfg(not(feature = "tracing"))]
+        let _ = callback;
+    }
+}
+
+// 回调中加锁时不因之前的 panic 而失败，锁内的数据在 panic 后仍然可用
+pub(crate) fn lock_unpoisoned<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// 空指针返回 None
+///
+/// # Safety
+///
+/// 非空时 ptr 必须指向以 \0 结尾的字符串
+pub unsafe fn c_ptr_to_string(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    Some(
+        unsafe { std::ffi::CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+/// 解码设备返回的字符串，遇到第一个 \0 截止
+///
+/// 新固件多为 UTF-8，老固件的通道名、用户名等为 GBK/GB2312：先按 UTF-8 解码，
+/// 失败时按 GBK 解码（未启用 encoding feature 时按 UTF-8 替换非法字节）。
+/// 部分 GBK 字节恰好也是合法 UTF-8（如 "通1" 为 cd a8 31，按 UTF-8 是 "ͨ1"），
+/// 只含双字节 UTF-8 字符且不像拉丁/希腊/西里尔文字时仍按 GBK 解码。
+/// 定长数组末尾被截断的半个多字节字符直接丢弃
+pub fn decode_device_string(raw: &[u8]) -> String {
+    let len = raw.iter().position(|&b| b == 0).unwrap_or(raw.len());
+    let raw = &raw[..len];
+    let utf8_error = match std::str::from_utf8(raw) {
+        // 纯 ASCII 也走这里
+        Ok(text) if looks_like_utf8(text) => return text.to_string(),
+        Ok(_) => None,
+        Err(e) => Some(e),
+    };
+    // 只有末尾不完整且前面确实有多字节字符时才认为是被截断的 UTF-8
+    if let Some(e) = utf8_error {
+        let valid = &raw[..e.valid_up_to()];
+        if e.error_len().is_none() && !valid.is_ascii() {
+            let text = String::from_utf8_lossy(valid);
+            if looks_like_utf8(&text) {
+                return text.into_owned();
+            }
+        }
+    }
+    #[cfg(feature = "encoding")]
+    {
+        decode_gbk(raw)
+    }
+    #[cfg(not(feature = "encoding"))]
+    {
+        String::from_utf8_lossy(raw).into_owned()
+    }
+}
+
+// GBK 双字节字符按 UTF-8 解码只会落在 U+0080–U+07FF，其中大多是组合符号等不会出现在名称里的字符；
+// 含三字节及以上字符时 GBK 几乎不可能恰好是合法 UTF-8
+fn looks_like_utf8(text: &str) -> bool {
+    if !cfg!(feature = "encoding") || text.chars().any(|c| c >= '\u{800}') {
+        return true;
+    }
+    text.chars().all(|c| {
+        matches!(c,
+            '\0'..='\u{7f}'
+            // Latin-1 字母（不含 × ÷）、Latin Extended-A/B
+            | '\u{c0}'..='\u{d6}' | '\u{d8}'..='\u{f6}' | '\u{f8}'..='\u{24f}'
+            // 希腊字母、西里尔字母
+            | '\u{386}'..='\u{3ce}' | '\u{400}'..='\u{4ff}')
+    })
+}
+
+#[cfg(feature = "encoding")]
+fn decode_gbk(raw: &[u8]) -> String {
+    let mut decoder = encoding_rs::GBK.new_decoder_without_bom_handling();
+    let capacity = decoder
+        .max_utf8_buffer_length(raw.len())
+        .unwrap_or(raw.len() * 3);
+    let mut text = String::with_capacity(capacity);
+    // last = false 时末尾的半个字符留在解码器中，不输出替换字符
+    let _ = decoder.decode_to_string(raw, &mut text, false);
+    text
+}
+
+/// 编码写回设备的字符串（通道名、用户名、OSD 等），max_len 不含结尾的 \0
+///
+/// 启用 encoding feature 时编码为 GBK，否则按 UTF-8 原样写入
+pub fn encode_device_string(src: &str, max_len: usize) -> anyhow::Result<Vec<u8>> {
+    #[cfg(feature = "encoding")]
+    let encoded = {
+        let (encoded, _, had_errors) = encoding_rs::GBK.encode(src);
+        if had_errors {
+            return Err(anyhow::anyhow!(
+                "String contains characters that cannot be encoded as GBK"
+            ));
+        }
+        encoded.into_owned()
+    };
+    #[cfg(not(feature = "encoding"))]
+    let encoded = src.as_bytes().to_vec();
+
+    if encoded.len() > max_len {
+        return Err(anyhow::anyhow!(
+            "String is too long: {} bytes, max {}",
+            encoded.len(),
+            max_len
+        ));
+    }
+    if encoded.contains(&0) {
+        return Err(anyhow::anyhow!("String contains an interior NUL byte"));
+    }
+    Ok(encoded)
+}
+
+pub(crate) fn copy_to_gbk_array(
+    dst: &mut [BYTE],
+    src: &str,
+    field: &'static str,
+) -> anyhow::Result<()> {
+    let encoded = encode_device_string(src, dst.len().saturating_sub(1)).map_err(|e| {
+        HikError::InvalidArgument {
+            field,
+            reason: e.to_string(),
+        }
+    })?;
+    dst.fill(0);
+    dst[..encoded.len()].copy_from_slice(&encoded);
+    Ok(())
+}
+
+// 以下写入函数都会保留结尾的 \0，超长时返回错误而不是截断
+
+fn check_fixed_str(src: &str, capacity: usize, field: &'static str) -> Result<(), HikError> {
+    if src.as_bytes().contains(&0) {
+        return Err(HikError::InvalidArgument {
+            field,
+            reason: "contains an interior NUL byte".to_string(),
+        });
+    }
+    if src.len() >= capacity {
+        return Err(HikError::InvalidArgument {
+            field,
+            reason: format!(
+                "too long: {} bytes, max {}",
+                src.len(),
+                capacity.saturating_sub(1)
+            ),
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn copy_to_c_array(
+    dst: &mut [c_char],
+    src: &str,
+    field: &'static str,
+) -> anyhow::Result<()> {
+    check_fixed_str(src, dst.len(), field)?;
+    write_str_to_c_array(dst, src)?;
+    Ok(())
+}
+
+pub(crate) fn copy_to_byte_array(
+    dst: &mut [BYTE],
+    src: &str,
+    field: &'static str,
+) -> anyhow::Result<()> {
+    check_fixed_str(src, dst.len(), field)?;
+    dst.fill(0);
+    dst[..src.len()].copy_from_slice(src.as_bytes());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_argument(result: anyhow::Result<()>) -> (&'static str, String) {
+        match result.unwrap_err().downcast::<HikError>() {
+            Ok(HikError::InvalidArgument { field, reason }) => (field, reason),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn byte_array_round_trip() {
+        let mut dst = [0xffu8; 16];
+        copy_to_byte_array(&mut dst, "mail.example", "server").unwrap();
+        assert_eq!(&dst[..12], b"mail.example");
+        assert!(dst[12..].iter().all(|&b| b == 0));
+        assert_eq!(decode_device_string(&dst), "mail.example");
+
+        // 覆盖写入时清除旧内容
+        copy_to_byte_array(&mut dst, "a", "server").unwrap();
+        assert_eq!(decode_device_string(&dst), "a");
+    }
+
+    #[test]
+    fn byte_array_too_long_leaves_dst_unchanged() {
+        let mut dst = *b"old\0\0\0\0\0";
+        let (field, reason) = invalid_argument(copy_to_byte_array(&mut dst, "12345678", "server"));
+        assert_eq!(field, "server");
+        assert_eq!(reason, "too long: 8 bytes, max 7");
+        assert_eq!(&dst, b"old\0\0\0\0\0");
+    }
+
+    #[test]
+    fn device_string_stops_at_first_nul() {
+        assert_eq!(decode_device_string(b"abc\0def"), "abc");
+        assert_eq!(decode_device_string(b"\0abc"), "");
+        // 没有 \0 时使用整个数组
+        assert_eq!(decode_device_string(b"abcd"), "abcd");
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn gbk_array_round_trip() {
+        let mut dst = [0u8; 32];
+        copy_to_gbk_array(&mut dst, "通道1", "name").unwrap();
+        assert_eq!(&dst[..6], &[0xcd, 0xa8, 0xb5, 0xc0, 0x31, 0]);
+        assert_eq!(decode_device_string(&dst), "通道1");
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn gbk_array_limits_are_in_encoded_bytes() {
+        // GBK 编码后 5 字节，加 \0 需要 6 字节
+        let mut dst = [0u8; 5];
+        let (field, reason) = invalid_argument(copy_to_gbk_array(&mut dst, "通道1", "name"));
+        assert_eq!(field, "name");
+        assert_eq!(reason, "String is too long: 5 bytes, max 4");
+        assert_eq!(dst, [0; 5]);
+
+        let mut dst = [0u8; 32];
+        let (_, reason) = invalid_argument(copy_to_gbk_array(&mut dst, "😀", "name"));
+        assert_eq!(
+            reason,
+            "String contains characters that cannot be encoded as GBK"
+        );
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn gbk_bytes_that_are_also_valid_utf8() {
+        assert_eq!(
+            decode_device_string(&[0xcd, 0xa8, 0xb5, 0xc0, 0x31]),
+            "通道1"
+        );
+        // cd a8 31 同时是合法 UTF-8 "ͨ1"
+        assert_eq!(decode_device_string(&[0xcd, 0xa8, 0x31, 0]), "通1");
+        assert_eq!(decode_device_string(&[0xcd, 0xa8]), "通");
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn truncated_gbk_drops_trailing_lead_byte() {
+        // 定长数组在 "通道" 后只放下了 "通" 的第一个字节
+        assert_eq!(
+            decode_device_string(&[0xcd, 0xa8, 0xb5, 0xc0, 0xcd]),
+            "通道"
+        );
+        // cd a8 cd 按 UTF-8 是 "ͨ" 加半个字符
+        assert_eq!(decode_device_string(&[0xcd, 0xa8, 0xcd]), "通");
+    }
+
+    #[test]
+    fn utf8_names_are_kept() {
+        assert_eq!(decode_device_string("通道1".as_bytes()), "通道1");
+        assert_eq!(decode_device_string("Entrée".as_bytes()), "Entrée");
+        assert_eq!(decode_device_string("Камера 2".as_bytes()), "Камера 2");
+        assert_eq!(decode_device_string("Κάμερα".as_bytes()), "Κάμερα");
+        // 截断在多字节字符中间
+        assert_eq!(decode_device_string(&"通道".as_bytes()[..5]), "通");
+    }
+
+    #[cfg(not(feature = "encoding"))]
+    #[test]
+    fn gbk_array_without_feature_writes_utf8() {
+        let mut dst = [0u8; 32];
+        copy_to_gbk_array(&mut dst, "通道1", "name").unwrap();
+        assert_eq!(&dst[..7], "通道1".as_bytes());
+        assert_eq!(decode_device_string(&dst), "通道1");
+    }
+
+    fn c_chars(bytes: &[u8]) -> Vec<c_char> {
+        bytes.iter().map(|&b| b as c_char).collect()
+    }
+
+    #[test]
+    fn c_array_with_invalid_bytes_does_not_panic() {
+        // 0xff 既不是 UTF-8 也不是 GBK 的合法字节
+        assert_eq!(c_array_to_string(&c_chars(b"ok\xff\0")), "ok\u{fffd}");
+        // 数组中没有 \0
+        assert_eq!(c_array_to_string(&c_chars(b"abcd")), "abcd");
+        assert_eq!(c_array_to_string(&[]), "");
+    }
+
+    #[test]
+    fn write_c_array_exact_length() {
+        let mut dst = [1 as c_char; 8];
+        // 7 个字节加结尾的 \0 正好放下
+        write_str_to_c_array(&mut dst, "1234567").unwrap();
+        assert_eq!(dst, *c_chars(b"1234567\0"));
+        assert_eq!(c_array_to_string(&dst), "1234567");
+
+        let err = write_str_to_c_array(&mut dst, "12345678").unwrap_err();
+        assert_eq!(err, TooLong { len: 8, max: 7 });
+        assert_eq!(err.to_string(), "String is too long: 8 bytes, max 7");
+        assert_eq!(c_array_to_string(&dst), "1234567");
+
+        write_str_to_c_array(&mut dst, "").unwrap();
+        assert_eq!(dst, [0; 8]);
+        assert_eq!(
+            write_str_to_c_array(&mut [], ""),
+            Err(TooLong { len: 0, max: 0 })
+        );
+    }
+
+    #[test]
+    fn c_array_rejects_interior_nul() {
+        let mut dst = [0 as c_char; 8];
+        let (field, reason) = invalid_argument(copy_to_c_array(&mut dst, "ab\0c", "username"));
+        assert_eq!(field, "username");
+        assert_eq!(reason, "contains an interior NUL byte");
+    }
+
+    #[test]
+    fn cstring_rejects_interior_nul() {
+        assert_eq!(to_cstring("abc", "file").unwrap().as_bytes(), b"abc");
+        assert_eq!(
+            to_cstring("ab\0c", "file"),
+            Err(HikError::InvalidArgument {
+                field: "file",
+                reason: "contains an interior NUL byte at position 2".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn path_checks() {
+        assert_eq!(
+            path_to_cstring(Path::new(""), "dir"),
+            Err(HikError::InvalidArgument {
+                field: "dir",
+                reason: "path is empty".to_string(),
+            })
+        );
+        assert!(matches!(
+            path_to_cstring(Path::new("/tmp/a\0b"), "dir"),
+            Err(HikError::InvalidArgument { field: "dir", .. })
+        ));
+        assert_eq!(
+            path_to_cstring(Path::new("/tmp/a.jpg"), "file")
+                .unwrap()
+                .as_bytes(),
+            b"/tmp/a.jpg"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn path_rejects_non_utf8() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let path = Path::new(OsStr::from_bytes(b"/tmp/\xff.jpg"));
+        assert_eq!(
+            path_to_cstring(path, "file"),
+            Err(HikError::InvalidArgument {
+                field: "file",
+                reason: "path is not valid UTF-8".to_string(),
+            })
+        );
+    }
+}
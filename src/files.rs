@@ -0,0 +1,241 @@
+use std::{os::raw::c_char, thread, time::Duration};
+
+use chrono::{DateTime, Local};
+
+use crate::{
+    DWORD, LONG, NET_DVR_FILE_EXCEPTION, NET_DVR_FILE_NOFIND, NET_DVR_FILE_SUCCESS,
+    NET_DVR_FILECOND_V40, NET_DVR_FINDDATA_V40, NET_DVR_FindClose_V30, NET_DVR_FindFile_V40,
+    NET_DVR_FindNextFile_V40, NET_DVR_ISFINDING, NET_DVR_LockFileByName, NET_DVR_NOMOREFILE,
+    NET_DVR_NOSUPPORT, NET_DVR_STATUS_RECORDFILE_WRITING_NOT_LOCK, NET_DVR_UnlockFileByName,
+    device::{HikDevice, sdk_code, sdk_error},
+    error::HikError,
+    ffi_util::c_array_to_string,
+    timezone::TimeMode,
+    trace::sdk_call,
+};
+
+// dwFileType、dwIsLocked 为 0xff 时不过滤
+const FIND_ALL: DWORD = 0xff;
+// 设备仍在检索时的重试间隔与次数，合计约 10 秒
+const FIND_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+const FIND_RETRY_LIMIT: u32 = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecordFile {
+    // 仅用于显示
+    pub name: String,
+    // 设备返回的原始文件名（不含结尾的 \0），加锁、解锁时原样传回设备
+    pub raw_name: Vec<u8>,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    // 文件大小，单位字节
+    pub size: u32,
+    pub locked: bool,
+    // 录像类型，与 NET_DVR_FILECOND_V40::dwFileType 的取值一致
+    pub file_type: u8,
+}
+
+impl TryFrom<&NET_DVR_FINDDATA_V40> for RecordFile {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: &NET_DVR_FINDDATA_V40) -> anyhow::Result<Self> {
+        let len = raw
+            .sFileName
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(raw.sFileName.len());
+        Ok(Self {
+            name: c_array_to_string(&raw.sFileName),
+            raw_name: raw.sFileName[..len].iter().map(|&c| c as u8).collect(),
+            start: DateTime::try_from(raw.struStartTime)?,
+            end: DateTime::try_from(raw.struStopTime)?,
+            size: raw.dwFileSize,
+            locked: raw.byLocked == 1,
+            file_type: raw.byFileType,
+        })
+    }
+}
+
+impl RecordFile {
+    fn c_name(&self) -> anyhow::Result<Vec<c_char>> {
+        if self.raw_name.contains(&0) {
+            return Err(anyhow::anyhow!("File name contains an interior NUL byte"));
+        }
+        Ok(self
+            .raw_name
+            .iter()
+            .map(|&b| b as c_char)
+            .chain(std::iter::once(0))
+            .collect())
+    }
+}
+
+pub struct RecordFileIter {
+    handle: LONG,
+    finished: bool,
+    buffer: NET_DVR_FINDDATA_V40,
+}
+
+impl RecordFileIter {
+    fn next_raw(&mut self) -> anyhow::Result<bool> {
+        let mut retries = 0;
+        loop {
+            let status =
+                unsafe { sdk_call!(NET_DVR_FindNextFile_V40(self.handle, &mut self.buffer)) };
+            if status < 0 {
+                return Err(sdk_error("Find next file"));
+            }
+            match status as DWORD {
+                NET_DVR_FILE_SUCCESS => return Ok(true),
+                NET_DVR_FILE_NOFIND | NET_DVR_NOMOREFILE => return Ok(false),
+                NET_DVR_ISFINDING => {
+                    retries += 1;
+                    if retries > FIND_RETRY_LIMIT {
+                        return Err(anyhow::anyhow!("Find next file timed out"));
+                    }
+                    thread::sleep(FIND_RETRY_INTERVAL);
+                }
+                NET_DVR_FILE_EXCEPTION => {
+                    return Err(anyhow::anyhow!("Find next file failed: device exception"));
+                }
+                other => {
+                    return Err(anyhow::anyhow!(
+                        "Find next file failed: unexpected status {}",
+                        other
+                    ));
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for RecordFileIter {
+    type Item = anyhow::Result<RecordFile>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.next_raw() {
+            Ok(true) => Some(RecordFile::try_from(&self.buffer)),
+            Ok(false) => {
+                self.finished = true;
+                None
+            }
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl Drop for RecordFileIter {
+    fn drop(&mut self) {
+        unsafe { sdk_call!(NET_DVR_FindClose_V30(self.handle)) };
+    }
+}
+
+// 录像正在写入或所在硬盘不支持加锁
+fn lock_error(action: &'static str) -> anyhow::Error {
+    let error = sdk_error(action);
+    match sdk_code(&error).map(|code| code as u32) {
+        Some(code @ (NET_DVR_STATUS_RECORDFILE_WRITING_NOT_LOCK | NET_DVR_NOSUPPORT)) => {
+            HikError::FileNotLockable { code: code as i32 }.into()
+        }
+        _ => error,
+    }
+}
+
+impl HikDevice {
+    /// 查找通道在时间段内的全部录像文件
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn find_files(
+        &self,
+        channel: u16,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> anyhow::Result<RecordFileIter> {
+        self.find_files_with(channel, start, end, TimeMode::DeviceLocal)
+    }
+
+    // time_mode 为 Utc 时按设备时区换算查询范围，返回的文件时间仍为设备本地时间
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn find_files_with(
+        &self,
+        channel: u16,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+        time_mode: TimeMode,
+    ) -> anyhow::Result<RecordFileIter> {
+        let channel = self.resolve_channel(channel)?;
+        let [start, end] = self.device_time_range(start, end, time_mode)?;
+        let mut cond = NET_DVR_FILECOND_V40 {
+            lChannel: channel,
+            dwFileType: FIND_ALL,
+            dwIsLocked: FIND_ALL,
+            struStartTime: start,
+            struStopTime: end,
+            ..Default::default()
+        };
+        let handle = self.with_session(|lu| {
+            let handle = unsafe { sdk_call!(NET_DVR_FindFile_V40(lu, &mut cond)) };
+            if handle < 0 {
+                return Err(sdk_error("Find files"));
+            }
+            Ok(handle)
+        })?;
+
+        Ok(RecordFileIter {
+            handle,
+            finished: false,
+            buffer: NET_DVR_FINDDATA_V40::default(),
+        })
+    }
+
+    // 加锁后录像不会被循环覆盖
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn lock_file(&self, file: &RecordFile) -> anyhow::Result<()> {
+        let mut name = file.c_name()?;
+        self.with_session(|lu| {
+            let res = unsafe { sdk_call!(NET_DVR_LockFileByName(lu, name.as_mut_ptr())) };
+            if res != 1 {
+                return Err(lock_error("Lock file"));
+            }
+            Ok(())
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn unlock_file(&self, file: &RecordFile) -> anyhow::Result<()> {
+        let mut name = file.c_name()?;
+        self.with_session(|lu| {
+            let res = unsafe { sdk_call!(NET_DVR_UnlockFileByName(lu, name.as_mut_ptr())) };
+            if res != 1 {
+                return Err(lock_error("Unlock file"));
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,461 @@
+use std::mem;
+
+use crate::{
+    BYTE, DWORD, FISHEYE_ABILITY, LONG, NET_DVR_GET_PREVIEW_DISPLAYCFG, NET_DVR_NOSUPPORT,
+    NET_DVR_NOT_SUPPORT, NET_DVR_PREVIEW_DISPLAYCFG, NET_DVR_REMOTECONTROL_PTZ,
+    NET_DVR_REMOTECONTROL_PTZ_PARAM, NET_DVR_RemoteControl, NET_DVR_SET_PREVIEW_DISPLAYCFG,
+    NET_VCA_POINT, PAN_LEFT, PAN_RIGHT, TILT_DOWN, TILT_UP, ZOOM_IN, ZOOM_OUT,
+    ability::{AbilityType, xml_tag_value},
+    device::{HikDevice, sdk_code, sdk_error},
+    error::HikError,
+    isapi::IsapiMethod,
+    trace::sdk_call,
+};
+
+// 云台速度范围，与 NET_DVR_PTZControlWithSpeed 一致
+const MAX_PTZ_SPEED: u8 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FisheyeMount {
+    // 吸顶
+    #[default]
+    Ceiling,
+    // 桌面
+    Desktop,
+    // 壁装
+    Wall,
+}
+
+impl From<FisheyeMount> for BYTE {
+    fn from(value: FisheyeMount) -> Self {
+        match value {
+            FisheyeMount::Ceiling => 1,
+            FisheyeMount::Desktop => 2,
+            FisheyeMount::Wall => 3,
+        }
+    }
+}
+
+impl TryFrom<BYTE> for FisheyeMount {
+    type Error = anyhow::Error;
+
+    fn try_from(value: BYTE) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(FisheyeMount::Ceiling),
+            2 => Ok(FisheyeMount::Desktop),
+            3 => Ok(FisheyeMount::Wall),
+            other => Err(anyhow::anyhow!("Unknown fisheye mount type: {}", other)),
+        }
+    }
+}
+
+/// 鱼眼图像的校正位置
+///
+/// 软件校正时设备只输出原始鱼眼画面，由客户端展开；
+/// 芯片校正时设备按安装方式展开画面，并提供可以单独控制的 PTZ 窗口
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FisheyeCorrection {
+    #[default]
+    Software,
+    Chip,
+}
+
+impl From<FisheyeCorrection> for BYTE {
+    fn from(value: FisheyeCorrection) -> Self {
+        match value {
+            FisheyeCorrection::Software => 1,
+            FisheyeCorrection::Chip => 2,
+        }
+    }
+}
+
+impl TryFrom<BYTE> for FisheyeCorrection {
+    type Error = anyhow::Error;
+
+    fn try_from(value: BYTE) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(FisheyeCorrection::Software),
+            2 => Ok(FisheyeCorrection::Chip),
+            other => Err(anyhow::anyhow!(
+                "Unknown fisheye correction mode: {}",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FisheyeConfig {
+    pub mount: FisheyeMount,
+    pub correction: FisheyeCorrection,
+    // 芯片校正时实时输出展开后的画面
+    pub real_time_output: bool,
+}
+
+impl TryFrom<&NET_DVR_PREVIEW_DISPLAYCFG> for FisheyeConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &NET_DVR_PREVIEW_DISPLAYCFG) -> Result<Self, Self::Error> {
+        Ok(Self {
+            mount: value.byMountType.try_into()?,
+            correction: value.byCorrectMode.try_into()?,
+            real_time_output: value.byRealTimeOutput == 1,
+        })
+    }
+}
+
+/// 芯片校正时设备输出画面的展开布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FisheyeLayout {
+    // 原始鱼眼画面
+    #[default]
+    Fisheye,
+    // 180 度全景
+    Panorama180,
+    // 360 度全景
+    Panorama360,
+    // 鱼眼画面加 PTZ 窗口
+    FisheyePtz,
+    // 四个 PTZ 窗口
+    QuadPtz,
+}
+
+impl FisheyeLayout {
+    fn isapi_name(self) -> &'static str {
+        match self {
+            FisheyeLayout::Fisheye => "fisheye",
+            FisheyeLayout::Panorama180 => "panorama180",
+            FisheyeLayout::Panorama360 => "panorama360",
+            FisheyeLayout::FisheyePtz => "fisheyePTZ",
+            FisheyeLayout::QuadPtz => "quadPTZ",
+        }
+    }
+
+    fn from_isapi_name(name: &str) -> Option<Self> {
+        [
+            FisheyeLayout::Fisheye,
+            FisheyeLayout::Panorama180,
+            FisheyeLayout::Panorama360,
+            FisheyeLayout::FisheyePtz,
+            FisheyeLayout::QuadPtz,
+        ]
+        .into_iter()
+        .find(|layout| layout.isapi_name().eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FisheyePtzCommand {
+    Up,
+    Down,
+    Left,
+    Right,
+    ZoomIn,
+    ZoomOut,
+}
+
+impl From<FisheyePtzCommand> for DWORD {
+    fn from(value: FisheyePtzCommand) -> Self {
+        match value {
+            FisheyePtzCommand::Up => TILT_UP,
+            FisheyePtzCommand::Down => TILT_DOWN,
+            FisheyePtzCommand::Left => PAN_LEFT,
+            FisheyePtzCommand::Right => PAN_RIGHT,
+            FisheyePtzCommand::ZoomIn => ZOOM_IN,
+            FisheyePtzCommand::ZoomOut => ZOOM_OUT,
+        }
+    }
+}
+
+/// 要控制的 PTZ 窗口，用窗口中心在鱼眼原始画面上的归一化坐标（0.0-1.0）表示
+///
+/// SDK 按坐标而不是窗口序号选择窗口，可以先在原始画面上点选
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FisheyeWindow {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl FisheyeWindow {
+    fn validate(&self) -> anyhow::Result<()> {
+        if !(0.0..=1.0).contains(&self.x) || !(0.0..=1.0).contains(&self.y) {
+            return Err(anyhow::anyhow!(
+                "Fisheye window position ({}, {}) out of range: coordinates are normalized to 0.0-1.0",
+                self.x,
+                self.y
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl HikDevice {
+    // 查询失败（包括设备不支持该能力集）时返回 false
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn supports_fisheye(&self, channel: u16) -> bool {
+        let in_xml = format!(
+            "<FishEyeIPCAbility version=\"2.0\"><channelNO>{}</channelNO></FishEyeIPCAbility>",
+            channel
+        );
+        self.get_device_ability(AbilityType::Other(FISHEYE_ABILITY), Some(&in_xml))
+            .is_ok()
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn get_fisheye_params(&self, channel: u16) -> anyhow::Result<FisheyeConfig> {
+        let sdk_channel = self.resolve_channel(channel)?;
+        let config: NET_DVR_PREVIEW_DISPLAYCFG = self
+            .get_std_config(
+                NET_DVR_GET_PREVIEW_DISPLAYCFG,
+                sdk_channel,
+                "Get fisheye params",
+            )
+            .map_err(|e| self.fisheye_error(channel, e))?;
+        FisheyeConfig::try_from(&config)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn set_fisheye_params(&self, channel: u16, config: FisheyeConfig) -> anyhow::Result<()> {
+        let sdk_channel = self.resolve_channel(channel)?;
+        let cfg = NET_DVR_PREVIEW_DISPLAYCFG {
+            dwSize: mem::size_of::<NET_DVR_PREVIEW_DISPLAYCFG>() as DWORD,
+            byCorrectMode: config.correction.into(),
+            byMountType: config.mount.into(),
+            byRealTimeOutput: config.real_time_output as BYTE,
+            ..Default::default()
+        };
+        self.set_std_config(
+            NET_DVR_SET_PREVIEW_DISPLAYCFG,
+            sdk_channel,
+            &cfg,
+            "Set fisheye params",
+        )
+        .map_err(|e| self.fisheye_error(channel, e))
+    }
+
+    /// 控制芯片校正展开后的虚拟 PTZ 窗口，speed 为 1-7，0 表示停止该命令
+    ///
+    /// 软件校正时设备没有 PTZ 窗口，调用前会先读取校正模式并直接返回错误
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn fisheye_ptz_control(
+        &self,
+        channel: u16,
+        window: FisheyeWindow,
+        command: FisheyePtzCommand,
+        speed: u8,
+    ) -> anyhow::Result<()> {
+        window.validate()?;
+        if speed > MAX_PTZ_SPEED {
+            return Err(anyhow::anyhow!(
+                "Fisheye PTZ speed {} out of range: expected 1-{}, or 0 to stop",
+                speed,
+                MAX_PTZ_SPEED
+            ));
+        }
+        let config = self.get_fisheye_params(channel)?;
+        if config.correction != FisheyeCorrection::Chip {
+            return Err(anyhow::anyhow!(
+                "Fisheye PTZ windows require chip correction, channel {} uses software correction; \
+                 switch with set_fisheye_params or dewarp on the client",
+                channel
+            ));
+        }
+
+        let sdk_channel: LONG = self.resolve_channel(channel)?;
+        let mut param = NET_DVR_REMOTECONTROL_PTZ_PARAM {
+            dwSize: mem::size_of::<NET_DVR_REMOTECONTROL_PTZ_PARAM>() as DWORD,
+            dwChannel: sdk_channel as DWORD,
+            dwPTZCommand: command.into(),
+            struVcaPoint: NET_VCA_POINT {
+                fX: window.x,
+                fY: window.y,
+            },
+            dwSpeed: speed.max(1) as DWORD,
+            // dwStop：0 开始，1 停止
+            dwStop: (speed == 0) as DWORD,
+            ..Default::default()
+        };
+        self.with_session(|lu| {
+            let res = unsafe {
+                sdk_call!(NET_DVR_RemoteControl(
+                    lu,
+                    NET_DVR_REMOTECONTROL_PTZ,
+                    &mut param as *mut _ as *mut std::ffi::c_void,
+                    mem::size_of::<NET_DVR_REMOTECONTROL_PTZ_PARAM>() as DWORD,
+                ))
+            };
+            if res != 1 {
+                return Err(sdk_error("Fisheye PTZ control"));
+            }
+            Ok(())
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn get_fisheye_layout(&self, channel: u16) -> anyhow::Result<FisheyeLayout> {
+        let url = fisheye_layout_url(self.rtsp_channel_id(channel)?);
+        let xml = self.isapi_get_string(&url)?;
+        let name = xml_tag_value(&xml, "displayMode")
+            .ok_or(anyhow::anyhow!("Fisheye layout not found in response"))?
+            .trim();
+        FisheyeLayout::from_isapi_name(name)
+            .ok_or(anyhow::anyhow!("Unknown fisheye layout: {}", name))
+    }
+
+    /// 选择芯片校正后的展开布局，软件校正时由客户端展开，直接返回错误
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn set_fisheye_layout(&self, channel: u16, layout: FisheyeLayout) -> anyhow::Result<()> {
+        if self.get_fisheye_params(channel)?.correction != FisheyeCorrection::Chip {
+            return Err(HikError::InvalidArgument {
+                field: "layout",
+                reason: format!(
+                    "channel {} uses software correction; switch to chip correction with set_fisheye_params first",
+                    channel
+                ),
+            }
+            .into());
+        }
+        let url = fisheye_layout_url(self.rtsp_channel_id(channel)?);
+        let body = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <FisheyeDisplayMode version=\"2.0\" xmlns=\"http://www.isapi.org/ver20/XMLSchema\">\
+             <displayMode>{}</displayMode>\
+             </FisheyeDisplayMode>",
+            layout.isapi_name()
+        );
+        let response = self.isapi_request(IsapiMethod::Put, &url, Some(body.as_bytes()))?;
+        if !response.success {
+            if response
+                .status_str()
+                .is_some_and(|status| status.contains("notSupport"))
+            {
+                return Err(HikError::Unsupported {
+                    feature: "Fisheye layout",
+                }
+                .into());
+            }
+            return Err(anyhow::anyhow!(
+                "ISAPI PUT {} failed: error code {}, status: {}",
+                url,
+                response.error_code,
+                response.status_str().unwrap_or_default()
+            ));
+        }
+        Ok(())
+    }
+
+    // 只在设备返回不支持时查询能力集，区分非鱼眼通道与其他错误
+    fn fisheye_error(&self, channel: u16, error: anyhow::Error) -> anyhow::Error {
+        match sdk_code(&error) {
+            Some(code)
+                if (code == NET_DVR_NOSUPPORT as i32 || code == NET_DVR_NOT_SUPPORT as i32)
+                    && !self.supports_fisheye(channel) =>
+            {
+                HikError::Unsupported {
+                    feature: "Fisheye correction",
+                }
+                .into()
+            }
+            _ => error,
+        }
+    }
+}
+
+fn fisheye_layout_url(channel_id: u16) -> String {
+    format!("/ISAPI/Image/channels/{}/fisheye/displayMode", channel_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::sdk::{MockSdk, device_info, logged_in_device};
+
+    #[test]
+    fn layout_names_round_trip() {
+        for layout in [
+            FisheyeLayout::Fisheye,
+            FisheyeLayout::Panorama180,
+            FisheyeLayout::Panorama360,
+            FisheyeLayout::FisheyePtz,
+            FisheyeLayout::QuadPtz,
+        ] {
+            assert_eq!(
+                FisheyeLayout::from_isapi_name(layout.isapi_name()),
+                Some(layout)
+            );
+        }
+        assert_eq!(FisheyeLayout::from_isapi_name("unknown"), None);
+    }
+
+    #[test]
+    fn layout_requires_chip_correction() {
+        let mock = Arc::new(MockSdk::new());
+        let device = logged_in_device(&mock, device_info(1, 1, 0, 0));
+        let software = NET_DVR_PREVIEW_DISPLAYCFG {
+            byCorrectMode: FisheyeCorrection::Software.into(),
+            byMountType: FisheyeMount::Ceiling.into(),
+            ..Default::default()
+        };
+        mock.set_config(NET_DVR_GET_PREVIEW_DISPLAYCFG, 1, &software);
+        let err = device
+            .set_fisheye_layout(1, FisheyeLayout::QuadPtz)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(HikError::InvalidArgument {
+                field: "layout",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,182 @@
+use std::{borrow::Cow, mem, os::raw::c_void};
+
+use crate::{
+    DWORD, NET_DVR_NOENOUGH_BUF, NET_DVR_STDXMLConfig, NET_DVR_XML_CONFIG_INPUT,
+    NET_DVR_XML_CONFIG_OUTPUT, common::get_last_error_code, device::HikDevice, error::HikError,
+    trace::sdk_call,
+};
+
+// 初始输出缓冲区大小，不足时按倍数扩大
+const INITIAL_OUT_BUFFER_SIZE: usize = 64 * 1024;
+const MAX_OUT_BUFFER_SIZE: usize = 16 * 1024 * 1024;
+const STATUS_BUFFER_SIZE: usize = 4 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsapiMethod {
+    Get,
+    Put,
+    Post,
+    Delete,
+}
+
+impl IsapiMethod {
+    fn as_str(self) -> &'static str {
+        match self {
+            IsapiMethod::Get => "GET",
+            IsapiMethod::Put => "PUT",
+            IsapiMethod::Post => "POST",
+            IsapiMethod::Delete => "DELETE",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IsapiResponse {
+    pub success: bool,
+    // 失败时的 SDK 错误码，成功时为 0
+    pub error_code: i32,
+    // 设备返回的 XML/JSON 数据
+    pub body: Vec<u8>,
+    // 失败时设备返回的 ResponseStatus
+    pub status: Option<Vec<u8>>,
+}
+
+impl IsapiResponse {
+    pub fn body_str(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.body)
+    }
+
+    pub fn status_str(&self) -> Option<Cow<'_, str>> {
+        self.status.as_deref().map(String::from_utf8_lossy)
+    }
+}
+
+impl HikDevice {
+    // url 为 ISAPI 路径，例如 /ISAPI/System/deviceInfo
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn isapi_request(
+        &self,
+        method: IsapiMethod,
+        url: &str,
+        body: Option<&[u8]>,
+    ) -> anyhow::Result<IsapiResponse> {
+        check_isapi_url(url)?;
+        let lu = self.user_id()?;
+
+        let request_url = format!("{} {}", method.as_str(), url);
+        let mut in_buffer = body.map(|body| body.to_vec()).unwrap_or_default();
+        let mut out_size = INITIAL_OUT_BUFFER_SIZE;
+
+        loop {
+            let mut out_buffer = vec![0u8; out_size];
+            let mut status_buffer = vec![0u8; STATUS_BUFFER_SIZE];
+
+            let mut input = NET_DVR_XML_CONFIG_INPUT {
+                dwSize: mem::size_of::<NET_DVR_XML_CONFIG_INPUT>() as DWORD,
+                lpRequestUrl: request_url.as_ptr() as *mut c_void,
+                dwRequestUrlLen: request_url.len() as DWORD,
+                ..Default::default()
+            };
+            if !in_buffer.is_empty() {
+                input.lpInBuffer = in_buffer.as_mut_ptr() as *mut c_void;
+                input.dwInBufferSize = in_buffer.len() as DWORD;
+            }
+
+            let mut output = NET_DVR_XML_CONFIG_OUTPUT {
+                dwSize: mem::size_of::<NET_DVR_XML_CONFIG_OUTPUT>() as DWORD,
+                lpOutBuffer: out_buffer.as_mut_ptr() as *mut c_void,
+                dwOutBufferSize: out_buffer.len() as DWORD,
+                lpStatusBuffer: status_buffer.as_mut_ptr() as *mut c_void,
+                dwStatusSize: status_buffer.len() as DWORD,
+                ..Default::default()
+            };
+
+            let res = unsafe { sdk_call!(NET_DVR_STDXMLConfig(lu, &mut input, &mut output)) };
+            if res == 1 {
+                let len = (output.dwReturnedXMLSize as usize).min(out_buffer.len());
+                out_buffer.truncate(len);
+                return Ok(IsapiResponse {
+                    success: true,
+                    error_code: 0,
+                    body: out_buffer,
+                    status: None,
+                });
+            }
+
+            let error_code = get_last_error_code();
+            if error_code == NET_DVR_NOENOUGH_BUF as i32 && out_size < MAX_OUT_BUFFER_SIZE {
+                out_size *= 4;
+                continue;
+            }
+
+            return Ok(IsapiResponse {
+                success: false,
+                error_code,
+                body: Vec::new(),
+                status: Some(trim_nul(status_buffer)),
+            });
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn isapi_get_string(&self, url: &str) -> anyhow::Result<String> {
+        let response = self.isapi_request(IsapiMethod::Get, url, None)?;
+        if !response.success {
+            return Err(anyhow::anyhow!(
+                "ISAPI GET {} failed: error code {}, status: {}",
+                url,
+                response.error_code,
+                response.status_str().unwrap_or_default()
+            ));
+        }
+        Ok(response.body_str().into_owned())
+    }
+}
+
+// 请求行按长度传给 SDK，\0 会截断路径，空白与换行会改写请求行
+fn check_isapi_url(url: &str) -> Result<(), HikError> {
+    let reason = if !url.starts_with('/') {
+        "must be an absolute path starting with /"
+    } else if url.chars().any(|c| c.is_control() || c.is_whitespace()) {
+        "must not contain whitespace or control characters"
+    } else {
+        return Ok(());
+    };
+    Err(HikError::InvalidArgument {
+        field: "url",
+        reason: reason.to_string(),
+    })
+}
+
+fn trim_nul(mut buffer: Vec<u8>) -> Vec<u8> {
+    let len = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    buffer.truncate(len);
+    buffer
+}
+
+// 按 RFC 3986 转义 URL 的 userinfo 或查询参数，密码中常见的 @ : / # & 等字符会被当作分隔符
+pub(crate) fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    percent_encode_into(&mut out, s);
+    out
+}
+
+// 直接写入 out，不产生中间字符串；out 预留足够容量时也不会因扩容留下旧副本
+pub(crate) fn percent_encode_into(out: &mut String, s: &str) {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            out.push(b as char);
+        } else {
+            out.push('%');
+            out.push(HEX[(b >> 4) as usize] as char);
+            out.push(HEX[(b & 0xf) as usize] as char);
+        }
+    }
+}
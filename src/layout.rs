@@ -0,0 +1,144 @@
+use std::mem;
+
+use crate::{
+    NET_DVR_ALARMER, NET_DVR_COMPRESSIONCFG_V30, NET_DVR_DEVICECFG_V40, NET_DVR_DEVICEINFO_V30,
+    NET_DVR_DEVICEINFO_V40, NET_DVR_FILECOND_V40, NET_DVR_FINDDATA_V40, NET_DVR_IPPARACFG_V40,
+    NET_DVR_JPEGPARA, NET_DVR_NETCFG_V50, NET_DVR_PDC_ALRAM_INFO, NET_DVR_PDC_QUERY_COND,
+    NET_DVR_PDC_RESULT, NET_DVR_PICCFG_V40, NET_DVR_PLAYCOND, NET_DVR_PREVIEWINFO, NET_DVR_PTZPOS,
+    NET_DVR_SETUPALARM_PARAM, NET_DVR_STD_CONFIG, NET_DVR_TIME, NET_DVR_TIME_EX,
+    NET_DVR_USER_LOGIN_INFO, NET_DVR_XML_CONFIG_INPUT, NET_DVR_XML_CONFIG_OUTPUT,
+};
+
+// 编译期检查本库使用的 SDK 结构体布局，期望值取自 64 位 SDK 头文件（Linux x86_64 与 Windows x64）
+// bindgen 按构建时找到的头文件生成，与目标平台的库不匹配时在这里编译失败，而不是运行时写坏内存
+// 新封装的结构体需要在这里补上检查
+macro_rules! assert_layout {
+    ($t:ty, size = $size:expr $(, $field:ident = $offset:expr)* $(,)?) => {
+        const _: () = {
+            assert!(
+                mem::size_of::<$t>() == $size,
+                concat!("Size of ", stringify!($t), " does not match HCNetSDK")
+            );
+            $(
+                assert!(
+                    mem::offset_of!($t, $field) == $offset,
+                    concat!(
+                        "Offset of ",
+                        stringify!($t),
+                        "::",
+                        stringify!($field),
+                        " does not match HCNetSDK"
+                    )
+                );
+            )*
+        };
+    };
+}
+
+// 只含定长字段，两个平台一致
+assert_layout!(NET_DVR_TIME, size = 24, dwDay = 8, dwSecond = 20);
+assert_layout!(NET_DVR_TIME_EX, size = 8);
+assert_layout!(
+    NET_DVR_DEVICEINFO_V30,
+    size = 80,
+    byChanNum = 52,
+    byStartChan = 53,
+    byIPChanNum = 55,
+    byStartDChan = 66,
+    byHighDChanNum = 68,
+);
+assert_layout!(
+    NET_DVR_DEVICEINFO_V40,
+    size = 344,
+    byRetryLoginTime = 81,
+    byPasswordLevel = 82,
+    dwSurplusLockTime = 84,
+);
+assert_layout!(
+    NET_DVR_IPPARACFG_V40,
+    size = 50792,
+    struIPDevInfo = 84,
+    struStreamMode = 19028,
+);
+assert_layout!(
+    NET_DVR_PLAYCOND,
+    size = 116,
+    struStartTime = 4,
+    struStopTime = 28,
+    byDrawFrame = 52,
+);
+assert_layout!(NET_DVR_JPEGPARA, size = 4, wPicQuality = 2);
+assert_layout!(
+    NET_DVR_FILECOND_V40,
+    size = 160,
+    struStartTime = 48,
+    struStopTime = 72,
+);
+assert_layout!(
+    NET_DVR_FINDDATA_V40,
+    size = 320,
+    struStartTime = 100,
+    dwFileSize = 148,
+);
+assert_layout!(NET_DVR_SETUPALARM_PARAM, size = 20);
+assert_layout!(NET_DVR_ALARMER, size = 372, lUserID = 8);
+assert_layout!(NET_DVR_PTZPOS, size = 8);
+assert_layout!(NET_DVR_DEVICECFG_V40, size = 180);
+assert_layout!(NET_DVR_PICCFG_V40, size = 77140);
+assert_layout!(NET_DVR_COMPRESSIONCFG_V30, size = 116);
+assert_layout!(NET_DVR_NETCFG_V50, size = 2640);
+assert_layout!(
+    NET_DVR_PDC_RESULT,
+    size = 284,
+    dwEnterNum = 20,
+    dwPeoplePassing = 80,
+);
+
+// 含指针的结构体，32 位 SDK 的布局不同
+#[cfg(target_pointer_width = "64")]
+mod pointer_sized {
+    use super::*;
+
+    assert_layout!(
+        NET_DVR_USER_LOGIN_INFO,
+        size = 416,
+        cbLoginResult = 264,
+        pUser = 272,
+    );
+    assert_layout!(NET_DVR_STD_CONFIG, size = 104, lpOutBuffer = 32);
+    assert_layout!(NET_DVR_XML_CONFIG_INPUT, size = 72);
+    assert_layout!(NET_DVR_XML_CONFIG_OUTPUT, size = 72);
+    assert_layout!(
+        NET_DVR_PDC_QUERY_COND,
+        size = 160,
+        byReportType = 24,
+        byMinTimeInterva = 57,
+    );
+    assert_layout!(
+        NET_DVR_PDC_ALRAM_INFO,
+        size = 344,
+        uStatModeParam = 156,
+        dwLeaveNum = 296,
+        dwPassingNum = 308,
+    );
+
+    // Linux 头文件中 HWND 是 unsigned int，Windows 上是指针，之后的字段整体后移
+    #[cfg(not(hik_sdk_platform = "windows"))]
+    assert_layout!(
+        NET_DVR_PREVIEWINFO,
+        size = 280,
+        hPlayWnd = 12,
+        bBlocked = 16,
+        byProtoType = 57,
+        dwDisplayBufNum = 60,
+    );
+    #[cfg(hik_sdk_platform = "windows")]
+    assert_layout!(
+        NET_DVR_PREVIEWINFO,
+        size = 288,
+        hPlayWnd = 16,
+        bBlocked = 24,
+        byProtoType = 65,
+        dwDisplayBufNum = 68,
+    );
+}
@@ -2,47 +2,84 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 
-include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+// bindgen 生成的原始绑定，根路径下的同名导出保留给 crate 内部和旧代码使用
+pub mod sys {
+    include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+}
+
+pub use sys::*;
 
+pub mod ability;
+pub mod alarm;
+pub mod alarm_host;
+pub mod alarm_io;
+#[cfg(feature = "tokio")]
+pub mod async_device;
+pub mod batch;
+pub mod cancel;
 pub mod common;
+pub mod compression;
+pub mod config_file;
+pub mod counting;
+#[cfg(feature = "decoder")]
+pub mod decoder;
 pub mod device;
+pub mod discovery;
+pub mod disk;
+pub mod email;
+pub mod error;
+pub mod events;
+pub mod ffi_util;
+pub mod files;
+pub mod fisheye;
+pub mod isapi;
+mod layout;
+pub mod log;
+pub mod manager;
+pub mod motion;
+pub mod network;
+pub mod picture;
+pub mod playback;
+pub mod preview;
+pub mod ptz;
+pub mod record;
+#[cfg(feature = "remux")]
+pub mod remux;
+pub mod rtsp;
+pub mod sdk;
+pub mod sdk_struct;
+pub mod serial;
+pub mod session;
+pub mod snapshot;
+pub mod status;
+pub mod stream_download;
+pub mod time;
+pub mod timezone;
+mod trace;
+pub mod upgrade;
+pub mod users;
 
+// 字符串中含 \0 时通过 ? 返回 HikError::InvalidArgument，调用处需要返回 Result
+// 第二个参数为错误中的参数名
 #[macro_export]
 macro_rules! as_c_string {
-    ($a:ident) => {
-        std::ffi::CString::new($a).unwrap()
-    };
     ($a:expr) => {
-        std::ffi::CString::new($a).unwrap()
+        $crate::as_c_string!($a, "string")
+    };
+    ($a:expr, $field:expr) => {
+        $crate::ffi_util::to_cstring(&$a, $field)?
     };
 }
 
+// 空指针时返回默认值（不传则为空字符串）
 #[macro_export]
 macro_rules! const_ptr_to_string {
-    ($a:ident) => {
-        unsafe { std::ffi::CStr::from_ptr($a).to_string_lossy().into_owned() }
-    };
     ($a:expr) => {
-        unsafe { std::ffi::CStr::from_ptr($a).to_string_lossy().into_owned() }
+        unsafe { $crate::ffi_util::c_ptr_to_string($a as *const std::os::raw::c_char) }
+            .unwrap_or_default()
     };
-    ($a:ident, $def:literal) => {
-        if $a.is_null() {
-            $def
-        } else {
-            unsafe { std::ffi::CStr::from_ptr($a).to_string_lossy().into_owned() }
-        }
+    ($a:expr, $def:expr) => {
+        unsafe { $crate::ffi_util::c_ptr_to_string($a as *const std::os::raw::c_char) }
+            .unwrap_or_else(|| ($def).into())
     };
-    ($a:expr, $def:expr) => {{
-        let ptr = $a as *const std::os::raw::c_char;
-        if ptr.is_null() {
-            $def
-        } else {
-            unsafe {
-                std::ffi::CStr::from_ptr(ptr)
-                    .to_str()
-                    .map(|s| s.to_string())
-                    .unwrap_or_else(|_| $def)
-            }
-        }
-    }};
 }
@@ -0,0 +1,358 @@
+use std::{net::IpAddr, thread, time::Duration};
+
+use chrono::{DateTime, Local};
+
+use crate::{
+    DWORD, LONG, MAJOR_ALARM, MAJOR_EVENT, MAJOR_EXCEPTION, MAJOR_INFORMATION, MAJOR_OPERATION,
+    MINOR_ALARM_IN, MINOR_ALARM_OUT, MINOR_DCD_LOST, MINOR_HD_ERROR, MINOR_HD_FULL, MINOR_HDD_INFO,
+    MINOR_HIDE_ALARM_START, MINOR_HIDE_ALARM_STOP, MINOR_ILLEGAL_ACCESS, MINOR_IP_CONFLICT,
+    MINOR_IPC_NO_LINK, MINOR_LOCAL_CFG_PARM, MINOR_LOCAL_LOGIN, MINOR_LOCAL_LOGOUT,
+    MINOR_LOCAL_UPGRADE, MINOR_MOTDET_START, MINOR_MOTDET_STOP, MINOR_NET_BROKEN, MINOR_REC_ERROR,
+    MINOR_REC_START, MINOR_REC_STOP, MINOR_REMOTE_ARM, MINOR_REMOTE_CFG_PARM, MINOR_REMOTE_DISARM,
+    MINOR_REMOTE_FORMAT_HDD, MINOR_REMOTE_GET_PARM, MINOR_REMOTE_LOGIN, MINOR_REMOTE_LOGOUT,
+    MINOR_REMOTE_PLAYBYFILE, MINOR_REMOTE_PLAYBYTIME, MINOR_REMOTE_REBOOT, MINOR_REMOTE_START_REC,
+    MINOR_REMOTE_STOP_REC, MINOR_REMOTE_UPGRADE, MINOR_START_DVR, MINOR_STOP_DVR,
+    MINOR_VCA_ALARM_START, MINOR_VCA_ALARM_STOP, MINOR_VI_LOST, NET_DVR_FILE_EXCEPTION,
+    NET_DVR_FILE_NOFIND, NET_DVR_FILE_SUCCESS, NET_DVR_FindDVRLog_V30, NET_DVR_FindLogClose_V30,
+    NET_DVR_FindNextLog_V30, NET_DVR_ISFINDING, NET_DVR_LOG_V30, NET_DVR_NOMOREFILE, NET_DVR_TIME,
+    device::{HikDevice, sdk_error},
+    ffi_util::decode_device_string,
+    network::parse_ipv4,
+    timezone::check_time_range,
+    trace::sdk_call,
+};
+
+// 按时间和类型查找
+const LOG_SELECT_BY_TIME_AND_TYPE: LONG = 3;
+// 设备仍在检索时的重试间隔与次数，合计约 10 秒
+const FIND_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+const FIND_RETRY_LIMIT: u32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LogMajorType {
+    // 查找时表示全部类型
+    All,
+    Alarm,
+    Exception,
+    Operation,
+    Information,
+    Event,
+    Other(u32),
+}
+
+impl From<u32> for LogMajorType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => LogMajorType::All,
+            MAJOR_ALARM => LogMajorType::Alarm,
+            MAJOR_EXCEPTION => LogMajorType::Exception,
+            MAJOR_OPERATION => LogMajorType::Operation,
+            MAJOR_INFORMATION => LogMajorType::Information,
+            MAJOR_EVENT => LogMajorType::Event,
+            other => LogMajorType::Other(other),
+        }
+    }
+}
+
+impl From<LogMajorType> for u32 {
+    fn from(value: LogMajorType) -> Self {
+        match value {
+            LogMajorType::All => 0,
+            LogMajorType::Alarm => MAJOR_ALARM,
+            LogMajorType::Exception => MAJOR_EXCEPTION,
+            LogMajorType::Operation => MAJOR_OPERATION,
+            LogMajorType::Information => MAJOR_INFORMATION,
+            LogMajorType::Event => MAJOR_EVENT,
+            LogMajorType::Other(other) => other,
+        }
+    }
+}
+
+// 只列出常用的次类型，其余保留原始值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LogMinorType {
+    // 报警
+    AlarmIn,
+    AlarmOut,
+    MotionStart,
+    MotionStop,
+    TamperStart,
+    TamperStop,
+    VcaAlarmStart,
+    VcaAlarmStop,
+    // 异常
+    VideoLost,
+    IllegalAccess,
+    DiskFull,
+    DiskError,
+    ModemLost,
+    IpConflict,
+    NetworkBroken,
+    RecordError,
+    IpcDisconnected,
+    // 操作
+    PowerOn,
+    PowerOff,
+    LocalLogin,
+    LocalLogout,
+    LocalConfig,
+    LocalUpgrade,
+    RemoteLogin,
+    RemoteLogout,
+    RemoteStartRecord,
+    RemoteStopRecord,
+    RemoteArm,
+    RemoteDisarm,
+    RemoteReboot,
+    RemoteGetConfig,
+    RemoteConfig,
+    RemoteUpgrade,
+    RemotePlaybackByFile,
+    RemotePlaybackByTime,
+    RemoteFormatDisk,
+    // 信息
+    DiskInfo,
+    RecordStart,
+    RecordStop,
+    Other(u32),
+}
+
+impl LogMinorType {
+    // 不同主类型下的次类型取值会重叠，必须结合主类型解析
+    pub fn from_raw(major: LogMajorType, minor: u32) -> Self {
+        let decoded = match major {
+            LogMajorType::Alarm => match minor {
+                MINOR_ALARM_IN => Some(LogMinorType::AlarmIn),
+                MINOR_ALARM_OUT => Some(LogMinorType::AlarmOut),
+                MINOR_MOTDET_START => Some(LogMinorType::MotionStart),
+                MINOR_MOTDET_STOP => Some(LogMinorType::MotionStop),
+                MINOR_HIDE_ALARM_START => Some(LogMinorType::TamperStart),
+                MINOR_HIDE_ALARM_STOP => Some(LogMinorType::TamperStop),
+                MINOR_VCA_ALARM_START => Some(LogMinorType::VcaAlarmStart),
+                MINOR_VCA_ALARM_STOP => Some(LogMinorType::VcaAlarmStop),
+                _ => None,
+            },
+            LogMajorType::Exception => match minor {
+                MINOR_VI_LOST => Some(LogMinorType::VideoLost),
+                MINOR_ILLEGAL_ACCESS => Some(LogMinorType::IllegalAccess),
+                MINOR_HD_FULL => Some(LogMinorType::DiskFull),
+                MINOR_HD_ERROR => Some(LogMinorType::DiskError),
+                MINOR_DCD_LOST => Some(LogMinorType::ModemLost),
+                MINOR_IP_CONFLICT => Some(LogMinorType::IpConflict),
+                MINOR_NET_BROKEN => Some(LogMinorType::NetworkBroken),
+                MINOR_REC_ERROR => Some(LogMinorType::RecordError),
+                MINOR_IPC_NO_LINK => Some(LogMinorType::IpcDisconnected),
+                _ => None,
+            },
+            LogMajorType::Operation => match minor {
+                MINOR_START_DVR => Some(LogMinorType::PowerOn),
+                MINOR_STOP_DVR => Some(LogMinorType::PowerOff),
+                MINOR_LOCAL_LOGIN => Some(LogMinorType::LocalLogin),
+                MINOR_LOCAL_LOGOUT => Some(LogMinorType::LocalLogout),
+                MINOR_LOCAL_CFG_PARM => Some(LogMinorType::LocalConfig),
+                MINOR_LOCAL_UPGRADE => Some(LogMinorType::LocalUpgrade),
+                MINOR_REMOTE_LOGIN => Some(LogMinorType::RemoteLogin),
+                MINOR_REMOTE_LOGOUT => Some(LogMinorType::RemoteLogout),
+                MINOR_REMOTE_START_REC => Some(LogMinorType::RemoteStartRecord),
+                MINOR_REMOTE_STOP_REC => Some(LogMinorType::RemoteStopRecord),
+                MINOR_REMOTE_ARM => Some(LogMinorType::RemoteArm),
+                MINOR_REMOTE_DISARM => Some(LogMinorType::RemoteDisarm),
+                MINOR_REMOTE_REBOOT => Some(LogMinorType::RemoteReboot),
+                MINOR_REMOTE_GET_PARM => Some(LogMinorType::RemoteGetConfig),
+                MINOR_REMOTE_CFG_PARM => Some(LogMinorType::RemoteConfig),
+                MINOR_REMOTE_UPGRADE => Some(LogMinorType::RemoteUpgrade),
+                MINOR_REMOTE_PLAYBYFILE => Some(LogMinorType::RemotePlaybackByFile),
+                MINOR_REMOTE_PLAYBYTIME => Some(LogMinorType::RemotePlaybackByTime),
+                MINOR_REMOTE_FORMAT_HDD => Some(LogMinorType::RemoteFormatDisk),
+                _ => None,
+            },
+            LogMajorType::Information => match minor {
+                MINOR_HDD_INFO => Some(LogMinorType::DiskInfo),
+                MINOR_REC_START => Some(LogMinorType::RecordStart),
+                MINOR_REC_STOP => Some(LogMinorType::RecordStop),
+                _ => None,
+            },
+            _ => None,
+        };
+        decoded.unwrap_or(LogMinorType::Other(minor))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LogEntry {
+    pub time: DateTime<Local>,
+    pub major: LogMajorType,
+    pub minor: LogMinorType,
+    // 日志对应的通道号，与通道无关时为 0
+    pub channel: u32,
+    pub remote_host: Option<IpAddr>,
+    // 远程用户名，本地面板操作时为面板用户名
+    pub user: String,
+    pub info: Vec<u8>,
+}
+
+impl TryFrom<&NET_DVR_LOG_V30> for LogEntry {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: &NET_DVR_LOG_V30) -> anyhow::Result<Self> {
+        let major = LogMajorType::from(raw.dwMajorType);
+        let net_user = decode_device_string(&raw.sNetUser);
+        let user = if net_user.is_empty() {
+            decode_device_string(&raw.sPanelUser)
+        } else {
+            net_user
+        };
+        let info_len = (raw.dwInfoLen as usize).min(raw.sInfo.len());
+        let info = raw.sInfo[..info_len].iter().map(|&b| b as u8).collect();
+
+        Ok(Self {
+            time: DateTime::try_from(raw.strLogTime)?,
+            major,
+            minor: LogMinorType::from_raw(major, raw.dwMinorType),
+            channel: raw.dwChannel,
+            remote_host: remote_host(raw),
+            user,
+            info,
+        })
+    }
+}
+
+fn remote_host(raw: &NET_DVR_LOG_V30) -> Option<IpAddr> {
+    if let Some(ip) = parse_ipv4(&raw.struRemoteHostAddr) {
+        return Some(IpAddr::V4(ip));
+    }
+    let ipv6 = &raw.struRemoteHostAddr.byIPv6;
+    let len = ipv6.iter().position(|&b| b == 0).unwrap_or(ipv6.len());
+    std::str::from_utf8(&ipv6[..len])
+        .ok()?
+        .parse()
+        .ok()
+        .filter(|ip: &IpAddr| !ip.is_unspecified())
+}
+
+pub struct LogIter {
+    handle: LONG,
+    channel: Option<u16>,
+    finished: bool,
+    // 单条日志约 12KB，复用同一块缓冲区
+    buffer: Box<NET_DVR_LOG_V30>,
+}
+
+impl LogIter {
+    fn next_raw(&mut self) -> anyhow::Result<bool> {
+        let mut retries = 0;
+        loop {
+            let status =
+                unsafe { sdk_call!(NET_DVR_FindNextLog_V30(self.handle, &mut *self.buffer)) };
+            if status < 0 {
+                return Err(sdk_error("Find next log"));
+            }
+            match status as DWORD {
+                NET_DVR_FILE_SUCCESS => return Ok(true),
+                NET_DVR_FILE_NOFIND | NET_DVR_NOMOREFILE => return Ok(false),
+                NET_DVR_ISFINDING => {
+                    retries += 1;
+                    if retries > FIND_RETRY_LIMIT {
+                        return Err(anyhow::anyhow!("Find next log timed out"));
+                    }
+                    thread::sleep(FIND_RETRY_INTERVAL);
+                }
+                NET_DVR_FILE_EXCEPTION => {
+                    return Err(anyhow::anyhow!("Find next log failed: device exception"));
+                }
+                other => {
+                    return Err(anyhow::anyhow!(
+                        "Find next log failed: unexpected status {}",
+                        other
+                    ));
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for LogIter {
+    type Item = anyhow::Result<LogEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            match self.next_raw() {
+                Ok(true) => {
+                    // V30 接口不支持按通道查找，在这里过滤
+                    if let Some(channel) = self.channel {
+                        if self.buffer.dwChannel != channel as DWORD {
+                            continue;
+                        }
+                    }
+                    return Some(LogEntry::try_from(&*self.buffer));
+                }
+                Ok(false) => self.finished = true,
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Drop for LogIter {
+    fn drop(&mut self) {
+        unsafe { sdk_call!(NET_DVR_FindLogClose_V30(self.handle)) };
+    }
+}
+
+impl HikDevice {
+    /// 查找设备日志，minor 为 None 时返回该主类型下的全部日志
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn find_logs(
+        &self,
+        channel: Option<u16>,
+        major: LogMajorType,
+        minor: Option<u32>,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> anyhow::Result<LogIter> {
+        if let Some(channel) = channel {
+            self.resolve_channel(channel)?;
+        }
+        check_time_range(start, end)?;
+        let mut start_time: NET_DVR_TIME = start.into();
+        let mut end_time: NET_DVR_TIME = end.into();
+        let handle = self.with_session(|lu| {
+            let handle = unsafe {
+                sdk_call!(NET_DVR_FindDVRLog_V30(
+                    lu,
+                    LOG_SELECT_BY_TIME_AND_TYPE,
+                    u32::from(major),
+                    minor.unwrap_or(0),
+                    &mut start_time,
+                    &mut end_time,
+                    0,
+                ))
+            };
+            if handle < 0 {
+                return Err(sdk_error("Find logs"));
+            }
+            Ok(handle)
+        })?;
+
+        Ok(LogIter {
+            handle,
+            channel,
+            finished: false,
+            buffer: unsafe { Box::new_zeroed().assume_init() },
+        })
+    }
+}
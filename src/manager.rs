@@ -0,0 +1,615 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::Deref,
+    sync::{
+        Arc, Mutex, Weak,
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    cancel::wait_stop,
+    device::{HikDevice, LoginOptions},
+    error::HikError,
+    sdk::{NetSdk, RealSdk},
+};
+
+// 后台线程检查空闲会话的最长间隔
+const EVICT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceAddress {
+    pub host: String,
+    pub port: u16,
+}
+
+impl DeviceAddress {
+    pub fn new(host: &str, port: u16) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+        }
+    }
+}
+
+impl fmt::Display for DeviceAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Credentials {
+    pub fn new(username: &str, password: &str) -> Self {
+        Self {
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+}
+
+// 不输出密码
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"***")
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ManagerOptions {
+    // 同时登录的会话数上限（包括空闲会话），None 为不限制
+    pub max_sessions: Option<usize>,
+    // 没有句柄引用的会话保留多久，期间再次 connect 直接复用；
+    // None 时最后一个句柄释放后立即注销
+    pub idle_timeout: Option<Duration>,
+    // 登录超时，含义同 LoginOptions::timeout_ms
+    pub login_timeout_ms: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManagerStats {
+    // 至少有一个句柄的会话
+    pub active_sessions: usize,
+    // 等待复用或超时注销的会话
+    pub idle_sessions: usize,
+    // 管理器创建以来成功登录的次数，复用会话不计入
+    pub total_logins: u64,
+}
+
+// 同一设备的不同用户各自登录
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SessionKey {
+    address: DeviceAddress,
+    username: String,
+}
+
+// 最后一个引用（池中的条目或句柄）释放时注销
+struct PooledDevice(HikDevice);
+
+impl Drop for PooledDevice {
+    fn drop(&mut self) {
+        let _ = self.0.logout();
+    }
+}
+
+struct PooledSession {
+    device: Arc<PooledDevice>,
+    // 连接池中的会话可能被其他调用方复用，密码不一致时拒绝
+    password: String,
+    handles: usize,
+    idle_since: Option<Instant>,
+}
+
+#[derive(Default)]
+struct Pool {
+    sessions: HashMap<SessionKey, PooledSession>,
+    // 正在登录的会话，占用 max_sessions 的名额
+    pending: usize,
+}
+
+struct ManagerInner {
+    options: ManagerOptions,
+    pool: Mutex<Pool>,
+    total_logins: AtomicU64,
+    sdk: Arc<dyn NetSdk>,
+    // 管理器释放时 Sender 被丢弃，空闲回收线程随之退出
+    _evictor_stop: Option<mpsc::Sender<()>>,
+}
+
+/// 多设备会话管理：按 (host, port, username) 复用登录，句柄全部释放后注销
+///
+/// connect 返回的 DeviceHandle 可以廉价克隆，通过 Deref 调用 HikDevice 的方法。
+/// 设置 idle_timeout 时会启动后台线程定期注销空闲会话
+#[derive(Clone)]
+pub struct HikDeviceManager {
+    inner: Arc<ManagerInner>,
+}
+
+impl Default for HikDeviceManager {
+    fn default() -> Self {
+        Self::new(ManagerOptions::default())
+    }
+}
+
+impl HikDeviceManager {
+    pub fn new(options: ManagerOptions) -> Self {
+        Self::with_sdk(options, Arc::new(RealSdk))
+    }
+
+    // 会话都通过 sdk 登录，主要用于配合 MockSdk 测试
+    pub fn with_sdk(options: ManagerOptions, sdk: Arc<dyn NetSdk>) -> Self {
+        let Some(idle_timeout) = options.idle_timeout else {
+            return Self {
+                inner: Arc::new(ManagerInner {
+                    options,
+                    pool: Mutex::default(),
+                    total_logins: AtomicU64::new(0),
+                    sdk,
+                    _evictor_stop: None,
+                }),
+            };
+        };
+
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let inner = Arc::new(ManagerInner {
+            options,
+            pool: Mutex::default(),
+            total_logins: AtomicU64::new(0),
+            sdk,
+            _evictor_stop: Some(stop_tx),
+        });
+        // 只持有弱引用，不阻止管理器释放
+        let weak = Arc::downgrade(&inner);
+        let interval = idle_timeout.min(EVICT_CHECK_INTERVAL);
+        thread::spawn(move || {
+            while !wait_stop(&stop_rx, None, interval) {
+                let Some(inner) = weak.upgrade() else { break };
+                inner.evict_idle(idle_timeout);
+            }
+        });
+        Self { inner }
+    }
+
+    /// 登录设备或复用已有会话
+    ///
+    /// 同一 (host, port, username) 的会话已存在时不再登录，但密码必须一致；
+    /// 会话数达到 max_sessions 时先注销最早空闲的会话，仍然没有名额则返回 HikError::TooManySessions
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(address = %address))
+    )]
+    pub fn connect(
+        &self,
+        address: DeviceAddress,
+        credentials: Credentials,
+    ) -> anyhow::Result<DeviceHandle> {
+        let key = SessionKey {
+            address,
+            username: credentials.username.clone(),
+        };
+        let mut evicted = Vec::new();
+        {
+            let mut pool = self.inner.pool.lock().unwrap();
+            if let Some(handle) = self.reuse(&mut pool, &key, &credentials)? {
+                return Ok(handle);
+            }
+            if let Some(max) = self.inner.options.max_sessions {
+                if pool.sessions.len() + pool.pending >= max {
+                    let oldest = pool
+                        .sessions
+                        .iter()
+                        .filter_map(|(key, session)| Some((session.idle_since?, key)))
+                        .min_by_key(|(idle_since, _)| *idle_since)
+                        .map(|(_, key)| key.clone());
+                    match oldest.and_then(|key| pool.sessions.remove(&key)) {
+                        Some(session) => evicted.push(session),
+                        None => return Err(HikError::TooManySessions { max }.into()),
+                    }
+                }
+            }
+            pool.pending += 1;
+        }
+        // 在锁外注销与登录，两者都是阻塞的网络请求
+        drop(evicted);
+
+        let login = self.login(&key, &credentials);
+        let mut pool = self.inner.pool.lock().unwrap();
+        pool.pending -= 1;
+        let device = login?;
+        self.inner.total_logins.fetch_add(1, Ordering::Relaxed);
+        // 并发登录同一会话时保留先完成的，这次的登录在锁外随 device 释放
+        if let Some(existing) = self.reuse(&mut pool, &key, &credentials).transpose() {
+            drop(pool);
+            drop(device);
+            return existing;
+        }
+        let device = Arc::new(device);
+        pool.sessions.insert(
+            key.clone(),
+            PooledSession {
+                device: device.clone(),
+                password: credentials.password,
+                handles: 1,
+                idle_since: None,
+            },
+        );
+        Ok(DeviceHandle {
+            device,
+            key,
+            manager: Arc::downgrade(&self.inner),
+        })
+    }
+
+    /// 注销空闲超过 max_idle 的会话，返回注销的数量
+    ///
+    /// 设置了 ManagerOptions::idle_timeout 时后台线程会自动调用
+    pub fn evict_idle(&self, max_idle: Duration) -> usize {
+        self.inner.evict_idle(max_idle)
+    }
+
+    pub fn stats(&self) -> ManagerStats {
+        let pool = self.inner.pool.lock().unwrap();
+        let idle_sessions = pool
+            .sessions
+            .values()
+            .filter(|session| session.handles == 0)
+            .count();
+        ManagerStats {
+            active_sessions: pool.sessions.len() - idle_sessions,
+            idle_sessions,
+            total_logins: self.inner.total_logins.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reuse(
+        &self,
+        pool: &mut Pool,
+        key: &SessionKey,
+        credentials: &Credentials,
+    ) -> anyhow::Result<Option<DeviceHandle>> {
+        let Some(session) = pool.sessions.get_mut(key) else {
+            return Ok(None);
+        };
+        if session.password != credentials.password {
+            return Err(HikError::InvalidArgument {
+                field: "password",
+                reason: format!(
+                    "does not match the open session for {} on {}",
+                    key.username, key.address
+                ),
+            }
+            .into());
+        }
+        session.handles += 1;
+        session.idle_since = None;
+        Ok(Some(DeviceHandle {
+            device: session.device.clone(),
+            key: key.clone(),
+            manager: Arc::downgrade(&self.inner),
+        }))
+    }
+
+    fn login(&self, key: &SessionKey, credentials: &Credentials) -> anyhow::Result<PooledDevice> {
+        let mut options = LoginOptions::new(
+            &key.address.host,
+            key.address.port,
+            &credentials.username,
+            &credentials.password,
+        );
+        options.timeout_ms = self.inner.options.login_timeout_ms;
+        let mut device = HikDevice::with_sdk(self.inner.sdk.clone());
+        device.login_v40(options)?;
+        Ok(PooledDevice(device))
+    }
+}
+
+impl ManagerInner {
+    fn evict_idle(&self, max_idle: Duration) -> usize {
+        let evicted: Vec<PooledSession> = {
+            let mut pool = self.pool.lock().unwrap();
+            let expired: Vec<SessionKey> = pool
+                .sessions
+                .iter()
+                .filter(|(_, session)| {
+                    session
+                        .idle_since
+                        .is_some_and(|since| since.elapsed() >= max_idle)
+                })
+                .map(|(key, _)| key.clone())
+                .collect();
+            expired
+                .iter()
+                .filter_map(|key| pool.sessions.remove(key))
+                .collect()
+        };
+        // 在锁外注销
+        evicted.len()
+    }
+
+    fn release(&self, key: &SessionKey, device: &Arc<PooledDevice>) {
+        let mut pool = self.pool.lock().unwrap();
+        let Some(session) = pool.sessions.get_mut(key) else {
+            return;
+        };
+        // 句柄所属的会话已被移除、同一用户又重新登录时，不影响新的会话
+        if !Arc::ptr_eq(&session.device, device) {
+            return;
+        }
+        session.handles -= 1;
+        if session.handles > 0 {
+            return;
+        }
+        if self.options.idle_timeout.is_some() {
+            session.idle_since = Some(Instant::now());
+            return;
+        }
+        // 条目中的引用在锁内释放，句柄持有的引用随后释放并注销
+        pool.sessions.remove(key);
+    }
+}
+
+/// HikDeviceManager 管理的会话句柄，克隆只增加引用计数
+///
+/// 最后一个句柄释放后会话变为空闲，按 ManagerOptions::idle_timeout 注销
+pub struct DeviceHandle {
+    device: Arc<PooledDevice>,
+    key: SessionKey,
+    manager: Weak<ManagerInner>,
+}
+
+impl DeviceHandle {
+    pub fn address(&self) -> &DeviceAddress {
+        &self.key.address
+    }
+
+    pub fn username(&self) -> &str {
+        &self.key.username
+    }
+}
+
+impl Deref for DeviceHandle {
+    type Target = HikDevice;
+
+    fn deref(&self) -> &HikDevice {
+        &self.device.0
+    }
+}
+
+impl Clone for DeviceHandle {
+    fn clone(&self) -> Self {
+        if let Some(manager) = self.manager.upgrade() {
+            let mut pool = manager.pool.lock().unwrap();
+            if let Some(session) = pool.sessions.get_mut(&self.key) {
+                if Arc::ptr_eq(&session.device, &self.device) {
+                    session.handles += 1;
+                }
+            }
+        }
+        Self {
+            device: self.device.clone(),
+            key: self.key.clone(),
+            manager: self.manager.clone(),
+        }
+    }
+}
+
+impl Drop for DeviceHandle {
+    fn drop(&mut self) {
+        // 管理器已释放时池中的引用也已释放，最后一个句柄释放时注销
+        if let Some(manager) = self.manager.upgrade() {
+            manager.release(&self.key, &self.device);
+        }
+    }
+}
+
+impl fmt::Debug for DeviceHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeviceHandle")
+            .field("address", &self.key.address)
+            .field("username", &self.key.username)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Barrier;
+
+    use super::*;
+    use crate::{
+        LONG,
+        sdk::{MockCall, MockSdk, device_info},
+    };
+
+    fn manager(mock: &Arc<MockSdk>, options: ManagerOptions) -> HikDeviceManager {
+        HikDeviceManager::with_sdk(options, mock.clone())
+    }
+
+    fn address() -> DeviceAddress {
+        DeviceAddress::new("192.0.2.1", 8000)
+    }
+
+    fn admin() -> Credentials {
+        Credentials::new("admin", "secret")
+    }
+
+    fn logins(mock: &MockSdk) -> usize {
+        mock.calls()
+            .iter()
+            .filter(|call| matches!(call, MockCall::Login { .. }))
+            .count()
+    }
+
+    fn logouts(mock: &MockSdk) -> Vec<LONG> {
+        mock.calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                MockCall::Logout { user_id } => Some(user_id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn handles_share_one_session_until_the_last_drops() {
+        let mock = Arc::new(MockSdk::new());
+        mock.push_login_ok(7, device_info(1, 4, 33, 0));
+        let manager = manager(&mock, ManagerOptions::default());
+
+        let first = manager.connect(address(), admin()).unwrap();
+        let second = manager.connect(address(), admin()).unwrap();
+        let third = second.clone();
+        assert!(std::ptr::eq(&*first, &*second));
+        assert_eq!(logins(&mock), 1);
+        assert_eq!(
+            manager.stats(),
+            ManagerStats {
+                active_sessions: 1,
+                idle_sessions: 0,
+                total_logins: 1,
+            }
+        );
+
+        drop(first);
+        drop(second);
+        assert!(logouts(&mock).is_empty());
+        assert_eq!(manager.stats().active_sessions, 1);
+
+        drop(third);
+        assert_eq!(logouts(&mock), [7]);
+        assert_eq!(manager.stats().active_sessions, 0);
+        assert_eq!(manager.stats().idle_sessions, 0);
+    }
+
+    #[test]
+    fn concurrent_logins_keep_one_session() {
+        let mock = Arc::new(MockSdk::new());
+        mock.push_login_ok(1, device_info(1, 4, 33, 0));
+        mock.push_login_ok(2, device_info(1, 4, 33, 0));
+        // 两个线程都进入登录后才返回，保证都错过了已有会话
+        mock.set_login_barrier(Arc::new(Barrier::new(2)));
+        let manager = manager(&mock, ManagerOptions::default());
+
+        let handles: Vec<DeviceHandle> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..2)
+                .map(|_| scope.spawn(|| manager.connect(address(), admin()).unwrap()))
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap())
+                .collect()
+        });
+        assert!(std::ptr::eq(&*handles[0], &*handles[1]));
+        assert_eq!(logins(&mock), 2);
+        // 后完成的登录被注销
+        let extra = logouts(&mock);
+        assert_eq!(extra.len(), 1);
+        assert_eq!(manager.stats().active_sessions, 1);
+        assert_eq!(manager.stats().total_logins, 2);
+
+        drop(handles);
+        let all = logouts(&mock);
+        assert_eq!(all.len(), 2);
+        assert_ne!(all[0], all[1]);
+    }
+
+    #[test]
+    fn idle_sessions_are_reused_then_evicted() {
+        let mock = Arc::new(MockSdk::new());
+        mock.push_login_ok(3, device_info(1, 4, 33, 0));
+        let manager = manager(
+            &mock,
+            ManagerOptions {
+                idle_timeout: Some(Duration::from_secs(3600)),
+                ..Default::default()
+            },
+        );
+
+        drop(manager.connect(address(), admin()).unwrap());
+        assert_eq!(manager.stats().idle_sessions, 1);
+        assert!(logouts(&mock).is_empty());
+
+        let handle = manager.connect(address(), admin()).unwrap();
+        assert_eq!(logins(&mock), 1);
+        assert_eq!(manager.stats().active_sessions, 1);
+        // 有句柄的会话不会被回收
+        assert_eq!(manager.evict_idle(Duration::ZERO), 0);
+
+        drop(handle);
+        assert_eq!(manager.evict_idle(Duration::ZERO), 1);
+        assert_eq!(logouts(&mock), [3]);
+        assert_eq!(
+            manager.stats(),
+            ManagerStats {
+                total_logins: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn full_pool_evicts_the_idle_session() {
+        let mock = Arc::new(MockSdk::new());
+        mock.push_login_ok(4, device_info(1, 4, 33, 0));
+        mock.push_login_ok(5, device_info(1, 4, 33, 0));
+        let manager = manager(
+            &mock,
+            ManagerOptions {
+                max_sessions: Some(1),
+                idle_timeout: Some(Duration::from_secs(3600)),
+                ..Default::default()
+            },
+        );
+
+        let first = manager.connect(address(), admin()).unwrap();
+        let other = DeviceAddress::new("192.0.2.2", 8000);
+        let err = manager.connect(other.clone(), admin()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<HikError>(),
+            Some(HikError::TooManySessions { max: 1 })
+        ));
+
+        drop(first);
+        let second = manager.connect(other, admin()).unwrap();
+        assert_eq!(logouts(&mock), [4]);
+        assert_eq!(second.address().host, "192.0.2.2");
+    }
+
+    #[test]
+    fn mismatched_password_is_rejected() {
+        let mock = Arc::new(MockSdk::new());
+        mock.push_login_ok(6, device_info(1, 4, 33, 0));
+        let manager = manager(&mock, ManagerOptions::default());
+
+        let _handle = manager.connect(address(), admin()).unwrap();
+        let err = manager
+            .connect(address(), Credentials::new("admin", "other"))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<HikError>(),
+            Some(HikError::InvalidArgument {
+                field: "password",
+                ..
+            })
+        ));
+        assert_eq!(logins(&mock), 1);
+    }
+}
@@ -0,0 +1,159 @@
+use bitflags::bitflags;
+
+use crate::{
+    NET_DVR_GET_PICCFG_V30, NET_DVR_PICCFG_V30, NET_DVR_SET_PICCFG_V30, device::HikDevice,
+};
+
+// 移动侦测区域按 22x18 的宏块划分
+pub const MOTION_GRID_WIDTH: usize = 22;
+pub const MOTION_GRID_HEIGHT: usize = 18;
+// 灵敏度 0-5，越大越灵敏，0xff 为关闭
+pub const MOTION_SENSITIVITY_MAX: u8 = 5;
+const MOTION_SENSITIVITY_OFF: u8 = 0xff;
+
+bitflags! {
+    // 对应 NET_DVR_HANDLEEXCEPTION_V30::dwHandleType
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct HandleType: u32 {
+        const MONITOR_ALARM = 0x01;
+        const AUDIO_ALARM = 0x02;
+        const UPLOAD_CENTER = 0x04;
+        const TRIGGER_ALARM_OUT = 0x08;
+        const SEND_EMAIL = 0x10;
+        const WIRELESS_LIGHT = 0x20;
+        const EMAP = 0x40;
+        const CAPTURE_FTP = 0x200;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MotionGrid {
+    cells: [[bool; MOTION_GRID_WIDTH]; MOTION_GRID_HEIGHT],
+}
+
+impl MotionGrid {
+    pub fn empty() -> Self {
+        Self {
+            cells: [[false; MOTION_GRID_WIDTH]; MOTION_GRID_HEIGHT],
+        }
+    }
+
+    pub fn get_cell(&self, x: usize, y: usize) -> bool {
+        self.cells
+            .get(y)
+            .and_then(|row| row.get(x))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    // 超出范围的坐标会被忽略
+    pub fn set_cell(&mut self, x: usize, y: usize, value: bool) {
+        if let Some(cell) = self.cells.get_mut(y).and_then(|row| row.get_mut(x)) {
+            *cell = value;
+        }
+    }
+
+    pub fn fill_all(&mut self) {
+        self.cells = [[true; MOTION_GRID_WIDTH]; MOTION_GRID_HEIGHT];
+    }
+
+    pub fn clear(&mut self) {
+        self.cells = [[false; MOTION_GRID_WIDTH]; MOTION_GRID_HEIGHT];
+    }
+}
+
+pub struct MotionConfig {
+    pub enabled: bool,
+    // None 表示关闭灵敏度（0xff）
+    pub sensitivity: Option<u8>,
+    pub grid: MotionGrid,
+    pub handle_type: HandleType,
+    // 触发的报警输出，下标为报警输出号 - 1
+    pub alarm_outputs: Vec<bool>,
+    // 保留设备返回的原始参数，set 时只修改上面的字段
+    raw: Box<NET_DVR_PICCFG_V30>,
+}
+
+impl MotionConfig {
+    fn from_raw(raw: Box<NET_DVR_PICCFG_V30>) -> Self {
+        let motion = &raw.struMotion;
+        let mut grid = MotionGrid::empty();
+        for (y, row) in grid.cells.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                *cell = motion.byMotionScope[y][x] == 1;
+            }
+        }
+
+        Self {
+            enabled: motion.byEnableHandleMotion == 1,
+            sensitivity: (motion.byMotionSensitive != MOTION_SENSITIVITY_OFF)
+                .then_some(motion.byMotionSensitive),
+            grid,
+            handle_type: HandleType::from_bits_retain(motion.struMotionHandleType.dwHandleType),
+            alarm_outputs: motion
+                .struMotionHandleType
+                .byRelAlarmOut
+                .iter()
+                .map(|&v| v == 1)
+                .collect(),
+            raw,
+        }
+    }
+
+    fn to_raw(&self) -> anyhow::Result<Box<NET_DVR_PICCFG_V30>> {
+        if let Some(sensitivity) = self.sensitivity.filter(|&s| s > MOTION_SENSITIVITY_MAX) {
+            return Err(anyhow::anyhow!(
+                "Motion sensitivity must be 0-{}, got {}",
+                MOTION_SENSITIVITY_MAX,
+                sensitivity
+            ));
+        }
+
+        let mut raw = self.raw.clone();
+        let motion = &mut raw.struMotion;
+        motion.byEnableHandleMotion = self.enabled as u8;
+        motion.byMotionSensitive = self.sensitivity.unwrap_or(MOTION_SENSITIVITY_OFF);
+        for (y, row) in self.grid.cells.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                motion.byMotionScope[y][x] = cell as u8;
+            }
+        }
+        motion.struMotionHandleType.dwHandleType = self.handle_type.bits();
+        let alarm_out = &mut motion.struMotionHandleType.byRelAlarmOut;
+        for (dst, &value) in alarm_out.iter_mut().zip(self.alarm_outputs.iter()) {
+            *dst = value as u8;
+        }
+        Ok(raw)
+    }
+}
+
+impl HikDevice {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn get_motion_config(&self, channel: u16) -> anyhow::Result<MotionConfig> {
+        let channel = self.resolve_channel(channel)?;
+        let raw: NET_DVR_PICCFG_V30 =
+            self.get_dvr_config(NET_DVR_GET_PICCFG_V30, channel, "Get motion config")?;
+        Ok(MotionConfig::from_raw(Box::new(raw)))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn set_motion_config(&self, channel: u16, config: &MotionConfig) -> anyhow::Result<()> {
+        let channel = self.resolve_channel(channel)?;
+        let raw = config.to_raw()?;
+        self.set_dvr_config(NET_DVR_SET_PICCFG_V30, channel, &*raw, "Set motion config")
+    }
+}
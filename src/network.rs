@@ -0,0 +1,184 @@
+use std::{net::Ipv4Addr, os::raw::c_char};
+
+use crate::{
+    NET_DVR_GET_NETCFG_V30, NET_DVR_GET_NETCFG_V50, NET_DVR_IPADDR, NET_DVR_NETCFG_V30,
+    NET_DVR_NETCFG_V50, NET_DVR_NOSUPPORT, NET_DVR_SET_NETCFG_V30, NET_DVR_SET_NETCFG_V50,
+    device::HikDevice,
+    error::HikError,
+    ffi_util::{c_array_to_string, copy_to_c_array},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NetworkConfig {
+    // 以下均为第一个网口的参数
+    pub ipv4_address: Ipv4Addr,
+    pub netmask: Ipv4Addr,
+    pub gateway: Ipv4Addr,
+    pub primary_dns: Option<Ipv4Addr>,
+    pub secondary_dns: Option<Ipv4Addr>,
+    pub dhcp: bool,
+    pub http_port: u16,
+    // SDK 登录端口
+    pub device_port: u16,
+}
+
+impl NetworkConfig {
+    // 修改这些字段后原有的连接会断开
+    fn changes_session(&self, other: &NetworkConfig) -> bool {
+        self.ipv4_address != other.ipv4_address
+            || self.device_port != other.device_port
+            || self.dhcp != other.dhcp
+    }
+}
+
+// V30 与 V50 前半部分字段完全一致
+macro_rules! impl_net_cfg {
+    ($cfg:ty) => {
+        impl From<&$cfg> for NetworkConfig {
+            fn from(cfg: &$cfg) -> Self {
+                let ethernet = &cfg.struEtherNet[0];
+                Self {
+                    ipv4_address: parse_ipv4(&ethernet.struDVRIP).unwrap_or(Ipv4Addr::UNSPECIFIED),
+                    netmask: parse_ipv4(&ethernet.struDVRIPMask).unwrap_or(Ipv4Addr::UNSPECIFIED),
+                    gateway: parse_ipv4(&cfg.struGatewayIpAddr).unwrap_or(Ipv4Addr::UNSPECIFIED),
+                    primary_dns: parse_ipv4(&cfg.struDnsServer1IpAddr),
+                    secondary_dns: parse_ipv4(&cfg.struDnsServer2IpAddr),
+                    dhcp: cfg.byUseDhcp == 1,
+                    http_port: cfg.wHttpPortNo,
+                    device_port: ethernet.wDVRPort,
+                }
+            }
+        }
+
+        impl ApplyNetworkConfig for $cfg {
+            fn apply(&mut self, config: &NetworkConfig) -> anyhow::Result<()> {
+                let ethernet = &mut self.struEtherNet[0];
+                write_ipv4(
+                    &mut ethernet.struDVRIP,
+                    Some(config.ipv4_address),
+                    "IPv4 address",
+                )?;
+                write_ipv4(&mut ethernet.struDVRIPMask, Some(config.netmask), "Netmask")?;
+                ethernet.wDVRPort = config.device_port;
+                write_ipv4(&mut self.struGatewayIpAddr, Some(config.gateway), "Gateway")?;
+                write_ipv4(
+                    &mut self.struDnsServer1IpAddr,
+                    config.primary_dns,
+                    "Primary DNS",
+                )?;
+                write_ipv4(
+                    &mut self.struDnsServer2IpAddr,
+                    config.secondary_dns,
+                    "Secondary DNS",
+                )?;
+                self.byUseDhcp = config.dhcp as u8;
+                self.wHttpPortNo = config.http_port;
+                Ok(())
+            }
+        }
+    };
+}
+
+trait ApplyNetworkConfig {
+    fn apply(&mut self, config: &NetworkConfig) -> anyhow::Result<()>;
+}
+
+impl_net_cfg!(NET_DVR_NETCFG_V30);
+impl_net_cfg!(NET_DVR_NETCFG_V50);
+
+enum RawNetCfg {
+    V50(Box<NET_DVR_NETCFG_V50>),
+    V30(Box<NET_DVR_NETCFG_V30>),
+}
+
+impl HikDevice {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_network_config(&self) -> anyhow::Result<NetworkConfig> {
+        Ok(match self.get_raw_network_config()? {
+            RawNetCfg::V50(cfg) => NetworkConfig::from(&*cfg),
+            RawNetCfg::V30(cfg) => NetworkConfig::from(&*cfg),
+        })
+    }
+
+    // 修改 IP、端口或 DHCP 后设备会断开当前连接，登录句柄随之失效，需要用新地址重新登录
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn set_network_config(&mut self, config: NetworkConfig) -> anyhow::Result<()> {
+        let raw = self.get_raw_network_config()?;
+        let old = match &raw {
+            RawNetCfg::V50(cfg) => NetworkConfig::from(&**cfg),
+            RawNetCfg::V30(cfg) => NetworkConfig::from(&**cfg),
+        };
+
+        match raw {
+            RawNetCfg::V50(mut cfg) => {
+                cfg.apply(&config)?;
+                self.set_dvr_config(NET_DVR_SET_NETCFG_V50, 0, &*cfg, "Set network config")?;
+            }
+            RawNetCfg::V30(mut cfg) => {
+                cfg.apply(&config)?;
+                self.set_dvr_config(NET_DVR_SET_NETCFG_V30, 0, &*cfg, "Set network config")?;
+            }
+        }
+
+        if old.changes_session(&config) {
+            self.invalidate_session();
+        }
+        Ok(())
+    }
+
+    // 设备级的组播地址，SDK 组播预览使用该地址，未配置时返回 None
+    pub(crate) fn multicast_address(&self) -> anyhow::Result<Option<Ipv4Addr>> {
+        Ok(match self.get_raw_network_config()? {
+            RawNetCfg::V50(cfg) => parse_ipv4(&cfg.struMulticastIpAddr),
+            RawNetCfg::V30(cfg) => parse_ipv4(&cfg.struMulticastIpAddr),
+        })
+    }
+
+    // 优先使用 V50，老固件不支持时回退到 V30
+    fn get_raw_network_config(&self) -> anyhow::Result<RawNetCfg> {
+        match self.get_dvr_config(NET_DVR_GET_NETCFG_V50, 0, "Get network config") {
+            Ok(cfg) => Ok(RawNetCfg::V50(Box::new(cfg))),
+            Err(e) if is_not_supported(&e) => {
+                let cfg = self.get_dvr_config(NET_DVR_GET_NETCFG_V30, 0, "Get network config")?;
+                Ok(RawNetCfg::V30(Box::new(cfg)))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+fn is_not_supported(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<HikError>()
+        .and_then(HikError::sdk_code)
+        == Some(NET_DVR_NOSUPPORT as i32)
+}
+
+pub(crate) fn parse_ipv4(addr: &NET_DVR_IPADDR) -> Option<Ipv4Addr> {
+    c_array_to_string(&addr.sIpV4)
+        .parse()
+        .ok()
+        .filter(|ip: &Ipv4Addr| !ip.is_unspecified())
+}
+
+fn write_ipv4(
+    addr: &mut NET_DVR_IPADDR,
+    ip: Option<Ipv4Addr>,
+    field: &'static str,
+) -> anyhow::Result<()> {
+    let dst: &mut [c_char] = &mut addr.sIpV4;
+    match ip {
+        Some(ip) => copy_to_c_array(dst, &ip.to_string(), field),
+        None => {
+            dst.fill(0);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use crate::{
+    DWORD, NET_DVR_PICCFG_V40, NET_DVR_SET_PICCFG_V40,
+    device::HikDevice,
+    ffi_util::{copy_to_gbk_array, decode_device_string},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DateFormat {
+    // XXXX-XX-XX 年月日
+    YearMonthDay,
+    // XX-XX-XXXX 月日年
+    MonthDayYear,
+    // XXXX年XX月XX日
+    YearMonthDayChinese,
+    // XX月XX日XXXX年
+    MonthDayYearChinese,
+    // XX-XX-XXXX 日月年
+    DayMonthYear,
+    // XX日XX月XXXX年
+    DayMonthYearChinese,
+    // xx/xx/xxxx 月/日/年
+    MonthDayYearSlash,
+    // xxxx/xx/xx 年/月/日
+    YearMonthDaySlash,
+    // xx/xx/xxxx 日/月/年
+    DayMonthYearSlash,
+    Other(u8),
+}
+
+impl From<u8> for DateFormat {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => DateFormat::YearMonthDay,
+            1 => DateFormat::MonthDayYear,
+            2 => DateFormat::YearMonthDayChinese,
+            3 => DateFormat::MonthDayYearChinese,
+            4 => DateFormat::DayMonthYear,
+            5 => DateFormat::DayMonthYearChinese,
+            6 => DateFormat::MonthDayYearSlash,
+            7 => DateFormat::YearMonthDaySlash,
+            8 => DateFormat::DayMonthYearSlash,
+            other => DateFormat::Other(other),
+        }
+    }
+}
+
+impl From<DateFormat> for u8 {
+    fn from(value: DateFormat) -> Self {
+        match value {
+            DateFormat::YearMonthDay => 0,
+            DateFormat::MonthDayYear => 1,
+            DateFormat::YearMonthDayChinese => 2,
+            DateFormat::MonthDayYearChinese => 3,
+            DateFormat::DayMonthYear => 4,
+            DateFormat::DayMonthYearChinese => 5,
+            DateFormat::MonthDayYearSlash => 6,
+            DateFormat::YearMonthDaySlash => 7,
+            DateFormat::DayMonthYearSlash => 8,
+            DateFormat::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TimeFormat {
+    Hour24,
+    Hour12,
+    Other(u8),
+}
+
+impl From<u8> for TimeFormat {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => TimeFormat::Hour24,
+            1 => TimeFormat::Hour12,
+            other => TimeFormat::Other(other),
+        }
+    }
+}
+
+impl From<TimeFormat> for u8 {
+    fn from(value: TimeFormat) -> Self {
+        match value {
+            TimeFormat::Hour24 => 0,
+            TimeFormat::Hour12 => 1,
+            TimeFormat::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OsdAttribute {
+    TransparentFlashing,
+    Transparent,
+    Flashing,
+    Opaque,
+    Other(u8),
+}
+
+impl From<u8> for OsdAttribute {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => OsdAttribute::TransparentFlashing,
+            2 => OsdAttribute::Transparent,
+            3 => OsdAttribute::Flashing,
+            4 => OsdAttribute::Opaque,
+            other => OsdAttribute::Other(other),
+        }
+    }
+}
+
+impl From<OsdAttribute> for u8 {
+    fn from(value: OsdAttribute) -> Self {
+        match value {
+            OsdAttribute::TransparentFlashing => 1,
+            OsdAttribute::Transparent => 2,
+            OsdAttribute::Flashing => 3,
+            OsdAttribute::Opaque => 4,
+            OsdAttribute::Other(other) => other,
+        }
+    }
+}
+
+pub struct PictureConfig {
+    pub name: String,
+    pub show_name: bool,
+    // 以 704*576 为基准的坐标
+    pub name_position: (u16, u16),
+    // 是否叠加日期时间
+    pub show_date: bool,
+    pub date_position: (u16, u16),
+    pub date_format: DateFormat,
+    pub time_format: TimeFormat,
+    pub show_week: bool,
+    pub osd_attribute: OsdAttribute,
+    // 图像参数取第一个时间段，范围 0-255
+    pub brightness: u8,
+    pub contrast: u8,
+    pub saturation: u8,
+    pub hue: u8,
+    // 保留设备返回的原始参数，set 时只修改上面的字段
+    raw: Box<NET_DVR_PICCFG_V40>,
+}
+
+impl PictureConfig {
+    fn from_raw(raw: Box<NET_DVR_PICCFG_V40>) -> Self {
+        let color = &raw.struViColor.struColor[0];
+        Self {
+            name: decode_device_string(&raw.sChanName),
+            show_name: raw.dwShowChanName == 1,
+            name_position: (raw.wShowNameTopLeftX, raw.wShowNameTopLeftY),
+            show_date: raw.dwShowOsd == 1,
+            date_position: (raw.wOSDTopLeftX, raw.wOSDTopLeftY),
+            date_format: DateFormat::from(raw.byOSDType),
+            time_format: TimeFormat::from(raw.byHourOSDType),
+            show_week: raw.byDispWeek == 1,
+            osd_attribute: OsdAttribute::from(raw.byOSDAttrib),
+            brightness: color.byBrightness,
+            contrast: color.byContrast,
+            saturation: color.bySaturation,
+            hue: color.byHue,
+            raw,
+        }
+    }
+
+    fn to_raw(&self) -> anyhow::Result<Box<NET_DVR_PICCFG_V40>> {
+        let mut raw = self.raw.clone();
+        // 设备按 GB2312 解析通道名称
+        copy_to_gbk_array(&mut raw.sChanName, &self.name, "Channel name")?;
+        raw.dwShowChanName = self.show_name as DWORD;
+        (raw.wShowNameTopLeftX, raw.wShowNameTopLeftY) = self.name_position;
+        raw.dwShowOsd = self.show_date as DWORD;
+        (raw.wOSDTopLeftX, raw.wOSDTopLeftY) = self.date_position;
+        raw.byOSDType = self.date_format.into();
+        raw.byHourOSDType = self.time_format.into();
+        raw.byDispWeek = self.show_week as u8;
+        raw.byOSDAttrib = self.osd_attribute.into();
+        let color = &mut raw.struViColor.struColor[0];
+        color.byBrightness = self.brightness;
+        color.byContrast = self.contrast;
+        color.bySaturation = self.saturation;
+        color.byHue = self.hue;
+        Ok(raw)
+    }
+}
+
+impl HikDevice {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn get_picture_config(&self, channel: u16) -> anyhow::Result<PictureConfig> {
+        let raw = self.get_pic_config(channel)?;
+        Ok(PictureConfig::from_raw(Box::new(raw)))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn set_picture_config(&self, channel: u16, config: &PictureConfig) -> anyhow::Result<()> {
+        let channel = self.resolve_channel(channel)?;
+        let raw = config.to_raw()?;
+        self.set_dvr_config(NET_DVR_SET_PICCFG_V40, channel, &*raw, "Set picture config")
+    }
+}
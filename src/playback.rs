@@ -0,0 +1,454 @@
+use std::{
+    mem,
+    os::raw::c_void,
+    slice,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use chrono::{DateTime, Local};
+
+use crate::{
+    BYTE, DWORD, LONG, NET_DVR_AUDIOSTREAMDATA, NET_DVR_PLAYFAST, NET_DVR_PLAYGETPOS,
+    NET_DVR_PLAYGETTIME, NET_DVR_PLAYNORMAL, NET_DVR_PLAYPAUSE, NET_DVR_PLAYRESTART,
+    NET_DVR_PLAYSETPOS, NET_DVR_PLAYSLOW, NET_DVR_PLAYSTART, NET_DVR_STREAMDATA, NET_DVR_SYSHEAD,
+    NET_DVR_SetPlayDataCallBack_V40, NET_DVR_StopPlayBack, NET_DVR_TIME,
+    common::{HandleKind, get_last_error_code, unwatch_handle, watch_handle},
+    error::HikError,
+    ffi_util::{call_user_callback, lock_unpoisoned},
+    sdk::{NetSdk, RealSdk, sdk_error_from},
+    session::SessionState,
+    trace::sdk_call,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackSpeed {
+    Slow8,
+    Slow4,
+    Slow2,
+    Normal,
+    Fast2,
+    Fast4,
+    Fast8,
+    Fast16,
+}
+
+impl PlaybackSpeed {
+    // 相对正常速度需要的 FAST(+)/SLOW(-) 档数
+    fn steps(self) -> i8 {
+        match self {
+            PlaybackSpeed::Slow8 => -3,
+            PlaybackSpeed::Slow4 => -2,
+            PlaybackSpeed::Slow2 => -1,
+            PlaybackSpeed::Normal => 0,
+            PlaybackSpeed::Fast2 => 1,
+            PlaybackSpeed::Fast4 => 2,
+            PlaybackSpeed::Fast8 => 3,
+            PlaybackSpeed::Fast16 => 4,
+        }
+    }
+}
+
+// 下载与回放共用 NET_DVR_PlayBackControl_V40 的控制命令
+pub trait PlaybackControl {
+    fn play_handle(&self) -> LONG;
+
+    fn is_started(&self) -> bool;
+
+    // 所属会话已登出时返回 HikError::SessionClosed
+    fn ensure_open(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    // 执行控制命令使用的 SDK，HikDownload 与 HikPlayback 使用创建它的设备的 SDK
+    fn sdk(&self) -> &dyn NetSdk {
+        &RealSdk
+    }
+
+    fn pause(&self) -> anyhow::Result<()> {
+        self.ensure_started()?;
+        play_back_control(
+            self.sdk(),
+            self.play_handle(),
+            NET_DVR_PLAYPAUSE,
+            None,
+            "Pause",
+        )
+    }
+
+    fn resume(&self) -> anyhow::Result<()> {
+        self.ensure_started()?;
+        play_back_control(
+            self.sdk(),
+            self.play_handle(),
+            NET_DVR_PLAYRESTART,
+            None,
+            "Resume",
+        )
+    }
+
+    fn set_speed(&self, speed: PlaybackSpeed) -> anyhow::Result<()> {
+        self.ensure_started()?;
+        // FAST/SLOW 每次只调整一档，先回到正常速度再逐档调整
+        play_back_control(
+            self.sdk(),
+            self.play_handle(),
+            NET_DVR_PLAYNORMAL,
+            None,
+            "Set speed",
+        )?;
+        let steps = speed.steps();
+        let code = if steps > 0 {
+            NET_DVR_PLAYFAST
+        } else {
+            NET_DVR_PLAYSLOW
+        };
+        for _ in 0..steps.unsigned_abs() {
+            play_back_control(self.sdk(), self.play_handle(), code, None, "Set speed")?;
+        }
+        Ok(())
+    }
+
+    fn seek_percent(&self, percent: u8) -> anyhow::Result<()> {
+        self.ensure_started()?;
+        if percent > 100 {
+            return Err(anyhow::anyhow!(
+                "Seek position must be 0-100, got {}",
+                percent
+            ));
+        }
+        // 定位到 100 时会立即结束，但句柄仍需由 stop/Drop 释放一次
+        play_back_control(
+            self.sdk(),
+            self.play_handle(),
+            NET_DVR_PLAYSETPOS,
+            Some(percent as DWORD),
+            "Seek",
+        )
+    }
+
+    fn get_position_time(&self) -> anyhow::Result<DateTime<Local>> {
+        self.ensure_started()?;
+        let time: NET_DVR_TIME = play_back_query(
+            self.sdk(),
+            self.play_handle(),
+            NET_DVR_PLAYGETTIME,
+            "Get position time",
+        )?;
+        DateTime::try_from(time)
+    }
+
+    fn ensure_started(&self) -> anyhow::Result<()> {
+        self.ensure_open()?;
+        if !self.is_started() {
+            return Err(anyhow::anyhow!("Playback not started"));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn play_back_control(
+    sdk: &dyn NetSdk,
+    handle: LONG,
+    control_code: DWORD,
+    input: Option<DWORD>,
+    action: &'static str,
+) -> anyhow::Result<()> {
+    let mut in_buffer = input.map(DWORD::to_ne_bytes);
+    let in_buffer: &mut [u8] = match in_buffer.as_mut() {
+        Some(bytes) => bytes,
+        None => &mut [],
+    };
+    let mut out_len: DWORD = 0;
+
+    let res = sdk.play_back_control(handle, control_code, in_buffer, &mut [], &mut out_len);
+    if res != 1 {
+        return Err(sdk_error_from(sdk, action));
+    }
+    Ok(())
+}
+
+// T 只用于 DWORD、NET_DVR_TIME 这类纯数据类型
+pub(crate) fn play_back_query<T: Default + Copy>(
+    sdk: &dyn NetSdk,
+    handle: LONG,
+    control_code: DWORD,
+    action: &'static str,
+) -> anyhow::Result<T> {
+    let mut output = T::default();
+    let mut out_len = mem::size_of::<T>() as DWORD;
+    let out_buffer =
+        unsafe { slice::from_raw_parts_mut(&mut output as *mut T as *mut u8, mem::size_of::<T>()) };
+
+    let res = sdk.play_back_control(handle, control_code, &mut [], out_buffer, &mut out_len);
+    if res != 1 {
+        return Err(sdk_error_from(sdk, action));
+    }
+    Ok(output)
+}
+
+#[derive(Debug)]
+pub enum PlaybackEvent<'a> {
+    // 系统头，需先于码流数据送入解码器
+    Header(&'a [u8]),
+    // PS 封装的音视频复合流
+    Stream(&'a [u8]),
+    Audio(&'a [u8]),
+    Other { data_type: u32, data: &'a [u8] },
+    // 回放结束，只会送达一次
+    Ended,
+}
+
+type PlaybackCallback = Box<dyn FnMut(PlaybackEvent<'_>) + Send>;
+
+struct PlaybackContext {
+    callback: Mutex<PlaybackCallback>,
+    ended: AtomicBool,
+}
+
+impl PlaybackContext {
+    fn emit(&self, event: PlaybackEvent<'_>) {
+        let mut callback = lock_unpoisoned(&self.callback);
+        call_user_callback("playback", || callback(event));
+    }
+
+    fn emit_ended(&self) {
+        if !self.ended.swap(true, Ordering::SeqCst) {
+            self.emit(PlaybackEvent::Ended);
+        }
+    }
+}
+
+pub struct HikPlayback {
+    handle: LONG,
+    sdk: Arc<dyn NetSdk>,
+    // 登出时由会话停止句柄
+    session: Arc<SessionState>,
+    is_start: AtomicBool,
+    is_stopped: AtomicBool,
+    // 由异常回调置为 false
+    healthy: Arc<AtomicBool>,
+    // SDK 回调持有该指针，必须在 NET_DVR_StopPlayBack 之后才能释放
+    context: Box<PlaybackContext>,
+}
+
+impl HikPlayback {
+    pub(crate) fn new<F>(
+        sdk: Arc<dyn NetSdk>,
+        user_id: LONG,
+        session: Arc<SessionState>,
+        handle: LONG,
+        callback: F,
+    ) -> anyhow::Result<Self>
+    where
+        F: FnMut(PlaybackEvent<'_>) + Send + 'static,
+    {
+        session.register(HandleKind::Playback, handle, move || unsafe {
+            sdk_call!(NET_DVR_StopPlayBack(handle));
+        });
+        let playback = Self {
+            handle,
+            sdk,
+            session,
+            is_start: AtomicBool::new(false),
+            is_stopped: AtomicBool::new(false),
+            healthy: watch_handle(HandleKind::Playback, user_id, handle),
+            context: Box::new(PlaybackContext {
+                callback: Mutex::new(Box::new(callback)),
+                ended: AtomicBool::new(false),
+            }),
+        };
+
+        let user = &*playback.context as *const PlaybackContext as *mut c_void;
+        let res = unsafe {
+            sdk_call!(NET_DVR_SetPlayDataCallBack_V40(
+                handle,
+                Some(play_data_callback),
+                user
+            ))
+        };
+        if res != 1 {
+            let error_code = get_last_error_code();
+            // playback 在这里被 drop，会负责停止句柄
+            return Err(anyhow::anyhow!(
+                "Set play data callback failed: error code {}",
+                error_code
+            ));
+        }
+
+        Ok(playback)
+    }
+
+    pub fn start(&mut self) -> anyhow::Result<()> {
+        self.session.ensure_open()?;
+        if self.is_start.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        if self.is_stopped.load(Ordering::Relaxed) {
+            return Err(anyhow::anyhow!("Playback already stopped"));
+        }
+        play_back_control(
+            &*self.sdk,
+            self.handle,
+            NET_DVR_PLAYSTART,
+            None,
+            "Start playback",
+        )?;
+        self.is_start.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::SeqCst)
+    }
+
+    pub fn get_progress(&self) -> anyhow::Result<i32> {
+        self.ensure_started()?;
+        if !self.is_healthy() {
+            return Err(anyhow::anyhow!("Playback connection lost"));
+        }
+        let pos: DWORD = play_back_query(
+            &*self.sdk,
+            self.handle,
+            NET_DVR_PLAYGETPOS,
+            "Get playback progress",
+        )?;
+        match pos {
+            0..=99 => Ok(pos as i32),
+            100 => {
+                self.context.emit_ended();
+                Ok(100)
+            }
+            200 => Err(anyhow::anyhow!("Get playback network error")),
+            _ => Err(anyhow::anyhow!("Get playback progress failed")),
+        }
+    }
+
+    pub fn stop(&self) -> anyhow::Result<()> {
+        self.is_start.store(false, Ordering::Relaxed);
+        if self.is_stopped.swap(true, Ordering::Relaxed) {
+            return Ok(());
+        }
+        if !self.session.unregister(HandleKind::Playback, self.handle) {
+            return Err(HikError::SessionClosed.into());
+        }
+        let res = unsafe { sdk_call!(NET_DVR_StopPlayBack(self.handle)) };
+        if res != 1 {
+            let error_code = get_last_error_code();
+            return Err(anyhow::anyhow!(
+                "Stop playback failed: error code {}",
+                error_code
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl PlaybackControl for HikPlayback {
+    fn play_handle(&self) -> LONG {
+        self.handle
+    }
+
+    fn ensure_open(&self) -> anyhow::Result<()> {
+        Ok(self.session.ensure_open()?)
+    }
+
+    fn sdk(&self) -> &dyn NetSdk {
+        &*self.sdk
+    }
+
+    fn is_started(&self) -> bool {
+        self.is_start.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for HikPlayback {
+    fn drop(&mut self) {
+        let _ = self.stop();
+        unwatch_handle(HandleKind::Playback, self.handle);
+    }
+}
+
+unsafe extern "C" fn play_data_callback(
+    _play_handle: LONG,
+    data_type: DWORD,
+    buffer: *mut BYTE,
+    buf_size: DWORD,
+    user: *mut c_void,
+) {
+    if user.is_null() {
+        return;
+    }
+    let context = unsafe { &*(user as *const PlaybackContext) };
+
+    // 回放结束时 SDK 会送来长度为 0 的数据
+    if buffer.is_null() || buf_size == 0 {
+        context.emit_ended();
+        return;
+    }
+
+    let data = unsafe { std::slice::from_raw_parts(buffer as *const u8, buf_size as usize) };
+    let event = match data_type {
+        NET_DVR_SYSHEAD => PlaybackEvent::Header(data),
+        NET_DVR_STREAMDATA => PlaybackEvent::Stream(data),
+        NET_DVR_AUDIOSTREAMDATA => PlaybackEvent::Audio(data),
+        data_type => PlaybackEvent::Other { data_type, data },
+    };
+    context.emit(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ptr, sync::atomic::AtomicUsize};
+
+    use super::*;
+    use crate::sdk::MockSdk;
+
+    #[test]
+    fn panicking_callback_still_delivers_end_and_stops() {
+        // 测试中 NET_DVR_SetPlayDataCallBack_V40 返回失败，直接构造回放
+        let handle = 9201;
+        let session = Arc::new(SessionState::default());
+        session.register(HandleKind::Playback, handle, || {});
+        let ended = Arc::new(AtomicUsize::new(0));
+        let counter = ended.clone();
+        let playback = HikPlayback {
+            handle,
+            sdk: Arc::new(MockSdk::new()),
+            session: session.clone(),
+            is_start: AtomicBool::new(true),
+            is_stopped: AtomicBool::new(false),
+            healthy: Arc::new(AtomicBool::new(true)),
+            context: Box::new(PlaybackContext {
+                callback: Mutex::new(Box::new(move |event| match event {
+                    PlaybackEvent::Ended => {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                    }
+                    _ => panic!("callback panicked"),
+                })),
+                ended: AtomicBool::new(false),
+            }),
+        };
+
+        let user = &*playback.context as *const PlaybackContext as *mut c_void;
+        let mut data = [0u8; 4];
+        unsafe {
+            play_data_callback(
+                handle,
+                NET_DVR_STREAMDATA,
+                data.as_mut_ptr(),
+                data.len() as DWORD,
+                user,
+            );
+            play_data_callback(handle, NET_DVR_STREAMDATA, ptr::null_mut(), 0, user);
+        }
+        assert_eq!(ended.load(Ordering::SeqCst), 1);
+        assert_eq!(session.handles(), [(HandleKind::Playback, handle)]);
+
+        // 测试中 NET_DVR_StopPlayBack 返回失败，句柄仍需从会话中移除
+        let _ = playback.stop();
+        assert!(session.handles().is_empty());
+    }
+}
@@ -0,0 +1,822 @@
+use std::{
+    os::raw::c_void,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+
+use crate::{
+    BYTE, DWORD, LONG, NET_DVR_AUDIOSTREAMDATA, NET_DVR_NOSUPPORT, NET_DVR_NOT_SUPPORT,
+    NET_DVR_PREVIEWINFO, NET_DVR_RealPlay_V40, NET_DVR_STREAMDATA, NET_DVR_SYSHEAD,
+    NET_DVR_SaveRealData_V30, NET_DVR_StopRealPlay, NET_DVR_StopSaveRealData,
+    cancel::{CancellationToken, wait_stop},
+    common::{HandleKind, unwatch_handle, watch_handle},
+    device::{HikDevice, TransportMode, sdk_code, sdk_error},
+    error::HikError,
+    ffi_util::{call_user_callback, lock_unpoisoned, path_to_cstring},
+    session::SessionState,
+    trace::sdk_call,
+};
+
+// 后台线程检查是否需要切换文件的间隔
+const SAVE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StreamType {
+    #[default]
+    Main,
+    Sub,
+    Third,
+}
+
+impl From<StreamType> for DWORD {
+    fn from(value: StreamType) -> Self {
+        match value {
+            StreamType::Main => 0,
+            StreamType::Sub => 1,
+            StreamType::Third => 2,
+        }
+    }
+}
+
+/// 取流方式，对应 NET_DVR_PREVIEWINFO 的 dwLinkMode 与 byProtoType
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LinkMode {
+    #[default]
+    Tcp,
+    // 延迟低，丢包时会花屏
+    Udp,
+    // 需要设备配置多播地址，部分设备只支持主码流多播
+    Multicast,
+    // 以下两种走 RTSP 协议
+    Rtp,
+    Https,
+}
+
+impl From<TransportMode> for LinkMode {
+    fn from(transport: TransportMode) -> Self {
+        match transport {
+            TransportMode::Tcp => LinkMode::Tcp,
+            TransportMode::Udp => LinkMode::Udp,
+        }
+    }
+}
+
+impl LinkMode {
+    // (dwLinkMode, byProtoType)
+    fn link_params(self) -> (DWORD, BYTE) {
+        match self {
+            LinkMode::Tcp => (0, 0),
+            LinkMode::Udp => (1, 0),
+            LinkMode::Multicast => (2, 0),
+            LinkMode::Rtp => (3, 1),
+            LinkMode::Https => (7, 1),
+        }
+    }
+}
+
+/// SDK 播放缓冲区的帧数（dwDisplayBufNum），只影响 SDK 内部解码（例如抓 BMP），
+/// 不影响回调送出码流的时机
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PreviewBuffer {
+    // 由 SDK 决定
+    #[default]
+    Default,
+    // 1 帧，延迟最低
+    LowLatency,
+    // 网络抖动较大时画面更平滑
+    Smooth,
+    // 1-50 帧
+    Frames(u32),
+}
+
+const MAX_DISPLAY_BUFFER_FRAMES: u32 = 50;
+
+impl PreviewBuffer {
+    fn frames(self) -> anyhow::Result<DWORD> {
+        match self {
+            PreviewBuffer::Default => Ok(0),
+            PreviewBuffer::LowLatency => Ok(1),
+            PreviewBuffer::Smooth => Ok(25),
+            PreviewBuffer::Frames(frames @ 1..=MAX_DISPLAY_BUFFER_FRAMES) => Ok(frames),
+            PreviewBuffer::Frames(frames) => Err(anyhow::anyhow!(
+                "Preview buffer must be 1-{} frames, got {}",
+                MAX_DISPLAY_BUFFER_FRAMES,
+                frames
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PreviewOptions {
+    pub stream_type: StreamType,
+    pub link_mode: LinkMode,
+    // 阻塞取流：连接成功或失败后才返回
+    pub blocked: bool,
+    pub buffer: PreviewBuffer,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        Self {
+            stream_type: StreamType::Main,
+            link_mode: LinkMode::Tcp,
+            blocked: true,
+            buffer: PreviewBuffer::Default,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VideoCodec {
+    // 海康早期私有 H.264
+    Hik264,
+    Mpeg2,
+    Mpeg4,
+    Mjpeg,
+    H264,
+    H265,
+    Other(u16),
+}
+
+impl From<u16> for VideoCodec {
+    fn from(value: u16) -> Self {
+        match value {
+            0x0001 => VideoCodec::Hik264,
+            0x0002 => VideoCodec::Mpeg2,
+            0x0003 => VideoCodec::Mpeg4,
+            0x0004 => VideoCodec::Mjpeg,
+            0x0005 => VideoCodec::H265,
+            0x0100 => VideoCodec::H264,
+            other => VideoCodec::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AudioCodec {
+    None,
+    G711U,
+    G711A,
+    G722,
+    G726,
+    Aac,
+    Mpeg,
+    Pcm,
+    Other(u16),
+}
+
+impl From<u16> for AudioCodec {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => AudioCodec::None,
+            0x7110 => AudioCodec::G711U,
+            0x7111 => AudioCodec::G711A,
+            0x7221 => AudioCodec::G722,
+            0x7260 | 0x7261 => AudioCodec::G726,
+            0x2001 => AudioCodec::Aac,
+            0x2000 => AudioCodec::Mpeg,
+            0x7001 => AudioCodec::Pcm,
+            other => AudioCodec::Other(other),
+        }
+    }
+}
+
+/// 设备实际发送的码流参数，编码格式来自系统头，分辨率来自码流中的 H.264 SPS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PreviewStreamInfo {
+    pub video_codec: VideoCodec,
+    pub audio_codec: AudioCodec,
+    pub audio_channels: u8,
+    pub audio_bits_per_sample: u8,
+    pub audio_sample_rate: u32,
+    // 需要 remux feature 解析 SPS，收到第一个 SPS 之前或非 H.264 码流时为 None
+    pub width: Option<u16>,
+    pub height: Option<u16>,
+}
+
+// 系统头为 40 字节的 IMKH 媒体信息，多字节字段为小端
+const MEDIA_HEAD_LEN: usize = 40;
+
+impl PreviewStreamInfo {
+    fn from_head(head: &[u8]) -> Option<Self> {
+        if head.len() < MEDIA_HEAD_LEN || &head[..4] != b"IMKH" {
+            return None;
+        }
+        let u16_at = |pos: usize| u16::from_le_bytes([head[pos], head[pos + 1]]);
+        Some(Self {
+            video_codec: u16_at(10).into(),
+            audio_codec: u16_at(12).into(),
+            audio_channels: head[14],
+            audio_bits_per_sample: head[15],
+            audio_sample_rate: u32::from_le_bytes([head[16], head[17], head[18], head[19]]),
+            width: None,
+            height: None,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum PreviewEvent<'a> {
+    // 系统头，需先于码流数据送入解码器
+    Header(&'a [u8]),
+    // PS 封装的音视频复合流
+    Stream(&'a [u8]),
+    Audio(&'a [u8]),
+    Other { data_type: u32, data: &'a [u8] },
+}
+
+type PreviewCallback = Box<dyn FnMut(PreviewEvent<'_>) + Send>;
+
+struct PreviewContext {
+    callback: Mutex<PreviewCallback>,
+    info: Mutex<Option<PreviewStreamInfo>>,
+}
+
+impl PreviewContext {
+    fn update_info(&self, data_type: DWORD, data: &[u8]) {
+        let mut info = lock_unpoisoned(&self.info);
+        match (data_type, info.as_mut()) {
+            (NET_DVR_SYSHEAD, _) => *info = PreviewStreamInfo::from_head(data),
+            #[cfg(feature = "remux")]
+            (NET_DVR_STREAMDATA, Some(info))
+                if info.width.is_none() && info.video_codec == VideoCodec::H264 =>
+            {
+                if let Some((width, height)) = find_sps_resolution(data) {
+                    info.width = Some(width);
+                    info.height = Some(height);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// PS 包里的 H.264 起始码后 forbidden_zero_bit 为 0，可与 PS/PES 的 stream id 区分
+#[cfg(feature = "remux")]
+fn find_sps_resolution(data: &[u8]) -> Option<(u16, u16)> {
+    let mut pos = 0;
+    while let Some(offset) = data[pos..].windows(3).position(|w| w == [0x00, 0x00, 0x01]) {
+        let start = pos + offset + 3;
+        let header = *data.get(start)?;
+        if header & 0x80 == 0 && header & 0x1f == 7 {
+            return crate::remux::sps_resolution(&data[start..]);
+        }
+        pos = start;
+    }
+    None
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StreamSaveFormat {
+    // 不转换，设备原始码流即 PS 封装
+    #[default]
+    Ps,
+    // 由 SDK 转封装为 MP4，部分设备或 SDK 版本不支持
+    Mp4,
+}
+
+impl StreamSaveFormat {
+    // NET_DVR_SaveRealData_V30 的 dwTransType
+    fn trans_type(self) -> DWORD {
+        match self {
+            StreamSaveFormat::Ps => 0,
+            StreamSaveFormat::Mp4 => 5,
+        }
+    }
+}
+
+// 分段文件的命名方式，编号或时间戳加在扩展名之前
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RotateNaming {
+    // record_0.mp4、record_1.mp4 ...
+    #[default]
+    Sequence,
+    // record_20240101120000.mp4
+    Timestamp,
+}
+
+/// 预览录像的保存参数
+///
+/// 切换文件时 SDK 立即停止当前文件并打开新文件，不会等到下一个 I 帧，
+/// 因此新文件开头可能有一小段无法解码的画面
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SaveOptions {
+    pub format: StreamSaveFormat,
+    // 单个文件的最长时间，None 为不限制
+    pub max_duration: Option<Duration>,
+    // 单个文件的最大字节数，按 1 秒间隔检查，可能略微超出
+    pub max_size: Option<u64>,
+    // 只在设置了 max_duration 或 max_size 时使用
+    pub rotate_naming: RotateNaming,
+    // 取消后与 stop_saving 效果相同
+    pub cancel: Option<CancellationToken>,
+}
+
+impl SaveOptions {
+    fn rotates(&self) -> bool {
+        self.max_duration.is_some() || self.max_size.is_some()
+    }
+}
+
+struct SaveWorker {
+    // 丢弃 Sender 即可让后台线程停止保存并退出
+    stop_tx: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+    error: Arc<Mutex<Option<anyhow::Error>>>,
+}
+
+pub struct HikPreview {
+    handle: LONG,
+    // 登出时由会话停止句柄
+    session: Arc<SessionState>,
+    // 由异常回调置为 false
+    healthy: Arc<AtomicBool>,
+    saving: Mutex<Option<SaveWorker>>,
+    // SDK 回调持有该指针，必须在 NET_DVR_StopRealPlay 之后才能释放
+    context: Box<PreviewContext>,
+}
+
+impl HikDevice {
+    // 不传窗口句柄，码流全部通过回调送出
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn start_preview<F>(
+        &self,
+        channel: u16,
+        stream_type: StreamType,
+        callback: F,
+    ) -> anyhow::Result<HikPreview>
+    where
+        F: FnMut(PreviewEvent<'_>) + Send + 'static,
+    {
+        self.start_preview_with(
+            channel,
+            PreviewOptions {
+                stream_type,
+                link_mode: self.transport().into(),
+                ..Default::default()
+            },
+            callback,
+        )
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn start_preview_with<F>(
+        &self,
+        channel: u16,
+        options: PreviewOptions,
+        callback: F,
+    ) -> anyhow::Result<HikPreview>
+    where
+        F: FnMut(PreviewEvent<'_>) + Send + 'static,
+    {
+        let channel = self.resolve_channel(channel)?;
+        let display_buffer = options.buffer.frames()?;
+        let context = Box::new(PreviewContext {
+            callback: Mutex::new(Box::new(callback)),
+            info: Mutex::new(None),
+        });
+
+        let (link_mode, proto_type) = options.link_mode.link_params();
+        let mut preview_info = NET_DVR_PREVIEWINFO {
+            lChannel: channel,
+            dwStreamType: options.stream_type.into(),
+            dwLinkMode: link_mode,
+            byProtoType: proto_type,
+            bBlocked: options.blocked as DWORD,
+            dwDisplayBufNum: display_buffer,
+            ..Default::default()
+        };
+        let user = &*context as *const PreviewContext as *mut c_void;
+        let (lu, handle) = self
+            .with_session(|lu| {
+                self.check_stream_limit(lu)?;
+                let handle = unsafe {
+                    sdk_call!(NET_DVR_RealPlay_V40(
+                        lu,
+                        &mut preview_info,
+                        Some(real_data_callback),
+                        user
+                    ))
+                };
+                if handle < 0 {
+                    return Err(sdk_error("Start preview"));
+                }
+                Ok((lu, handle))
+            })
+            .map_err(|e| match sdk_code(&e) {
+                // 不回退为单播，由调用方决定改用主码流还是其他取流方式
+                Some(code)
+                    if options.link_mode == LinkMode::Multicast
+                        && options.stream_type != StreamType::Main
+                        && (code == NET_DVR_NOSUPPORT as i32
+                            || code == NET_DVR_NOT_SUPPORT as i32) =>
+                {
+                    HikError::Unsupported {
+                        feature: "Multicast preview of sub stream",
+                    }
+                    .into()
+                }
+                _ => e,
+            })?;
+
+        let session = self.session.state();
+        session.register(HandleKind::Preview, handle, move || unsafe {
+            sdk_call!(NET_DVR_StopRealPlay(handle));
+        });
+        Ok(HikPreview {
+            handle,
+            session,
+            healthy: watch_handle(HandleKind::Preview, lu, handle),
+            saving: Mutex::new(None),
+            context,
+        })
+    }
+}
+
+impl HikPreview {
+    pub fn handle(&self) -> LONG {
+        self.handle
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::SeqCst)
+    }
+
+    // 收到系统头之前返回 None
+    pub fn get_stream_info(&self) -> Option<PreviewStreamInfo> {
+        *self.context.info.lock().unwrap()
+    }
+
+    /// 把预览码流同时写入文件，已经在保存时先停止之前的保存
+    ///
+    /// 设置了 max_duration 或 max_size 时按 options.rotate_naming 生成分段文件名
+    pub fn save_to_file(&self, path: impl AsRef<Path>, options: SaveOptions) -> anyhow::Result<()> {
+        self.session.ensure_open()?;
+        self.stop_saving();
+        let path = path.as_ref().to_path_buf();
+        let mut seq = 0;
+        let mut current = segment_path(&path, &options, seq);
+        start_save(self.handle, &current, options.format)?;
+
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let error = Arc::new(Mutex::new(None));
+        let thread_error = error.clone();
+        let handle = self.handle;
+        let thread = thread::spawn(move || {
+            let mut started = Instant::now();
+            while !wait_stop(&stop_rx, options.cancel.as_ref(), SAVE_CHECK_INTERVAL) {
+                let too_long = options
+                    .max_duration
+                    .is_some_and(|max| started.elapsed() >= max);
+                let too_large = options.max_size.is_some_and(|max| {
+                    std::fs::metadata(&current).is_ok_and(|meta| meta.len() >= max)
+                });
+                if !too_long && !too_large {
+                    continue;
+                }
+                stop_save(handle);
+                seq += 1;
+                current = segment_path(&path, &options, seq);
+                if let Err(e) = start_save(handle, &current, options.format) {
+                    *thread_error.lock().unwrap() = Some(e);
+                    return;
+                }
+                started = Instant::now();
+            }
+            stop_save(handle);
+        });
+
+        *self.saving.lock().unwrap() = Some(SaveWorker {
+            stop_tx: Some(stop_tx),
+            thread: Some(thread),
+            error,
+        });
+        Ok(())
+    }
+
+    // 没有在保存时直接返回
+    pub fn stop_saving(&self) {
+        let Some(mut worker) = self.saving.lock().unwrap().take() else {
+            return;
+        };
+        worker.stop_tx.take();
+        if let Some(thread) = worker.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    pub fn is_saving(&self) -> bool {
+        self.saving
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|worker| worker.thread.as_ref().is_some_and(|t| !t.is_finished()))
+    }
+
+    // 切换文件失败的原因，失败后保存停止
+    pub fn take_save_error(&self) -> Option<anyhow::Error> {
+        self.saving
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|worker| worker.error.lock().unwrap().take())
+    }
+}
+
+fn segment_path(path: &Path, options: &SaveOptions, seq: u64) -> PathBuf {
+    if !options.rotates() {
+        return path.to_path_buf();
+    }
+    let suffix = match options.rotate_naming {
+        RotateNaming::Sequence => seq.to_string(),
+        RotateNaming::Timestamp => Local::now().format("%Y%m%d%H%M%S").to_string(),
+    };
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}_{}.{}", stem, suffix, ext.to_string_lossy()),
+        None => format!("{}_{}", stem, suffix),
+    };
+    path.with_file_name(name)
+}
+
+fn start_save(handle: LONG, path: &Path, format: StreamSaveFormat) -> anyhow::Result<()> {
+    let file = path_to_cstring(path, "path")?;
+    let res = unsafe {
+        sdk_call!(NET_DVR_SaveRealData_V30(
+            handle,
+            format.trans_type(),
+            file.as_ptr() as *mut _
+        ))
+    };
+    if res != 1 {
+        return Err(sdk_error("Save preview data"));
+    }
+    Ok(())
+}
+
+fn stop_save(handle: LONG) {
+    unsafe { sdk_call!(NET_DVR_StopSaveRealData(handle)) };
+}
+
+impl Drop for HikPreview {
+    fn drop(&mut self) {
+        // 先停止保存，避免句柄停止后文件一直处于写入状态
+        self.stop_saving();
+        // 登出时已经停止的句柄不再重复停止
+        if self.session.unregister(HandleKind::Preview, self.handle) {
+            unsafe { sdk_call!(NET_DVR_StopRealPlay(self.handle)) };
+        }
+        unwatch_handle(HandleKind::Preview, self.handle);
+    }
+}
+
+unsafe extern "C" fn real_data_callback(
+    _real_handle: LONG,
+    data_type: DWORD,
+    buffer: *mut BYTE,
+    buf_size: DWORD,
+    user: *mut c_void,
+) {
+    if user.is_null() || buffer.is_null() || buf_size == 0 {
+        return;
+    }
+    let context = unsafe { &*(user as *const PreviewContext) };
+    let data = unsafe { std::slice::from_raw_parts(buffer as *const u8, buf_size as usize) };
+    context.update_info(data_type, data);
+    let event = match data_type {
+        NET_DVR_SYSHEAD => PreviewEvent::Header(data),
+        NET_DVR_STREAMDATA => PreviewEvent::Stream(data),
+        NET_DVR_AUDIOSTREAMDATA => PreviewEvent::Audio(data),
+        data_type => PreviewEvent::Other { data_type, data },
+    };
+    let mut callback = lock_unpoisoned(&context.callback);
+    call_user_callback("preview", || callback(event));
+}
+
+#[cfg(feature = "playctrl")]
+mod capture {
+    use std::{
+        os::raw::c_char,
+        sync::mpsc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::{HikPreview, PreviewEvent, StreamType};
+    use crate::{
+        CAPTURE_MODE_BMP_MODE, DWORD, NET_DVR_CapturePicture, NET_DVR_CapturePictureBlock_New,
+        NET_DVR_NOENOUGH_BUF, NET_DVR_SetCapturePictureMode,
+        common::get_last_error_code,
+        device::{HikDevice, sdk_error},
+        error::HikError,
+        ffi_util::to_cstring,
+        trace::sdk_call,
+    };
+
+    const DEFAULT_FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+    // 1080P 的 24 位 BMP 约 6MB，不够时按倍数扩大
+    const BMP_INITIAL_BUFFER_SIZE: usize = 8 * 1024 * 1024;
+    const BMP_MAX_BUFFER_SIZE: usize = 64 * 1024 * 1024;
+    // 收到 I 帧后解码器还需要一点时间才能出图
+    const CAPTURE_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+    // 抓图依赖 SDK 内部调用 PlayCtrl 解码，需要随 SDK 一起部署 PlayCtrl 相关 DLL
+    impl HikPreview {
+        pub fn capture_bmp(&self, path: &str) -> anyhow::Result<()> {
+            set_bmp_mode()?;
+            let path = to_cstring(path, "path")?;
+            let res = unsafe {
+                sdk_call!(NET_DVR_CapturePicture(
+                    self.handle,
+                    path.as_ptr() as *mut c_char
+                ))
+            };
+            if res != 1 {
+                return Err(sdk_error("Capture BMP"));
+            }
+            Ok(())
+        }
+
+        pub fn capture_bmp_data(&self) -> anyhow::Result<Vec<u8>> {
+            set_bmp_mode()?;
+            let mut buffer = vec![0u8; BMP_INITIAL_BUFFER_SIZE];
+            loop {
+                let mut returned: DWORD = 0;
+                let res = unsafe {
+                    sdk_call!(NET_DVR_CapturePictureBlock_New(
+                        self.handle,
+                        buffer.as_mut_ptr() as *mut c_char,
+                        buffer.len() as DWORD,
+                        &mut returned,
+                    ))
+                };
+                if res == 1 {
+                    buffer.truncate(returned as usize);
+                    return Ok(buffer);
+                }
+                if get_last_error_code() == NET_DVR_NOENOUGH_BUF as i32
+                    && buffer.len() < BMP_MAX_BUFFER_SIZE
+                {
+                    buffer.resize(buffer.len() * 2, 0);
+                    continue;
+                }
+                return Err(sdk_error("Capture BMP"));
+            }
+        }
+    }
+
+    impl HikDevice {
+        // 临时开启预览，等到第一个 I 帧后抓取一张 BMP
+        #[cfg_attr(
+            feature = "tracing",
+            tracing::instrument(
+                level = "debug",
+                skip_all,
+                fields(ip = %self.session.host(), channel = channel)
+            )
+        )]
+        pub fn capture_frame(
+            &self,
+            channel: u16,
+            stream_type: StreamType,
+        ) -> anyhow::Result<Vec<u8>> {
+            self.capture_frame_timeout(channel, stream_type, DEFAULT_FRAME_TIMEOUT)
+        }
+
+        #[cfg_attr(
+            feature = "tracing",
+            tracing::instrument(
+                level = "debug",
+                skip_all,
+                fields(ip = %self.session.host(), channel = channel)
+            )
+        )]
+        pub fn capture_frame_timeout(
+            &self,
+            channel: u16,
+            stream_type: StreamType,
+            timeout: Duration,
+        ) -> anyhow::Result<Vec<u8>> {
+            let deadline = Instant::now() + timeout;
+            let (tx, rx) = mpsc::sync_channel::<()>(1);
+            let preview = self.start_preview(channel, stream_type, move |event| {
+                if let PreviewEvent::Stream(data) = event {
+                    if is_key_frame(data) {
+                        let _ = tx.try_send(());
+                    }
+                }
+            })?;
+
+            if rx.recv_timeout(timeout).is_err() {
+                return Err(HikError::FrameTimeout.into());
+            }
+            loop {
+                match preview.capture_bmp_data() {
+                    Ok(data) => return Ok(data),
+                    Err(e) if Instant::now() >= deadline => return Err(e),
+                    Err(_) => thread::sleep(CAPTURE_RETRY_INTERVAL),
+                }
+            }
+        }
+    }
+
+    fn set_bmp_mode() -> anyhow::Result<()> {
+        let res = unsafe {
+            sdk_call!(NET_DVR_SetCapturePictureMode(
+                CAPTURE_MODE_BMP_MODE as DWORD
+            ))
+        };
+        if res != 1 {
+            return Err(sdk_error("Set capture mode"));
+        }
+        Ok(())
+    }
+
+    // 海康 PS 流只在 I 帧前携带系统头 00 00 01 BB
+    fn is_key_frame(data: &[u8]) -> bool {
+        data.windows(4).any(|w| w == [0x00, 0x00, 0x01, 0xBB])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::sdk::{MockSdk, device_info, logged_in_device};
+
+    #[test]
+    fn transport_maps_to_link_mode() {
+        assert_eq!(LinkMode::from(TransportMode::Tcp), LinkMode::Tcp);
+        assert_eq!(LinkMode::from(TransportMode::Udp), LinkMode::Udp);
+        assert_eq!(LinkMode::from(TransportMode::Udp).link_params(), (1, 0));
+    }
+
+    #[test]
+    fn panicking_callback_keeps_stream_and_stops_on_drop() {
+        let mock = Arc::new(MockSdk::new());
+        let device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        let received = Arc::new(AtomicUsize::new(0));
+        let counter = received.clone();
+        let preview = device
+            .start_preview(1, StreamType::Main, move |event| {
+                if let PreviewEvent::Header(_) = event {
+                    panic!("callback panicked");
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        let session = device.session.state();
+        assert_eq!(session.handles(), [(HandleKind::Preview, preview.handle)]);
+
+        let user = &*preview.context as *const PreviewContext as *mut c_void;
+        let mut data = [0u8; 4];
+        for data_type in [NET_DVR_SYSHEAD, NET_DVR_STREAMDATA, NET_DVR_STREAMDATA] {
+            unsafe {
+                real_data_callback(
+                    preview.handle,
+                    data_type,
+                    data.as_mut_ptr(),
+                    data.len() as DWORD,
+                    user,
+                )
+            };
+        }
+        assert_eq!(received.load(Ordering::SeqCst), 2);
+
+        drop(preview);
+        assert!(session.handles().is_empty());
+    }
+}
@@ -0,0 +1,634 @@
+use crate::{
+    AUX_PWRON1, AUX_PWRON2, BYTE, CLE_PRE_SEQ, DEL_SEQ, DWORD, FAN_PWRON, FILL_PRE_SEQ,
+    HEATER_PWRON, LIGHT_PWRON, NET_DVR_GET_PTZPOS, NET_DVR_GET_PTZSCOPE,
+    NET_DVR_GET_SUPPLEMENTLIGHT, NET_DVR_NOSUPPORT, NET_DVR_NOT_SUPPORT, NET_DVR_PTZControl_Other,
+    NET_DVR_PTZCruise_Other, NET_DVR_PTZPOS, NET_DVR_PTZSCOPE, NET_DVR_PTZTrack_Other,
+    NET_DVR_SET_PTZPOS, NET_DVR_SET_SUPPLEMENTLIGHT, NET_DVR_SUPPLEMENTLIGHT, RUN_CRUISE, RUN_SEQ,
+    SET_SEQ_DWELL, SET_SEQ_SPEED, STA_MEM_CRUISE, STO_MEM_CRUISE, STOP_SEQ, WIPER_PWRON, WORD,
+    device::{HikDevice, sdk_code, sdk_error},
+    error::HikError,
+    trace::sdk_call,
+};
+
+// 巡航路径与每条路径上的点均从 1 开始
+pub const MAX_CRUISE_ROUTES: u8 = 32;
+pub const MAX_CRUISE_POINTS: usize = 32;
+pub const CRUISE_SPEED_MAX: u8 = 40;
+
+// SDK 把十进制数的每一位写进一个十六进制半字节，最后一位是小数，
+// 例如 0x1234 表示 123.4，最大只能表示 999.9
+const PTZ_VALUE_MAX: f32 = 999.9;
+
+pub fn decode_ptz_value(raw: WORD) -> anyhow::Result<f32> {
+    let mut value: u32 = 0;
+    for shift in [12, 8, 4, 0] {
+        let digit = (raw >> shift) & 0xf;
+        if digit > 9 {
+            return Err(anyhow::anyhow!("Invalid PTZ value: {:#06x}", raw));
+        }
+        value = value * 10 + digit as u32;
+    }
+    Ok(value as f32 / 10.0)
+}
+
+// 按 0.1 四舍五入；先取整到 0.01，避免 123.45 这类在 f32 中略小于真值的数被舍掉
+pub fn encode_ptz_value(value: f32) -> anyhow::Result<WORD> {
+    if !value.is_finite() || !(0.0..=PTZ_VALUE_MAX).contains(&value) {
+        return Err(anyhow::anyhow!(
+            "PTZ value out of range: {}, expected 0-{}",
+            value,
+            PTZ_VALUE_MAX
+        ));
+    }
+    let mut tenths = ((value as f64 * 100.0).round() / 10.0).round() as u32;
+    let mut raw: WORD = 0;
+    for shift in [0, 4, 8, 12] {
+        raw |= ((tenths % 10) as WORD) << shift;
+        tenths /= 10;
+    }
+    Ok(raw)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PtzPosition {
+    // 水平角度，单位度
+    pub pan: f32,
+    // 垂直角度，单位度
+    pub tilt: f32,
+    // 变倍倍数，1.0 为不放大
+    pub zoom: f32,
+}
+
+impl TryFrom<&NET_DVR_PTZPOS> for PtzPosition {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: &NET_DVR_PTZPOS) -> anyhow::Result<Self> {
+        Ok(Self {
+            pan: decode_ptz_value(raw.wPanPos)?,
+            tilt: decode_ptz_value(raw.wTiltPos)?,
+            zoom: decode_ptz_value(raw.wZoomPos)?,
+        })
+    }
+}
+
+// NET_DVR_PTZPOS::wAction，未选中的分量会被设备忽略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PtzAction {
+    #[default]
+    All,
+    PanOnly,
+    TiltOnly,
+    ZoomOnly,
+    PanTilt,
+}
+
+impl From<PtzAction> for WORD {
+    fn from(value: PtzAction) -> Self {
+        match value {
+            PtzAction::All => 1,
+            PtzAction::PanOnly => 2,
+            PtzAction::TiltOnly => 3,
+            PtzAction::ZoomOnly => 4,
+            PtzAction::PanTilt => 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PtzRange {
+    pub pan_min: f32,
+    pub pan_max: f32,
+    pub tilt_min: f32,
+    pub tilt_max: f32,
+    pub zoom_min: f32,
+    pub zoom_max: f32,
+}
+
+impl PtzRange {
+    pub fn contains(&self, position: &PtzPosition) -> bool {
+        (self.pan_min..=self.pan_max).contains(&position.pan)
+            && (self.tilt_min..=self.tilt_max).contains(&position.tilt)
+            && (self.zoom_min..=self.zoom_max).contains(&position.zoom)
+    }
+
+    pub fn clamp(&self, position: PtzPosition) -> PtzPosition {
+        PtzPosition {
+            pan: position.pan.clamp(self.pan_min, self.pan_max),
+            tilt: position.tilt.clamp(self.tilt_min, self.tilt_max),
+            zoom: position.zoom.clamp(self.zoom_min, self.zoom_max),
+        }
+    }
+}
+
+impl TryFrom<&NET_DVR_PTZSCOPE> for PtzRange {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: &NET_DVR_PTZSCOPE) -> anyhow::Result<Self> {
+        let range = Self {
+            pan_min: decode_ptz_value(raw.wPanPosMin)?,
+            pan_max: decode_ptz_value(raw.wPanPosMax)?,
+            tilt_min: decode_ptz_value(raw.wTiltPosMin)?,
+            tilt_max: decode_ptz_value(raw.wTiltPosMax)?,
+            zoom_min: decode_ptz_value(raw.wZoomPosMin)?,
+            zoom_max: decode_ptz_value(raw.wZoomPosMax)?,
+        };
+        // clamp 要求 min <= max
+        if range.pan_min > range.pan_max
+            || range.tilt_min > range.tilt_max
+            || range.zoom_min > range.zoom_max
+        {
+            return Err(anyhow::anyhow!("Invalid PTZ range: {:?}", range));
+        }
+        Ok(range)
+    }
+}
+
+impl HikDevice {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn ptz_get_position(&self, channel: u16) -> anyhow::Result<PtzPosition> {
+        let channel = self.resolve_channel(channel)?;
+        let raw: NET_DVR_PTZPOS =
+            self.get_dvr_config(NET_DVR_GET_PTZPOS, channel, "Get PTZ position")?;
+        PtzPosition::try_from(&raw)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn ptz_set_position(&self, channel: u16, position: PtzPosition) -> anyhow::Result<()> {
+        self.ptz_set_position_with(channel, position, PtzAction::All)
+    }
+
+    // 设备不会自动限位，超出范围时行为取决于机型，建议先用 ptz_get_range 的 clamp 处理
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn ptz_set_position_with(
+        &self,
+        channel: u16,
+        position: PtzPosition,
+        action: PtzAction,
+    ) -> anyhow::Result<()> {
+        let channel = self.resolve_channel(channel)?;
+        let raw = NET_DVR_PTZPOS {
+            wAction: action.into(),
+            wPanPos: encode_ptz_value(position.pan)?,
+            wTiltPos: encode_ptz_value(position.tilt)?,
+            wZoomPos: encode_ptz_value(position.zoom)?,
+        };
+        self.set_dvr_config(NET_DVR_SET_PTZPOS, channel, &raw, "Set PTZ position")
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn ptz_get_range(&self, channel: u16) -> anyhow::Result<PtzRange> {
+        let channel = self.resolve_channel(channel)?;
+        let raw: NET_DVR_PTZSCOPE =
+            self.get_dvr_config(NET_DVR_GET_PTZSCOPE, channel, "Get PTZ range")?;
+        PtzRange::try_from(&raw)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CruiseAction {
+    // input 为预置点号
+    FillPreset,
+    // input 为速度 1-40
+    SetSpeed,
+    // input 为停留时间，单位秒
+    SetDwellTime,
+    DeletePreset,
+    // 删除整条巡航路径，部分设备不支持
+    DeleteRoute,
+    Run,
+    Stop,
+}
+
+impl From<CruiseAction> for DWORD {
+    fn from(value: CruiseAction) -> Self {
+        match value {
+            CruiseAction::FillPreset => FILL_PRE_SEQ,
+            CruiseAction::SetSpeed => SET_SEQ_SPEED,
+            CruiseAction::SetDwellTime => SET_SEQ_DWELL,
+            CruiseAction::DeletePreset => CLE_PRE_SEQ,
+            CruiseAction::DeleteRoute => DEL_SEQ,
+            CruiseAction::Run => RUN_SEQ,
+            CruiseAction::Stop => STOP_SEQ,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CruiseStep {
+    pub preset: u16,
+    // 1-40
+    pub speed: u8,
+    // 1-255
+    pub dwell_secs: u8,
+}
+
+// 花样扫描（轨迹）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TrackAction {
+    // 开始记录，之后的云台动作会被记录下来
+    Begin,
+    Stop,
+    Run,
+}
+
+impl From<TrackAction> for DWORD {
+    fn from(value: TrackAction) -> Self {
+        match value {
+            TrackAction::Begin => STA_MEM_CRUISE,
+            TrackAction::Stop => STO_MEM_CRUISE,
+            TrackAction::Run => RUN_CRUISE,
+        }
+    }
+}
+
+fn check_cruise_step(step: &CruiseStep) -> anyhow::Result<()> {
+    if step.preset == 0 {
+        return Err(anyhow::anyhow!("Cruise preset must start from 1"));
+    }
+    if !(1..=CRUISE_SPEED_MAX).contains(&step.speed) {
+        return Err(anyhow::anyhow!(
+            "Cruise speed out of range: {}, expected 1-{}",
+            step.speed,
+            CRUISE_SPEED_MAX
+        ));
+    }
+    if step.dwell_secs == 0 {
+        return Err(anyhow::anyhow!(
+            "Cruise dwell time out of range: 0, expected 1-255"
+        ));
+    }
+    Ok(())
+}
+
+impl HikDevice {
+    // 直接对应 NET_DVR_PTZCruise_Other，Run/Stop/DeleteRoute 忽略 preset_point 与 input
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn ptz_cruise(
+        &self,
+        channel: u16,
+        action: CruiseAction,
+        cruise_route: u8,
+        preset_point: u8,
+        input: u16,
+    ) -> anyhow::Result<()> {
+        if !(1..=MAX_CRUISE_ROUTES).contains(&cruise_route) {
+            return Err(anyhow::anyhow!(
+                "Cruise route out of range: {}, expected 1-{}",
+                cruise_route,
+                MAX_CRUISE_ROUTES
+            ));
+        }
+        let channel = self.resolve_channel(channel)?;
+        self.with_session(|lu| {
+            let res = unsafe {
+                sdk_call!(NET_DVR_PTZCruise_Other(
+                    lu,
+                    channel,
+                    action.into(),
+                    cruise_route as BYTE,
+                    preset_point as BYTE,
+                    input as WORD,
+                ))
+            };
+            if res != 1 {
+                return Err(sdk_error("PTZ cruise"));
+            }
+            Ok(())
+        })
+    }
+
+    // 依次写入每个点的预置点、速度和停留时间
+    // 路径上原有的、超出 steps 数量的点不会被删除，需要时先调用 DeleteRoute
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn set_cruise_route(
+        &self,
+        channel: u16,
+        route_index: u8,
+        steps: Vec<CruiseStep>,
+    ) -> anyhow::Result<()> {
+        if steps.is_empty() || steps.len() > MAX_CRUISE_POINTS {
+            return Err(anyhow::anyhow!(
+                "Cruise route must have 1-{} points, got {}",
+                MAX_CRUISE_POINTS,
+                steps.len()
+            ));
+        }
+        self.resolve_channel(channel)?;
+        for step in &steps {
+            check_cruise_step(step)?;
+        }
+
+        for (i, step) in steps.iter().enumerate() {
+            let point = (i + 1) as u8;
+            self.ptz_cruise(
+                channel,
+                CruiseAction::FillPreset,
+                route_index,
+                point,
+                step.preset,
+            )?;
+            self.ptz_cruise(
+                channel,
+                CruiseAction::SetSpeed,
+                route_index,
+                point,
+                step.speed as u16,
+            )?;
+            self.ptz_cruise(
+                channel,
+                CruiseAction::SetDwellTime,
+                route_index,
+                point,
+                step.dwell_secs as u16,
+            )?;
+        }
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn ptz_track(&self, channel: u16, action: TrackAction) -> anyhow::Result<()> {
+        let channel = self.resolve_channel(channel)?;
+        self.with_session(|lu| {
+            let res = unsafe { sdk_call!(NET_DVR_PTZTrack_Other(lu, channel, action.into())) };
+            if res != 1 {
+                return Err(sdk_error("PTZ track"));
+            }
+            Ok(())
+        })
+    }
+}
+
+/// 云台辅助设备开关，对应 NET_DVR_PTZControl_Other 的 PWRON 命令
+///
+/// SDK 的 dwStop 参数为 0 时表示“开始”，即打开设备，为 1 时关闭；
+/// ptz_aux 的 on 参数已做转换，on = true 对应 dwStop = 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AuxCommand {
+    // 雨刷
+    Wiper,
+    // 灯光（红外或白光补光灯）
+    Light,
+    Fan,
+    Heater,
+    Aux1,
+    Aux2,
+}
+
+impl From<AuxCommand> for DWORD {
+    fn from(value: AuxCommand) -> Self {
+        match value {
+            AuxCommand::Wiper => WIPER_PWRON,
+            AuxCommand::Light => LIGHT_PWRON,
+            AuxCommand::Fan => FAN_PWRON,
+            AuxCommand::Heater => HEATER_PWRON,
+            AuxCommand::Aux1 => AUX_PWRON1,
+            AuxCommand::Aux2 => AUX_PWRON2,
+        }
+    }
+}
+
+// dwStop：0 开始（打开），1 停止（关闭）
+fn aux_stop_flag(on: bool) -> DWORD {
+    if on { 0 } else { 1 }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LightMode {
+    // 按设备上已配置的时间段开关
+    Schedule,
+    // 按 LightControl::on 手动开关
+    Manual,
+    // 设备根据画面亮度自动开关
+    Auto,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightControl {
+    pub mode: LightMode,
+    // 只在 Manual 模式下生效
+    pub on: bool,
+    // 0-100，None 为不修改；只有补光灯配置支持亮度
+    pub brightness: Option<u8>,
+}
+
+impl HikDevice {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn ptz_aux(&self, channel: u16, command: AuxCommand, on: bool) -> anyhow::Result<()> {
+        let channel = self.resolve_channel(channel)?;
+        self.with_session(|lu| {
+            let res = unsafe {
+                sdk_call!(NET_DVR_PTZControl_Other(
+                    lu,
+                    channel,
+                    command.into(),
+                    aux_stop_flag(on)
+                ))
+            };
+            if res != 1 {
+                return Err(sdk_error("PTZ aux control"));
+            }
+            Ok(())
+        })
+    }
+
+    /// 控制补光灯
+    ///
+    /// 先读取补光灯配置（NET_DVR_SUPPLEMENTLIGHT），设备不支持时退回到灯光辅助命令，
+    /// 此时只支持 Manual 模式且不能设置亮度
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn ptz_light_control(&self, channel: u16, control: LightControl) -> anyhow::Result<()> {
+        if control.brightness.is_some_and(|b| b > 100) {
+            return Err(anyhow::anyhow!(
+                "Light brightness out of range: {:?}, expected 0-100",
+                control.brightness
+            ));
+        }
+        let sdk_channel = self.resolve_channel(channel)?;
+        let mut light: NET_DVR_SUPPLEMENTLIGHT = match self.get_dvr_config(
+            NET_DVR_GET_SUPPLEMENTLIGHT,
+            sdk_channel,
+            "Get supplement light",
+        ) {
+            Ok(light) => light,
+            Err(e) if is_not_supported(&e) => {
+                if control.mode != LightMode::Manual || control.brightness.is_some() {
+                    return Err(HikError::Unsupported {
+                        feature: "Supplement light mode and brightness",
+                    }
+                    .into());
+                }
+                return self.ptz_aux(channel, AuxCommand::Light, control.on);
+            }
+            Err(e) => return Err(e),
+        };
+
+        // byMode：0 定时，1 开启，2 关闭，3 自动
+        light.byMode = match control.mode {
+            LightMode::Schedule => 0,
+            LightMode::Manual if control.on => 1,
+            LightMode::Manual => 2,
+            LightMode::Auto => 3,
+        };
+        light.byEnable = 1;
+        if let Some(brightness) = control.brightness {
+            // 手动调节亮度
+            light.byBrightnessRegulatMode = 0;
+            light.byBrightness = brightness;
+        }
+        self.set_dvr_config(
+            NET_DVR_SET_SUPPLEMENTLIGHT,
+            sdk_channel,
+            &light,
+            "Set supplement light",
+        )
+    }
+}
+
+fn is_not_supported(e: &anyhow::Error) -> bool {
+    matches!(
+        sdk_code(e),
+        Some(code) if code == NET_DVR_NOSUPPORT as i32 || code == NET_DVR_NOT_SUPPORT as i32
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ptz_value_decode() {
+        assert_eq!(decode_ptz_value(0x1234).unwrap(), 123.4);
+        assert_eq!(decode_ptz_value(0x0000).unwrap(), 0.0);
+        assert_eq!(decode_ptz_value(0x0010).unwrap(), 1.0);
+        assert_eq!(decode_ptz_value(0x9999).unwrap(), 999.9);
+    }
+
+    #[test]
+    fn ptz_value_round_trip() {
+        for tenths in 0..=9999u32 {
+            let raw = (0..4).fold(0, |raw: WORD, i| {
+                raw | (((tenths / 10u32.pow(i)) % 10) as WORD) << (i * 4)
+            });
+            let value = decode_ptz_value(raw).unwrap();
+            assert_eq!(encode_ptz_value(value).unwrap(), raw, "{:#06x}", raw);
+        }
+    }
+
+    #[test]
+    fn ptz_value_rounding() {
+        assert_eq!(encode_ptz_value(123.45).unwrap(), 0x1235);
+        assert_eq!(encode_ptz_value(123.44).unwrap(), 0x1234);
+        assert_eq!(encode_ptz_value(0.05).unwrap(), 0x0001);
+        assert_eq!(encode_ptz_value(0.04).unwrap(), 0x0000);
+        assert_eq!(encode_ptz_value(359.99).unwrap(), 0x3600);
+    }
+
+    #[test]
+    fn ptz_value_rejects_invalid_nibbles() {
+        for raw in [0x000a, 0x00f0, 0x0b00, 0xc000, 0xffff] {
+            assert!(decode_ptz_value(raw).is_err(), "{:#06x}", raw);
+        }
+    }
+
+    #[test]
+    fn ptz_value_rejects_out_of_range() {
+        for value in [-0.1, 999.95, 1000.0, f32::NAN, f32::INFINITY] {
+            assert!(encode_ptz_value(value).is_err(), "{}", value);
+        }
+        assert_eq!(encode_ptz_value(999.9).unwrap(), 0x9999);
+    }
+
+    #[test]
+    fn aux_stop_flag_is_inverted() {
+        // on = true 对应 dwStop = 0（开始）
+        assert_eq!(aux_stop_flag(true), 0);
+        assert_eq!(aux_stop_flag(false), 1);
+    }
+
+    #[test]
+    fn aux_command_constants() {
+        let cases = [
+            (AuxCommand::Wiper, WIPER_PWRON),
+            (AuxCommand::Light, LIGHT_PWRON),
+            (AuxCommand::Fan, FAN_PWRON),
+            (AuxCommand::Heater, HEATER_PWRON),
+            (AuxCommand::Aux1, AUX_PWRON1),
+            (AuxCommand::Aux2, AUX_PWRON2),
+        ];
+        for (command, expected) in cases {
+            assert_eq!(DWORD::from(command), expected, "{:?}", command);
+        }
+    }
+}
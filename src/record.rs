@@ -0,0 +1,397 @@
+use std::time::Duration;
+
+use chrono::{NaiveTime, Timelike as _};
+
+use crate::{
+    BYTE, DWORD, LONG, NET_DVR_GET_RECORDCFG_V40, NET_DVR_RECORD_V40, NET_DVR_SCHEDTIME,
+    NET_DVR_SET_RECORDCFG_V40, NET_DVR_StartDVRRecord, NET_DVR_StopDVRRecord,
+    device::{HikDevice, sdk_error},
+    trace::sdk_call,
+};
+
+// 每天最多 8 个时间段
+pub const MAX_RECORD_SEGMENTS: usize = 8;
+// 下标 0 为周一
+pub const DAYS_PER_WEEK: usize = 7;
+
+// dwPreRecordTime 的取值 0-6，7 表示尽可能长的预录
+const PRE_RECORD_SECS: [u64; 7] = [0, 5, 10, 15, 20, 25, 30];
+const PRE_RECORD_MAX: DWORD = 7;
+// dwRecordTime 的取值 0-6
+const POST_RECORD_SECS: [u64; 7] = [5, 10, 30, 60, 120, 300, 600];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RecordType {
+    // 定时录像
+    Continuous,
+    Motion,
+    Alarm,
+    MotionOrAlarm,
+    MotionAndAlarm,
+    Command,
+    // 智能事件录像
+    Event,
+    Other(u8),
+}
+
+impl From<BYTE> for RecordType {
+    fn from(value: BYTE) -> Self {
+        match value {
+            0 => Self::Continuous,
+            1 => Self::Motion,
+            2 => Self::Alarm,
+            3 => Self::MotionOrAlarm,
+            4 => Self::MotionAndAlarm,
+            5 => Self::Command,
+            6 => Self::Event,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl From<RecordType> for BYTE {
+    fn from(value: RecordType) -> Self {
+        match value {
+            RecordType::Continuous => 0,
+            RecordType::Motion => 1,
+            RecordType::Alarm => 2,
+            RecordType::MotionOrAlarm => 3,
+            RecordType::MotionAndAlarm => 4,
+            RecordType::Command => 5,
+            RecordType::Event => 6,
+            RecordType::Other(other) => other,
+        }
+    }
+}
+
+// NET_DVR_StartDVRRecord 的 lRecordType
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ManualRecordType {
+    Manual,
+    Alarm,
+    // 回传录像
+    Backup,
+    Signal,
+    Motion,
+    // 遮挡报警录像
+    Tamper,
+}
+
+impl From<ManualRecordType> for LONG {
+    fn from(value: ManualRecordType) -> Self {
+        match value {
+            ManualRecordType::Manual => 0,
+            ManualRecordType::Alarm => 1,
+            ManualRecordType::Backup => 2,
+            ManualRecordType::Signal => 3,
+            ManualRecordType::Motion => 4,
+            ManualRecordType::Tamper => 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecordSegment {
+    pub start: NaiveTime,
+    // 设备的 24:00 表示为 23:59:59
+    pub end: NaiveTime,
+    pub record_type: RecordType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DaySchedule {
+    // Some 时全天录像，忽略 segments
+    pub all_day: Option<RecordType>,
+    // 最多 MAX_RECORD_SEGMENTS 个
+    pub segments: Vec<RecordSegment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecordConfig {
+    pub enabled: bool,
+    // 周一到周日，固定 7 天
+    pub days: Vec<DaySchedule>,
+    // 只能取 0/5/10/15/20/25/30 秒，Duration::MAX 表示尽可能长
+    pub pre_record: Duration,
+    // 只能取 5/10/30 秒或 1/2/5/10 分钟
+    pub post_record: Duration,
+    // 录像保存天数，0 表示不限制
+    pub retention_days: u32,
+    // 冗余录像，需要设备配置冗余盘
+    pub redundancy: bool,
+}
+
+impl RecordConfig {
+    // 每天全天按同一种方式录像，预录 5 秒、延录 30 秒
+    pub fn always(record_type: RecordType) -> Self {
+        Self {
+            enabled: true,
+            days: vec![
+                DaySchedule {
+                    all_day: Some(record_type),
+                    segments: Vec::new(),
+                };
+                DAYS_PER_WEEK
+            ],
+            pre_record: Duration::from_secs(5),
+            post_record: Duration::from_secs(30),
+            retention_days: 0,
+            redundancy: false,
+        }
+    }
+
+    pub fn motion_only() -> Self {
+        Self::always(RecordType::Motion)
+    }
+
+    // 在设备当前配置上修改，码流类型、音频等未建模的字段保持不变
+    fn apply_to(&self, raw: &mut NET_DVR_RECORD_V40) -> anyhow::Result<()> {
+        if self.days.len() != DAYS_PER_WEEK {
+            return Err(anyhow::anyhow!(
+                "Record schedule must have {} days, got {}",
+                DAYS_PER_WEEK,
+                self.days.len()
+            ));
+        }
+
+        for (day_index, day) in self.days.iter().enumerate() {
+            if day.segments.len() > MAX_RECORD_SEGMENTS {
+                return Err(anyhow::anyhow!(
+                    "Too many record segments on day {}: {}, max {}",
+                    day_index,
+                    day.segments.len(),
+                    MAX_RECORD_SEGMENTS
+                ));
+            }
+            let all_day = &mut raw.struRecAllDay[day_index];
+            all_day.byAllDayRecord = day.all_day.is_some() as BYTE;
+            if let Some(record_type) = day.all_day {
+                all_day.byRecordType = record_type.into();
+            }
+
+            for (i, sched) in raw.struRecordSched[day_index].iter_mut().enumerate() {
+                match day.segments.get(i) {
+                    Some(segment) => {
+                        if segment.start >= segment.end {
+                            return Err(anyhow::anyhow!(
+                                "Invalid record segment on day {}: {} - {}",
+                                day_index,
+                                segment.start,
+                                segment.end
+                            ));
+                        }
+                        sched.struRecordTime = sched_time(segment.start, segment.end);
+                        sched.byRecordType = segment.record_type.into();
+                    }
+                    None => {
+                        sched.struRecordTime = NET_DVR_SCHEDTIME::default();
+                        sched.byRecordType = 0;
+                    }
+                }
+            }
+        }
+
+        raw.dwRecord = self.enabled as DWORD;
+        raw.dwPreRecordTime = if self.pre_record == Duration::MAX {
+            PRE_RECORD_MAX
+        } else {
+            duration_index(&PRE_RECORD_SECS, self.pre_record, "Pre-record")?
+        };
+        raw.dwRecordTime = duration_index(&POST_RECORD_SECS, self.post_record, "Post-record")?;
+        raw.dwRecorderDuration = self.retention_days;
+        raw.byRedundancyRec = self.redundancy as BYTE;
+        Ok(())
+    }
+}
+
+impl From<&NET_DVR_RECORD_V40> for RecordConfig {
+    fn from(raw: &NET_DVR_RECORD_V40) -> Self {
+        let days = raw
+            .struRecAllDay
+            .iter()
+            .zip(raw.struRecordSched.iter())
+            .map(|(all_day, scheds)| DaySchedule {
+                all_day: (all_day.byAllDayRecord == 1).then(|| all_day.byRecordType.into()),
+                segments: scheds
+                    .iter()
+                    .filter_map(|sched| {
+                        let (start, end) = segment_times(&sched.struRecordTime)?;
+                        Some(RecordSegment {
+                            start,
+                            end,
+                            record_type: sched.byRecordType.into(),
+                        })
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Self {
+            enabled: raw.dwRecord == 1,
+            days,
+            pre_record: match raw.dwPreRecordTime {
+                PRE_RECORD_MAX => Duration::MAX,
+                index => index_duration(&PRE_RECORD_SECS, index),
+            },
+            post_record: index_duration(&POST_RECORD_SECS, raw.dwRecordTime),
+            retention_days: raw.dwRecorderDuration,
+            redundancy: raw.byRedundancyRec == 1,
+        }
+    }
+}
+
+// 起止时间都为 0 的时间段未启用
+fn segment_times(time: &NET_DVR_SCHEDTIME) -> Option<(NaiveTime, NaiveTime)> {
+    if time.byStartHour == 0 && time.byStartMin == 0 && time.byStopHour == 0 && time.byStopMin == 0
+    {
+        return None;
+    }
+    let start = NaiveTime::from_hms_opt(time.byStartHour as u32, time.byStartMin as u32, 0)?;
+    let end = if time.byStopHour >= 24 {
+        NaiveTime::from_hms_opt(23, 59, 59)?
+    } else {
+        NaiveTime::from_hms_opt(time.byStopHour as u32, time.byStopMin as u32, 0)?
+    };
+    Some((start, end))
+}
+
+fn sched_time(start: NaiveTime, end: NaiveTime) -> NET_DVR_SCHEDTIME {
+    let (stop_hour, stop_min) = if end.hour() == 23 && end.minute() == 59 && end.second() == 59 {
+        (24, 0)
+    } else {
+        (end.hour() as BYTE, end.minute() as BYTE)
+    };
+    NET_DVR_SCHEDTIME {
+        byStartHour: start.hour() as BYTE,
+        byStartMin: start.minute() as BYTE,
+        byStopHour: stop_hour,
+        byStopMin: stop_min,
+    }
+}
+
+fn index_duration(table: &[u64], index: DWORD) -> Duration {
+    let secs = table.get(index as usize).copied().unwrap_or(table[0]);
+    Duration::from_secs(secs)
+}
+
+fn duration_index(table: &[u64], duration: Duration, field: &str) -> anyhow::Result<DWORD> {
+    table
+        .iter()
+        .position(|&secs| Duration::from_secs(secs) == duration)
+        .map(|index| index as DWORD)
+        .ok_or(anyhow::anyhow!(
+            "{} duration {:?} is not supported, expected one of {:?} seconds",
+            field,
+            duration,
+            table
+        ))
+}
+
+impl HikDevice {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn get_record_config(&self, channel: u16) -> anyhow::Result<RecordConfig> {
+        let channel = self.resolve_channel(channel)?;
+        let raw: NET_DVR_RECORD_V40 =
+            self.get_dvr_config(NET_DVR_GET_RECORDCFG_V40, channel, "Get record config")?;
+        Ok(RecordConfig::from(&raw))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn set_record_config(&self, channel: u16, config: &RecordConfig) -> anyhow::Result<()> {
+        let channel = self.resolve_channel(channel)?;
+        let mut raw: NET_DVR_RECORD_V40 =
+            self.get_dvr_config(NET_DVR_GET_RECORDCFG_V40, channel, "Get record config")?;
+        config.apply_to(&mut raw)?;
+        self.set_dvr_config(
+            NET_DVR_SET_RECORDCFG_V40,
+            channel,
+            &raw,
+            "Set record config",
+        )
+    }
+
+    // 不受录像计划影响，直到调用 stop_manual_record 或设备重启
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn start_manual_record(
+        &self,
+        channel: u16,
+        record_type: ManualRecordType,
+    ) -> anyhow::Result<()> {
+        let channel = self.resolve_channel(channel)?;
+        self.with_session(|lu| {
+            let res = unsafe { sdk_call!(NET_DVR_StartDVRRecord(lu, channel, record_type.into())) };
+            if res != 1 {
+                return Err(sdk_error("Start manual record"));
+            }
+            Ok(())
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn stop_manual_record(&self, channel: u16) -> anyhow::Result<()> {
+        let channel = self.resolve_channel(channel)?;
+        self.with_session(|lu| {
+            let res = unsafe { sdk_call!(NET_DVR_StopDVRRecord(lu, channel)) };
+            if res != 1 {
+                return Err(sdk_error("Stop manual record"));
+            }
+            Ok(())
+        })
+    }
+
+    // 通过工作状态查询，计划录像与手动录像都会返回 true
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn is_recording(&self, channel: u16) -> anyhow::Result<bool> {
+        let sdk_channel = self.resolve_channel(channel)? as u32;
+        let state = self.get_work_state()?;
+        state
+            .channels
+            .iter()
+            .find(|c| c.chan_num == sdk_channel)
+            .map(|c| c.recording)
+            .ok_or(anyhow::anyhow!(
+                "Channel {} not found in work state",
+                channel
+            ))
+    }
+}
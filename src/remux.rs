@@ -0,0 +1,865 @@
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    device::HikDevice,
+    error::HikError,
+    preview::{HikPreview, PreviewEvent, StreamType},
+};
+
+// PSM 中的 stream_type，H.265 为 0x24
+const STREAM_TYPE_H264: u8 = 0x1B;
+
+const PACK_HEADER: u8 = 0xBA;
+const PROGRAM_END: u8 = 0xB9;
+const PROGRAM_STREAM_MAP: u8 = 0xBC;
+const VIDEO_STREAM_MIN: u8 = 0xE0;
+const VIDEO_STREAM_MAX: u8 = 0xEF;
+
+const NAL_IDR: u8 = 5;
+const NAL_SPS: u8 = 7;
+const NAL_PPS: u8 = 8;
+const NAL_AUD: u8 = 9;
+
+// PES 时间戳为 90kHz，直接作为 MP4 的 timescale
+const TIMESCALE: u32 = 90_000;
+const PTS_MASK: u64 = (1 << 33) - 1;
+// 时间戳异常（为 0 或跳变超过 10 秒）时按 25 帧计算
+const DEFAULT_FRAME_DURATION: u32 = TIMESCALE / 25;
+const MAX_FRAME_DURATION: u64 = TIMESCALE as u64 * 10;
+// 找不到完整 PS 包时缓冲区的上限，超过说明数据已经错乱
+const MAX_BUFFER_SIZE: usize = 4 * 1024 * 1024;
+
+const TRACK_ID: u32 = 1;
+// trun 中的 sample_flags
+const SAMPLE_FLAGS_KEY: u32 = 0x0200_0000;
+const SAMPLE_FLAGS_DELTA: u32 = 0x0101_0000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mp4Segment {
+    // ftyp + moov，需先于媒体段送入 SourceBuffer，分辨率变化时会再次发送
+    Init {
+        // 用于 MediaSource.addSourceBuffer，例如 avc1.64001f
+        codec: String,
+        width: u16,
+        height: u16,
+        data: Vec<u8>,
+    },
+    // moof + mdat，每段一帧，第一个媒体段总是 I 帧
+    Media {
+        sequence: u32,
+        keyframe: bool,
+        data: Vec<u8>,
+    },
+}
+
+impl Mp4Segment {
+    pub fn data(&self) -> &[u8] {
+        match self {
+            Mp4Segment::Init { data, .. } | Mp4Segment::Media { data, .. } => data,
+        }
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        match self {
+            Mp4Segment::Init { data, .. } | Mp4Segment::Media { data, .. } => data,
+        }
+    }
+}
+
+struct AccessUnit {
+    pts: u64,
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Sps {
+    raw: Vec<u8>,
+    profile: u8,
+    compatibility: u8,
+    level: u8,
+    chroma_format: u32,
+    bit_depth_luma: u32,
+    bit_depth_chroma: u32,
+    width: u16,
+    height: u16,
+}
+
+impl Sps {
+    fn codec(&self) -> String {
+        format!(
+            "avc1.{:02x}{:02x}{:02x}",
+            self.profile, self.compatibility, self.level
+        )
+    }
+}
+
+/// 把海康 PS 流转换为 fMP4，目前只支持 H.264
+///
+/// 与 SDK 无关，也可以用来处理回放或下载得到的 PS 数据
+#[derive(Default)]
+pub struct Remuxer {
+    buffer: Vec<u8>,
+    video_stream_type: Option<u8>,
+    current: Option<AccessUnit>,
+    sps: Option<Sps>,
+    pps: Option<Vec<u8>>,
+    // 发送过初始化段后才发送媒体段
+    init_sps: Option<Sps>,
+    waiting_key_frame: bool,
+    sequence: u32,
+    decode_time: u64,
+    last_duration: Option<u32>,
+}
+
+impl Remuxer {
+    pub fn new() -> Self {
+        Self {
+            waiting_key_frame: true,
+            ..Default::default()
+        }
+    }
+
+    /// 送入预览回调的码流数据，返回本次能输出的片段
+    pub fn push(&mut self, data: &[u8]) -> anyhow::Result<Vec<Mp4Segment>> {
+        self.buffer.extend_from_slice(data);
+        let mut segments = Vec::new();
+        let mut pos = 0;
+        while let Some(len) = self.next_packet(pos)? {
+            let packet = self.buffer[pos..pos + len].to_vec();
+            pos += len;
+            self.handle_packet(&packet, &mut segments)?;
+        }
+        self.buffer.drain(..pos);
+        if self.buffer.len() > MAX_BUFFER_SIZE {
+            self.buffer.clear();
+            self.resync();
+        }
+        Ok(segments)
+    }
+
+    /// 丢弃到下一个 I 帧为止，并重新发送初始化段
+    ///
+    /// 下游来不及消费而丢掉片段后需要调用，否则解码器会花屏
+    pub fn resync(&mut self) {
+        self.waiting_key_frame = true;
+        self.init_sps = None;
+    }
+
+    // 返回从 pos 开始的完整 PS 包长度，数据不足时返回 None
+    fn next_packet(&mut self, pos: usize) -> anyhow::Result<Option<usize>> {
+        loop {
+            let buf = &self.buffer[pos..];
+            let Some(start) = find_start_code(buf) else {
+                // 保留末尾可能是起始码一部分的字节
+                let keep = buf.len().min(3);
+                let drop = buf.len() - keep;
+                self.buffer.drain(pos..pos + drop);
+                return Ok(None);
+            };
+            if start > 0 {
+                self.buffer.drain(pos..pos + start);
+                continue;
+            }
+            if buf.len() < 4 {
+                return Ok(None);
+            }
+            let len = match buf[3] {
+                PACK_HEADER => {
+                    if buf.len() < 14 {
+                        return Ok(None);
+                    }
+                    14 + (buf[13] & 0x07) as usize
+                }
+                PROGRAM_END => 4,
+                // 其余为 PES 或系统头、PSM，都带 16 位长度
+                id if id > PROGRAM_END => {
+                    if buf.len() < 6 {
+                        return Ok(None);
+                    }
+                    6 + u16::from_be_bytes([buf[4], buf[5]]) as usize
+                }
+                // 不是 PS 的起始码，跳过继续查找
+                _ => {
+                    self.buffer.drain(pos..pos + 3);
+                    continue;
+                }
+            };
+            if buf.len() < len {
+                return Ok(None);
+            }
+            return Ok(Some(len));
+        }
+    }
+
+    fn handle_packet(
+        &mut self,
+        packet: &[u8],
+        segments: &mut Vec<Mp4Segment>,
+    ) -> anyhow::Result<()> {
+        match packet[3] {
+            PROGRAM_STREAM_MAP => {
+                if let Some(stream_type) = parse_psm(packet) {
+                    self.video_stream_type = Some(stream_type);
+                }
+            }
+            VIDEO_STREAM_MIN..=VIDEO_STREAM_MAX => {
+                match self.video_stream_type {
+                    None | Some(STREAM_TYPE_H264) => {}
+                    Some(stream_type) => {
+                        return Err(HikError::UnsupportedCodec { stream_type }.into());
+                    }
+                }
+                let Some((pts, payload)) = parse_pes(packet) else {
+                    return Ok(());
+                };
+                match (pts, self.current.as_mut()) {
+                    // 时间戳变化说明上一帧已经完整
+                    (Some(pts), Some(current)) if pts != current.pts => {
+                        let finished = self.current.replace(AccessUnit {
+                            pts,
+                            data: payload.to_vec(),
+                        });
+                        if let Some(finished) = finished {
+                            let duration = frame_duration(finished.pts, pts, self.last_duration);
+                            self.last_duration = Some(duration);
+                            self.handle_access_unit(finished, duration, segments)?;
+                        }
+                    }
+                    (_, Some(current)) => current.data.extend_from_slice(payload),
+                    (Some(pts), None) => {
+                        self.current = Some(AccessUnit {
+                            pts,
+                            data: payload.to_vec(),
+                        })
+                    }
+                    // 还没有收到带时间戳的包
+                    (None, None) => {}
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_access_unit(
+        &mut self,
+        unit: AccessUnit,
+        duration: u32,
+        segments: &mut Vec<Mp4Segment>,
+    ) -> anyhow::Result<()> {
+        let mut keyframe = false;
+        let mut sample = Vec::with_capacity(unit.data.len());
+        for nal in split_nal_units(&unit.data) {
+            match nal[0] & 0x1f {
+                NAL_SPS => self.sps = Some(parse_sps(nal)?),
+                NAL_PPS => self.pps = Some(nal.to_vec()),
+                NAL_AUD => {}
+                nal_type => {
+                    keyframe |= nal_type == NAL_IDR;
+                    // avc1 要求 4 字节长度前缀
+                    sample.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+                    sample.extend_from_slice(nal);
+                }
+            }
+        }
+        if sample.is_empty() {
+            return Ok(());
+        }
+
+        if keyframe {
+            if let (Some(sps), Some(pps)) = (&self.sps, &self.pps) {
+                if self.init_sps.as_ref() != Some(sps) {
+                    segments.push(Mp4Segment::Init {
+                        codec: sps.codec(),
+                        width: sps.width,
+                        height: sps.height,
+                        data: init_segment(sps, pps),
+                    });
+                    self.init_sps = Some(sps.clone());
+                }
+                self.waiting_key_frame = false;
+            }
+        }
+        if self.waiting_key_frame {
+            return Ok(());
+        }
+
+        self.sequence += 1;
+        segments.push(Mp4Segment::Media {
+            sequence: self.sequence,
+            keyframe,
+            data: media_segment(self.sequence, self.decode_time, duration, keyframe, &sample),
+        });
+        self.decode_time += duration as u64;
+        Ok(())
+    }
+}
+
+fn find_start_code(buf: &[u8]) -> Option<usize> {
+    buf.windows(3).position(|w| w == [0x00, 0x00, 0x01])
+}
+
+fn frame_duration(pts: u64, next_pts: u64, last: Option<u32>) -> u32 {
+    let delta = next_pts.wrapping_sub(pts) & PTS_MASK;
+    if delta == 0 || delta > MAX_FRAME_DURATION {
+        last.unwrap_or(DEFAULT_FRAME_DURATION)
+    } else {
+        delta as u32
+    }
+}
+
+// 返回 PSM 中第一个视频流的 stream_type
+fn parse_psm(packet: &[u8]) -> Option<u8> {
+    let info_len = u16::from_be_bytes([*packet.get(8)?, *packet.get(9)?]) as usize;
+    let map_start = 10 + info_len;
+    let map_len =
+        u16::from_be_bytes([*packet.get(map_start)?, *packet.get(map_start + 1)?]) as usize;
+    let mut pos = map_start + 2;
+    let end = (pos + map_len).min(packet.len());
+    while pos + 4 <= end {
+        let stream_type = packet[pos];
+        let stream_id = packet[pos + 1];
+        let es_info_len = u16::from_be_bytes([packet[pos + 2], packet[pos + 3]]) as usize;
+        if (VIDEO_STREAM_MIN..=VIDEO_STREAM_MAX).contains(&stream_id) {
+            return Some(stream_type);
+        }
+        pos += 4 + es_info_len;
+    }
+    None
+}
+
+// 返回 PTS 和负载
+fn parse_pes(packet: &[u8]) -> Option<(Option<u64>, &[u8])> {
+    if packet.len() < 9 {
+        return None;
+    }
+    let header_len = packet[8] as usize;
+    let payload = packet.get(9 + header_len..)?;
+    let pts = if packet[7] & 0x80 != 0 && header_len >= 5 {
+        let p = &packet[9..14];
+        Some(
+            ((p[0] as u64 >> 1) & 0x07) << 30
+                | (p[1] as u64) << 22
+                | (p[2] as u64 >> 1) << 15
+                | (p[3] as u64) << 7
+                | p[4] as u64 >> 1,
+        )
+    } else {
+        None
+    };
+    Some((pts, payload))
+}
+
+// 按 00 00 01 / 00 00 00 01 起始码切分 NAL
+fn split_nal_units(data: &[u8]) -> Vec<&[u8]> {
+    let mut starts = Vec::new();
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i..i + 3] == [0x00, 0x00, 0x01] {
+            starts.push(i + 3);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    starts
+        .iter()
+        .enumerate()
+        .filter_map(|(n, &start)| {
+            let mut end = starts.get(n + 1).map_or(data.len(), |&next| next - 3);
+            // 4 字节起始码多出来的 0 以及 trailing_zero 都不属于 NAL
+            while end > start && data[end - 1] == 0 {
+                end -= 1;
+            }
+            (end > start).then(|| &data[start..end])
+        })
+        .collect()
+}
+
+struct BitReader {
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl BitReader {
+    // 去掉防竞争字节 00 00 03
+    fn new(nal: &[u8]) -> Self {
+        let mut data = Vec::with_capacity(nal.len());
+        let mut zeros = 0;
+        for &b in nal {
+            if zeros >= 2 && b == 0x03 {
+                zeros = 0;
+                continue;
+            }
+            zeros = if b == 0 { zeros + 1 } else { 0 };
+            data.push(b);
+        }
+        Self { data, pos: 0 }
+    }
+
+    fn bit(&mut self) -> anyhow::Result<u32> {
+        let byte = self
+            .data
+            .get(self.pos / 8)
+            .ok_or(anyhow::anyhow!("Invalid H.264 SPS: unexpected end"))?;
+        let bit = (byte >> (7 - self.pos % 8)) & 1;
+        self.pos += 1;
+        Ok(bit as u32)
+    }
+
+    fn bits(&mut self, n: u32) -> anyhow::Result<u32> {
+        let mut value = 0;
+        for _ in 0..n {
+            value = (value << 1) | self.bit()?;
+        }
+        Ok(value)
+    }
+
+    fn ue(&mut self) -> anyhow::Result<u32> {
+        let mut zeros = 0;
+        while self.bit()? == 0 {
+            zeros += 1;
+            if zeros > 31 {
+                return Err(anyhow::anyhow!("Invalid H.264 SPS: bad exp-golomb code"));
+            }
+        }
+        Ok(((1u64 << zeros) - 1 + self.bits(zeros)? as u64) as u32)
+    }
+
+    fn se(&mut self) -> anyhow::Result<i32> {
+        let value = self.ue()? as i64;
+        Ok(if value % 2 == 1 {
+            ((value + 1) / 2) as i32
+        } else {
+            -(value / 2) as i32
+        })
+    }
+}
+
+// 预览用来报告分辨率，nal 从 NAL 头开始
+pub(crate) fn sps_resolution(nal: &[u8]) -> Option<(u16, u16)> {
+    parse_sps(nal).ok().map(|sps| (sps.width, sps.height))
+}
+
+fn parse_sps(nal: &[u8]) -> anyhow::Result<Sps> {
+    let mut r = BitReader::new(nal);
+    // NAL 头
+    r.bits(8)?;
+    let profile = r.bits(8)? as u8;
+    let compatibility = r.bits(8)? as u8;
+    let level = r.bits(8)? as u8;
+    r.ue()?;
+
+    let mut chroma_format = 1;
+    let mut bit_depth_luma = 8;
+    let mut bit_depth_chroma = 8;
+    let mut separate_colour_plane = false;
+    if matches!(
+        profile,
+        100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+    ) {
+        chroma_format = r.ue()?;
+        if chroma_format == 3 {
+            separate_colour_plane = r.bit()? == 1;
+        }
+        bit_depth_luma = r.ue()? + 8;
+        bit_depth_chroma = r.ue()? + 8;
+        r.bit()?;
+        if r.bit()? == 1 {
+            let lists = if chroma_format == 3 { 12 } else { 8 };
+            for i in 0..lists {
+                if r.bit()? == 1 {
+                    skip_scaling_list(&mut r, if i < 6 { 16 } else { 64 })?;
+                }
+            }
+        }
+    }
+
+    r.ue()?;
+    match r.ue()? {
+        0 => {
+            r.ue()?;
+        }
+        1 => {
+            r.bit()?;
+            r.se()?;
+            r.se()?;
+            for _ in 0..r.ue()? {
+                r.se()?;
+            }
+        }
+        _ => {}
+    }
+    r.ue()?;
+    r.bit()?;
+    let width_mbs = r.ue()? + 1;
+    let height_map_units = r.ue()? + 1;
+    let frame_mbs_only = r.bit()?;
+    if frame_mbs_only == 0 {
+        r.bit()?;
+    }
+    r.bit()?;
+    let (mut crop_left, mut crop_right, mut crop_top, mut crop_bottom) = (0, 0, 0, 0);
+    if r.bit()? == 1 {
+        crop_left = r.ue()?;
+        crop_right = r.ue()?;
+        crop_top = r.ue()?;
+        crop_bottom = r.ue()?;
+    }
+
+    let (crop_unit_x, crop_unit_y) = match (chroma_format, separate_colour_plane) {
+        (0, _) | (3, true) => (1, 2 - frame_mbs_only),
+        (1, _) => (2, 2 * (2 - frame_mbs_only)),
+        (2, _) => (2, 2 - frame_mbs_only),
+        _ => (1, 2 - frame_mbs_only),
+    };
+    let width = (width_mbs * 16).saturating_sub(crop_unit_x * (crop_left + crop_right));
+    let height = ((2 - frame_mbs_only) * height_map_units * 16)
+        .saturating_sub(crop_unit_y * (crop_top + crop_bottom));
+
+    Ok(Sps {
+        raw: nal.to_vec(),
+        profile,
+        compatibility,
+        level,
+        chroma_format,
+        bit_depth_luma,
+        bit_depth_chroma,
+        width: u16::try_from(width)?,
+        height: u16::try_from(height)?,
+    })
+}
+
+fn skip_scaling_list(r: &mut BitReader, size: usize) -> anyhow::Result<()> {
+    let mut last_scale = 8;
+    let mut next_scale = 8;
+    for _ in 0..size {
+        if next_scale != 0 {
+            next_scale = (last_scale + r.se()? + 256) % 256;
+        }
+        if next_scale != 0 {
+            last_scale = next_scale;
+        }
+    }
+    Ok(())
+}
+
+fn mp4_box(out: &mut Vec<u8>, kind: &[u8; 4], body: impl FnOnce(&mut Vec<u8>)) {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(kind);
+    body(out);
+    let size = (out.len() - start) as u32;
+    out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+fn full_box(
+    out: &mut Vec<u8>,
+    kind: &[u8; 4],
+    version: u8,
+    flags: u32,
+    body: impl FnOnce(&mut Vec<u8>),
+) {
+    mp4_box(out, kind, |out| {
+        out.push(version);
+        out.extend_from_slice(&flags.to_be_bytes()[1..]);
+        body(out);
+    });
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_matrix(out: &mut Vec<u8>) {
+    for value in [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000] {
+        put_u32(out, value);
+    }
+}
+
+fn init_segment(sps: &Sps, pps: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    mp4_box(&mut out, b"ftyp", |out| {
+        out.extend_from_slice(b"isom");
+        put_u32(out, 0x200);
+        out.extend_from_slice(b"isomiso6avc1mp41");
+    });
+    mp4_box(&mut out, b"moov", |out| {
+        full_box(out, b"mvhd", 0, 0, |out| {
+            put_u32(out, 0);
+            put_u32(out, 0);
+            put_u32(out, 1000);
+            put_u32(out, 0);
+            put_u32(out, 0x0001_0000);
+            put_u16(out, 0x0100);
+            out.extend_from_slice(&[0; 10]);
+            put_matrix(out);
+            out.extend_from_slice(&[0; 24]);
+            put_u32(out, TRACK_ID + 1);
+        });
+        mp4_box(out, b"trak", |out| {
+            full_box(out, b"tkhd", 0, 0x03, |out| {
+                put_u32(out, 0);
+                put_u32(out, 0);
+                put_u32(out, TRACK_ID);
+                put_u32(out, 0);
+                put_u32(out, 0);
+                out.extend_from_slice(&[0; 8]);
+                put_u16(out, 0);
+                put_u16(out, 0);
+                put_u16(out, 0);
+                put_u16(out, 0);
+                put_matrix(out);
+                put_u32(out, (sps.width as u32) << 16);
+                put_u32(out, (sps.height as u32) << 16);
+            });
+            mp4_box(out, b"mdia", |out| {
+                full_box(out, b"mdhd", 0, 0, |out| {
+                    put_u32(out, 0);
+                    put_u32(out, 0);
+                    put_u32(out, TIMESCALE);
+                    put_u32(out, 0);
+                    // und
+                    put_u16(out, 0x55C4);
+                    put_u16(out, 0);
+                });
+                full_box(out, b"hdlr", 0, 0, |out| {
+                    put_u32(out, 0);
+                    out.extend_from_slice(b"vide");
+                    out.extend_from_slice(&[0; 12]);
+                    out.extend_from_slice(b"VideoHandler\0");
+                });
+                mp4_box(out, b"minf", |out| {
+                    full_box(out, b"vmhd", 0, 0x01, |out| out.extend_from_slice(&[0; 8]));
+                    mp4_box(out, b"dinf", |out| {
+                        full_box(out, b"dref", 0, 0, |out| {
+                            put_u32(out, 1);
+                            full_box(out, b"url ", 0, 0x01, |_| {});
+                        });
+                    });
+                    mp4_box(out, b"stbl", |out| {
+                        full_box(out, b"stsd", 0, 0, |out| {
+                            put_u32(out, 1);
+                            avc1_entry(out, sps, pps);
+                        });
+                        full_box(out, b"stts", 0, 0, |out| put_u32(out, 0));
+                        full_box(out, b"stsc", 0, 0, |out| put_u32(out, 0));
+                        full_box(out, b"stsz", 0, 0, |out| {
+                            put_u32(out, 0);
+                            put_u32(out, 0);
+                        });
+                        full_box(out, b"stco", 0, 0, |out| put_u32(out, 0));
+                    });
+                });
+            });
+        });
+        mp4_box(out, b"mvex", |out| {
+            full_box(out, b"trex", 0, 0, |out| {
+                put_u32(out, TRACK_ID);
+                put_u32(out, 1);
+                put_u32(out, 0);
+                put_u32(out, 0);
+                put_u32(out, 0);
+            });
+        });
+    });
+    out
+}
+
+fn avc1_entry(out: &mut Vec<u8>, sps: &Sps, pps: &[u8]) {
+    mp4_box(out, b"avc1", |out| {
+        out.extend_from_slice(&[0; 6]);
+        put_u16(out, 1);
+        out.extend_from_slice(&[0; 16]);
+        put_u16(out, sps.width);
+        put_u16(out, sps.height);
+        put_u32(out, 0x0048_0000);
+        put_u32(out, 0x0048_0000);
+        put_u32(out, 0);
+        put_u16(out, 1);
+        out.extend_from_slice(&[0; 32]);
+        put_u16(out, 0x0018);
+        put_u16(out, 0xFFFF);
+        mp4_box(out, b"avcC", |out| {
+            out.extend_from_slice(&[1, sps.profile, sps.compatibility, sps.level]);
+            // 4 字节长度前缀，1 个 SPS
+            out.extend_from_slice(&[0xFF, 0xE1]);
+            put_u16(out, sps.raw.len() as u16);
+            out.extend_from_slice(&sps.raw);
+            out.push(1);
+            put_u16(out, pps.len() as u16);
+            out.extend_from_slice(pps);
+            if matches!(sps.profile, 100 | 110 | 122 | 144) {
+                out.push(0xFC | sps.chroma_format as u8);
+                out.push(0xF8 | (sps.bit_depth_luma - 8) as u8);
+                out.push(0xF8 | (sps.bit_depth_chroma - 8) as u8);
+                out.push(0);
+            }
+        });
+    });
+}
+
+fn media_segment(
+    sequence: u32,
+    decode_time: u64,
+    duration: u32,
+    keyframe: bool,
+    sample: &[u8],
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(sample.len() + 128);
+    let mut data_offset_pos = 0;
+    mp4_box(&mut out, b"moof", |out| {
+        full_box(out, b"mfhd", 0, 0, |out| put_u32(out, sequence));
+        mp4_box(out, b"traf", |out| {
+            // default-base-is-moof
+            full_box(out, b"tfhd", 0, 0x02_0000, |out| put_u32(out, TRACK_ID));
+            full_box(out, b"tfdt", 1, 0, |out| {
+                out.extend_from_slice(&decode_time.to_be_bytes())
+            });
+            // data-offset、sample-duration、sample-size、sample-flags
+            full_box(out, b"trun", 0, 0x0701, |out| {
+                put_u32(out, 1);
+                data_offset_pos = out.len();
+                put_u32(out, 0);
+                put_u32(out, duration);
+                put_u32(out, sample.len() as u32);
+                put_u32(
+                    out,
+                    if keyframe {
+                        SAMPLE_FLAGS_KEY
+                    } else {
+                        SAMPLE_FLAGS_DELTA
+                    },
+                );
+            });
+        });
+    });
+    // 数据偏移相对 moof 起始位置，指向 mdat 的负载
+    let data_offset = (out.len() + 8) as u32;
+    out[data_offset_pos..data_offset_pos + 4].copy_from_slice(&data_offset.to_be_bytes());
+    mp4_box(&mut out, b"mdat", |out| out.extend_from_slice(sample));
+    out
+}
+
+pub struct HikStreamer {
+    preview: HikPreview,
+    failed: Arc<AtomicBool>,
+    error: Arc<Mutex<Option<anyhow::Error>>>,
+}
+
+impl HikStreamer {
+    pub fn preview(&self) -> &HikPreview {
+        &self.preview
+    }
+
+    // 预览断开或转封装出错后返回 false，不会再有新的片段
+    pub fn is_healthy(&self) -> bool {
+        self.preview.is_healthy() && !self.failed.load(Ordering::SeqCst)
+    }
+
+    // 转封装出错的原因，例如 HikError::UnsupportedCodec
+    pub fn take_error(&self) -> Option<anyhow::Error> {
+        self.error.lock().unwrap().take()
+    }
+}
+
+impl HikDevice {
+    /// 开启预览并把码流转换为 fMP4，可直接送入浏览器的 MediaSource
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn start_stream<F>(
+        &self,
+        channel: u16,
+        stream_type: StreamType,
+        mut callback: F,
+    ) -> anyhow::Result<HikStreamer>
+    where
+        F: FnMut(Mp4Segment) + Send + 'static,
+    {
+        self.spawn_streamer(channel, stream_type, move |segment| {
+            callback(segment);
+            true
+        })
+    }
+
+    // 接收端来不及消费时丢帧，直到下一个 I 帧再继续
+    #[cfg(feature = "tokio")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn start_stream_channel(
+        &self,
+        channel: u16,
+        stream_type: StreamType,
+        capacity: usize,
+    ) -> anyhow::Result<(HikStreamer, tokio::sync::mpsc::Receiver<Mp4Segment>)> {
+        let (tx, rx) = tokio::sync::mpsc::channel(capacity);
+        let streamer = self.spawn_streamer(channel, stream_type, move |segment| {
+            tx.try_send(segment).is_ok()
+        })?;
+        Ok((streamer, rx))
+    }
+
+    // publish 返回 false 表示片段没有送达
+    fn spawn_streamer<F>(
+        &self,
+        channel: u16,
+        stream_type: StreamType,
+        mut publish: F,
+    ) -> anyhow::Result<HikStreamer>
+    where
+        F: FnMut(Mp4Segment) -> bool + Send + 'static,
+    {
+        let failed = Arc::new(AtomicBool::new(false));
+        let error = Arc::new(Mutex::new(None));
+        let mut remuxer = Remuxer::new();
+        let (stream_failed, stream_error) = (failed.clone(), error.clone());
+        let preview = self.start_preview(channel, stream_type, move |event| {
+            // 海康的 40 字节文件头不属于 PS 流
+            let PreviewEvent::Stream(data) = event else {
+                return;
+            };
+            if stream_failed.load(Ordering::SeqCst) {
+                return;
+            }
+            match remuxer.push(data) {
+                Ok(segments) => {
+                    for segment in segments {
+                        if !publish(segment) {
+                            remuxer.resync();
+                            break;
+                        }
+                    }
+                }
+                Err(e) => {
+                    *stream_error.lock().unwrap() = Some(e);
+                    stream_failed.store(true, Ordering::SeqCst);
+                }
+            }
+        })?;
+
+        Ok(HikStreamer {
+            preview,
+            failed,
+            error,
+        })
+    }
+}
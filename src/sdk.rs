@@ -0,0 +1,829 @@
+use std::{
+    ffi::CStr,
+    mem,
+    os::raw::{c_char, c_void},
+    ptr, slice,
+};
+
+use crate::{
+    DWORD, LONG, NET_DVR_ACTIVATECFG, NET_DVR_ActivateDevice, NET_DVR_CaptureJPEGPicture,
+    NET_DVR_CaptureJPEGPicture_NEW, NET_DVR_DEVICEINFO_V40, NET_DVR_GetDVRConfig,
+    NET_DVR_GetDownloadPos, NET_DVR_GetFileByTime_V40, NET_DVR_GetSTDConfig, NET_DVR_JPEGPARA,
+    NET_DVR_Login_V40, NET_DVR_Logout_V30, NET_DVR_PLAYCOND, NET_DVR_PlayBackByTime_V40,
+    NET_DVR_PlayBackControl_V40, NET_DVR_RebootDVR, NET_DVR_RemoteControl, NET_DVR_RestoreConfig,
+    NET_DVR_STD_CONFIG, NET_DVR_SetDVRConfig, NET_DVR_SetSTDConfig, NET_DVR_ShutDownDVR,
+    NET_DVR_StopGetFile, NET_DVR_USER_LOGIN_INFO, NET_DVR_VOD_PARA, common::get_last_error_code,
+    error::HikError, sdk_struct::SdkStruct, trace::sdk_call,
+};
+
+#[cfg(any(test, feature = "mock"))]
+pub use mock::{MockCall, MockSdk};
+#[cfg(test)]
+pub(crate) use mock::{device_info, logged_in_device};
+
+/// HikDevice 使用的 SDK 函数，返回值与对应的 NET_DVR_* 函数一致
+///
+/// 默认使用 [`RealSdk`]，测试时可以通过 [`HikDevice::with_sdk`](crate::device::HikDevice::with_sdk) 替换
+pub trait NetSdk: Send + Sync {
+    fn login_v40(
+        &self,
+        login_info: &mut NET_DVR_USER_LOGIN_INFO,
+        device_info: &mut NET_DVR_DEVICEINFO_V40,
+    ) -> LONG;
+
+    fn logout(&self, user_id: LONG) -> i32;
+
+    // buffer 为配置结构体的内存
+    fn get_dvr_config(
+        &self,
+        user_id: LONG,
+        command: DWORD,
+        channel: LONG,
+        buffer: &mut [u8],
+        returned: &mut DWORD,
+    ) -> i32;
+
+    fn set_dvr_config(&self, user_id: LONG, command: DWORD, channel: LONG, buffer: &[u8]) -> i32;
+
+    fn capture_jpeg_picture(
+        &self,
+        user_id: LONG,
+        channel: LONG,
+        params: &mut NET_DVR_JPEGPARA,
+        file: &CStr,
+    ) -> i32;
+
+    // 抓图写入 buffer，returned 为图片实际大小
+    fn capture_jpeg_picture_new(
+        &self,
+        user_id: LONG,
+        channel: LONG,
+        params: &mut NET_DVR_JPEGPARA,
+        buffer: &mut [u8],
+        returned: &mut DWORD,
+    ) -> i32;
+
+    // cond 为条件结构体的内存，output 为配置结构体的内存
+    fn get_std_config(
+        &self,
+        user_id: LONG,
+        command: DWORD,
+        cond: &mut [u8],
+        output: &mut [u8],
+    ) -> i32;
+
+    fn set_std_config(&self, user_id: LONG, command: DWORD, cond: &mut [u8], input: &[u8]) -> i32;
+
+    fn playback_by_time(&self, user_id: LONG, vod_para: &NET_DVR_VOD_PARA) -> LONG;
+
+    fn get_file_by_time(&self, user_id: LONG, file: &CStr, cond: &mut NET_DVR_PLAYCOND) -> LONG;
+
+    // input、output 为空时传空指针
+    fn play_back_control(
+        &self,
+        handle: LONG,
+        control_code: DWORD,
+        input: &mut [u8],
+        output: &mut [u8],
+        out_len: &mut DWORD,
+    ) -> i32;
+
+    fn get_download_pos(&self, handle: LONG) -> i32;
+
+    fn stop_get_file(&self, handle: LONG) -> i32;
+
+    fn reboot(&self, user_id: LONG) -> i32;
+
+    fn shutdown(&self, user_id: LONG) -> i32;
+
+    fn restore_config(&self, user_id: LONG) -> i32;
+
+    // input 为空时传空指针
+    fn remote_control(&self, user_id: LONG, command: DWORD, input: &mut [u8]) -> i32;
+
+    // 激活不需要登录句柄，config 中带有明文密码
+    fn activate_device(&self, ip: &CStr, port: u16, config: &mut NET_DVR_ACTIVATECFG) -> i32;
+
+    fn get_last_error(&self) -> DWORD;
+}
+
+// 直接调用 HCNetSDK
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealSdk;
+
+fn buffer_ptr(buffer: &mut [u8]) -> *mut c_void {
+    if buffer.is_empty() {
+        ptr::null_mut()
+    } else {
+        buffer.as_mut_ptr() as *mut c_void
+    }
+}
+
+impl NetSdk for RealSdk {
+    fn login_v40(
+        &self,
+        login_info: &mut NET_DVR_USER_LOGIN_INFO,
+        device_info: &mut NET_DVR_DEVICEINFO_V40,
+    ) -> LONG {
+        unsafe { sdk_call!(NET_DVR_Login_V40(login_info, device_info)) }
+    }
+
+    fn logout(&self, user_id: LONG) -> i32 {
+        unsafe { sdk_call!(NET_DVR_Logout_V30(user_id)) }
+    }
+
+    fn get_dvr_config(
+        &self,
+        user_id: LONG,
+        command: DWORD,
+        channel: LONG,
+        buffer: &mut [u8],
+        returned: &mut DWORD,
+    ) -> i32 {
+        unsafe {
+            sdk_call!(NET_DVR_GetDVRConfig(
+                user_id,
+                command,
+                channel,
+                buffer.as_mut_ptr() as *mut c_void,
+                buffer.len() as DWORD,
+                returned,
+            ))
+        }
+    }
+
+    fn set_dvr_config(&self, user_id: LONG, command: DWORD, channel: LONG, buffer: &[u8]) -> i32 {
+        // SDK 不会修改输入缓冲区
+        unsafe {
+            sdk_call!(NET_DVR_SetDVRConfig(
+                user_id,
+                command,
+                channel,
+                buffer.as_ptr() as *mut c_void,
+                buffer.len() as DWORD,
+            ))
+        }
+    }
+
+    fn capture_jpeg_picture(
+        &self,
+        user_id: LONG,
+        channel: LONG,
+        params: &mut NET_DVR_JPEGPARA,
+        file: &CStr,
+    ) -> i32 {
+        unsafe {
+            sdk_call!(NET_DVR_CaptureJPEGPicture(
+                user_id,
+                channel,
+                params,
+                file.as_ptr() as *mut c_char,
+            ))
+        }
+    }
+
+    fn capture_jpeg_picture_new(
+        &self,
+        user_id: LONG,
+        channel: LONG,
+        params: &mut NET_DVR_JPEGPARA,
+        buffer: &mut [u8],
+        returned: &mut DWORD,
+    ) -> i32 {
+        unsafe {
+            sdk_call!(NET_DVR_CaptureJPEGPicture_NEW(
+                user_id,
+                channel,
+                params,
+                buffer.as_mut_ptr() as *mut c_char,
+                buffer.len() as DWORD,
+                returned,
+            ))
+        }
+    }
+
+    fn get_std_config(
+        &self,
+        user_id: LONG,
+        command: DWORD,
+        cond: &mut [u8],
+        output: &mut [u8],
+    ) -> i32 {
+        let mut std_config = NET_DVR_STD_CONFIG {
+            lpCondBuffer: buffer_ptr(cond),
+            dwCondSize: cond.len() as DWORD,
+            lpOutBuffer: buffer_ptr(output),
+            dwOutSize: output.len() as DWORD,
+            ..Default::default()
+        };
+        unsafe { sdk_call!(NET_DVR_GetSTDConfig(user_id, command, &mut std_config)) }
+    }
+
+    fn set_std_config(&self, user_id: LONG, command: DWORD, cond: &mut [u8], input: &[u8]) -> i32 {
+        let mut std_config = NET_DVR_STD_CONFIG {
+            lpCondBuffer: buffer_ptr(cond),
+            dwCondSize: cond.len() as DWORD,
+            // SDK 不会写入输入缓冲区
+            lpInBuffer: input.as_ptr() as *mut c_void,
+            dwInSize: input.len() as DWORD,
+            ..Default::default()
+        };
+        unsafe { sdk_call!(NET_DVR_SetSTDConfig(user_id, command, &mut std_config)) }
+    }
+
+    fn playback_by_time(&self, user_id: LONG, vod_para: &NET_DVR_VOD_PARA) -> LONG {
+        unsafe { sdk_call!(NET_DVR_PlayBackByTime_V40(user_id, vod_para)) }
+    }
+
+    fn get_file_by_time(&self, user_id: LONG, file: &CStr, cond: &mut NET_DVR_PLAYCOND) -> LONG {
+        unsafe {
+            sdk_call!(NET_DVR_GetFileByTime_V40(
+                user_id,
+                file.as_ptr() as *mut c_char,
+                cond
+            ))
+        }
+    }
+
+    fn play_back_control(
+        &self,
+        handle: LONG,
+        control_code: DWORD,
+        input: &mut [u8],
+        output: &mut [u8],
+        out_len: &mut DWORD,
+    ) -> i32 {
+        unsafe {
+            sdk_call!(NET_DVR_PlayBackControl_V40(
+                handle,
+                control_code,
+                buffer_ptr(input),
+                input.len() as DWORD,
+                buffer_ptr(output),
+                out_len,
+            ))
+        }
+    }
+
+    fn get_download_pos(&self, handle: LONG) -> i32 {
+        unsafe { sdk_call!(NET_DVR_GetDownloadPos(handle)) }
+    }
+
+    fn stop_get_file(&self, handle: LONG) -> i32 {
+        unsafe { sdk_call!(NET_DVR_StopGetFile(handle)) }
+    }
+
+    fn reboot(&self, user_id: LONG) -> i32 {
+        unsafe { sdk_call!(NET_DVR_RebootDVR(user_id)) }
+    }
+
+    fn shutdown(&self, user_id: LONG) -> i32 {
+        unsafe { sdk_call!(NET_DVR_ShutDownDVR(user_id)) }
+    }
+
+    fn restore_config(&self, user_id: LONG) -> i32 {
+        unsafe { sdk_call!(NET_DVR_RestoreConfig(user_id)) }
+    }
+
+    fn remote_control(&self, user_id: LONG, command: DWORD, input: &mut [u8]) -> i32 {
+        unsafe {
+            sdk_call!(NET_DVR_RemoteControl(
+                user_id,
+                command,
+                buffer_ptr(input),
+                input.len() as DWORD,
+            ))
+        }
+    }
+
+    fn activate_device(&self, ip: &CStr, port: u16, config: &mut NET_DVR_ACTIVATECFG) -> i32 {
+        unsafe {
+            sdk_call!(NET_DVR_ActivateDevice(
+                ip.as_ptr() as *mut c_char,
+                port,
+                config
+            ))
+        }
+    }
+
+    fn get_last_error(&self) -> DWORD {
+        get_last_error_code() as DWORD
+    }
+}
+
+// 与 device::sdk_error 相同，但错误码取自调用失败的 sdk
+pub(crate) fn sdk_error_from(sdk: &dyn NetSdk, action: &'static str) -> anyhow::Error {
+    HikError::Sdk {
+        action,
+        code: sdk.get_last_error() as i32,
+    }
+    .into()
+}
+
+// 配置结构体按字节传给 NetSdk，SdkStruct 保证任意字节都来自合法的全 0 初始化
+pub(crate) fn struct_bytes<T: SdkStruct>(value: &T) -> &[u8] {
+    unsafe { slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+}
+
+pub(crate) fn struct_bytes_mut<T: SdkStruct>(value: &mut T) -> &mut [u8] {
+    unsafe { slice::from_raw_parts_mut(value as *mut T as *mut u8, mem::size_of::<T>()) }
+}
+
+#[cfg(any(test, feature = "mock"))]
+mod mock {
+    use std::{
+        collections::{HashMap, VecDeque},
+        ffi::CStr,
+        sync::{Arc, Barrier, Mutex},
+    };
+
+    use super::{NetSdk, struct_bytes};
+    use crate::{
+        DWORD, LONG, NET_DVR_ACTIVATECFG, NET_DVR_DEVICEINFO_V40, NET_DVR_JPEGPARA,
+        NET_DVR_PLAYCOND, NET_DVR_USER_LOGIN_INFO, NET_DVR_VOD_PARA, ffi_util::c_array_to_string,
+        sdk_struct::SdkStruct,
+    };
+
+    // 记录下来的调用，不包含密码
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum MockCall {
+        Login {
+            host: String,
+            port: u16,
+            username: String,
+        },
+        Logout {
+            user_id: LONG,
+        },
+        GetDvrConfig {
+            command: DWORD,
+            channel: LONG,
+        },
+        SetDvrConfig {
+            command: DWORD,
+            channel: LONG,
+            data: Vec<u8>,
+        },
+        CaptureJpegPicture {
+            channel: LONG,
+            file: String,
+        },
+        CaptureJpegPictureNew {
+            channel: LONG,
+        },
+        // channel 为条件缓冲区中的通道号
+        GetStdConfig {
+            command: DWORD,
+            channel: LONG,
+        },
+        SetStdConfig {
+            command: DWORD,
+            channel: LONG,
+            data: Vec<u8>,
+        },
+        PlayBackByTime {
+            channel: DWORD,
+        },
+        GetFileByTime {
+            channel: DWORD,
+            file: String,
+        },
+        PlayBackControl {
+            handle: LONG,
+            control_code: DWORD,
+            input: Vec<u8>,
+        },
+        GetDownloadPos {
+            handle: LONG,
+        },
+        StopGetFile {
+            handle: LONG,
+        },
+        Reboot {
+            user_id: LONG,
+        },
+        Shutdown {
+            user_id: LONG,
+        },
+        RestoreConfig {
+            user_id: LONG,
+        },
+        RemoteControl {
+            user_id: LONG,
+            command: DWORD,
+            input: Vec<u8>,
+        },
+        // 只记录密码长度
+        ActivateDevice {
+            ip: String,
+            port: u16,
+            password_len: usize,
+        },
+    }
+
+    #[derive(Default)]
+    struct MockState {
+        calls: Vec<MockCall>,
+        last_error: DWORD,
+        // 按顺序消费，用完后登录失败
+        logins: VecDeque<Result<(LONG, NET_DVR_DEVICEINFO_V40), (DWORD, NET_DVR_DEVICEINFO_V40)>>,
+        configs: HashMap<(DWORD, LONG), Vec<u8>>,
+        // 按函数名设置的失败错误码，对之后的每次调用都生效
+        failures: HashMap<&'static str, DWORD>,
+        // 按顺序返回，最后一个值会一直重复
+        download_positions: VecDeque<i32>,
+        // CaptureJPEGPicture_NEW 返回的图片
+        jpeg: Vec<u8>,
+        // 登录前等待，用于构造并发登录
+        login_barrier: Option<Arc<Barrier>>,
+        next_handle: LONG,
+    }
+
+    /// 记录调用并返回预设结果的 SDK，未预设的调用一律成功
+    #[derive(Default)]
+    pub struct MockSdk {
+        state: Mutex<MockState>,
+    }
+
+    impl MockSdk {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn calls(&self) -> Vec<MockCall> {
+            self.state.lock().unwrap().calls.clone()
+        }
+
+        pub fn push_login_ok(&self, user_id: LONG, device_info: NET_DVR_DEVICEINFO_V40) {
+            self.state
+                .lock()
+                .unwrap()
+                .logins
+                .push_back(Ok((user_id, device_info)));
+        }
+
+        // device_info 用于返回剩余重试次数、锁定时间等
+        pub fn push_login_err(&self, code: DWORD, device_info: NET_DVR_DEVICEINFO_V40) {
+            self.state
+                .lock()
+                .unwrap()
+                .logins
+                .push_back(Err((code, device_info)));
+        }
+
+        // GetDVRConfig 与 GetSTDConfig 返回的内容，未设置时返回全 0
+        pub fn set_config<T: SdkStruct>(&self, command: DWORD, channel: LONG, value: &T) {
+            self.state
+                .lock()
+                .unwrap()
+                .configs
+                .insert((command, channel), struct_bytes(value).to_vec());
+        }
+
+        // function 为 SDK 函数名，例如 "NET_DVR_GetDVRConfig"
+        pub fn fail(&self, function: &'static str, code: DWORD) {
+            self.state.lock().unwrap().failures.insert(function, code);
+        }
+
+        pub fn clear_failure(&self, function: &'static str) {
+            self.state.lock().unwrap().failures.remove(function);
+        }
+
+        pub fn push_download_positions(&self, positions: impl IntoIterator<Item = i32>) {
+            self.state
+                .lock()
+                .unwrap()
+                .download_positions
+                .extend(positions);
+        }
+
+        pub fn set_jpeg(&self, jpeg: Vec<u8>) {
+            self.state.lock().unwrap().jpeg = jpeg;
+        }
+
+        pub fn set_login_barrier(&self, barrier: Arc<Barrier>) {
+            self.state.lock().unwrap().login_barrier = Some(barrier);
+        }
+
+        fn next_handle(&self) -> LONG {
+            let mut state = self.state.lock().unwrap();
+            let handle = state.next_handle;
+            state.next_handle += 1;
+            handle
+        }
+
+        // 记录调用，设置了失败时返回 false 并更新错误码
+        fn record(&self, function: &'static str, call: MockCall) -> bool {
+            let mut state = self.state.lock().unwrap();
+            state.calls.push(call);
+            match state.failures.get(function).copied() {
+                Some(code) => {
+                    state.last_error = code;
+                    false
+                }
+                None => true,
+            }
+        }
+    }
+
+    impl NetSdk for MockSdk {
+        fn login_v40(
+            &self,
+            login_info: &mut NET_DVR_USER_LOGIN_INFO,
+            device_info: &mut NET_DVR_DEVICEINFO_V40,
+        ) -> LONG {
+            let call = MockCall::Login {
+                host: c_array_to_string(&login_info.sDeviceAddress),
+                port: login_info.wPort,
+                username: c_array_to_string(&login_info.sUserName),
+            };
+            if !self.record("NET_DVR_Login_V40", call) {
+                return -1;
+            }
+            let barrier = self.state.lock().unwrap().login_barrier.clone();
+            if let Some(barrier) = barrier {
+                barrier.wait();
+            }
+            let mut state = self.state.lock().unwrap();
+            match state.logins.pop_front() {
+                Some(Ok((user_id, info))) => {
+                    *device_info = info;
+                    user_id
+                }
+                Some(Err((code, info))) => {
+                    *device_info = info;
+                    state.last_error = code;
+                    -1
+                }
+                None => {
+                    state.last_error = crate::NET_DVR_PASSWORD_ERROR;
+                    -1
+                }
+            }
+        }
+
+        fn logout(&self, user_id: LONG) -> i32 {
+            self.record("NET_DVR_Logout_V30", MockCall::Logout { user_id }) as i32
+        }
+
+        fn get_dvr_config(
+            &self,
+            _user_id: LONG,
+            command: DWORD,
+            channel: LONG,
+            buffer: &mut [u8],
+            returned: &mut DWORD,
+        ) -> i32 {
+            let call = MockCall::GetDvrConfig { command, channel };
+            if !self.record("NET_DVR_GetDVRConfig", call) {
+                return 0;
+            }
+            let state = self.state.lock().unwrap();
+            if let Some(data) = state.configs.get(&(command, channel)) {
+                let len = data.len().min(buffer.len());
+                buffer[..len].copy_from_slice(&data[..len]);
+            }
+            *returned = buffer.len() as DWORD;
+            1
+        }
+
+        fn set_dvr_config(
+            &self,
+            _user_id: LONG,
+            command: DWORD,
+            channel: LONG,
+            buffer: &[u8],
+        ) -> i32 {
+            let call = MockCall::SetDvrConfig {
+                command,
+                channel,
+                data: buffer.to_vec(),
+            };
+            if !self.record("NET_DVR_SetDVRConfig", call) {
+                return 0;
+            }
+            // 之后的 GetDVRConfig 读到刚写入的内容
+            self.state
+                .lock()
+                .unwrap()
+                .configs
+                .insert((command, channel), buffer.to_vec());
+            1
+        }
+
+        fn capture_jpeg_picture(
+            &self,
+            _user_id: LONG,
+            channel: LONG,
+            _params: &mut NET_DVR_JPEGPARA,
+            file: &CStr,
+        ) -> i32 {
+            let call = MockCall::CaptureJpegPicture {
+                channel,
+                file: file.to_string_lossy().into_owned(),
+            };
+            self.record("NET_DVR_CaptureJPEGPicture", call) as i32
+        }
+
+        fn get_file_by_time(
+            &self,
+            _user_id: LONG,
+            file: &CStr,
+            cond: &mut NET_DVR_PLAYCOND,
+        ) -> LONG {
+            let call = MockCall::GetFileByTime {
+                channel: cond.dwChannel,
+                file: file.to_string_lossy().into_owned(),
+            };
+            if !self.record("NET_DVR_GetFileByTime_V40", call) {
+                return -1;
+            }
+            self.next_handle()
+        }
+
+        fn capture_jpeg_picture_new(
+            &self,
+            _user_id: LONG,
+            channel: LONG,
+            _params: &mut NET_DVR_JPEGPARA,
+            buffer: &mut [u8],
+            returned: &mut DWORD,
+        ) -> i32 {
+            let call = MockCall::CaptureJpegPictureNew { channel };
+            if !self.record("NET_DVR_CaptureJPEGPicture_NEW", call) {
+                return 0;
+            }
+            let state = self.state.lock().unwrap();
+            let len = state.jpeg.len().min(buffer.len());
+            buffer[..len].copy_from_slice(&state.jpeg[..len]);
+            *returned = len as DWORD;
+            1
+        }
+
+        fn get_std_config(
+            &self,
+            _user_id: LONG,
+            command: DWORD,
+            cond: &mut [u8],
+            output: &mut [u8],
+        ) -> i32 {
+            let channel = cond_channel(cond);
+            let call = MockCall::GetStdConfig { command, channel };
+            if !self.record("NET_DVR_GetSTDConfig", call) {
+                return 0;
+            }
+            let state = self.state.lock().unwrap();
+            if let Some(data) = state.configs.get(&(command, channel)) {
+                let len = data.len().min(output.len());
+                output[..len].copy_from_slice(&data[..len]);
+            }
+            1
+        }
+
+        fn set_std_config(
+            &self,
+            _user_id: LONG,
+            command: DWORD,
+            cond: &mut [u8],
+            input: &[u8],
+        ) -> i32 {
+            let channel = cond_channel(cond);
+            let call = MockCall::SetStdConfig {
+                command,
+                channel,
+                data: input.to_vec(),
+            };
+            if !self.record("NET_DVR_SetSTDConfig", call) {
+                return 0;
+            }
+            self.state
+                .lock()
+                .unwrap()
+                .configs
+                .insert((command, channel), input.to_vec());
+            1
+        }
+
+        fn playback_by_time(&self, _user_id: LONG, vod_para: &NET_DVR_VOD_PARA) -> LONG {
+            let call = MockCall::PlayBackByTime {
+                channel: vod_para.struIDInfo.dwChannel,
+            };
+            if !self.record("NET_DVR_PlayBackByTime_V40", call) {
+                return -1;
+            }
+            self.next_handle()
+        }
+
+        fn play_back_control(
+            &self,
+            handle: LONG,
+            control_code: DWORD,
+            input: &mut [u8],
+            _output: &mut [u8],
+            _out_len: &mut DWORD,
+        ) -> i32 {
+            let call = MockCall::PlayBackControl {
+                handle,
+                control_code,
+                input: input.to_vec(),
+            };
+            self.record("NET_DVR_PlayBackControl_V40", call) as i32
+        }
+
+        fn get_download_pos(&self, handle: LONG) -> i32 {
+            if !self.record(
+                "NET_DVR_GetDownloadPos",
+                MockCall::GetDownloadPos { handle },
+            ) {
+                return -1;
+            }
+            let mut state = self.state.lock().unwrap();
+            match state.download_positions.len() {
+                0 => 0,
+                1 => state.download_positions[0],
+                _ => state.download_positions.pop_front().unwrap_or(0),
+            }
+        }
+
+        fn stop_get_file(&self, handle: LONG) -> i32 {
+            self.record("NET_DVR_StopGetFile", MockCall::StopGetFile { handle }) as i32
+        }
+
+        fn reboot(&self, user_id: LONG) -> i32 {
+            self.record("NET_DVR_RebootDVR", MockCall::Reboot { user_id }) as i32
+        }
+
+        fn shutdown(&self, user_id: LONG) -> i32 {
+            self.record("NET_DVR_ShutDownDVR", MockCall::Shutdown { user_id }) as i32
+        }
+
+        fn restore_config(&self, user_id: LONG) -> i32 {
+            self.record("NET_DVR_RestoreConfig", MockCall::RestoreConfig { user_id }) as i32
+        }
+
+        fn remote_control(&self, user_id: LONG, command: DWORD, input: &mut [u8]) -> i32 {
+            let call = MockCall::RemoteControl {
+                user_id,
+                command,
+                input: input.to_vec(),
+            };
+            self.record("NET_DVR_RemoteControl", call) as i32
+        }
+
+        fn activate_device(&self, ip: &CStr, port: u16, config: &mut NET_DVR_ACTIVATECFG) -> i32 {
+            let password_len = config
+                .sPassword
+                .iter()
+                .position(|&b| b == 0)
+                .unwrap_or(config.sPassword.len());
+            let call = MockCall::ActivateDevice {
+                ip: ip.to_string_lossy().into_owned(),
+                port,
+                password_len,
+            };
+            self.record("NET_DVR_ActivateDevice", call) as i32
+        }
+
+        fn get_last_error(&self) -> DWORD {
+            self.state.lock().unwrap().last_error
+        }
+    }
+
+    // 条件缓冲区以 DWORD 通道号开头
+    fn cond_channel(cond: &[u8]) -> LONG {
+        cond.get(..4)
+            .map(|bytes| LONG::from_ne_bytes(bytes.try_into().unwrap()))
+            .unwrap_or(0)
+    }
+
+    // 测试用的设备信息，ip_count 超过 255 时写入 byHighDChanNum
+    #[cfg(test)]
+    pub(crate) fn device_info(
+        analog_start: u8,
+        analog_count: u8,
+        ip_start: u8,
+        ip_count: u16,
+    ) -> NET_DVR_DEVICEINFO_V40 {
+        let mut info = NET_DVR_DEVICEINFO_V40::default();
+        info.struDeviceV30.byStartChan = analog_start;
+        info.struDeviceV30.byChanNum = analog_count;
+        info.struDeviceV30.byStartDChan = ip_start;
+        info.struDeviceV30.byIPChanNum = (ip_count % 256) as u8;
+        info.struDeviceV30.byHighDChanNum = (ip_count / 256) as u8;
+        info
+    }
+
+    // 通过 MockSdk 登录的设备，user id 为 0
+    #[cfg(test)]
+    pub(crate) fn logged_in_device(
+        mock: &std::sync::Arc<MockSdk>,
+        info: NET_DVR_DEVICEINFO_V40,
+    ) -> crate::device::HikDevice {
+        mock.push_login_ok(0, info);
+        let mut device = crate::device::HikDevice::with_sdk(mock.clone());
+        device
+            .login("192.0.2.1", "admin", "secret", 8000)
+            .expect("mock login");
+        device
+    }
+}
@@ -0,0 +1,87 @@
+use std::mem;
+
+use crate::{
+    DWORD, NET_DVR_ALARMINCFG_V30, NET_DVR_ALARMOUTCFG_V30, NET_DVR_COMPLETE_RESTORE_INFO,
+    NET_DVR_COMPRESSIONCFG_V30, NET_DVR_DEVICECFG_V40, NET_DVR_DIGITAL_CHANNEL_STATE,
+    NET_DVR_DISK_QUOTA_CFG, NET_DVR_EMAILCFG_V30, NET_DVR_HDCFG, NET_DVR_IPPARACFG_V40,
+    NET_DVR_NETCFG_V30, NET_DVR_NETCFG_V50, NET_DVR_NTPPARA, NET_DVR_PICCFG_V30,
+    NET_DVR_PICCFG_V40, NET_DVR_PREVIEW_DISPLAYCFG, NET_DVR_PTZPOS, NET_DVR_PTZSCOPE,
+    NET_DVR_RECORD_V40, NET_DVR_SUPPLEMENTLIGHT, NET_DVR_TIME, NET_DVR_USER_V30, NET_DVR_USER_V50,
+    NET_DVR_ZONEANDDST,
+};
+
+/// 通过 NET_DVR_GetDVRConfig / NET_DVR_SetDVRConfig 收发的配置结构体
+///
+/// # Safety
+///
+/// 全 0 必须是该类型的合法值
+pub unsafe trait SdkStruct: Sized {
+    // 设置 dwSize 等 SDK 要求调用方填写的字段，其余字段保持不变
+    fn init_for_sdk(&mut self);
+
+    fn new_for_sdk() -> Self {
+        let mut value: Self = unsafe { mem::zeroed() };
+        value.init_for_sdk();
+        value
+    }
+}
+
+// 带 dwSize 的结构体：编译期检查 dwSize 是第一个字段，且结构体大小能写进 DWORD
+macro_rules! impl_sdk_struct {
+    ($($t:ty),* $(,)?) => {
+        $(
+            const _: () = assert!(
+                mem::offset_of!($t, dwSize) == 0
+                    && mem::size_of::<DWORD>() == 4
+                    && mem::size_of::<$t>() <= DWORD::MAX as usize
+            );
+
+            unsafe impl SdkStruct for $t {
+                fn init_for_sdk(&mut self) {
+                    self.dwSize = mem::size_of::<$t>() as DWORD;
+                }
+            }
+        )*
+    };
+}
+
+// 没有 dwSize 的老结构体，只需要全 0 初始化
+macro_rules! impl_sdk_struct_unsized {
+    ($($t:ty),* $(,)?) => {
+        $(
+            unsafe impl SdkStruct for $t {
+                fn init_for_sdk(&mut self) {}
+            }
+        )*
+    };
+}
+
+impl_sdk_struct!(
+    NET_DVR_ALARMINCFG_V30,
+    NET_DVR_ALARMOUTCFG_V30,
+    NET_DVR_COMPLETE_RESTORE_INFO,
+    NET_DVR_COMPRESSIONCFG_V30,
+    NET_DVR_DEVICECFG_V40,
+    NET_DVR_DIGITAL_CHANNEL_STATE,
+    NET_DVR_DISK_QUOTA_CFG,
+    NET_DVR_EMAILCFG_V30,
+    NET_DVR_HDCFG,
+    NET_DVR_IPPARACFG_V40,
+    NET_DVR_NETCFG_V30,
+    NET_DVR_NETCFG_V50,
+    NET_DVR_PICCFG_V30,
+    NET_DVR_PICCFG_V40,
+    NET_DVR_PREVIEW_DISPLAYCFG,
+    NET_DVR_RECORD_V40,
+    NET_DVR_USER_V30,
+    NET_DVR_USER_V50,
+    NET_DVR_ZONEANDDST,
+);
+
+impl_sdk_struct_unsized!(
+    NET_DVR_NTPPARA,
+    NET_DVR_PTZPOS,
+    NET_DVR_PTZSCOPE,
+    NET_DVR_SUPPLEMENTLIGHT,
+    NET_DVR_TIME
+);
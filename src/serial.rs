@@ -0,0 +1,207 @@
+use std::{
+    mem,
+    os::raw::{c_char, c_void},
+    sync::Mutex,
+};
+
+use crate::{
+    DWORD, LONG, NET_DVR_SERIALSTART_V40, NET_DVR_SerialSend, NET_DVR_SerialStart_V40,
+    NET_DVR_SerialStop,
+    device::{HikDevice, sdk_error},
+    ffi_util::{call_user_callback, lock_unpoisoned},
+    trace::sdk_call,
+};
+
+// NET_DVR_SerialSend 单次最多发送的字节数
+const SERIAL_SEND_CHUNK_SIZE: usize = 1016;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SerialPortKind {
+    Rs232,
+    Rs485,
+}
+
+impl From<SerialPortKind> for DWORD {
+    fn from(value: SerialPortKind) -> Self {
+        match value {
+            SerialPortKind::Rs232 => 1,
+            SerialPortKind::Rs485 => 2,
+        }
+    }
+}
+
+type SerialCallback = Box<dyn FnMut(&[u8]) + Send>;
+
+struct SerialContext {
+    callback: Mutex<Option<SerialCallback>>,
+}
+
+pub struct HikSerial {
+    handle: LONG,
+    // RS-485 所接通道的 SDK 通道号，RS-232 为 0
+    channel: LONG,
+    closed: bool,
+    // SDK 回调持有该指针，必须在 NET_DVR_SerialStop 之后才能释放
+    context: Box<SerialContext>,
+}
+
+impl HikDevice {
+    // RS-485 透明通道需要指定所接的通道号，RS-232 忽略 channel
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn open_serial(&self, kind: SerialPortKind, channel: u16) -> anyhow::Result<HikSerial> {
+        let channel = match kind {
+            SerialPortKind::Rs232 => 0,
+            SerialPortKind::Rs485 => self.resolve_channel(channel)?,
+        };
+        let context = Box::new(SerialContext {
+            callback: Mutex::new(None),
+        });
+
+        let mut start = NET_DVR_SERIALSTART_V40 {
+            dwSize: mem::size_of::<NET_DVR_SERIALSTART_V40>() as DWORD,
+            dwSerialType: kind.into(),
+            bySerialNum: 1,
+            ..Default::default()
+        };
+        let user = &*context as *const SerialContext as *mut c_void;
+        let handle = self.with_session(|lu| {
+            let handle = unsafe {
+                sdk_call!(NET_DVR_SerialStart_V40(
+                    lu,
+                    &mut start as *mut _ as *mut c_void,
+                    mem::size_of::<NET_DVR_SERIALSTART_V40>() as LONG,
+                    Some(serial_data_callback),
+                    user,
+                ))
+            };
+            if handle < 0 {
+                return Err(sdk_error("Start serial"));
+            }
+            Ok(handle)
+        })?;
+
+        Ok(HikSerial {
+            handle,
+            channel,
+            closed: false,
+            context,
+        })
+    }
+}
+
+impl HikSerial {
+    // 设置接收回调，之前收到的数据不会补发
+    pub fn on_receive<F>(&self, callback: F)
+    where
+        F: FnMut(&[u8]) + Send + 'static,
+    {
+        *self.context.callback.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    pub fn send(&self, data: &[u8]) -> anyhow::Result<()> {
+        if self.closed {
+            return Err(anyhow::anyhow!("Serial already closed"));
+        }
+        for chunk in data.chunks(SERIAL_SEND_CHUNK_SIZE) {
+            let res = unsafe {
+                sdk_call!(NET_DVR_SerialSend(
+                    self.handle,
+                    self.channel,
+                    chunk.as_ptr() as *mut c_char,
+                    chunk.len() as DWORD,
+                ))
+            };
+            if res != 1 {
+                return Err(sdk_error("Send serial data"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn close(&mut self) -> anyhow::Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        let res = unsafe { sdk_call!(NET_DVR_SerialStop(self.handle)) };
+        if res != 1 {
+            return Err(sdk_error("Stop serial"));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for HikSerial {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+unsafe extern "C" fn serial_data_callback(
+    _serial_handle: LONG,
+    _channel: LONG,
+    buffer: *mut c_char,
+    buf_size: DWORD,
+    user: *mut c_void,
+) {
+    if user.is_null() || buffer.is_null() || buf_size == 0 {
+        return;
+    }
+    let context = unsafe { &*(user as *const SerialContext) };
+    let data = unsafe { std::slice::from_raw_parts(buffer as *const u8, buf_size as usize) };
+    if let Some(callback) = lock_unpoisoned(&context.callback).as_mut() {
+        call_user_callback("serial", || callback(data));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::sdk::{MockSdk, device_info, logged_in_device};
+
+    #[test]
+    fn panicking_callback_keeps_receiving() {
+        let mock = Arc::new(MockSdk::new());
+        let device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        let serial = device.open_serial(SerialPortKind::Rs232, 0).unwrap();
+        let received = Arc::new(AtomicUsize::new(0));
+        let counter = received.clone();
+        serial.on_receive(move |data| {
+            if data == b"bad" {
+                panic!("callback panicked");
+            }
+            counter.fetch_add(data.len(), Ordering::SeqCst);
+        });
+
+        let user = &*serial.context as *const SerialContext as *mut c_void;
+        for data in [&b"bad"[..], b"ok"] {
+            let mut data = data.to_vec();
+            unsafe {
+                serial_data_callback(
+                    serial.handle,
+                    0,
+                    data.as_mut_ptr() as *mut c_char,
+                    data.len() as DWORD,
+                    user,
+                )
+            };
+        }
+        assert_eq!(received.load(Ordering::SeqCst), 2);
+
+        // 回调 panic 后仍可替换回调
+        serial.on_receive(|_| {});
+    }
+}
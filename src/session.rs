@@ -0,0 +1,707 @@
+use std::{
+    mem, ptr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicI32, Ordering, compiler_fence},
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    LONG, NET_DVR_CHECK_USER_STATUS, NET_DVR_NETWORK_FAIL_CONNECT, NET_DVR_NETWORK_RECV_ERROR,
+    NET_DVR_NETWORK_RECV_TIMEOUT, NET_DVR_NETWORK_SEND_ERROR, NET_DVR_PASSWORD_ERROR,
+    NET_DVR_USER_LOCKED, NET_DVR_USERNOTEXIST,
+    common::{
+        HandleInfo, HandleKind, active_handles, check_stream_limit, register_session_host,
+        set_connect_time, set_recv_timeout, unregister_session_host, unwatch_handle,
+    },
+    device::{HikDevice, HikDeviceInfo, LoginOptions, Timeouts, login_blocking, sdk_code},
+    error::HikError,
+};
+
+// SDK 的 user id 从 0 开始，-1 表示未登录
+const NO_USER_ID: LONG = -1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    // 单次重登录最多尝试的次数
+    pub max_attempts: u32,
+    // 两次尝试之间的间隔，设备重启通常需要一两分钟
+    pub interval: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            interval: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReloginEvent {
+    // 操作因会话失效失败，code 为触发重登录的错误码
+    Started { code: Option<i32> },
+    Succeeded { attempts: u32 },
+    Failed { attempts: u32, code: Option<i32> },
+}
+
+type ReloginHook = Arc<dyn Fn(&ReloginEvent) + Send + Sync>;
+
+// 保存的登录凭据，drop 时清零用户名与密码
+struct StoredCredentials(LoginOptions);
+
+impl Drop for StoredCredentials {
+    fn drop(&mut self) {
+        zeroize(&mut self.0.username);
+        zeroize(&mut self.0.password);
+    }
+}
+
+fn zeroize(s: &mut String) {
+    let bytes = unsafe { s.as_mut_vec() };
+    for b in bytes.iter_mut() {
+        unsafe { ptr::write_volatile(b, 0) };
+    }
+    compiler_fence(Ordering::SeqCst);
+    bytes.clear();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEvent {
+    LoggedIn { user_id: i32 },
+    LoggedOut { user_id: i32 },
+    // 登出时由本库停止的句柄
+    ChildStopped { kind: HandleKind, handle: i32 },
+}
+
+type SessionHook = Arc<dyn Fn(SessionEvent) + Send + Sync>;
+
+type StopChild = Box<dyn FnOnce() + Send>;
+
+struct ChildHandle {
+    kind: HandleKind,
+    handle: LONG,
+    stop: StopChild,
+}
+
+// 登出时的停止顺序：先停码流，再撤防
+fn stop_order(kind: HandleKind) -> u8 {
+    match kind {
+        HandleKind::Preview => 0,
+        HandleKind::Playback => 1,
+        HandleKind::Alarm => 2,
+    }
+}
+
+/// 一次登录的状态，由 HikDevice 与它打开的下载、预览、回放和布防句柄共享
+///
+/// 登出时先按顺序停止仍在登记的句柄再注销，之后这些句柄上的操作都返回 `HikError::SessionClosed`
+#[derive(Default)]
+pub(crate) struct SessionState {
+    closed: AtomicBool,
+    children: Mutex<Vec<ChildHandle>>,
+    hook: Arc<Mutex<Option<SessionHook>>>,
+}
+
+impl SessionState {
+    fn with_hook(hook: Arc<Mutex<Option<SessionHook>>>) -> Self {
+        Self {
+            hook,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn ensure_open(&self) -> Result<(), HikError> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(HikError::SessionClosed);
+        }
+        Ok(())
+    }
+
+    // stop 只在登出时调用，句柄自行停止前需要先 unregister
+    pub(crate) fn register<F>(&self, kind: HandleKind, handle: LONG, stop: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.children.lock().unwrap().push(ChildHandle {
+            kind,
+            handle,
+            stop: Box::new(stop),
+        });
+    }
+
+    // 返回 false 表示句柄已在登出时被停止，调用方不能再停止一次
+    pub(crate) fn unregister(&self, kind: HandleKind, handle: LONG) -> bool {
+        let mut children = self.children.lock().unwrap();
+        match children
+            .iter()
+            .position(|child| child.kind == kind && child.handle == handle)
+        {
+            Some(index) => {
+                children.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn handles(&self) -> Vec<(HandleKind, LONG)> {
+        self.children
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|child| (child.kind, child.handle))
+            .collect()
+    }
+
+    fn emit(&self, event: SessionEvent) {
+        let hook = self.hook.lock().unwrap().clone();
+        if let Some(hook) = hook {
+            hook(event);
+        }
+    }
+
+    // 同类句柄后打开的先停止
+    fn close(&self) {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let mut children = mem::take(&mut *self.children.lock().unwrap());
+        children.reverse();
+        children.sort_by_key(|child| stop_order(child.kind));
+        for child in children {
+            (child.stop)();
+            unwatch_handle(child.kind, child.handle);
+            self.emit(SessionEvent::ChildStopped {
+                kind: child.kind,
+                handle: child.handle,
+            });
+        }
+    }
+}
+
+#[derive(Default)]
+struct ReloginState {
+    credentials: Option<StoredCredentials>,
+    // 重启前保存的凭据，由 wait_for_reboot 取走后重新登录
+    reboot_credentials: Option<StoredCredentials>,
+    policy: Option<RetryPolicy>,
+    hook: Option<ReloginHook>,
+    timeouts: Option<Timeouts>,
+}
+
+pub(crate) struct Session {
+    // 自动重登录需要在 &self 下替换 user id
+    user_id: AtomicI32,
+    // 同时只允许一个线程重登录
+    relogin: Mutex<ReloginState>,
+    // 设备地址，只用于日志；单独加锁，避免重登录期间阻塞
+    host: Mutex<Option<String>>,
+    // 登录返回的设备信息，重登录后换成新的
+    device_info: Mutex<Option<Arc<HikDeviceInfo>>>,
+    max_streams: Mutex<Option<usize>>,
+    state: Mutex<Arc<SessionState>>,
+    state_hook: Arc<Mutex<Option<SessionHook>>>,
+}
+
+impl Session {
+    pub(crate) fn new() -> Self {
+        Self {
+            user_id: AtomicI32::new(NO_USER_ID),
+            relogin: Mutex::new(ReloginState::default()),
+            host: Mutex::new(None),
+            device_info: Mutex::new(None),
+            max_streams: Mutex::new(None),
+            state: Mutex::new(Arc::new(SessionState {
+                closed: AtomicBool::new(true),
+                ..Default::default()
+            })),
+            state_hook: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub(crate) fn user_id(&self) -> Option<LONG> {
+        let user_id = self.user_id.load(Ordering::SeqCst);
+        (user_id != NO_USER_ID).then_some(user_id)
+    }
+
+    pub(crate) fn start(&self, user_id: LONG, options: &LoginOptions, info: HikDeviceInfo) {
+        let mut relogin = self.relogin.lock().unwrap();
+        relogin.credentials = Some(StoredCredentials(options.clone()));
+        relogin.reboot_credentials = None;
+        drop(relogin);
+        *self.host.lock().unwrap() = Some(options.host.clone());
+        *self.device_info.lock().unwrap() = Some(Arc::new(info));
+        register_session_host(user_id, &options.host);
+        self.user_id.store(user_id, Ordering::SeqCst);
+        self.install_state(user_id);
+    }
+
+    // 每次登录成功（包括重登录）都换成新的状态，之前打开的句柄留在旧状态中
+    fn install_state(&self, user_id: LONG) {
+        let state = Arc::new(SessionState::with_hook(self.state_hook.clone()));
+        *self.state.lock().unwrap() = state.clone();
+        state.emit(SessionEvent::LoggedIn { user_id });
+    }
+
+    pub(crate) fn state(&self) -> Arc<SessionState> {
+        self.state.lock().unwrap().clone()
+    }
+
+    // 停止该会话仍在登记的句柄，之后再注销登录
+    pub(crate) fn close_children(&self) {
+        self.state().close();
+    }
+
+    pub(crate) fn emit_logged_out(&self, user_id: LONG) {
+        self.state().emit(SessionEvent::LoggedOut { user_id });
+    }
+
+    // 返回旧的 user id，由调用方决定是否注销
+    pub(crate) fn end(&self) -> Option<LONG> {
+        self.relogin.lock().unwrap().credentials = None;
+        self.clear()
+    }
+
+    // 设备即将重启：结束会话，但保留凭据供 wait_for_reboot 重新登录
+    pub(crate) fn end_for_reboot(&self) -> Option<LONG> {
+        let mut relogin = self.relogin.lock().unwrap();
+        relogin.reboot_credentials = relogin.credentials.take();
+        drop(relogin);
+        self.clear()
+    }
+
+    // 重新登录成功（start）后清除
+    pub(crate) fn reboot_options(&self) -> Option<LoginOptions> {
+        let relogin = self.relogin.lock().unwrap();
+        Some(relogin.reboot_credentials.as_ref()?.0.clone())
+    }
+
+    fn clear(&self) -> Option<LONG> {
+        *self.host.lock().unwrap() = None;
+        *self.device_info.lock().unwrap() = None;
+        let user_id = self.user_id.swap(NO_USER_ID, Ordering::SeqCst);
+        unregister_session_host(user_id);
+        (user_id != NO_USER_ID).then_some(user_id)
+    }
+
+    // SDK 的超时是全局设置，每次登录前写入该设备的值
+    pub(crate) fn apply_timeouts(&self) -> anyhow::Result<()> {
+        let Some(timeouts) = self.relogin.lock().unwrap().timeouts else {
+            return Ok(());
+        };
+        apply_timeouts(&timeouts)
+    }
+
+    // 借出登录时使用的用户名与密码，不复制到别处；调用方不能输出到日志
+    pub(crate) fn with_credentials<R>(&self, f: impl FnOnce(&str, &str) -> R) -> Option<R> {
+        let relogin = self.relogin.lock().unwrap();
+        let options = &relogin.credentials.as_ref()?.0;
+        Some(f(&options.username, &options.password))
+    }
+
+    pub(crate) fn device_info(&self) -> Option<Arc<HikDeviceInfo>> {
+        self.device_info.lock().unwrap().clone()
+    }
+
+    // 未登录时为空字符串
+    pub(crate) fn host(&self) -> String {
+        self.host.lock().unwrap().clone().unwrap_or_default()
+    }
+}
+
+fn apply_timeouts(timeouts: &Timeouts) -> anyhow::Result<()> {
+    if timeouts.connect_ms > 0 {
+        set_connect_time(timeouts.connect_ms, timeouts.retries)?;
+    }
+    if timeouts.recv_ms > 0 {
+        set_recv_timeout(timeouts.recv_ms)?;
+    }
+    Ok(())
+}
+
+// 设备重启或断网后 SDK 返回的错误码
+fn is_session_error(error: &anyhow::Error) -> bool {
+    matches!(
+        sdk_code(error).map(|code| code as u32),
+        Some(
+            NET_DVR_USERNOTEXIST
+                | NET_DVR_NETWORK_FAIL_CONNECT
+                | NET_DVR_NETWORK_SEND_ERROR
+                | NET_DVR_NETWORK_RECV_ERROR
+                | NET_DVR_NETWORK_RECV_TIMEOUT
+        )
+    )
+}
+
+impl HikDevice {
+    // 通过 SDK 的用户状态检查确认会话仍然有效
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn check_alive(&self) -> bool {
+        let Some(lu) = self.session.user_id() else {
+            return false;
+        };
+        self.sdk
+            .remote_control(lu, NET_DVR_CHECK_USER_STATUS, &mut [])
+            == 1
+    }
+
+    // 会话失效时自动用 login 时的凭据重新登录，并重试一次失败的操作
+    // 重启、关机、恢复出厂等不可重复的操作不会重试
+    pub fn enable_auto_relogin(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.session.relogin.lock().unwrap().policy = Some(policy);
+        self
+    }
+
+    pub fn disable_auto_relogin(&mut self) -> &mut Self {
+        self.session.relogin.lock().unwrap().policy = None;
+        self
+    }
+
+    /// 设置登录与重登录时使用的连接、接收超时
+    ///
+    /// SDK 只有全局的超时设置，这里在每次登录前写入，多个设备并发登录时以最后写入的为准
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) -> &mut Self {
+        self.session.relogin.lock().unwrap().timeouts = Some(timeouts);
+        self
+    }
+
+    /// 限制该设备同时打开的预览与回放/下载句柄数，0 表示不限制
+    ///
+    /// 超过上限时在调用 SDK 之前返回 `HikError::TooManyStreams`；
+    /// 批量下载会等已有的下载结束后再开始排队的通道
+    pub fn set_max_concurrent_streams(&mut self, max: usize) -> &mut Self {
+        *self.session.max_streams.lock().unwrap() = (max > 0).then_some(max);
+        self
+    }
+
+    /// 列出该设备当前登录下由本库打开且尚未关闭的句柄
+    pub fn active_handles(&self) -> Vec<HandleInfo> {
+        let Some(user_id) = self.session.user_id() else {
+            return Vec::new();
+        };
+        active_handles()
+            .into_iter()
+            .filter(|info| info.user_id == user_id)
+            .collect()
+    }
+
+    pub(crate) fn max_streams(&self) -> Option<usize> {
+        *self.session.max_streams.lock().unwrap()
+    }
+
+    // 在打开预览或回放/下载之前调用
+    pub(crate) fn check_stream_limit(&self, user_id: LONG) -> anyhow::Result<()> {
+        Ok(check_stream_limit(user_id, self.max_streams())?)
+    }
+
+    // 回调在登录、登出的线程中同步调用，不要在回调里调用该设备的接口
+    pub fn on_state_change<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(SessionEvent) + Send + Sync + 'static,
+    {
+        *self.session.state_hook.lock().unwrap() = Some(Arc::new(hook));
+        self
+    }
+
+    // 回调在重登录过程中同步调用，不要在回调里调用该设备的接口
+    pub fn on_relogin<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(&ReloginEvent) + Send + Sync + 'static,
+    {
+        self.session.relogin.lock().unwrap().hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// 当前的 SDK 登录句柄，用于调用本库没有封装的 SDK 接口（见 [`crate::sys`]）
+    ///
+    /// 自动重登录会替换句柄，注销后句柄失效，拿到的值不要长期保存；
+    /// 需要保证调用期间句柄有效时使用 with_user_id
+    pub fn raw_user_id(&self) -> Option<LONG> {
+        self.session.user_id()
+    }
+
+    /// 在登录句柄有效期间执行 f，未登录时返回 HikError::NotLoggedIn
+    ///
+    /// 执行期间持有重登录锁，其他线程的自动重登录会等待 f 返回；
+    /// logout 需要 &mut self，不会与 f 同时发生
+    ///
+    /// # Safety
+    ///
+    /// f 中不能调用该设备的其他方法（可能触发重登录导致死锁），
+    /// 也不能注销句柄或在返回后继续使用它
+    pub unsafe fn with_user_id<R>(&self, f: impl FnOnce(LONG) -> R) -> anyhow::Result<R> {
+        let _relogin = self.session.relogin.lock().unwrap();
+        let lu = self.session.user_id().ok_or(HikError::NotLoggedIn)?;
+        Ok(f(lu))
+    }
+
+    pub(crate) fn with_session<T, F>(&self, mut op: F) -> anyhow::Result<T>
+    where
+        F: FnMut(LONG) -> anyhow::Result<T>,
+    {
+        let lu = match self.session.user_id() {
+            Some(lu) => lu,
+            // 上次自动重登录失败时 user id 被清空，凭据仍在则再试一次
+            None => self
+                .relogin(NO_USER_ID, None)
+                .ok_or(HikError::NotLoggedIn)?,
+        };
+        match op(lu) {
+            Err(e) if is_session_error(&e) => match self.relogin(lu, sdk_code(&e)) {
+                Some(lu) => op(lu),
+                None => Err(e),
+            },
+            res => res,
+        }
+    }
+
+    // 未开启自动重登录或重登录失败时返回 None
+    fn relogin(&self, failed: LONG, code: Option<i32>) -> Option<LONG> {
+        let state = self.session.relogin.lock().unwrap();
+        let (Some(policy), Some(credentials)) = (state.policy, state.credentials.as_ref()) else {
+            return None;
+        };
+        // 其他线程已经完成重登录
+        let current = self.session.user_id.load(Ordering::SeqCst);
+        if current != failed {
+            return (current != NO_USER_ID).then_some(current);
+        }
+
+        let emit = |event: ReloginEvent| {
+            if let Some(hook) = &state.hook {
+                hook(&event);
+            }
+        };
+        emit(ReloginEvent::Started { code });
+        // 旧句柄大多已经失效，这里只是释放 SDK 内部资源；与 logout 一样先停止旧状态登记的句柄
+        if failed != NO_USER_ID {
+            let old_state = self.session.state();
+            old_state.close();
+            self.sdk.logout(failed);
+            unregister_session_host(failed);
+            old_state.emit(SessionEvent::LoggedOut { user_id: failed });
+        }
+
+        if let Some(timeouts) = &state.timeouts {
+            let _ = apply_timeouts(timeouts);
+        }
+        let mut last_code = None;
+        let mut attempts = 0;
+        while attempts < policy.max_attempts.max(1) {
+            if attempts > 0 {
+                thread::sleep(policy.interval);
+            }
+            attempts += 1;
+            match login_blocking(&*self.sdk, &credentials.0) {
+                Ok((user_id, info)) => {
+                    // 设备可能在重启后更换了通道配置
+                    *self.session.device_info.lock().unwrap() =
+                        Some(Arc::new(HikDeviceInfo::from_v40(info)));
+                    register_session_host(user_id, &credentials.0.host);
+                    self.session.user_id.store(user_id, Ordering::SeqCst);
+                    self.session.install_state(user_id);
+                    emit(ReloginEvent::Succeeded { attempts });
+                    return Some(user_id);
+                }
+                Err(_) => {
+                    let error_code = self.sdk.get_last_error() as i32;
+                    last_code = Some(error_code);
+                    // 密码已被修改或账号被锁定时继续重试只会延长锁定时间
+                    if matches!(
+                        error_code as u32,
+                        NET_DVR_PASSWORD_ERROR | NET_DVR_USER_LOCKED
+                    ) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // 旧句柄已注销，不能继续使用，下次调用时再尝试重登录
+        self.session.user_id.store(NO_USER_ID, Ordering::SeqCst);
+        emit(ReloginEvent::Failed {
+            attempts,
+            code: last_code,
+        });
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdk::{MockSdk, device_info, logged_in_device};
+
+    fn record_events(device: &mut HikDevice) -> Arc<Mutex<Vec<SessionEvent>>> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorder = events.clone();
+        device.on_state_change(move |event| recorder.lock().unwrap().push(event));
+        events
+    }
+
+    fn register_recorded(
+        state: &SessionState,
+        stopped: &Arc<Mutex<Vec<(HandleKind, LONG)>>>,
+        kind: HandleKind,
+        handle: LONG,
+    ) {
+        let stopped = stopped.clone();
+        state.register(kind, handle, move || {
+            stopped.lock().unwrap().push((kind, handle))
+        });
+    }
+
+    #[test]
+    fn logout_stops_children_in_order() {
+        let mock = Arc::new(MockSdk::new());
+        let mut device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        let events = record_events(&mut device);
+        let state = device.session.state();
+        let stopped = Arc::new(Mutex::new(Vec::new()));
+        for (kind, handle) in [
+            (HandleKind::Alarm, 1),
+            (HandleKind::Preview, 2),
+            (HandleKind::Playback, 3),
+            (HandleKind::Preview, 4),
+            (HandleKind::Alarm, 5),
+            (HandleKind::Playback, 6),
+        ] {
+            register_recorded(&state, &stopped, kind, handle);
+        }
+        // 自行停止的句柄不再由会话停止
+        assert!(state.unregister(HandleKind::Playback, 6));
+
+        device.logout().unwrap();
+
+        let order = [
+            (HandleKind::Preview, 4),
+            (HandleKind::Preview, 2),
+            (HandleKind::Playback, 3),
+            (HandleKind::Alarm, 5),
+            (HandleKind::Alarm, 1),
+        ];
+        assert_eq!(*stopped.lock().unwrap(), order);
+        let mut expected: Vec<SessionEvent> = order
+            .iter()
+            .map(|&(kind, handle)| SessionEvent::ChildStopped { kind, handle })
+            .collect();
+        expected.push(SessionEvent::LoggedOut { user_id: 0 });
+        assert_eq!(*events.lock().unwrap(), expected);
+        assert_eq!(state.ensure_open(), Err(HikError::SessionClosed));
+        assert!(!state.unregister(HandleKind::Alarm, 1));
+    }
+
+    #[test]
+    fn close_is_idempotent() {
+        let state = SessionState::default();
+        let stopped = Arc::new(Mutex::new(Vec::new()));
+        register_recorded(&state, &stopped, HandleKind::Preview, 1);
+        state.close();
+        state.close();
+        assert_eq!(*stopped.lock().unwrap(), [(HandleKind::Preview, 1)]);
+    }
+
+    #[test]
+    fn relogin_replaces_session_state() {
+        let mock = Arc::new(MockSdk::new());
+        let mut device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        device.enable_auto_relogin(RetryPolicy {
+            max_attempts: 1,
+            interval: Duration::ZERO,
+        });
+        let events = record_events(&mut device);
+        let old_state = device.session.state();
+        let stopped = Arc::new(Mutex::new(Vec::new()));
+        register_recorded(&old_state, &stopped, HandleKind::Preview, 7);
+        mock.push_login_ok(1, device_info(1, 4, 0, 0));
+
+        let mut attempts = Vec::new();
+        let user_id = device
+            .with_session(|lu| {
+                attempts.push(lu);
+                if attempts.len() == 1 {
+                    return Err(HikError::Sdk {
+                        action: "Get config",
+                        code: NET_DVR_NETWORK_FAIL_CONNECT as i32,
+                    }
+                    .into());
+                }
+                Ok(lu)
+            })
+            .unwrap();
+
+        assert_eq!(user_id, 1);
+        assert_eq!(attempts, [0, 1]);
+        assert_eq!(*stopped.lock().unwrap(), [(HandleKind::Preview, 7)]);
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                SessionEvent::ChildStopped {
+                    kind: HandleKind::Preview,
+                    handle: 7,
+                },
+                SessionEvent::LoggedOut { user_id: 0 },
+                SessionEvent::LoggedIn { user_id: 1 },
+            ]
+        );
+        // 旧状态上的句柄已关闭，新打开的句柄登记到新状态
+        assert_eq!(old_state.ensure_open(), Err(HikError::SessionClosed));
+        let new_state = device.session.state();
+        assert!(!Arc::ptr_eq(&old_state, &new_state));
+        assert_eq!(new_state.ensure_open(), Ok(()));
+        assert!(new_state.handles().is_empty());
+    }
+
+    #[test]
+    fn relogin_refreshes_device_info() {
+        let mock = Arc::new(MockSdk::new());
+        let mut device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        device.enable_auto_relogin(RetryPolicy {
+            max_attempts: 1,
+            interval: Duration::ZERO,
+        });
+        // 设备重启后改成了 8 个模拟通道和 2 个 IP 通道
+        mock.push_login_ok(1, device_info(1, 8, 33, 2));
+        assert!(device.resolve_channel(6).is_err());
+
+        let mut failed = false;
+        device
+            .with_session(|lu| {
+                if !failed {
+                    failed = true;
+                    return Err(HikError::Sdk {
+                        action: "Get config",
+                        code: NET_DVR_NETWORK_FAIL_CONNECT as i32,
+                    }
+                    .into());
+                }
+                Ok(lu)
+            })
+            .unwrap();
+
+        assert_eq!(device.get_device_info().unwrap().v30().byChanNum, 8);
+        assert_eq!(device.resolve_channel(6).unwrap(), 6);
+        assert_eq!(device.resolve_channel(34).unwrap(), 34);
+    }
+
+    #[test]
+    fn credentials_are_borrowed_until_logout() {
+        let mock = Arc::new(MockSdk::new());
+        let mut device = logged_in_device(&mock, device_info(1, 4, 0, 0));
+        assert_eq!(
+            device.session.with_credentials(|u, p| format!("{u}/{p}")),
+            Some("admin/secret".to_string())
+        );
+        device.logout().unwrap();
+        assert_eq!(device.session.with_credentials(|_, _| ()), None);
+        assert!(device.get_device_info().is_none());
+    }
+}
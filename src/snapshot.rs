@@ -0,0 +1,127 @@
+use std::{
+    collections::HashMap,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local};
+
+use crate::{
+    LONG,
+    alarm::AlarmSubscription,
+    device::{HikDevice, JpegParams, capture_jpeg_to_vec},
+    events::AlarmEvent,
+};
+
+#[derive(Debug, Clone)]
+pub struct MotionSnapshot {
+    pub channel: u16,
+    // 收到报警的时间
+    pub time: DateTime<Local>,
+    pub jpeg: Vec<u8>,
+}
+
+pub struct SnapshotSubscription {
+    alarm: Option<AlarmSubscription>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl SnapshotSubscription {
+    pub fn is_healthy(&self) -> bool {
+        self.alarm.as_ref().is_some_and(|alarm| alarm.is_healthy())
+    }
+
+    // 撤防并等待抓图线程处理完已排队的报警
+    pub fn stop(&mut self) -> anyhow::Result<()> {
+        // 撤防后 handler 被释放，Sender 随之丢弃，抓图线程退出
+        let result = match self.alarm.take() {
+            Some(mut alarm) => alarm.stop(),
+            None => Ok(()),
+        };
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        result
+    }
+}
+
+impl Drop for SnapshotSubscription {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+impl HikDevice {
+    /// 指定通道触发移动侦测时抓图，并在后台线程中调用 handler
+    ///
+    /// 抓图在单独的线程中按顺序执行，不会阻塞 SDK 的报警回调；
+    /// 每个通道在 debounce 时间内只抓一张，期间的报警直接忽略
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channels = ?channels)
+        )
+    )]
+    pub fn on_motion_snapshot<F>(
+        &self,
+        channels: &[u16],
+        debounce: Duration,
+        handler: F,
+    ) -> anyhow::Result<SnapshotSubscription>
+    where
+        F: Fn(MotionSnapshot) + Send + Sync + 'static,
+    {
+        let lu = self.user_id()?;
+        let sdk = self.sdk.clone();
+        let watched = channels
+            .iter()
+            .map(|&channel| Ok((channel, self.resolve_channel(channel)?)))
+            .collect::<anyhow::Result<HashMap<u16, LONG>>>()?;
+
+        let (tx, rx) = mpsc::channel::<(u16, DateTime<Local>)>();
+        let alarm = self.subscribe_alarms(move |event| {
+            if let AlarmEvent::Motion { channels } = event {
+                let time = Local::now();
+                for channel in channels {
+                    let _ = tx.send((channel, time));
+                }
+            }
+        })?;
+
+        let thread = thread::spawn(move || {
+            let mut last: HashMap<u16, Instant> = HashMap::new();
+            for (channel, time) in rx {
+                let Some(&sdk_channel) = watched.get(&channel) else {
+                    continue;
+                };
+                let now = Instant::now();
+                if last
+                    .get(&channel)
+                    .is_some_and(|&at| now.duration_since(at) < debounce)
+                {
+                    continue;
+                }
+                last.insert(channel, now);
+                match capture_jpeg_to_vec(&*sdk, lu, sdk_channel, JpegParams::default()) {
+                    Ok(jpeg) => handler(MotionSnapshot {
+                        channel,
+                        time,
+                        jpeg,
+                    }),
+                    Err(_e) => {
+                        #[cfg(feature = "tracing")]
+                        tracing::warn!(channel, error = %_e, "Motion snapshot failed");
+                    }
+                }
+            }
+        });
+
+        Ok(SnapshotSubscription {
+            alarm: Some(alarm),
+            thread: Some(thread),
+        })
+    }
+}
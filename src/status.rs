@@ -0,0 +1,305 @@
+use crate::{
+    LONG, NET_DVR_CHANNELSTATE_V30, NET_DVR_DIGITAL_CHANNEL_STATE, NET_DVR_DISKSTATE,
+    NET_DVR_GET_DIGITAL_CHANNEL_STATE, NET_DVR_GET_HDCFG, NET_DVR_GetDVRWorkState_V30,
+    NET_DVR_HDCFG, NET_DVR_NOSUPPORT, NET_DVR_NOT_SUPPORT, NET_DVR_SINGLE_HD,
+    NET_DVR_WORKSTATE_V30, NET_SDK_DIGITAL_CHANNEL_STATUS_NET_SDK_DC_STATUS_CONNECTED,
+    device::{
+        HikDevice, IP_CHANNELS_PER_GROUP, ip_chan_num, ip_channel_online, sdk_code, sdk_error,
+    },
+    trace::sdk_call,
+};
+
+// byDigitalChanState 中 0 表示该序号没有 IP 通道，其余非连接状态均按离线处理
+const DIGITAL_CHANNEL_CONNECTED: u8 =
+    NET_SDK_DIGITAL_CHANNEL_STATUS_NET_SDK_DC_STATUS_CONNECTED as u8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeviceStatus {
+    Normal,
+    CpuOverload,
+    HardwareError,
+    Other(u32),
+}
+
+impl From<u32> for DeviceStatus {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => DeviceStatus::Normal,
+            1 => DeviceStatus::CpuOverload,
+            2 => DeviceStatus::HardwareError,
+            other => DeviceStatus::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DiskStatus {
+    Ok,
+    // 休眠
+    Idle,
+    Error,
+    Unformatted,
+    Formatting,
+    Offline,
+    Other(u32),
+}
+
+impl DiskStatus {
+    // NET_DVR_DISKSTATE::dwHardDiskStatic
+    fn from_work_state(value: u32) -> Self {
+        match value {
+            0 => DiskStatus::Ok,
+            1 => DiskStatus::Idle,
+            // 2 为不正常，3 为休眠硬盘出错
+            2 | 3 => DiskStatus::Error,
+            4 => DiskStatus::Unformatted,
+            5 => DiskStatus::Offline,
+            6 => DiskStatus::Formatting,
+            other => DiskStatus::Other(other),
+        }
+    }
+
+    // NET_DVR_SINGLE_HD::dwHdStatus
+    fn from_hd_status(value: u32) -> Self {
+        match value {
+            0 => DiskStatus::Ok,
+            1 => DiskStatus::Unformatted,
+            // 2 为异常，3 为 SMART 状态，4 为不匹配
+            2..=4 => DiskStatus::Error,
+            5 => DiskStatus::Idle,
+            6 => DiskStatus::Offline,
+            11 => DiskStatus::Formatting,
+            other => DiskStatus::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiskState {
+    // 容量，单位 MB
+    pub volume_mb: u32,
+    pub free_space_mb: u32,
+    pub status: DiskStatus,
+}
+
+impl From<&NET_DVR_DISKSTATE> for DiskState {
+    fn from(disk: &NET_DVR_DISKSTATE) -> Self {
+        Self {
+            volume_mb: disk.dwVolume,
+            free_space_mb: disk.dwFreeSpace,
+            status: DiskStatus::from_work_state(disk.dwHardDiskStatic),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelState {
+    pub chan_num: u32,
+    pub recording: bool,
+    pub signal_lost: bool,
+    pub hardware_error: bool,
+    // 实际码率，单位 bps
+    pub bitrate: u32,
+    // 客户端连接数
+    pub client_connections: u32,
+}
+
+impl ChannelState {
+    fn new(chan_num: u32, state: &NET_DVR_CHANNELSTATE_V30) -> Self {
+        Self {
+            // 部分设备会返回实际通道号
+            chan_num: if state.dwChannelNo != 0 {
+                state.dwChannelNo
+            } else {
+                chan_num
+            },
+            recording: state.byRecordStatic == 1,
+            signal_lost: state.bySignalStatic == 1,
+            hardware_error: state.byHardwareStatic == 1,
+            bitrate: state.dwBitRate,
+            client_connections: state.dwLinkNum,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WorkState {
+    pub device_status: DeviceStatus,
+    pub disks: Vec<DiskState>,
+    pub channels: Vec<ChannelState>,
+    pub alarm_inputs: Vec<bool>,
+    pub alarm_outputs: Vec<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HddInfo {
+    pub number: u32,
+    // 容量，单位 MB
+    pub capacity_mb: u32,
+    pub free_space_mb: u32,
+    pub status: DiskStatus,
+    pub group: u32,
+    // 是否循环覆盖写
+    pub overwrite: bool,
+}
+
+impl From<&NET_DVR_SINGLE_HD> for HddInfo {
+    fn from(hd: &NET_DVR_SINGLE_HD) -> Self {
+        Self {
+            number: hd.dwHDNo,
+            capacity_mb: hd.dwCapacity,
+            free_space_mb: hd.dwFreeSpace,
+            status: DiskStatus::from_hd_status(hd.dwHdStatus),
+            group: hd.dwHdGroup,
+            overwrite: hd.byRecycling == 1,
+        }
+    }
+}
+
+impl HikDevice {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_work_state(&self) -> anyhow::Result<WorkState> {
+        let device_info = self
+            .get_device_info()
+            .ok_or(anyhow::anyhow!("Device info not found"))?;
+        let device_info = device_info.v30();
+
+        // 数组按最大值分配，需要按设备实际数量截断
+        let disk_num = device_info.byDiskNum as usize;
+        let chan_num = device_info.byChanNum as usize
+            + device_info.byIPChanNum as usize
+            + device_info.byHighDChanNum as usize * 256;
+        let alarm_in_num = device_info.byAlarmInPortNum as usize;
+        let alarm_out_num = device_info.byAlarmOutPortNum as usize;
+
+        let mut state = Box::<NET_DVR_WORKSTATE_V30>::default();
+        self.with_session(|lu| {
+            let res = unsafe { sdk_call!(NET_DVR_GetDVRWorkState_V30(lu, &mut *state)) };
+            if res != 1 {
+                return Err(sdk_error("Get work state"));
+            }
+            Ok(())
+        })?;
+
+        // 模拟通道在前，IP 通道从 byStartDChan 开始
+        let analog_num = device_info.byChanNum as usize;
+        let channels = state
+            .struChanStatic
+            .iter()
+            .take(chan_num)
+            .enumerate()
+            .map(|(i, chan)| {
+                let num = if i < analog_num {
+                    device_info.byStartChan as u32 + i as u32
+                } else {
+                    device_info.byStartDChan as u32 + (i - analog_num) as u32
+                };
+                ChannelState::new(num, chan)
+            })
+            .collect();
+
+        Ok(WorkState {
+            device_status: DeviceStatus::from(state.dwDeviceStatic),
+            disks: state
+                .struHardDiskStatic
+                .iter()
+                .take(disk_num)
+                .map(DiskState::from)
+                .collect(),
+            channels,
+            alarm_inputs: state
+                .byAlarmInStatic
+                .iter()
+                .take(alarm_in_num)
+                .map(|&v| v == 1)
+                .collect(),
+            alarm_outputs: state
+                .byAlarmOutStatic
+                .iter()
+                .take(alarm_out_num)
+                .map(|&v| v == 1)
+                .collect(),
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_hdd_config(&self) -> anyhow::Result<Vec<HddInfo>> {
+        let config: NET_DVR_HDCFG = self.get_dvr_config(NET_DVR_GET_HDCFG, 0, "Get HDD config")?;
+        let count = (config.dwHDCount as usize).min(config.struHDInfo.len());
+        Ok(config.struHDInfo[..count]
+            .iter()
+            .map(HddInfo::from)
+            .collect())
+    }
+
+    /// IP 通道的在线状态，返回 (实际通道号, 是否在线)，没有添加设备的通道不返回
+    ///
+    /// 只查询状态，比 get_channels 少读取通道名称等配置；设备不支持
+    /// NET_DVR_GET_DIGITAL_CHANNEL_STATE 时退回读取 IP 通道配置
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_channel_status(&self) -> anyhow::Result<Vec<(u16, bool)>> {
+        let device_info = self
+            .get_device_info()
+            .ok_or(anyhow::anyhow!("Device info not found"))?;
+        let ip_start = device_info.ip_start();
+        let ip_count = device_info.ip_channel_count() as usize;
+
+        let error = match self.get_dvr_config::<NET_DVR_DIGITAL_CHANNEL_STATE>(
+            NET_DVR_GET_DIGITAL_CHANNEL_STATE,
+            0,
+            "Get digital channel state",
+        ) {
+            Ok(state) => {
+                // 前 64 个通道在 byDigitalChanState，之后的在 byDigitalChanStateEx
+                return Ok(state
+                    .byDigitalChanState
+                    .iter()
+                    .chain(state.byDigitalChanStateEx.iter())
+                    .take(ip_count)
+                    .enumerate()
+                    .filter(|&(_, &state)| state != 0)
+                    .map(|(i, &state)| {
+                        (
+                            ip_chan_num(ip_start, i as u16),
+                            state == DIGITAL_CHANNEL_CONNECTED,
+                        )
+                    })
+                    .collect());
+            }
+            Err(e) => e,
+        };
+        if !matches!(sdk_code(&error), Some(code)
+            if code == NET_DVR_NOSUPPORT as i32 || code == NET_DVR_NOT_SUPPORT as i32)
+        {
+            return Err(error);
+        }
+
+        let mut status = Vec::new();
+        for group in 0..ip_count.div_ceil(IP_CHANNELS_PER_GROUP) {
+            let config = self.get_ip_channel_config(group as LONG)?;
+            let count = (ip_count - group * IP_CHANNELS_PER_GROUP).min(IP_CHANNELS_PER_GROUP);
+            for offset in 0..count {
+                if let Some(online) = ip_channel_online(&config, offset) {
+                    let index = (group * IP_CHANNELS_PER_GROUP + offset) as u16;
+                    status.push((ip_chan_num(ip_start, index), online));
+                }
+            }
+        }
+        Ok(status)
+    }
+}
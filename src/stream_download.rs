@@ -0,0 +1,281 @@
+use std::{
+    io::Write,
+    os::raw::c_void,
+    ptr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Duration,
+};
+
+use chrono::{DateTime, Local};
+
+use crate::{
+    BYTE, DWORD, LONG, NET_DVR_GetFileByTime_V40, NET_DVR_PLAYCOND, NET_DVR_STREAMDATA,
+    NET_DVR_SYSHEAD, NET_DVR_SetPlayDataCallBack_V40,
+    cancel::CancellationToken,
+    device::{DownloadState, HikDevice, HikDownload, sdk_error},
+    error::HikError,
+    ffi_util::lock_unpoisoned,
+    timezone::TimeMode,
+    trace::sdk_call,
+};
+
+// 队列中最多缓存的数据块数，SDK 每块通常为几 KB 到几十 KB
+const QUEUE_CAPACITY: usize = 256;
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StreamDownloadProgress {
+    pub percent: u8,
+    // 已写入 writer 的字节数
+    pub bytes_written: u64,
+    // 队列满、SDK 回调等待 writer 的次数，持续增长说明 writer 跟不上下载速度
+    pub stalls: u64,
+}
+
+struct StreamContext {
+    // 下载结束或出错后置为 None，写入线程随之退出
+    sender: Mutex<Option<mpsc::SyncSender<Vec<u8>>>>,
+    aborted: Arc<AtomicBool>,
+    stalls: AtomicU64,
+}
+
+/// 通过数据回调下载录像并写入调用方提供的 writer，不经过本地文件
+///
+/// 写入在单独的线程中进行；writer 出错时停止下载，错误由 wait 返回
+pub struct HikStreamDownload {
+    download: Option<HikDownload>,
+    bytes_written: Arc<AtomicU64>,
+    // 写入线程返回 writer 的错误
+    writer: Option<thread::JoinHandle<std::io::Result<()>>>,
+    last_percent: u8,
+    // SDK 回调持有该指针，必须在停止下载之后才能释放
+    context: Box<StreamContext>,
+}
+
+impl HikStreamDownload {
+    pub fn progress(&self) -> StreamDownloadProgress {
+        StreamDownloadProgress {
+            percent: self.last_percent,
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            stalls: self.context.stalls.load(Ordering::Relaxed),
+        }
+    }
+
+    /// 阻塞到下载结束并且数据全部写入，返回写入的字节数
+    ///
+    /// 取消时停止下载并返回 HikError::Cancelled
+    pub fn wait(&mut self, cancel: &CancellationToken) -> anyhow::Result<u64> {
+        loop {
+            if self.context.aborted.load(Ordering::SeqCst) {
+                return self.finish();
+            }
+            let Some(download) = &self.download else {
+                return self.finish();
+            };
+            let status = download.poll(self.last_percent);
+            self.last_percent = status.percent;
+            match status.state {
+                DownloadState::Running => {}
+                DownloadState::Finished => return self.finish(),
+                DownloadState::Failed(e) => {
+                    self.finish()?;
+                    return Err(e.into());
+                }
+                _ => return self.finish(),
+            }
+            if cancel.wait_timeout(POLL_INTERVAL) {
+                self.finish()?;
+                return Err(HikError::Cancelled.into());
+            }
+        }
+    }
+
+    pub fn stop(&mut self) -> anyhow::Result<()> {
+        self.finish().map(|_| ())
+    }
+
+    // 先停止下载句柄，再关闭队列并等待写入线程把剩余数据写完
+    fn finish(&mut self) -> anyhow::Result<u64> {
+        let stopped = match self.download.take() {
+            Some(download) => download.stop(),
+            None => Ok(()),
+        };
+        lock_unpoisoned(&self.context.sender).take();
+        if let Some(writer) = self.writer.take() {
+            match writer.join() {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => return Err(anyhow::anyhow!("Write download data failed: {}", e)),
+                Err(_) => return Err(anyhow::anyhow!("Download writer thread panicked")),
+            }
+        }
+        stopped?;
+        Ok(self.bytes_written.load(Ordering::Relaxed))
+    }
+}
+
+impl Drop for HikStreamDownload {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+unsafe extern "C" fn stream_data_callback(
+    _play_handle: LONG,
+    data_type: DWORD,
+    buffer: *mut BYTE,
+    buf_size: DWORD,
+    user: *mut c_void,
+) {
+    if user.is_null() || buffer.is_null() || buf_size == 0 {
+        return;
+    }
+    // 系统头与 PS 复合流按顺序写入即为完整的录像文件
+    if data_type != NET_DVR_SYSHEAD && data_type != NET_DVR_STREAMDATA {
+        return;
+    }
+    let context = unsafe { &*(user as *const StreamContext) };
+    if context.aborted.load(Ordering::SeqCst) {
+        return;
+    }
+    let data = unsafe { std::slice::from_raw_parts(buffer as *const u8, buf_size as usize) };
+    let sender = lock_unpoisoned(&context.sender).clone();
+    let Some(sender) = sender else {
+        return;
+    };
+    match sender.try_send(data.to_vec()) {
+        Ok(()) => {}
+        // 队列满时阻塞 SDK 的下载线程，由 TCP 把压力传回设备，不能丢弃录像数据
+        Err(mpsc::TrySendError::Full(chunk)) => {
+            context.stalls.fetch_add(1, Ordering::Relaxed);
+            let _ = sender.send(chunk);
+        }
+        Err(mpsc::TrySendError::Disconnected(_)) => {}
+    }
+}
+
+impl HikDevice {
+    /// 按时间下载录像并把码流写入 writer，适合直接上传到对象存储等场景
+    ///
+    /// 时间按设备本地时间解释；需要按 UTC 换算时使用 download_by_time_to_writer_with
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn download_by_time_to_writer<W>(
+        &self,
+        channel: u16,
+        start_time: DateTime<Local>,
+        end_time: DateTime<Local>,
+        writer: W,
+    ) -> anyhow::Result<HikStreamDownload>
+    where
+        W: Write + Send + 'static,
+    {
+        self.download_by_time_to_writer_with(
+            channel,
+            start_time,
+            end_time,
+            TimeMode::default(),
+            writer,
+        )
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(ip = %self.session.host(), channel = channel)
+        )
+    )]
+    pub fn download_by_time_to_writer_with<W>(
+        &self,
+        channel: u16,
+        start_time: DateTime<Local>,
+        end_time: DateTime<Local>,
+        time_mode: TimeMode,
+        mut writer: W,
+    ) -> anyhow::Result<HikStreamDownload>
+    where
+        W: Write + Send + 'static,
+    {
+        let channel = self.resolve_channel(channel)?;
+        let [start_time, end_time] = self.device_time_range(start_time, end_time, time_mode)?;
+        let (lu, handle) = self.with_session(|lu| {
+            self.check_stream_limit(lu)?;
+            let mut cond = NET_DVR_PLAYCOND {
+                dwChannel: channel as DWORD,
+                struStartTime: start_time,
+                struStopTime: end_time,
+                ..Default::default()
+            };
+            // 文件名为空时 SDK 不写文件，数据全部通过回调送出
+            let handle =
+                unsafe { sdk_call!(NET_DVR_GetFileByTime_V40(lu, ptr::null_mut(), &mut cond)) };
+            if handle < 0 {
+                return Err(sdk_error("Get file by time"));
+            }
+            Ok((lu, handle))
+        })?;
+        let (tx, rx) = mpsc::sync_channel::<Vec<u8>>(QUEUE_CAPACITY);
+        let aborted = Arc::new(AtomicBool::new(false));
+        let context = Box::new(StreamContext {
+            sender: Mutex::new(Some(tx)),
+            aborted: aborted.clone(),
+            stalls: AtomicU64::new(0),
+        });
+        let bytes_written = Arc::new(AtomicU64::new(0));
+        let thread_bytes = bytes_written.clone();
+        let writer = thread::spawn(move || {
+            let result = rx.iter().try_for_each(|chunk| {
+                writer.write_all(&chunk)?;
+                thread_bytes.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                Ok(())
+            });
+            let result = result.and_then(|_| writer.flush());
+            // 出错后回调不再入队，wait 负责停止下载
+            if result.is_err() {
+                aborted.store(true, Ordering::SeqCst);
+            }
+            result
+        });
+
+        // 出错时 stream 被 drop，会先停止句柄再结束写入线程、释放回调上下文
+        let mut stream = HikStreamDownload {
+            download: Some(HikDownload::with_sdk(
+                self.sdk.clone(),
+                lu,
+                self.session.state(),
+                handle,
+            )),
+            bytes_written,
+            writer: Some(writer),
+            last_percent: 0,
+            context,
+        };
+        let user = &*stream.context as *const StreamContext as *mut c_void;
+        let res = unsafe {
+            sdk_call!(NET_DVR_SetPlayDataCallBack_V40(
+                handle,
+                Some(stream_data_callback),
+                user
+            ))
+        };
+        if res != 1 {
+            return Err(sdk_error("Set download data callback"));
+        }
+        if let Some(download) = stream.download.as_mut() {
+            download.start()?;
+        }
+        Ok(stream)
+    }
+}
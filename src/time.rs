@@ -0,0 +1,68 @@
+use chrono::{DateTime, Datelike as _, Local, NaiveDateTime, TimeZone as _, Timelike as _};
+
+use crate::{DWORD, NET_DVR_TIME};
+
+// 设备接受 2000 年之前的时间但会导致录像计划等异常
+const MIN_DEVICE_YEAR: i32 = 2000;
+
+impl From<DateTime<Local>> for NET_DVR_TIME {
+    fn from(time: DateTime<Local>) -> Self {
+        time.naive_local().into()
+    }
+}
+
+impl From<NaiveDateTime> for NET_DVR_TIME {
+    fn from(time: NaiveDateTime) -> Self {
+        NET_DVR_TIME {
+            dwYear: time.year() as DWORD,
+            dwMonth: time.month() as DWORD,
+            dwDay: time.day() as DWORD,
+            dwHour: time.hour() as DWORD,
+            dwMinute: time.minute() as DWORD,
+            dwSecond: time.second() as DWORD,
+        }
+    }
+}
+
+impl TryFrom<NET_DVR_TIME> for DateTime<Local> {
+    type Error = anyhow::Error;
+
+    fn try_from(time: NET_DVR_TIME) -> Result<Self, Self::Error> {
+        Local
+            .with_ymd_and_hms(
+                time.dwYear as i32,
+                time.dwMonth,
+                time.dwDay,
+                time.dwHour,
+                time.dwMinute,
+                time.dwSecond,
+            )
+            .single()
+            .ok_or(anyhow::anyhow!("Invalid device time: {:?}", time))
+    }
+}
+
+pub(crate) fn check_device_time(time: &DateTime<Local>) -> anyhow::Result<()> {
+    if time.year() < MIN_DEVICE_YEAR {
+        return Err(anyhow::anyhow!(
+            "Invalid device time {}: year must be >= {}",
+            time,
+            MIN_DEVICE_YEAR
+        ));
+    }
+    Ok(())
+}
+
+// 报警结构体中压缩的绝对时间：年（从 2000 起）6 位、月 4 位、日 5 位、时 5 位、分 6 位、秒 6 位
+pub(crate) fn packed_time_to_local(time: DWORD) -> Option<DateTime<Local>> {
+    Local
+        .with_ymd_and_hms(
+            (time >> 26) as i32 + 2000,
+            (time >> 22) & 0xf,
+            (time >> 17) & 0x1f,
+            (time >> 12) & 0x1f,
+            (time >> 6) & 0x3f,
+            time & 0x3f,
+        )
+        .single()
+}
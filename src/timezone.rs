@@ -0,0 +1,474 @@
+use chrono::{DateTime, Datelike as _, Duration, Local, NaiveDate, NaiveDateTime, Utc, Weekday};
+
+use crate::{
+    BYTE, DWORD, NET_DVR_GET_NTPCFG, NET_DVR_GET_ZONEANDDST, NET_DVR_NTPPARA, NET_DVR_SET_NTPCFG,
+    NET_DVR_SET_ZONEANDDST, NET_DVR_TIME, NET_DVR_TIMEPOINT, NET_DVR_ZONEANDDST, device::HikDevice,
+    error::HikError,
+};
+
+// 下载、查找录像时传入的时间如何解释
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TimeMode {
+    // 直接使用 DateTime<Local> 的年月日时分秒作为设备时间
+    #[default]
+    DeviceLocal,
+    // 按时间点换算，使用设备的时区与夏令时规则转换为设备时间
+    Utc,
+}
+
+// 夏令时切换时刻：某月第几个星期几的几点几分（设备本地时间）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DstRule {
+    // 1-12
+    pub month: u32,
+    // 1-4 为第几周，5 为最后一周
+    pub week: u8,
+    pub weekday: Weekday,
+    pub hour: u32,
+    pub minute: u32,
+}
+
+impl DstRule {
+    // 指定年份中切换发生的时刻
+    pub fn at(&self, year: i32) -> Option<NaiveDateTime> {
+        let date = if self.week >= 5 {
+            // 从下个月 1 号往前找最后一个 weekday
+            let next_month = match self.month {
+                12 => NaiveDate::from_ymd_opt(year + 1, 1, 1)?,
+                month => NaiveDate::from_ymd_opt(year, month + 1, 1)?,
+            };
+            let last = next_month.pred_opt()?;
+            let back = (last.weekday().num_days_from_sunday() + 7
+                - self.weekday.num_days_from_sunday())
+                % 7;
+            last - Duration::days(back as i64)
+        } else {
+            NaiveDate::from_weekday_of_month_opt(year, self.month, self.weekday, self.week)?
+        };
+        date.and_hms_opt(self.hour, self.minute, 0)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if !(1..=12).contains(&self.month)
+            || !(1..=5).contains(&self.week)
+            || self.hour > 23
+            || self.minute > 59
+        {
+            return Err(anyhow::anyhow!("Invalid DST rule: {:?}", self));
+        }
+        Ok(())
+    }
+}
+
+impl From<&NET_DVR_TIMEPOINT> for DstRule {
+    // SDK 中月份为 0-11，周为 0-4（4 为最后一周），星期 0 为周日
+    fn from(point: &NET_DVR_TIMEPOINT) -> Self {
+        Self {
+            month: point.dwMonth + 1,
+            week: (point.dwWeekNo + 1).min(5) as u8,
+            weekday: Weekday::try_from(((point.dwWeekDate + 6) % 7) as u8).unwrap_or(Weekday::Sun),
+            hour: point.dwHour,
+            minute: point.dwMin,
+        }
+    }
+}
+
+impl From<&DstRule> for NET_DVR_TIMEPOINT {
+    fn from(rule: &DstRule) -> Self {
+        Self {
+            dwMonth: rule.month - 1,
+            dwWeekNo: (rule.week - 1) as DWORD,
+            dwWeekDate: rule.weekday.num_days_from_sunday(),
+            dwHour: rule.hour,
+            dwMin: rule.minute,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DstConfig {
+    // 开始时刻按标准时间计算
+    pub start: DstRule,
+    // 结束时刻按夏令时计算
+    pub end: DstRule,
+    // 夏令时比标准时间快多少分钟：30、60、90、120
+    pub bias_minutes: u8,
+}
+
+impl DstConfig {
+    // standard 为不含夏令时偏移的设备本地时间
+    pub fn is_active(&self, standard: NaiveDateTime) -> bool {
+        let year = standard.year();
+        let (Some(start), Some(end)) = (self.start.at(year), self.end.at(year)) else {
+            return false;
+        };
+        let end = end - Duration::minutes(self.bias_minutes as i64);
+        if start <= end {
+            start <= standard && standard < end
+        } else {
+            // 南半球的夏令时跨年
+            standard >= start || standard < end
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TzConfig {
+    // 标准时间相对 UTC 的偏移，东区为正，单位分钟
+    pub utc_offset_minutes: i32,
+    // None 表示未启用夏令时
+    pub dst: Option<DstConfig>,
+}
+
+impl TzConfig {
+    fn from_sdk(ntp: &NET_DVR_NTPPARA, zone: &NET_DVR_ZONEANDDST) -> Self {
+        Self {
+            // 分钟部分与小时同号，例如 -3:30 为 -3 和 -30
+            utc_offset_minutes: ntp.cTimeDifferenceH as i32 * 60 + ntp.cTimeDifferenceM as i32,
+            dst: (zone.dwEnableDST == 1).then(|| DstConfig {
+                start: DstRule::from(&zone.struBeginPoint),
+                end: DstRule::from(&zone.struEndPoint),
+                bias_minutes: zone.byDSTBias,
+            }),
+        }
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if !(-12 * 60..=14 * 60).contains(&self.utc_offset_minutes) {
+            return Err(anyhow::anyhow!(
+                "Invalid UTC offset: {} minutes",
+                self.utc_offset_minutes
+            ));
+        }
+        if let Some(dst) = &self.dst {
+            if ![30, 60, 90, 120].contains(&dst.bias_minutes) {
+                return Err(anyhow::anyhow!(
+                    "Invalid DST bias: {} minutes",
+                    dst.bias_minutes
+                ));
+            }
+            dst.start.validate()?;
+            dst.end.validate()?;
+        }
+        Ok(())
+    }
+}
+
+// UTC 时间点对应的设备本地时间
+pub fn utc_to_device(utc: DateTime<Utc>, tz: &TzConfig) -> NaiveDateTime {
+    let standard = utc.naive_utc() + Duration::minutes(tz.utc_offset_minutes as i64);
+    match &tz.dst {
+        Some(dst) if dst.is_active(standard) => {
+            standard + Duration::minutes(dst.bias_minutes as i64)
+        }
+        _ => standard,
+    }
+}
+
+impl HikDevice {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_timezone_config(&self) -> anyhow::Result<TzConfig> {
+        let ntp: NET_DVR_NTPPARA = self.get_dvr_config(NET_DVR_GET_NTPCFG, 0, "Get NTP config")?;
+        let zone: NET_DVR_ZONEANDDST =
+            self.get_dvr_config(NET_DVR_GET_ZONEANDDST, 0, "Get DST config")?;
+        Ok(TzConfig::from_sdk(&ntp, &zone))
+    }
+
+    // 时差保存在 NTP 参数中，夏令时单独保存，两者都先读取再修改
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn set_timezone_config(&self, config: &TzConfig) -> anyhow::Result<()> {
+        config.validate()?;
+        let mut ntp: NET_DVR_NTPPARA =
+            self.get_dvr_config(NET_DVR_GET_NTPCFG, 0, "Get NTP config")?;
+        ntp.cTimeDifferenceH = (config.utc_offset_minutes / 60) as i8;
+        ntp.cTimeDifferenceM = (config.utc_offset_minutes % 60) as i8;
+        self.set_dvr_config(NET_DVR_SET_NTPCFG, 0, &ntp, "Set NTP config")?;
+
+        let mut zone: NET_DVR_ZONEANDDST =
+            self.get_dvr_config(NET_DVR_GET_ZONEANDDST, 0, "Get DST config")?;
+        match &config.dst {
+            Some(dst) => {
+                zone.dwEnableDST = 1;
+                zone.byDSTBias = dst.bias_minutes as BYTE;
+                zone.struBeginPoint = NET_DVR_TIMEPOINT::from(&dst.start);
+                zone.struEndPoint = NET_DVR_TIMEPOINT::from(&dst.end);
+            }
+            None => zone.dwEnableDST = 0,
+        }
+        self.set_dvr_config(NET_DVR_SET_ZONEANDDST, 0, &zone, "Set DST config")
+    }
+
+    // 检查 start < end 后按 mode 转换为设备时间
+    pub(crate) fn device_time_range(
+        &self,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+        mode: TimeMode,
+    ) -> anyhow::Result<[NET_DVR_TIME; 2]> {
+        check_time_range(start, end)?;
+        self.device_times([start, end], mode)
+    }
+
+    // 按 mode 把调用方给出的时间转换为设备时间，Utc 模式会先读取一次设备时区
+    pub(crate) fn device_times<const N: usize>(
+        &self,
+        times: [DateTime<Local>; N],
+        mode: TimeMode,
+    ) -> anyhow::Result<[NET_DVR_TIME; N]> {
+        match mode {
+            TimeMode::DeviceLocal => Ok(times.map(NET_DVR_TIME::from)),
+            TimeMode::Utc => {
+                let tz = self.get_timezone_config()?;
+                Ok(times.map(|time| utc_to_device(time.with_timezone(&Utc), &tz).into()))
+            }
+        }
+    }
+}
+
+// 结束时间不晚于开始时间时设备会返回参数错误，这里提前拒绝
+pub(crate) fn check_time_range(
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+) -> Result<(), HikError> {
+    if end <= start {
+        return Err(HikError::InvalidArgument {
+            field: "end",
+            reason: format!("end time {} is not after start time {}", end, start),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone as _;
+
+    use super::*;
+
+    fn naive(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap()
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&naive(y, m, d, h, min))
+    }
+
+    fn rule(month: u32, week: u8, weekday: Weekday, hour: u32) -> DstRule {
+        DstRule {
+            month,
+            week,
+            weekday,
+            hour,
+            minute: 0,
+        }
+    }
+
+    // 中欧：3 月最后一个周日 02:00 开始，10 月最后一个周日 03:00（夏令时）结束
+    fn central_europe() -> TzConfig {
+        TzConfig {
+            utc_offset_minutes: 60,
+            dst: Some(DstConfig {
+                start: rule(3, 5, Weekday::Sun, 2),
+                end: rule(10, 5, Weekday::Sun, 3),
+                bias_minutes: 60,
+            }),
+        }
+    }
+
+    // 悉尼：10 月第一个周日 02:00 开始，次年 4 月第一个周日 03:00（夏令时）结束
+    fn sydney() -> TzConfig {
+        TzConfig {
+            utc_offset_minutes: 600,
+            dst: Some(DstConfig {
+                start: rule(10, 1, Weekday::Sun, 2),
+                end: rule(4, 1, Weekday::Sun, 3),
+                bias_minutes: 60,
+            }),
+        }
+    }
+
+    #[test]
+    fn last_week_rule() {
+        assert_eq!(
+            rule(3, 5, Weekday::Sun, 2).at(2024),
+            Some(naive(2024, 3, 31, 2, 0))
+        );
+        assert_eq!(
+            rule(3, 5, Weekday::Sun, 2).at(2025),
+            Some(naive(2025, 3, 30, 2, 0))
+        );
+        // 12 月需要跨到下一年找月末
+        assert_eq!(
+            rule(12, 5, Weekday::Sun, 0).at(2024),
+            Some(naive(2024, 12, 29, 0, 0))
+        );
+        // 月末当天就是 weekday
+        assert_eq!(
+            rule(2, 5, Weekday::Sat, 0).at(2026),
+            Some(naive(2026, 2, 28, 0, 0))
+        );
+        assert_eq!(
+            rule(3, 2, Weekday::Sun, 2).at(2024),
+            Some(naive(2024, 3, 10, 2, 0))
+        );
+    }
+
+    #[test]
+    fn northern_dst_boundaries() {
+        let tz = central_europe();
+        // 2024-03-31 02:00 CET = 01:00 UTC
+        assert_eq!(
+            utc_to_device(utc(2024, 3, 31, 0, 59), &tz),
+            naive(2024, 3, 31, 1, 59)
+        );
+        assert_eq!(
+            utc_to_device(utc(2024, 3, 31, 1, 0), &tz),
+            naive(2024, 3, 31, 3, 0)
+        );
+        assert_eq!(
+            utc_to_device(utc(2024, 3, 31, 1, 1), &tz),
+            naive(2024, 3, 31, 3, 1)
+        );
+        // 2024-10-27 03:00 CEST = 01:00 UTC
+        assert_eq!(
+            utc_to_device(utc(2024, 10, 27, 0, 59), &tz),
+            naive(2024, 10, 27, 2, 59)
+        );
+        assert_eq!(
+            utc_to_device(utc(2024, 10, 27, 1, 0), &tz),
+            naive(2024, 10, 27, 2, 0)
+        );
+        assert_eq!(
+            utc_to_device(utc(2024, 10, 27, 1, 1), &tz),
+            naive(2024, 10, 27, 2, 1)
+        );
+
+        let dst = tz.dst.unwrap();
+        assert!(!dst.is_active(naive(2024, 1, 15, 12, 0)));
+        assert!(dst.is_active(naive(2024, 7, 1, 12, 0)));
+    }
+
+    #[test]
+    fn southern_dst_wraps_around_new_year() {
+        let tz = sydney();
+        let dst = tz.dst.unwrap();
+        assert!(dst.is_active(naive(2024, 1, 15, 12, 0)));
+        assert!(dst.is_active(naive(2024, 12, 31, 23, 59)));
+        assert!(!dst.is_active(naive(2024, 7, 1, 12, 0)));
+        // 2024-04-07 03:00 AEDT = 02:00 AEST
+        assert!(dst.is_active(naive(2024, 4, 7, 1, 59)));
+        assert!(!dst.is_active(naive(2024, 4, 7, 2, 0)));
+        // 2024-10-06 02:00 AEST
+        assert!(!dst.is_active(naive(2024, 10, 6, 1, 59)));
+        assert!(dst.is_active(naive(2024, 10, 6, 2, 0)));
+
+        // 2024-10-06 02:00 AEST = 10-05 16:00 UTC
+        assert_eq!(
+            utc_to_device(utc(2024, 10, 5, 15, 59), &tz),
+            naive(2024, 10, 6, 1, 59)
+        );
+        assert_eq!(
+            utc_to_device(utc(2024, 10, 5, 16, 0), &tz),
+            naive(2024, 10, 6, 3, 0)
+        );
+        // 2024-04-07 03:00 AEDT = 04-06 16:00 UTC
+        assert_eq!(
+            utc_to_device(utc(2024, 4, 6, 15, 59), &tz),
+            naive(2024, 4, 7, 2, 59)
+        );
+        assert_eq!(
+            utc_to_device(utc(2024, 4, 6, 16, 0), &tz),
+            naive(2024, 4, 7, 2, 0)
+        );
+    }
+
+    #[test]
+    fn no_dst_only_applies_offset() {
+        let tz = TzConfig {
+            utc_offset_minutes: -210,
+            dst: None,
+        };
+        assert_eq!(
+            utc_to_device(utc(2024, 7, 1, 3, 0), &tz),
+            naive(2024, 6, 30, 23, 30)
+        );
+    }
+
+    #[test]
+    fn timepoint_weekday_mapping() {
+        // SDK 中星期 0 为周日，chrono 的 Weekday 从周一开始
+        let point = |week_date: DWORD| NET_DVR_TIMEPOINT {
+            dwMonth: 2,
+            dwWeekNo: 4,
+            dwWeekDate: week_date,
+            dwHour: 2,
+            dwMin: 30,
+        };
+        assert_eq!(
+            DstRule::from(&point(0)),
+            DstRule {
+                month: 3,
+                week: 5,
+                weekday: Weekday::Sun,
+                hour: 2,
+                minute: 30,
+            }
+        );
+        assert_eq!(DstRule::from(&point(1)).weekday, Weekday::Mon);
+        assert_eq!(DstRule::from(&point(6)).weekday, Weekday::Sat);
+
+        for week_date in 0..7 {
+            let rule = DstRule::from(&point(week_date));
+            let back = NET_DVR_TIMEPOINT::from(&rule);
+            assert_eq!(
+                (
+                    back.dwMonth,
+                    back.dwWeekNo,
+                    back.dwWeekDate,
+                    back.dwHour,
+                    back.dwMin
+                ),
+                (2, 4, week_date, 2, 30)
+            );
+        }
+    }
+
+    #[test]
+    fn time_range_must_move_forward() {
+        let start = Local::now();
+        let end = start + Duration::seconds(1);
+        assert_eq!(check_time_range(start, end), Ok(()));
+        for (from, to) in [(start, start), (end, start)] {
+            assert!(matches!(
+                check_time_range(from, to),
+                Err(HikError::InvalidArgument { field: "end", .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn negative_half_hour_offset_from_sdk() {
+        let ntp = NET_DVR_NTPPARA {
+            cTimeDifferenceH: -3,
+            cTimeDifferenceM: -30,
+            ..Default::default()
+        };
+        let zone = NET_DVR_ZONEANDDST::default();
+        let tz = TzConfig::from_sdk(&ntp, &zone);
+        assert_eq!(tz.utc_offset_minutes, -210);
+        assert_eq!(tz.dst, None);
+    }
+}
@@ -0,0 +1,147 @@
+// 调用 SDK 函数并记录函数名与原始返回值，失败时的错误码由 get_last_error_code 记录
+// 需要在调用处的 unsafe 块中使用，未启用 tracing feature 时等同于直接调用
+macro_rules! sdk_call {
+    ($f:ident($($arg:expr),* $(,)?)) => {{
+        let ret = $f($($arg),*);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(function = stringify!($f), ret = ret as i64, "SDK call");
+        ret
+    }};
+}
+
+pub(crate) use sdk_call;
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::{
+        fmt,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicU64, Ordering},
+        },
+    };
+
+    use tracing::{
+        Event, Metadata, Subscriber,
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+    };
+
+    use crate::{
+        NET_DVR_ERROR_RISK_PASSWORD, NET_DVR_PASSWORD_ERROR,
+        common::activate_device_with,
+        device::{HikDevice, LoginOptions},
+        email::{EmailAddress, EmailConfig},
+        error::HikError,
+        sdk::{MockCall, MockSdk, device_info},
+    };
+
+    const PASSWORD: &str = "Hunter2-Pa55!";
+
+    // 记录所有 span 与事件的字段，不依赖 tracing-subscriber
+    #[derive(Default)]
+    struct Capture {
+        lines: Arc<Mutex<Vec<String>>>,
+        next_id: AtomicU64,
+    }
+
+    struct Fields<'a>(&'a mut Vec<String>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.push(format!("{}={:?}", field.name(), value));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.push(format!("{}={}", field.name(), value));
+        }
+    }
+
+    impl Subscriber for Capture {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut lines = self.lines.lock().unwrap();
+            lines.push(format!("span {}", span.metadata().name()));
+            span.record(&mut Fields(&mut lines));
+            Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+        }
+
+        fn record(&self, _span: &Id, values: &Record<'_>) {
+            values.record(&mut Fields(&mut self.lines.lock().unwrap()));
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            event.record(&mut Fields(&mut self.lines.lock().unwrap()));
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn password_is_never_traced() {
+        let capture = Capture::default();
+        let lines = capture.lines.clone();
+        tracing::subscriber::with_default(capture, || {
+            let mock = Arc::new(MockSdk::new());
+            let mut device = HikDevice::with_sdk(mock.clone());
+            mock.push_login_err(NET_DVR_PASSWORD_ERROR, device_info(1, 4, 0, 0));
+            assert!(device.login("192.0.2.1", "admin", PASSWORD, 8000).is_err());
+
+            mock.push_login_ok(0, device_info(1, 4, 0, 0));
+            device.login("192.0.2.1", "admin", PASSWORD, 8000).unwrap();
+            device.logout().unwrap();
+
+            mock.push_login_ok(1, device_info(1, 4, 0, 0));
+            device
+                .login_v40(LoginOptions::new("192.0.2.1", 8000, "admin", PASSWORD))
+                .unwrap();
+
+            let config = EmailConfig {
+                smtp_server: "smtp.example.com".to_string(),
+                smtp_port: 465,
+                username: "alarm@example.com".to_string(),
+                password: Some(PASSWORD.to_string()),
+                sender: EmailAddress {
+                    name: "NVR".to_string(),
+                    address: "alarm@example.com".to_string(),
+                },
+                receivers: Vec::new(),
+                ssl: true,
+                attach_snapshot: false,
+            };
+            device.set_email_config(&config).unwrap();
+
+            mock.fail("NET_DVR_ActivateDevice", NET_DVR_ERROR_RISK_PASSWORD);
+            let err = activate_device_with(&*mock, "192.0.2.10", 8000, PASSWORD).unwrap_err();
+            assert_eq!(err.downcast_ref(), Some(&HikError::RiskyPassword));
+            mock.clear_failure("NET_DVR_ActivateDevice");
+            activate_device_with(&*mock, "192.0.2.10", 8000, PASSWORD).unwrap();
+            let activations: Vec<_> = mock
+                .calls()
+                .into_iter()
+                .filter(|call| matches!(call, MockCall::ActivateDevice { .. }))
+                .collect();
+            let expected = MockCall::ActivateDevice {
+                ip: "192.0.2.10".to_string(),
+                port: 8000,
+                password_len: PASSWORD.len(),
+            };
+            assert_eq!(activations, [expected.clone(), expected]);
+        });
+
+        let lines = lines.lock().unwrap();
+        assert!(lines.iter().any(|line| line.contains("192.0.2.1")));
+        assert!(lines.iter().any(|line| line.contains("192.0.2.10")));
+        assert!(lines.iter().any(|line| line == "span set_email_config"));
+        for line in lines.iter() {
+            assert!(!line.contains(PASSWORD), "password traced: {}", line);
+        }
+    }
+}
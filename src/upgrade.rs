@@ -0,0 +1,153 @@
+use std::{
+    os::raw::c_char,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    _ENUM_UPGRADE_TYPE_ENUM_UPGRADE_DVR, DWORD, LONG, NET_DVR_CloseUpgradeHandle,
+    NET_DVR_GetUpgradeProgress, NET_DVR_GetUpgradeState, NET_DVR_UPGRADE_PARAM,
+    NET_DVR_Upgrade_V50,
+    cancel::CancellationToken,
+    device::{HikDevice, sdk_error},
+    error::HikError,
+    ffi_util::path_to_cstring,
+    trace::sdk_call,
+};
+
+const UPGRADE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpgradeState {
+    // 升级文件上传中，带上传百分比
+    Uploading(u8),
+    // 上传完成，设备正在写入
+    Upgrading,
+    Succeeded,
+    // 升级包的语言版本与设备不一致
+    FailedLanguageMismatch,
+    // NET_DVR_GetUpgradeState 返回的其他状态，例如 3 升级失败、4 网络断开
+    Failed(i32),
+}
+
+impl UpgradeState {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, UpgradeState::Uploading(_) | UpgradeState::Upgrading)
+    }
+}
+
+pub struct HikUpgrade<'a> {
+    // 升级成功后设备会重启，需要让登录句柄失效
+    device: &'a mut HikDevice,
+    handle: LONG,
+    closed: bool,
+}
+
+impl HikDevice {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn upgrade_firmware(&mut self, path: &Path) -> anyhow::Result<HikUpgrade<'_>> {
+        let lu = self.user_id()?;
+        // SDK 对不存在的文件只返回笼统的错误码，这里提前检查
+        if !path.is_file() {
+            return Err(anyhow::anyhow!(
+                "Firmware file not found: {}",
+                path.display()
+            ));
+        }
+        let file = path_to_cstring(path, "path")?;
+
+        let mut param = NET_DVR_UPGRADE_PARAM {
+            dwUpgradeType: _ENUM_UPGRADE_TYPE_ENUM_UPGRADE_DVR as DWORD,
+            sFileName: file.as_ptr() as *mut c_char,
+            ..Default::default()
+        };
+        let handle = unsafe { sdk_call!(NET_DVR_Upgrade_V50(lu as DWORD, &mut param)) };
+        if handle < 0 {
+            return Err(sdk_error("Upgrade firmware"));
+        }
+
+        Ok(HikUpgrade {
+            device: self,
+            handle,
+            closed: false,
+        })
+    }
+}
+
+impl HikUpgrade<'_> {
+    pub fn progress(&mut self) -> anyhow::Result<UpgradeState> {
+        if self.closed {
+            return Err(anyhow::anyhow!("Upgrade handle already closed"));
+        }
+        let state = unsafe { sdk_call!(NET_DVR_GetUpgradeState(self.handle)) };
+        let state = match state {
+            -1 => return Err(sdk_error("Get upgrade state")),
+            1 => UpgradeState::Succeeded,
+            2 => {
+                let progress = unsafe { sdk_call!(NET_DVR_GetUpgradeProgress(self.handle)) };
+                match progress {
+                    -1 => return Err(sdk_error("Get upgrade progress")),
+                    0..=99 => UpgradeState::Uploading(progress as u8),
+                    _ => UpgradeState::Upgrading,
+                }
+            }
+            5 => UpgradeState::FailedLanguageMismatch,
+            code => UpgradeState::Failed(code),
+        };
+        if state == UpgradeState::Succeeded {
+            self.device.invalidate_for_reboot();
+        }
+        Ok(state)
+    }
+
+    // 阻塞直到升级结束，超时返回错误，升级本身不会被取消
+    pub fn wait(&mut self, timeout: Duration) -> anyhow::Result<UpgradeState> {
+        self.wait_cancellable(timeout, &CancellationToken::new())
+    }
+
+    // 取消时返回 HikError::Cancelled，与超时一样不会中止设备上的升级
+    pub fn wait_cancellable(
+        &mut self,
+        timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> anyhow::Result<UpgradeState> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let state = self.progress()?;
+            if state.is_finished() {
+                return Ok(state);
+            }
+            if Instant::now() >= deadline {
+                return Err(anyhow::anyhow!(
+                    "Upgrade did not finish within {:?}, last state: {:?}",
+                    timeout,
+                    state
+                ));
+            }
+            if cancel.wait_timeout(UPGRADE_POLL_INTERVAL) {
+                return Err(HikError::Cancelled.into());
+            }
+        }
+    }
+
+    pub fn close(&mut self) -> anyhow::Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        let res = unsafe { sdk_call!(NET_DVR_CloseUpgradeHandle(self.handle)) };
+        if res != 1 {
+            return Err(sdk_error("Close upgrade handle"));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for HikUpgrade<'_> {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
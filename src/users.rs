@@ -0,0 +1,502 @@
+use std::{collections::BTreeSet, fmt, net::Ipv4Addr, os::raw::c_char};
+
+use bitflags::bitflags;
+
+use crate::{
+    BYTE, DWORD, NET_DVR_ERROR_RISK_PASSWORD, NET_DVR_GET_USERCFG_V30, NET_DVR_GET_USERCFG_V50,
+    NET_DVR_IPADDR, NET_DVR_NOSUPPORT, NET_DVR_SET_USERCFG_V30, NET_DVR_SET_USERCFG_V50,
+    NET_DVR_USER_INFO_V30, NET_DVR_USER_INFO_V40, NET_DVR_USER_V30, NET_DVR_USER_V50,
+    device::{HikDevice, sdk_code},
+    error::HikError,
+    ffi_util::{copy_to_byte_array, copy_to_c_array, copy_to_gbk_array, decode_device_string},
+    network::parse_ipv4,
+};
+
+// V40 的通道权限数组以 0xffffffff 结束
+const CHANNEL_LIST_END: DWORD = 0xffff_ffff;
+
+bitflags! {
+    // byLocalRight 每个字节对应一项权限
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct LocalRights: u32 {
+        const PTZ = 1 << 0;
+        const RECORD = 1 << 1;
+        const PLAYBACK = 1 << 2;
+        const SETUP = 1 << 3;
+        const LOG = 1 << 4;
+        // 升级、格式化等
+        const ADVANCED = 1 << 5;
+        const VIEW_PARAMS = 1 << 6;
+        const CAMERA_MANAGEMENT = 1 << 7;
+        const BACKUP = 1 << 8;
+        const SHUTDOWN = 1 << 9;
+    }
+}
+
+bitflags! {
+    // byRemoteRight 每个字节对应一项权限
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct RemoteRights: u32 {
+        const PTZ = 1 << 0;
+        const RECORD = 1 << 1;
+        const PLAYBACK = 1 << 2;
+        const SETUP = 1 << 3;
+        const LOG = 1 << 4;
+        // 升级、格式化、重启等
+        const ADVANCED = 1 << 5;
+        const TALK = 1 << 6;
+        const PREVIEW = 1 << 7;
+        const ALARM = 1 << 8;
+        const LOCAL_OUTPUT = 1 << 9;
+        const SERIAL = 1 << 10;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UserLevel {
+    Viewer,
+    Operator,
+    Admin,
+    Other(u8),
+}
+
+impl From<u8> for UserLevel {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => UserLevel::Viewer,
+            1 => UserLevel::Operator,
+            2 => UserLevel::Admin,
+            other => UserLevel::Other(other),
+        }
+    }
+}
+
+impl From<UserLevel> for u8 {
+    fn from(value: UserLevel) -> Self {
+        match value {
+            UserLevel::Viewer => 0,
+            UserLevel::Operator => 1,
+            UserLevel::Admin => 2,
+            UserLevel::Other(other) => other,
+        }
+    }
+}
+
+// 各项通道权限，集合中为通道号
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChannelPermissions {
+    pub remote_preview: BTreeSet<u32>,
+    pub remote_playback: BTreeSet<u32>,
+    pub remote_record: BTreeSet<u32>,
+    pub remote_ptz: BTreeSet<u32>,
+    pub local_playback: BTreeSet<u32>,
+    pub local_record: BTreeSet<u32>,
+    pub local_ptz: BTreeSet<u32>,
+    pub local_backup: BTreeSet<u32>,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct UserSpec {
+    pub username: String,
+    // 设备不会返回密码，None 表示保持原密码
+    pub password: Option<String>,
+    pub level: UserLevel,
+    // 绑定的 IP/MAC，None 为不限制
+    pub bind_ip: Option<Ipv4Addr>,
+    pub bind_mac: Option<[u8; 6]>,
+    pub local_rights: LocalRights,
+    pub remote_rights: RemoteRights,
+    pub channels: ChannelPermissions,
+}
+
+impl UserSpec {
+    pub fn new(username: &str, password: &str, level: UserLevel) -> Self {
+        Self {
+            username: username.to_string(),
+            password: Some(password.to_string()),
+            level,
+            bind_ip: None,
+            bind_mac: None,
+            local_rights: LocalRights::empty(),
+            remote_rights: RemoteRights::empty(),
+            channels: ChannelPermissions::default(),
+        }
+    }
+}
+
+// 不输出密码
+impl fmt::Debug for UserSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UserSpec")
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("level", &self.level)
+            .field("bind_ip", &self.bind_ip)
+            .field("bind_mac", &self.bind_mac)
+            .field("local_rights", &self.local_rights)
+            .field("remote_rights", &self.remote_rights)
+            .field("channels", &self.channels)
+            .finish()
+    }
+}
+
+// V30 与 V40 的单个用户结构体，只有通道权限的表示方式不同
+trait UserSlot {
+    fn username(&self) -> String;
+    fn to_spec(&self) -> UserSpec;
+    fn apply(&mut self, spec: &UserSpec) -> anyhow::Result<()>;
+    fn clear(&mut self);
+}
+
+macro_rules! impl_user_common {
+    () => {
+        fn username(&self) -> String {
+            decode_device_string(&self.sUserName)
+        }
+
+        fn clear(&mut self) {
+            *self = Default::default();
+        }
+    };
+}
+
+impl UserSlot for NET_DVR_USER_INFO_V40 {
+    impl_user_common!();
+
+    fn to_spec(&self) -> UserSpec {
+        UserSpec {
+            username: self.username(),
+            password: None,
+            level: UserLevel::from(self.byPriority),
+            bind_ip: parse_ipv4(&self.struUserIP),
+            bind_mac: parse_mac(self.byMACAddr),
+            local_rights: LocalRights::from_bits_retain(rights_to_bits(&self.byLocalRight)),
+            remote_rights: RemoteRights::from_bits_retain(rights_to_bits(&self.byRemoteRight)),
+            channels: ChannelPermissions {
+                remote_preview: channel_list_to_set(&self.dwNetPreviewRight),
+                remote_playback: channel_list_to_set(&self.dwNetPlaybackRight),
+                remote_record: channel_list_to_set(&self.dwNetRecordRight),
+                remote_ptz: channel_list_to_set(&self.dwNetPTZRight),
+                local_playback: channel_list_to_set(&self.dwLocalPlaybackRight),
+                local_record: channel_list_to_set(&self.dwLocalRecordRight),
+                local_ptz: channel_list_to_set(&self.dwLocalPTZRight),
+                local_backup: channel_list_to_set(&self.dwLocalBackupRight),
+            },
+        }
+    }
+
+    fn apply(&mut self, spec: &UserSpec) -> anyhow::Result<()> {
+        apply_common(
+            &mut self.sUserName,
+            &mut self.sPassword,
+            &mut self.struUserIP,
+            &mut self.byMACAddr,
+            spec,
+        )?;
+        self.byPriority = spec.level.into();
+        bits_to_rights(spec.local_rights.bits(), &mut self.byLocalRight);
+        bits_to_rights(spec.remote_rights.bits(), &mut self.byRemoteRight);
+        let channels = &spec.channels;
+        set_to_channel_list(&channels.remote_preview, &mut self.dwNetPreviewRight);
+        set_to_channel_list(&channels.remote_playback, &mut self.dwNetPlaybackRight);
+        set_to_channel_list(&channels.remote_record, &mut self.dwNetRecordRight);
+        set_to_channel_list(&channels.remote_ptz, &mut self.dwNetPTZRight);
+        set_to_channel_list(&channels.local_playback, &mut self.dwLocalPlaybackRight);
+        set_to_channel_list(&channels.local_record, &mut self.dwLocalRecordRight);
+        set_to_channel_list(&channels.local_ptz, &mut self.dwLocalPTZRight);
+        set_to_channel_list(&channels.local_backup, &mut self.dwLocalBackupRight);
+        Ok(())
+    }
+}
+
+// V30 的通道权限按下标存放，下标 i 对应通道号 i + 1
+impl UserSlot for NET_DVR_USER_INFO_V30 {
+    impl_user_common!();
+
+    fn to_spec(&self) -> UserSpec {
+        UserSpec {
+            username: self.username(),
+            password: None,
+            level: UserLevel::from(self.byPriority),
+            bind_ip: parse_ipv4(&self.struUserIP),
+            bind_mac: parse_mac(self.byMACAddr),
+            local_rights: LocalRights::from_bits_retain(rights_to_bits(&self.byLocalRight)),
+            remote_rights: RemoteRights::from_bits_retain(rights_to_bits(&self.byRemoteRight)),
+            channels: ChannelPermissions {
+                remote_preview: channel_flags_to_set(&self.byNetPreviewRight),
+                remote_playback: channel_flags_to_set(&self.byNetPlaybackRight),
+                remote_record: channel_flags_to_set(&self.byNetRecordRight),
+                remote_ptz: channel_flags_to_set(&self.byNetPTZRight),
+                local_playback: channel_flags_to_set(&self.byLocalPlaybackRight),
+                local_record: channel_flags_to_set(&self.byLocalRecordRight),
+                local_ptz: channel_flags_to_set(&self.byLocalPTZRight),
+                local_backup: channel_flags_to_set(&self.byLocalBackupRight),
+            },
+        }
+    }
+
+    fn apply(&mut self, spec: &UserSpec) -> anyhow::Result<()> {
+        apply_common(
+            &mut self.sUserName,
+            &mut self.sPassword,
+            &mut self.struUserIP,
+            &mut self.byMACAddr,
+            spec,
+        )?;
+        self.byPriority = spec.level.into();
+        bits_to_rights(spec.local_rights.bits(), &mut self.byLocalRight);
+        bits_to_rights(spec.remote_rights.bits(), &mut self.byRemoteRight);
+        let channels = &spec.channels;
+        set_to_channel_flags(&channels.remote_preview, &mut self.byNetPreviewRight);
+        set_to_channel_flags(&channels.remote_playback, &mut self.byNetPlaybackRight);
+        set_to_channel_flags(&channels.remote_record, &mut self.byNetRecordRight);
+        set_to_channel_flags(&channels.remote_ptz, &mut self.byNetPTZRight);
+        set_to_channel_flags(&channels.local_playback, &mut self.byLocalPlaybackRight);
+        set_to_channel_flags(&channels.local_record, &mut self.byLocalRecordRight);
+        set_to_channel_flags(&channels.local_ptz, &mut self.byLocalPTZRight);
+        set_to_channel_flags(&channels.local_backup, &mut self.byLocalBackupRight);
+        Ok(())
+    }
+}
+
+enum RawUsers {
+    V50(Box<NET_DVR_USER_V50>),
+    V30(Box<NET_DVR_USER_V30>),
+}
+
+impl RawUsers {
+    fn slots(&self) -> Vec<&dyn UserSlot> {
+        match self {
+            RawUsers::V50(raw) => raw.struUser.iter().map(|u| u as &dyn UserSlot).collect(),
+            RawUsers::V30(raw) => raw.struUser.iter().map(|u| u as &dyn UserSlot).collect(),
+        }
+    }
+
+    fn slots_mut(&mut self) -> Vec<&mut dyn UserSlot> {
+        match self {
+            RawUsers::V50(raw) => raw
+                .struUser
+                .iter_mut()
+                .map(|u| u as &mut dyn UserSlot)
+                .collect(),
+            RawUsers::V30(raw) => raw
+                .struUser
+                .iter_mut()
+                .map(|u| u as &mut dyn UserSlot)
+                .collect(),
+        }
+    }
+
+    fn users(&self) -> Vec<UserSpec> {
+        self.slots()
+            .into_iter()
+            .filter(|slot| !slot.username().is_empty())
+            .map(|slot| slot.to_spec())
+            .collect()
+    }
+
+    // 已有用户在原位置修改，保留未建模的字段；新用户放入空位
+    fn replace_users(&mut self, users: &[UserSpec]) -> anyhow::Result<()> {
+        let mut pending: Vec<&UserSpec> = users.iter().collect();
+        for slot in self.slots_mut() {
+            let name = slot.username();
+            if name.is_empty() {
+                continue;
+            }
+            match pending.iter().position(|spec| spec.username == name) {
+                Some(pos) => slot.apply(pending.remove(pos))?,
+                None => slot.clear(),
+            }
+        }
+
+        let mut empty_slots = self
+            .slots_mut()
+            .into_iter()
+            .filter(|slot| slot.username().is_empty());
+        for spec in pending {
+            if spec.password.is_none() {
+                return Err(anyhow::anyhow!(
+                    "New user {} requires a password",
+                    spec.username
+                ));
+            }
+            let slot = empty_slots
+                .next()
+                .ok_or(anyhow::anyhow!("No free user slot for {}", spec.username))?;
+            slot.apply(spec)?;
+        }
+        Ok(())
+    }
+}
+
+impl HikDevice {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn get_users(&self) -> anyhow::Result<Vec<UserSpec>> {
+        Ok(self.get_raw_users()?.users())
+    }
+
+    // 设备上不在 users 中的用户会被删除
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn set_users(&self, users: &[UserSpec]) -> anyhow::Result<()> {
+        let mut raw = self.get_raw_users()?;
+        raw.replace_users(users)?;
+        self.set_raw_users(&raw)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn change_password(&self, username: &str, new_password: &str) -> anyhow::Result<()> {
+        self.modify_users(|users| {
+            let user = users
+                .iter_mut()
+                .find(|user| user.username == username)
+                .ok_or(anyhow::anyhow!("User {} not found", username))?;
+            user.password = Some(new_password.to_string());
+            Ok(())
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn add_user(&self, user: UserSpec) -> anyhow::Result<()> {
+        self.modify_users(|users| {
+            if users.iter().any(|u| u.username == user.username) {
+                return Err(anyhow::anyhow!("User {} already exists", user.username));
+            }
+            users.push(user);
+            Ok(())
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ip = %self.session.host()))
+    )]
+    pub fn delete_user(&self, username: &str) -> anyhow::Result<()> {
+        self.modify_users(|users| {
+            let len = users.len();
+            users.retain(|user| user.username != username);
+            if users.len() == len {
+                return Err(anyhow::anyhow!("User {} not found", username));
+            }
+            Ok(())
+        })
+    }
+
+    fn modify_users<F>(&self, f: F) -> anyhow::Result<()>
+    where
+        F: FnOnce(&mut Vec<UserSpec>) -> anyhow::Result<()>,
+    {
+        let mut raw = self.get_raw_users()?;
+        let mut users = raw.users();
+        f(&mut users)?;
+        raw.replace_users(&users)?;
+        self.set_raw_users(&raw)
+    }
+
+    // 优先使用 V50，设备不支持时回退到 V30
+    fn get_raw_users(&self) -> anyhow::Result<RawUsers> {
+        match self.get_dvr_config_boxed(NET_DVR_GET_USERCFG_V50, 0, "Get users") {
+            Ok(raw) => Ok(RawUsers::V50(raw)),
+            Err(e) if sdk_code(&e) == Some(NET_DVR_NOSUPPORT as i32) => Ok(RawUsers::V30(
+                self.get_dvr_config_boxed(NET_DVR_GET_USERCFG_V30, 0, "Get users")?,
+            )),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn set_raw_users(&self, raw: &RawUsers) -> anyhow::Result<()> {
+        let res = match raw {
+            RawUsers::V50(raw) => {
+                self.set_dvr_config(NET_DVR_SET_USERCFG_V50, 0, &**raw, "Set users")
+            }
+            RawUsers::V30(raw) => {
+                self.set_dvr_config(NET_DVR_SET_USERCFG_V30, 0, &**raw, "Set users")
+            }
+        };
+        res.map_err(|e| {
+            if sdk_code(&e) == Some(NET_DVR_ERROR_RISK_PASSWORD as i32) {
+                HikError::RiskyPassword.into()
+            } else {
+                e
+            }
+        })
+    }
+}
+
+fn apply_common(
+    username: &mut [BYTE],
+    password: &mut [BYTE],
+    user_ip: &mut NET_DVR_IPADDR,
+    mac: &mut [BYTE; 6],
+    spec: &UserSpec,
+) -> anyhow::Result<()> {
+    copy_to_gbk_array(username, &spec.username, "Username")?;
+    if let Some(new_password) = &spec.password {
+        copy_to_byte_array(password, new_password, "Password")?;
+    }
+    let ip = spec.bind_ip.map(|ip| ip.to_string()).unwrap_or_default();
+    let dst: &mut [c_char] = &mut user_ip.sIpV4;
+    copy_to_c_array(dst, &ip, "Bind IP")?;
+    *mac = spec.bind_mac.unwrap_or_default();
+    Ok(())
+}
+
+fn parse_mac(mac: [BYTE; 6]) -> Option<[u8; 6]> {
+    (mac != [0; 6]).then_some(mac)
+}
+
+fn rights_to_bits(rights: &[BYTE]) -> u32 {
+    rights
+        .iter()
+        .take(32)
+        .enumerate()
+        .filter(|(_, v)| **v == 1)
+        .fold(0, |bits, (i, _)| bits | (1 << i))
+}
+
+fn bits_to_rights(bits: u32, rights: &mut [BYTE]) {
+    for (i, right) in rights.iter_mut().take(32).enumerate() {
+        *right = ((bits >> i) & 1) as BYTE;
+    }
+}
+
+fn channel_list_to_set(list: &[DWORD]) -> BTreeSet<u32> {
+    list.iter()
+        .take_while(|&&chan| chan != CHANNEL_LIST_END)
+        .copied()
+        .collect()
+}
+
+fn set_to_channel_list(set: &BTreeSet<u32>, list: &mut [DWORD]) {
+    list.fill(CHANNEL_LIST_END);
+    for (dst, &chan) in list.iter_mut().zip(set) {
+        *dst = chan;
+    }
+}
+
+fn channel_flags_to_set(flags: &[BYTE]) -> BTreeSet<u32> {
+    flags
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| **v == 1)
+        .map(|(i, _)| i as u32 + 1)
+        .collect()
+}
+
+fn set_to_channel_flags(set: &BTreeSet<u32>, flags: &mut [BYTE]) {
+    for (i, flag) in flags.iter_mut().enumerate() {
+        *flag = set.contains(&(i as u32 + 1)) as BYTE;
+    }
+}
@@ -0,0 +1,40 @@
+// 需要真实设备：HIK_TEST_HOST、HIK_TEST_USER、HIK_TEST_PASSWORD，可选 HIK_TEST_PORT
+// cargo test --test email_roundtrip -- --ignored
+use hik_net_sdk::{common, device::HikDevice};
+
+fn env(name: &str) -> String {
+    std::env::var(name).unwrap_or_else(|_| panic!("{} is not set", name))
+}
+
+#[test]
+#[ignore = "requires a device"]
+fn email_config_round_trip() -> anyhow::Result<()> {
+    let port = std::env::var("HIK_TEST_PORT")
+        .ok()
+        .map(|port| port.parse())
+        .transpose()?
+        .unwrap_or(8000);
+
+    common::init()?;
+    let mut device = HikDevice::new();
+    device.login(
+        &env("HIK_TEST_HOST"),
+        &env("HIK_TEST_USER"),
+        &env("HIK_TEST_PASSWORD"),
+        port,
+    )?;
+
+    // 改一个无副作用的字段后读回，最后恢复原配置；password 为 None 时不修改密码
+    let original = device.get_email_config()?;
+    let mut changed = original.clone();
+    changed.attach_snapshot = !original.attach_snapshot;
+    device.set_email_config(&changed)?;
+    let read_back = device.get_email_config();
+    device.set_email_config(&original)?;
+    assert_eq!(read_back?, changed);
+    assert_eq!(device.get_email_config()?, original);
+
+    device.logout()?;
+    common::cleanup()?;
+    Ok(())
+}
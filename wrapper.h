@@ -1 +0,0 @@
-#include "include/HCNetSDK.h"
\ No newline at end of file